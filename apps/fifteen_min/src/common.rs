@@ -5,7 +5,7 @@ use geom::Distance;
 use map_gui::tools::{CityPicker, Navigator};
 use map_gui::ID;
 use map_model::connectivity::WalkingOptions;
-use map_model::{AmenityType, BuildingID};
+use map_model::{AmenityType, BuildingID, WalkingProfile};
 use widgetry::tools::{ColorLegend, PopupMsg};
 use widgetry::{
     lctrl, Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Panel,
@@ -178,6 +178,12 @@ fn options_to_controls(ctx: &mut EventCtx, opts: &Options) -> Widget {
                     .map(|(label, speed)| Choice::new(label, speed))
                     .collect(),
             ));
+            rows.push(Toggle::switch(
+                ctx,
+                "Only use wheelchair-accessible sidewalks and crossings",
+                None,
+                opts.profile.is_some(),
+            ));
 
             rows.push(ColorLegend::row(ctx, Color::BLUE, "unwalkable roads"));
         }
@@ -195,6 +201,14 @@ pub fn options_from_controls(panel: &Panel) -> MovementOptions {
             walking_speed: panel
                 .maybe_dropdown_value("speed")
                 .unwrap_or_else(WalkingOptions::default_speed),
+            profile: if panel
+                .maybe_is_checked("Only use wheelchair-accessible sidewalks and crossings")
+                .unwrap_or(false)
+            {
+                Some(WalkingProfile::wheelchair())
+            } else {
+                None
+            },
         })
     } else {
         MovementOptions::Biking
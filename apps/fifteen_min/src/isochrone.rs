@@ -6,7 +6,7 @@ use geom::Duration;
 use map_gui::tools::draw_isochrone;
 use map_model::{
     connectivity, AmenityType, BuildingID, BuildingType, IntersectionID, LaneType, Map, Path,
-    PathConstraints, PathRequest,
+    PathConstraints, PathRequest, PathfinderCaching,
 };
 use widgetry::mapspace::{ToggleZoomed, ToggleZoomedBuilder};
 use widgetry::{Color, EventCtx};
@@ -154,14 +154,18 @@ impl Isochrone {
             return None;
         }
 
-        let constraints = match self.options.movement {
-            MovementOptions::Walking(_) => PathConstraints::Pedestrian,
-            MovementOptions::Biking => PathConstraints::Bike,
+        let (constraints, profile) = match self.options.movement {
+            MovementOptions::Walking(ref opts) => (PathConstraints::Pedestrian, &opts.profile),
+            MovementOptions::Biking => (PathConstraints::Bike, &None),
         };
+        let mut params = map.routing_params().clone();
+        params.walking_profile = profile.clone();
 
         let all_paths = self.start.iter().filter_map(|b_id| {
-            PathRequest::between_buildings(map, *b_id, to, constraints)
-                .and_then(|req| map.pathfind(req).ok())
+            PathRequest::between_buildings(map, *b_id, to, constraints).and_then(|req| {
+                map.pathfind_with_params(req, &params, PathfinderCaching::CacheDijkstra)
+                    .ok()
+            })
         });
 
         all_paths.min_by_key(|path| path.total_length())
@@ -11,8 +11,8 @@ pub fn draw_star(ctx: &mut EventCtx, b: &Building) -> GeomBatch {
 }
 
 pub fn draw_unwalkable_roads(ctx: &mut EventCtx, app: &App) -> Drawable {
-    let allow_shoulders = match app.session.movement {
-        MovementOptions::Walking(ref opts) => opts.allow_shoulders,
+    let (allow_shoulders, profile) = match app.session.movement {
+        MovementOptions::Walking(ref opts) => (opts.allow_shoulders, opts.profile.as_ref()),
         MovementOptions::Biking => {
             return Drawable::empty(ctx);
         }
@@ -24,10 +24,13 @@ pub fn draw_unwalkable_roads(ctx: &mut EventCtx, app: &App) -> Drawable {
             continue;
         }
         for l in &road.lanes {
-            if l.lane_type == LaneType::Sidewalk
+            if (l.lane_type == LaneType::Sidewalk
                 || l.lane_type == LaneType::Footway
                 || l.lane_type == LaneType::SharedUse
-                || (l.lane_type == LaneType::Shoulder && allow_shoulders)
+                || (l.lane_type == LaneType::Shoulder && allow_shoulders))
+                && profile
+                    .map(|p| p.lane_barrier(l, &app.map).is_none())
+                    .unwrap_or(true)
             {
                 continue 'ROADS;
             }
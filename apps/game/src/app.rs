@@ -15,7 +15,7 @@ use map_gui::render::{DrawMap, DrawOptions};
 use map_gui::tools::CameraState;
 use map_model::AreaType;
 use map_model::{BufferType, IntersectionID, LaneType, Map, Traversable};
-use sim::{AgentID, Analytics, Sim, SimCallback, SimFlags, VehicleType};
+use sim::{AgentID, Analytics, Sim, SimCallback, SimFlags};
 use synthpop::Scenario;
 use widgetry::mapspace::ToggleZoomed;
 use widgetry::{Cached, Canvas, EventCtx, GfxCtx, Prerender, SharedAppState, State};
@@ -331,7 +331,7 @@ impl App {
                 match id {
                     ID::Pedestrian(_) => {}
                     ID::Car(c) => {
-                        if c.vehicle_type != VehicleType::Bike {
+                        if !c.vehicle_type.rides_bike_lanes() {
                             return Some(id);
                         }
                     }
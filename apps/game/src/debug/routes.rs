@@ -7,13 +7,13 @@ use map_gui::tools::{cmp_count, ColorNetwork};
 use map_gui::AppLike;
 use map_model::{
     DirectedRoadID, Direction, PathConstraints, PathRequest, PathStepV2, Pathfinder, RoadID,
-    RoutingParams, NORMAL_LANE_THICKNESS,
+    RoutingParams, WalkingProfile, NORMAL_LANE_THICKNESS,
 };
 use synthpop::{TripEndpoint, TripMode};
 use widgetry::mapspace::ToggleZoomed;
 use widgetry::{
     Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
-    RoundedF64, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
+    RoundedF64, Spinner, State, Text, TextExt, Toggle, VerticalAlignment, Widget,
 };
 
 use crate::app::{App, Transition};
@@ -45,6 +45,7 @@ impl RouteExplorer {
                     .build_def(ctx),
                 params_to_controls(ctx, TripMode::Bike, app.primary.map.routing_params())
                     .named("params"),
+                Widget::nothing().named("explanation"),
             ]))
             .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
             .build(ctx),
@@ -53,26 +54,50 @@ impl RouteExplorer {
 
     fn recalc_paths(&mut self, ctx: &mut EventCtx, app: &App) {
         let (mode, params) = controls_to_params(&self.panel);
+        let map = &app.primary.map;
+        let mut explanation = Widget::nothing();
 
         if let Some((ref goal, _, ref mut preview)) = self.goal {
             *preview = Drawable::empty(ctx);
-            if let Some(polygon) = TripEndpoint::path_req(self.start, *goal, mode, &app.primary.map)
+            let req = TripEndpoint::path_req(self.start, *goal, mode, map);
+            let path = req
+                .clone()
                 .and_then(|req| {
                     Pathfinder::new_dijkstra(
-                        &app.primary.map,
-                        params,
+                        map,
+                        params.clone(),
                         vec![req.constraints],
                         &mut Timer::throwaway(),
                     )
-                    .pathfind_v2(req, &app.primary.map)
+                    .pathfind_v2(req, map)
                 })
-                .and_then(|path| path.into_v1(&app.primary.map).ok())
-                .and_then(|path| path.trace(&app.primary.map))
+                .and_then(|path| path.into_v1(map).ok());
+            if let Some(polygon) = path
+                .as_ref()
+                .and_then(|path| path.trace(map))
                 .map(|pl| pl.make_polygons(NORMAL_LANE_THICKNESS))
             {
                 *preview = GeomBatch::from(vec![(Color::PURPLE, polygon)]).upload(ctx);
             }
+
+            // Show what blocks the usual walking route
+            if let (None, Some(req), Some(profile)) = (path, req, &params.walking_profile) {
+                let mut txt = Text::from("No accessible route. The usual route has:");
+                let mut batch = GeomBatch::new();
+                if let Ok(usual) = map.pathfind(req) {
+                    for (on, barrier) in profile.barriers_along(&usual, map) {
+                        txt.add_line(format!("- {} on {}", barrier, on));
+                        batch.push(
+                            Color::RED,
+                            on.get_polyline(map).make_polygons(NORMAL_LANE_THICKNESS),
+                        );
+                    }
+                }
+                *preview = batch.upload(ctx);
+                explanation = txt.wrap_to_pct(ctx, 20).into_widget(ctx);
+            }
         }
+        self.panel.replace(ctx, "explanation", explanation);
     }
 }
 
@@ -175,7 +200,7 @@ impl State<App> for RouteExplorer {
             match self.start {
                 TripEndpoint::Border(i) => app.primary.map.get_i(i).polygon.clone(),
                 TripEndpoint::Building(b) => app.primary.map.get_b(b).polygon.clone(),
-                TripEndpoint::SuddenlyAppear(_) => unreachable!(),
+                TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) => unreachable!(),
             },
         );
         if let Some((ref endpt, _, ref draw)) = self.goal {
@@ -184,7 +209,7 @@ impl State<App> for RouteExplorer {
                 match endpt {
                     TripEndpoint::Border(i) => app.primary.map.get_i(*i).polygon.clone(),
                     TripEndpoint::Building(b) => app.primary.map.get_b(*b).polygon.clone(),
-                    TripEndpoint::SuddenlyAppear(_) => unreachable!(),
+                    TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) => unreachable!(),
                 },
             );
             g.redraw(draw);
@@ -225,6 +250,14 @@ fn params_to_controls(ctx: &mut EventCtx, mode: TripMode, params: &RoutingParams
             ),
         ]));
     }
+    if mode == TripMode::Walk {
+        rows.push(Toggle::switch(
+            ctx,
+            "Wheelchair-accessible only",
+            None,
+            params.walking_profile.is_some(),
+        ));
+    }
     if mode == TripMode::Bike {
         rows.push(Widget::row(vec![
             "Bike lane penalty:".text_widget(ctx).margin_right(20),
@@ -289,6 +322,9 @@ fn controls_to_params(panel: &Panel) -> (TripMode, RoutingParams) {
         return (TripMode::Drive, params);
     }
     if !panel.is_button_enabled("pedestrians") {
+        if panel.is_checked("Wheelchair-accessible only") {
+            params.walking_profile = Some(WalkingProfile::wheelchair());
+        }
         return (TripMode::Walk, params);
     }
     params.unprotected_turn_penalty = panel.spinner("unprotected_turn_penalty");
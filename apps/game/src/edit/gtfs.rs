@@ -0,0 +1,369 @@
+//! Export the (possibly edited) transit network as a static GTFS feed, so that agencies can load
+//! proposals into their own tools. See https://developers.google.com/transit/gtfs/reference.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use anyhow::Result;
+
+use geom::{Duration, Pt2D, Time};
+use map_model::{Map, TransitMode, TransitRoute, TransitRouteID, TransitStopID};
+use sim::{CarID, Sim};
+
+use crate::app::App;
+
+/// Where do the stop times come from?
+#[derive(Clone, Copy)]
+pub enum StopTimes {
+    /// One trip per spawn time of every route. Timetabled routes use their schedule; others
+    /// estimate running times from free-flow speeds.
+    Scheduled,
+    /// One trip per vehicle that's run so far in the current simulation, using the times it
+    /// actually reached each stop
+    Simulated,
+}
+
+/// Every trip in the feed runs on this one service
+const SERVICE_ID: &str = "abst";
+
+/// Writes a GTFS zip file and returns its path.
+pub fn export_gtfs(app: &App, stop_times: StopTimes) -> Result<String> {
+    let map = &app.primary.map;
+    let stop_ids = stop_ids(map);
+    let (trips, times) = trips_and_stop_times(map, &app.primary.sim, &stop_ids, stop_times)?;
+
+    let zip = write_zip(vec![
+        ("agency.txt", agency()?),
+        ("stops.txt", stops(map, &stop_ids)?),
+        ("routes.txt", routes(map)?),
+        ("shapes.txt", shapes(map)?),
+        ("calendar.txt", calendar()?),
+        ("trips.txt", trips),
+        ("stop_times.txt", times),
+    ])?;
+
+    let path = abstio::path_player(format!(
+        "gtfs/{}_{}.zip",
+        map.get_name().as_filename(),
+        match stop_times {
+            StopTimes::Scheduled => "scheduled".to_string(),
+            StopTimes::Simulated => app.primary.sim.time().as_filename(),
+        }
+    ));
+    abstio::write_raw(path.clone(), &zip)?;
+    Ok(path)
+}
+
+fn agency() -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "agency_id,agency_name,agency_url,agency_timezone")?;
+    // TODO The simulation clock is local time, but maps don't know their time zone. Agencies
+    // importing this will have to fix it up.
+    writeln!(
+        out,
+        "{},A/B Street,https://abstreet.org,Etc/UTC",
+        SERVICE_ID
+    )?;
+    Ok(out)
+}
+
+fn calendar() -> Result<String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date"
+    )?;
+    // The simulation models one day, so just repeat it every day
+    writeln!(out, "{},1,1,1,1,1,1,1,20000101,20991231", SERVICE_ID)?;
+    Ok(out)
+}
+
+/// Keep the original IDs when stops came from GTFS in the first place
+fn stop_ids(map: &Map) -> BTreeMap<TransitStopID, String> {
+    map.all_transit_stops()
+        .values()
+        .enumerate()
+        .map(|(idx, ts)| {
+            let id = if ts.gtfs_id.is_empty() {
+                format!("stop{}", idx)
+            } else {
+                ts.gtfs_id.clone()
+            };
+            (ts.id, id)
+        })
+        .collect()
+}
+
+fn stops(map: &Map, stop_ids: &BTreeMap<TransitStopID, String>) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "stop_id,stop_name,stop_lat,stop_lon")?;
+    for ts in map.all_transit_stops().values() {
+        let gps = ts.sidewalk_pos.pt(map).to_gps(map.get_gps_bounds());
+        writeln!(
+            out,
+            "{},{},{},{}",
+            quote(&stop_ids[&ts.id]),
+            quote(&ts.name),
+            gps.y(),
+            gps.x()
+        )?;
+    }
+    Ok(out)
+}
+
+fn routes(map: &Map) -> Result<String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "route_id,agency_id,route_short_name,route_long_name,route_type"
+    )?;
+    for route in map.all_transit_routes() {
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            quote(&route_id(route)),
+            SERVICE_ID,
+            quote(&route.short_name),
+            quote(&route.long_name),
+            // Match what the importer expects
+            match route.mode {
+                TransitMode::Bus => 3,
+                TransitMode::Tram => 0,
+                TransitMode::Train => 1,
+            }
+        )?;
+    }
+    Ok(out)
+}
+
+fn shapes(map: &Map) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "shape_id,shape_pt_lat,shape_pt_lon,shape_pt_sequence")?;
+    for route in map.all_transit_routes() {
+        let mut pts: Vec<Pt2D> = Vec::new();
+        for path in route.all_paths(map)? {
+            if let Some(pl) = path.trace(map) {
+                pts.extend(pl.into_points());
+            }
+        }
+        pts.dedup();
+        for (idx, gps) in map
+            .get_gps_bounds()
+            .convert_back(&pts)
+            .into_iter()
+            .enumerate()
+        {
+            writeln!(
+                out,
+                "{},{},{},{}",
+                quote(&route_id(route)),
+                gps.y(),
+                gps.x(),
+                idx
+            )?;
+        }
+    }
+    Ok(out)
+}
+
+/// Returns the contents of trips.txt and stop_times.txt
+fn trips_and_stop_times(
+    map: &Map,
+    sim: &Sim,
+    stop_ids: &BTreeMap<TransitStopID, String>,
+    source: StopTimes,
+) -> Result<(String, String)> {
+    let mut trips = String::new();
+    writeln!(trips, "route_id,service_id,trip_id,shape_id")?;
+    let mut stop_times = String::new();
+    writeln!(
+        stop_times,
+        "trip_id,arrival_time,departure_time,stop_id,stop_sequence"
+    )?;
+
+    // Per route, every run with the time it reached each stop
+    let mut runs: BTreeMap<TransitRouteID, Vec<Vec<(TransitStopID, Time)>>> = BTreeMap::new();
+    match source {
+        StopTimes::Scheduled => {
+            for route in map.all_transit_routes() {
+                let estimated = if route.stop_time_offsets.is_empty() {
+                    estimated_offsets(map, route)?
+                } else {
+                    Vec::new()
+                };
+                runs.insert(
+                    route.id,
+                    route
+                        .spawn_times
+                        .iter()
+                        .map(|t| {
+                            let offsets = route.scheduled_offsets(*t).unwrap_or(&estimated);
+                            route
+                                .stops
+                                .iter()
+                                .zip(offsets.iter())
+                                .map(|(stop, offset)| (*stop, *t + *offset))
+                                .collect()
+                        })
+                        .collect(),
+                );
+            }
+        }
+        StopTimes::Simulated => {
+            // Every vehicle only serves one run of one route
+            let mut per_vehicle: BTreeMap<(TransitRouteID, CarID), Vec<(TransitStopID, Time)>> =
+                BTreeMap::new();
+            for (t, car, route, stop) in &sim.get_analytics().bus_arrivals {
+                if *t > sim.time() {
+                    break;
+                }
+                per_vehicle
+                    .entry((*route, *car))
+                    .or_insert_with(Vec::new)
+                    .push((*stop, *t));
+            }
+            for ((route, _), run) in per_vehicle {
+                runs.entry(route).or_insert_with(Vec::new).push(run);
+            }
+        }
+    }
+
+    for (route, runs) in runs {
+        let route = map.get_tr(route);
+        for (idx, run) in runs.into_iter().enumerate() {
+            let trip_id = quote(&format!("{}_{}", route_id(route), idx + 1));
+            writeln!(
+                trips,
+                "{},{},{},{}",
+                quote(&route_id(route)),
+                SERVICE_ID,
+                trip_id,
+                quote(&route_id(route))
+            )?;
+            for (seq, (stop, time)) in run.into_iter().enumerate() {
+                let time = gtfs_time(time);
+                writeln!(
+                    stop_times,
+                    "{},{},{},{},{}",
+                    trip_id,
+                    time,
+                    time,
+                    quote(&stop_ids[&stop]),
+                    seq + 1
+                )?;
+            }
+        }
+    }
+    Ok((trips, stop_times))
+}
+
+/// How long after spawning does a vehicle reach each stop, for routes without a timetable?
+/// Assume free-flow speeds and the shortest dwell at every stop.
+fn estimated_offsets(map: &Map, route: &TransitRoute) -> Result<Vec<Duration>> {
+    // The last path leaves the final stop, so skip it.
+    let mut offsets = Vec::new();
+    let mut total = Duration::ZERO;
+    for path in route.all_paths(map)?.into_iter().take(route.stops.len()) {
+        total = total + path.estimate_duration(map, None);
+        offsets.push(total);
+        total = total + sim::MIN_DWELL_TIME;
+    }
+    Ok(offsets)
+}
+
+fn route_id(route: &TransitRoute) -> String {
+    if route.gtfs_id.is_empty() {
+        format!("route{}", route.id.0)
+    } else {
+        route.gtfs_id.clone()
+    }
+}
+
+/// HH:MM:SS, with hours past 24 for service after midnight
+fn gtfs_time(time: Time) -> String {
+    let secs = time.inner_seconds().round() as usize;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// Quote a CSV field if needed
+fn quote(x: &str) -> String {
+    if x.contains(',') || x.contains('"') || x.contains('\n') {
+        format!("\"{}\"", x.replace('"', "\"\""))
+    } else {
+        x.to_string()
+    }
+}
+
+/// Bundle the files into one deflated zip archive
+fn write_zip(files: Vec<(&str, String)>) -> Result<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, contents) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_gtfs_time() {
+        assert_eq!(
+            gtfs_time(Time::START_OF_DAY + Duration::hours(7) + Duration::seconds(5.4)),
+            "07:00:05"
+        );
+        // Service after midnight keeps counting hours
+        assert_eq!(
+            gtfs_time(
+                Time::START_OF_DAY
+                    + Duration::hours(25)
+                    + Duration::minutes(3)
+                    + Duration::seconds(4.0)
+            ),
+            "25:03:04"
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("Route 48"), "Route 48");
+        assert_eq!(quote("Pine St, 3rd Ave"), "\"Pine St, 3rd Ave\"");
+        assert_eq!(quote("The \"Express\""), "\"The \"\"Express\"\"\"");
+    }
+
+    #[test]
+    fn test_write_zip() {
+        let bytes = write_zip(vec![
+            ("agency.txt", agency().unwrap()),
+            ("calendar.txt", calendar().unwrap()),
+        ])
+        .unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        for (name, expected) in [
+            ("agency.txt", agency().unwrap()),
+            ("calendar.txt", calendar().unwrap()),
+        ] {
+            let mut contents = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, expected);
+        }
+    }
+}
@@ -25,6 +25,7 @@ use crate::debug::DebugMode;
 use crate::sandbox::{GameplayMode, SandboxMode, TimeWarpScreen};
 
 mod crosswalks;
+mod gtfs;
 mod multiple_roads;
 mod roads;
 mod routes;
@@ -939,7 +940,9 @@ fn cmd_to_id(cmd: &EditCmd) -> Option<ID> {
     match cmd {
         EditCmd::ChangeRoad { r, .. } => Some(ID::Road(*r)),
         EditCmd::ChangeIntersection { i, .. } => Some(ID::Intersection(*i)),
-        EditCmd::ChangeRouteSchedule { .. } => None,
+        EditCmd::ChangeRouteSchedule { .. }
+        | EditCmd::ChangeRouteCapacity { .. }
+        | EditCmd::ChangeParkingPricing { .. } => None,
     }
 }
 
@@ -1,5 +1,6 @@
 use geom::{Duration, Time};
 use map_model::{EditCmd, TransitRouteID};
+use widgetry::tools::PopupMsg;
 use widgetry::{
     EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner, State, TextExt,
     VerticalAlignment, Widget,
@@ -8,6 +9,7 @@ use widgetry::{
 use crate::app::App;
 use crate::app::Transition;
 use crate::edit::apply_map_edits;
+use crate::edit::gtfs::{export_gtfs, StopTimes};
 
 pub struct RouteEditor {
     panel: Panel,
@@ -37,11 +39,25 @@ impl RouteEditor {
                         Duration::minutes(1),
                     ),
                 ]),
+                Widget::row(vec![
+                    "People per vehicle".text_widget(ctx),
+                    Spinner::widget(ctx, "capacity", (1, 1000), route.vehicle_capacity(), 1),
+                ]),
                 ctx.style()
                     .btn_solid_primary
                     .text("Apply")
                     .hotkey(Key::Enter)
                     .build_def(ctx),
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_outline
+                        .text("Export schedule to GTFS")
+                        .build_def(ctx),
+                    ctx.style()
+                        .btn_outline
+                        .text("Export simulated runs to GTFS")
+                        .build_def(ctx),
+                ]),
             ]))
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
             .build(ctx),
@@ -74,10 +90,36 @@ impl State<App> for RouteEditor {
                         old: app.primary.map.get_tr(self.route).spawn_times.clone(),
                         new: hourly_times,
                     });
+                    let route = app.primary.map.get_tr(self.route);
+                    let capacity: usize = self.panel.spinner("capacity");
+                    if capacity != route.vehicle_capacity() {
+                        edits.commands.push(EditCmd::ChangeRouteCapacity {
+                            id: self.route,
+                            old: route.capacity,
+                            new: Some(capacity),
+                        });
+                    }
                     apply_map_edits(ctx, app, edits);
 
                     return Transition::Pop;
                 }
+                "Export schedule to GTFS" | "Export simulated runs to GTFS" => {
+                    let stop_times = if x == "Export schedule to GTFS" {
+                        StopTimes::Scheduled
+                    } else {
+                        StopTimes::Simulated
+                    };
+                    return Transition::Push(match export_gtfs(app, stop_times) {
+                        Ok(path) => PopupMsg::new_state(
+                            ctx,
+                            "Transit network exported",
+                            vec![format!("GTFS feed exported to {}", path)],
+                        ),
+                        Err(err) => {
+                            PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()])
+                        }
+                    });
+                }
                 _ => unreachable!(),
             }
         }
@@ -1,4 +1,4 @@
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_gui::tools::FilePicker;
 use map_model::{
     ControlStopSign, ControlTrafficSignal, EditIntersectionControl, IntersectionID, StageType,
@@ -6,7 +6,7 @@ use map_model::{
 use widgetry::tools::{ChooseSomething, PopupMsg};
 use widgetry::{
     Choice, DrawBaselayer, EventCtx, Key, Line, Panel, SimpleState, Spinner, State, Text, TextExt,
-    Widget,
+    Toggle, Widget,
 };
 
 use crate::app::{App, Transition};
@@ -18,6 +18,8 @@ pub struct ChangeDuration {
     idx: usize,
 }
 
+const ACTUATED: &str = "actuate with vehicle detectors";
+
 impl ChangeDuration {
     pub fn new_state(
         ctx: &mut EventCtx,
@@ -26,6 +28,22 @@ impl ChangeDuration {
         idx: usize,
     ) -> Box<dyn State<App>> {
         let i = app.primary.map.get_i(signal.id);
+        let stage_type = &signal.stages[idx].stage_type;
+        // Suggest some reasonable actuated timing if the stage isn't actuated yet
+        let (is_actuated, max, passage, detector_setback) = match stage_type {
+            StageType::Actuated {
+                max,
+                passage,
+                detector_setback,
+                ..
+            } => (true, *max, *passage, *detector_setback),
+            _ => (
+                false,
+                2.0 * stage_type.simple_duration(),
+                Duration::seconds(3.0),
+                Distance::meters(30.0),
+            ),
+        };
         let panel = Panel::new_builder(Widget::col(vec![
             Widget::row(vec![
                 Line("How long should this stage last?")
@@ -39,7 +57,7 @@ impl ChangeDuration {
                     ctx,
                     "duration",
                     (signal.get_min_crossing_time(idx, i), Duration::minutes(5)),
-                    signal.stages[idx].stage_type.simple_duration(),
+                    stage_type.simple_duration(),
                     Duration::seconds(1.0),
                 ),
             ]),
@@ -47,18 +65,9 @@ impl ChangeDuration {
                 .secondary()
                 .into_widget(ctx),
             Widget::col(vec![
-                Text::from_all(match signal.stages[idx].stage_type {
-                    StageType::Fixed(_) => vec![
-                        Line("Fixed timing").small_heading(),
-                        Line(" (Adjust both values below to enable variable timing)"),
-                    ],
-                    StageType::Variable(_, _, _) => vec![
-                        Line("Variable timing").small_heading(),
-                        Line(" (Set either values below to 0 to use fixed timing."),
-                    ],
-                })
-                .into_widget(ctx)
-                .named("timing type"),
+                timing_type(stage_type)
+                    .into_widget(ctx)
+                    .named("timing type"),
                 Widget::row(vec![
                     "How much additional time can this stage last?"
                         .text_widget(ctx)
@@ -67,9 +76,9 @@ impl ChangeDuration {
                         ctx,
                         "additional",
                         (Duration::ZERO, Duration::minutes(5)),
-                        match signal.stages[idx].stage_type {
-                            StageType::Fixed(_) => Duration::ZERO,
-                            StageType::Variable(_, _, additional) => additional,
+                        match stage_type {
+                            StageType::Variable(_, _, additional) => *additional,
+                            _ => Duration::ZERO,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -82,9 +91,9 @@ impl ChangeDuration {
                         ctx,
                         "delay",
                         (Duration::ZERO, Duration::seconds(300.0)),
-                        match signal.stages[idx].stage_type {
-                            StageType::Fixed(_) => Duration::ZERO,
-                            StageType::Variable(_, delay, _) => delay,
+                        match stage_type {
+                            StageType::Variable(_, delay, _) => *delay,
+                            _ => Duration::ZERO,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -93,6 +102,46 @@ impl ChangeDuration {
             .padding(10)
             .bg(app.cs.inner_panel_bg)
             .outline(ctx.style().section_outline),
+            Widget::col(vec![
+                Toggle::checkbox(ctx, ACTUATED, None, is_actuated),
+                Widget::row(vec![
+                    "Maximum duration:".text_widget(ctx).centered_vert(),
+                    Spinner::widget(
+                        ctx,
+                        "max",
+                        (Duration::seconds(1.0), Duration::minutes(5)),
+                        max,
+                        Duration::seconds(1.0),
+                    ),
+                ]),
+                Widget::row(vec![
+                    "Extend by this much per detected vehicle:"
+                        .text_widget(ctx)
+                        .centered_vert(),
+                    Spinner::widget(
+                        ctx,
+                        "passage",
+                        (Duration::seconds(1.0), Duration::seconds(30.0)),
+                        passage,
+                        Duration::seconds(1.0),
+                    ),
+                ]),
+                Widget::row(vec![
+                    "Place detectors this far before the stop line:"
+                        .text_widget(ctx)
+                        .centered_vert(),
+                    Spinner::widget(
+                        ctx,
+                        "detector setback",
+                        (Distance::ZERO, Distance::meters(100.0)),
+                        detector_setback,
+                        Distance::meters(1.0),
+                    ),
+                ]),
+            ])
+            .padding(10)
+            .bg(app.cs.inner_panel_bg)
+            .outline(ctx.style().section_outline),
             ctx.style()
                 .btn_solid_primary
                 .text("Apply")
@@ -104,6 +153,45 @@ impl ChangeDuration {
     }
 }
 
+/// Reads the stage timing currently set in the panel.
+fn stage_type_from_panel(panel: &Panel) -> StageType {
+    let dt = panel.spinner("duration");
+    let delay = panel.spinner("delay");
+    let additional = panel.spinner("additional");
+    if panel.is_checked(ACTUATED) {
+        StageType::Actuated {
+            min: dt,
+            max: panel.spinner::<Duration>("max").max(dt),
+            passage: panel.spinner("passage"),
+            detector_setback: panel.spinner("detector setback"),
+        }
+    } else if delay == Duration::ZERO || additional == Duration::ZERO {
+        StageType::Fixed(dt)
+    } else {
+        StageType::Variable(dt, delay, additional)
+    }
+}
+
+fn timing_type(stage_type: &StageType) -> Text {
+    Text::from_all(match stage_type {
+        StageType::Actuated { max, passage, .. } => vec![
+            Line("Actuated timing").small_heading(),
+            Line(format!(
+                " (Extended by {} per detected vehicle, up to {})",
+                passage, max
+            )),
+        ],
+        StageType::Variable(_, _, _) => vec![
+            Line("Variable timing").small_heading(),
+            Line(" (Set either values below to 0 to use fixed timing."),
+        ],
+        _ => vec![
+            Line("Fixed timing").small_heading(),
+            Line(" (Adjust both values below to enable variable timing)"),
+        ],
+    })
+}
+
 impl SimpleState<App> for ChangeDuration {
     fn on_click(
         &mut self,
@@ -115,14 +203,7 @@ impl SimpleState<App> for ChangeDuration {
         match x {
             "close" => Transition::Pop,
             "Apply" => {
-                let dt = panel.spinner("duration");
-                let delay = panel.spinner("delay");
-                let additional = panel.spinner("additional");
-                let new_type = if delay == Duration::ZERO || additional == Duration::ZERO {
-                    StageType::Fixed(dt)
-                } else {
-                    StageType::Variable(dt, delay, additional)
-                };
+                let new_type = stage_type_from_panel(panel);
                 let idx = self.idx;
                 Transition::Multi(vec![
                     Transition::Pop,
@@ -144,22 +225,7 @@ impl SimpleState<App> for ChangeDuration {
         _: &mut App,
         panel: &mut Panel,
     ) -> Option<Transition> {
-        let new_label = Text::from_all(
-            if panel.spinner::<Duration>("delay") == Duration::ZERO
-                || panel.spinner::<Duration>("additional") == Duration::ZERO
-            {
-                vec![
-                    Line("Fixed timing").small_heading(),
-                    Line(" (Adjust both values below to enable variable timing)"),
-                ]
-            } else {
-                vec![
-                    Line("Variable timing").small_heading(),
-                    Line(" (Set either values below to 0 to use fixed timing."),
-                ]
-            },
-        )
-        .into_widget(ctx);
+        let new_label = timing_type(&stage_type_from_panel(panel)).into_widget(ctx);
         panel.replace(ctx, "timing type", new_label);
         None
     }
@@ -193,6 +259,7 @@ pub fn edit_entire_signal(
 
     let use_template = "use template";
     let all_walk = "add an all-walk stage at the end";
+    let ped_intervals = "change pedestrian signal intervals";
     let major_minor_timing = "use timing pattern for a major/minor intersection";
     let stop_sign = "convert to stop signs";
     let close = "close intersection for construction";
@@ -208,6 +275,7 @@ pub fn edit_entire_signal(
     let mut choices = vec![use_template.to_string()];
     if has_sidewalks {
         choices.push(all_walk.to_string());
+        choices.push(ped_intervals.to_string());
     }
     choices.push(major_minor_timing.to_string());
     // TODO Conflating stop signs and construction here
@@ -258,6 +326,53 @@ pub fn edit_entire_signal(
                     }
                 })),
             ]),
+            x if x == ped_intervals => Transition::Replace(ChooseSomething::new_state(
+                ctx,
+                "How should pedestrian signals behave?",
+                vec![
+                    Choice::new("WALK for the whole stage", None),
+                    Choice::new(
+                        "5s leading pedestrian interval",
+                        Some((Duration::seconds(5.0), false)),
+                    ),
+                    Choice::new("pedestrian call buttons", Some((Duration::ZERO, true))),
+                    Choice::new(
+                        "5s leading pedestrian interval, with call buttons",
+                        Some((Duration::seconds(5.0), true)),
+                    ),
+                ],
+                Box::new(move |choice, ctx, app| {
+                    let mut new_signal = app.primary.map.get_traffic_signal(i).clone();
+                    let result = if let Some((leading_interval, call_button)) = choice {
+                        new_signal.set_ped_intervals(
+                            leading_interval,
+                            call_button,
+                            app.primary.map.get_i(i),
+                        )
+                    } else {
+                        for stage in &mut new_signal.stages {
+                            stage.ped_intervals = None;
+                        }
+                        Ok(())
+                    };
+                    match result {
+                        Ok(()) => Transition::Multi(vec![
+                            Transition::Pop,
+                            Transition::ModifyState(Box::new(move |state, ctx, app| {
+                                let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                                editor.add_new_edit(ctx, app, 0, |ts| {
+                                    *ts = new_signal.clone();
+                                });
+                            })),
+                        ]),
+                        Err(err) => Transition::Replace(PopupMsg::new_state(
+                            ctx,
+                            "Error",
+                            vec![err.to_string()],
+                        )),
+                    }
+                }),
+            )),
             x if x == major_minor_timing => Transition::Replace(ChooseSomething::new_state(
                 ctx,
                 "Use what timing split?",
@@ -54,6 +54,7 @@ pub fn import(map: &Map, i: IntersectionID, bytes: &Vec<u8>) -> Result<ControlTr
                     protected_movements: BTreeSet::new(),
                     yield_movements: BTreeSet::new(),
                     stage_type: StageType::Fixed(Duration::seconds(rec.green_time as f64)),
+                    ped_intervals: None,
                 });
             }
             std::cmp::Ordering::Less => {
@@ -0,0 +1,191 @@
+use std::collections::BTreeSet;
+
+use geom::{Duration, Speed};
+use map_model::{make_green_wave, measure_progression, IntersectionID};
+use widgetry::tools::PopupMsg;
+use widgetry::{
+    Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Panel, RewriteColor,
+    SimpleState, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
+};
+
+use crate::app::{App, Transition};
+use crate::common::CommonState;
+use crate::edit::traffic_signals::fade_irrelevant;
+
+/// Pick a corridor of signals in order, then coordinate their offsets for a two-way green wave.
+pub struct GreenWave {
+    members: BTreeSet<IntersectionID>,
+    corridor: Vec<IntersectionID>,
+    labels: Drawable,
+}
+
+impl GreenWave {
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &App,
+        members: BTreeSet<IntersectionID>,
+        corridor: Vec<IntersectionID>,
+    ) -> Box<dyn State<App>> {
+        let map = &app.primary.map;
+        let mut batch = fade_irrelevant(app, &members);
+        for pair in corridor.windows(2) {
+            if let Some((roads, _)) = map.simple_path_btwn(pair[0], pair[1]) {
+                for r in roads {
+                    batch.push(app.cs.route, map.get_r(r).get_thick_polygon());
+                }
+            }
+        }
+        for (idx, i) in corridor.iter().enumerate() {
+            batch.append(
+                Text::from(format!("{}", idx + 1))
+                    .bg(Color::PURPLE)
+                    .render_autocropped(ctx)
+                    .color(RewriteColor::ChangeAlpha(0.8))
+                    .scale(0.3)
+                    .centered_on(map.get_i(*i).polygon.center()),
+            );
+        }
+
+        let mut col = vec![
+            Widget::row(vec![
+                Line("Coordinate a green wave")
+                    .small_heading()
+                    .into_widget(ctx),
+                ctx.style().btn_close_widget(ctx),
+            ]),
+            "Select signals along the corridor, in order".text_widget(ctx),
+        ];
+        col.push(Widget::row(vec![
+            "Target speed (mph):".text_widget(ctx).centered_vert(),
+            Spinner::widget(ctx, "speed", (5, 70), 25, 1),
+        ]));
+        col.push(Widget::row(vec![
+            "Cycle length:".text_widget(ctx).centered_vert(),
+            Spinner::widget(
+                ctx,
+                "cycle length",
+                (Duration::ZERO, Duration::minutes(5)),
+                Duration::ZERO,
+                Duration::seconds(5.0),
+            ),
+        ]));
+        col.push(
+            Line("A cycle length of 0 picks one automatically")
+                .secondary()
+                .into_widget(ctx),
+        );
+        col.push(Widget::row(vec![
+            ctx.style()
+                .btn_solid_primary
+                .text("Make green wave")
+                .hotkey(Key::Enter)
+                .disabled(corridor.len() < 2)
+                .build_def(ctx),
+            ctx.style()
+                .btn_outline
+                .text("Start over")
+                .disabled(corridor.is_empty())
+                .build_def(ctx),
+        ]));
+
+        let panel = Panel::new_builder(Widget::col(col))
+            .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+            .build(ctx);
+        <dyn SimpleState<_>>::new_state(
+            panel,
+            Box::new(GreenWave {
+                members,
+                corridor,
+                labels: ctx.upload(batch),
+            }),
+        )
+    }
+}
+
+impl SimpleState<App> for GreenWave {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        panel: &mut Panel,
+    ) -> Transition {
+        match x {
+            "close" => Transition::Pop,
+            "Start over" => Transition::Replace(GreenWave::new_state(
+                ctx,
+                app,
+                self.members.clone(),
+                Vec::new(),
+            )),
+            "Make green wave" => {
+                let speed = Speed::miles_per_hour(panel.spinner::<usize>("speed") as f64);
+                let cycle_length = panel.spinner::<Duration>("cycle length");
+                let cycle_length = if cycle_length == Duration::ZERO {
+                    None
+                } else {
+                    Some(cycle_length)
+                };
+                let map = &app.primary.map;
+                let before = measure_progression(map, &self.corridor, speed);
+                match make_green_wave(map, &self.corridor, speed, cycle_length) {
+                    Ok((signals, after)) => {
+                        // TODO Like tuning offsets manually, these changes won't show up in the
+                        // undo stack.
+                        for ts in signals {
+                            app.primary.map.incremental_edit_traffic_signal(ts);
+                        }
+                        let mut lines =
+                            vec![format!("Cycle length: {}", after.cycle_length.unwrap())];
+                        if let Ok(before) = before {
+                            lines.push(format!(
+                                "Before: {}% outbound, {}% inbound make it without stopping",
+                                (100.0 * before.outbound).round(),
+                                (100.0 * before.inbound).round()
+                            ));
+                        }
+                        lines.push(format!(
+                            "After: {}% outbound, {}% inbound make it without stopping",
+                            (100.0 * after.outbound).round(),
+                            (100.0 * after.inbound).round()
+                        ));
+                        Transition::Replace(PopupMsg::new_state(ctx, "Green wave", lines))
+                    }
+                    Err(err) => {
+                        Transition::Push(PopupMsg::new_state(ctx, "Error", vec![err.to_string()]))
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn on_mouseover(&mut self, ctx: &mut EventCtx, app: &mut App) {
+        app.primary.current_selection = app.mouseover_unzoomed_intersections(ctx).filter(|id| {
+            let i = id.as_intersection();
+            self.members.contains(&i) && !self.corridor.contains(&i)
+        });
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+        if let Some(i) = app.click_on_intersection(ctx, "add to corridor") {
+            let mut corridor = self.corridor.clone();
+            corridor.push(i);
+            return Transition::Replace(GreenWave::new_state(
+                ctx,
+                app,
+                self.members.clone(),
+                corridor,
+            ));
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        CommonState::draw_osd(g, app);
+
+        g.redraw(&self.labels);
+    }
+}
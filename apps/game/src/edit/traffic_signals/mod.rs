@@ -24,6 +24,7 @@ use crate::sandbox::GameplayMode;
 
 mod edits;
 mod gmns;
+mod green_wave;
 mod offsets;
 mod picker;
 mod preview;
@@ -228,6 +229,14 @@ impl State<App> for TrafficSignalEditor {
                         self.members.clone(),
                     ));
                 }
+                "Coordinate a green wave" => {
+                    return Transition::Push(green_wave::GreenWave::new_state(
+                        ctx,
+                        app,
+                        self.members.clone(),
+                        Vec::new(),
+                    ));
+                }
                 "Add a new stage" => {
                     self.add_new_edit(ctx, app, num_stages, |ts| {
                         ts.stages.push(Stage::new());
@@ -683,6 +692,12 @@ fn make_side_panel(
                     "Stage duration: {}, {}, {} (variable)",
                     min, delay, additional
                 ),
+                StageType::Actuated {
+                    min, max, passage, ..
+                } => format!(
+                    "Stage duration: {} to {}, extended by {} (actuated)",
+                    min, max, passage
+                ),
             }
             .text_widget(ctx)
             .centered_vert(),
@@ -724,6 +739,7 @@ fn make_side_panel(
                 match canonical_signal.stages[idx].stage_type {
                     StageType::Fixed(d) => format!("{}", d),
                     StageType::Variable(min, _, _) => format!("{} (v)", min),
+                    StageType::Actuated { min, .. } => format!("{} (a)", min),
                 },
             )))
             .render(ctx),
@@ -774,11 +790,18 @@ fn make_side_panel(
                 .hotkey(Key::E)
                 .build_def(ctx)
         } else {
-            ctx.style()
-                .btn_outline
-                .text("Tune offsets between signals")
-                .hotkey(Key::O)
-                .build_def(ctx)
+            Widget::row(vec![
+                ctx.style()
+                    .btn_outline
+                    .text("Tune offsets between signals")
+                    .hotkey(Key::O)
+                    .build_def(ctx),
+                ctx.style()
+                    .btn_outline
+                    .text("Coordinate a green wave")
+                    .hotkey(Key::G)
+                    .build_def(ctx),
+            ])
         },
     ]));
 
@@ -2,7 +2,9 @@ use std::collections::BTreeMap;
 
 use geom::{Angle, Circle, Distance, Speed, Time};
 use map_model::{BuildingID, LaneID, OffstreetParking, Traversable, SIDEWALK_THICKNESS};
-use sim::{DrawPedestrianInput, PedestrianID, PersonID, TripResult, VehicleType};
+use sim::{
+    Analytics, DrawPedestrianInput, NoiseLevels, PedestrianID, PersonID, TripResult, VehicleType,
+};
 use synthpop::TripMode;
 use widgetry::{Color, EventCtx, Line, Text, TextExt, Widget};
 
@@ -56,6 +58,20 @@ fn info_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BuildingI
         kv.push(("Parking", "None".to_string()));
     }
 
+    let now = app.primary.sim.time();
+    let noise =
+        |analytics: &Analytics| NoiseLevels::for_building(analytics, &app.primary.map, now, id);
+    let mut describe_noise = match noise(app.primary.sim.get_analytics()) {
+        Some(level) => format!("{:.0} dB(A) in the loudest hour", level),
+        None => "No traffic nearby yet".to_string(),
+    };
+    if app.has_prebaked().is_some() {
+        if let Some(level) = noise(app.prebaked()) {
+            describe_noise.push_str(&format!(" ({:.0} dB(A) before changes)", level));
+        }
+    }
+    kv.push(("Road traffic noise", describe_noise));
+
     rows.extend(make_table(ctx, kv));
 
     let mut txt = Text::new();
@@ -158,6 +158,29 @@ fn delay_body(
 
     rows.push(delay_plot(ctx, app, id, opts, fan_chart));
 
+    let data = if opts.show_before {
+        app.prebaked()
+    } else {
+        app.primary.sim.get_analytics()
+    };
+    let limit = if opts.show_end_of_day {
+        app.primary.sim.get_end_of_day()
+    } else {
+        app.primary.sim.time()
+    };
+    rows.push(
+        match data.pedestrian_crossing_delay(limit, id) {
+            Some((mean, worst, count)) => Line(format!(
+                "{} pedestrians crossed, waiting {} on average and {} at worst",
+                prettyprint_usize(count),
+                mean,
+                worst
+            )),
+            None => Line("No pedestrians have crossed yet"),
+        }
+        .into_widget(ctx),
+    );
+
     Widget::col(rows)
 }
 
@@ -339,9 +362,40 @@ fn traffic_signal_body(ctx: &mut EventCtx, app: &App, id: IntersectionID) -> Wid
                     delay,
                     additional
                 )),
+                StageType::Actuated {
+                    min,
+                    max,
+                    passage,
+                    detector_setback,
+                } => Line(format!(
+                    "Stage {}: {} to {}, extended by {} per vehicle detected {} back (actuated)",
+                    idx + 1,
+                    min,
+                    max,
+                    passage,
+                    detector_setback
+                )),
             }
             .into_widget(ctx),
         );
+        if let Some(ref p) = stage.ped_intervals {
+            let mut line = if stage.is_ped_scramble() {
+                "Pedestrian scramble: ".to_string()
+            } else {
+                "Pedestrians: ".to_string()
+            };
+            if p.leading_interval > Duration::ZERO {
+                line.push_str(&format!("{} head start, ", p.leading_interval));
+            }
+            line.push_str(&format!(
+                "{} WALK, {} flashing DON'T WALK",
+                p.walk, p.flashing_dont_walk
+            ));
+            if p.call_button {
+                line.push_str(", call button");
+            }
+            rows.push(Line(line).secondary().into_widget(ctx));
+        }
 
         {
             let mut orig_batch = GeomBatch::new();
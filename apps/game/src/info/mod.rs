@@ -154,8 +154,10 @@ impl Tab {
                         "schedule" => Tab::PersonSchedule(p),
                         _ => unreachable!(),
                     }
-                } else if c.vehicle_type == VehicleType::Bus || c.vehicle_type == VehicleType::Train
-                {
+                } else if matches!(
+                    c.vehicle_type,
+                    VehicleType::Bus | VehicleType::Train | VehicleType::Tram
+                ) {
                     match app.session.info_panel_tab["bus"] {
                         "status" => Tab::TransitVehicleStatus(c),
                         _ => unreachable!(),
@@ -437,7 +439,7 @@ impl InfoPanel {
                     // Some objects are much wider/taller than others
                     let multiplier = match id {
                         ID::Car(c) => {
-                            if c.vehicle_type == VehicleType::Bike {
+                            if c.vehicle_type.rides_bike_lanes() {
                                 3.0
                             } else {
                                 0.75
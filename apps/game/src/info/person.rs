@@ -147,8 +147,11 @@ fn trips_body(
                     ctx.prerender,
                     match trip.mode {
                         TripMode::Walk => "system/assets/meters/pedestrian.svg",
-                        TripMode::Bike => "system/assets/meters/bike.svg",
-                        TripMode::Drive => "system/assets/meters/car.svg",
+                        TripMode::Bike | TripMode::Scooter => "system/assets/meters/bike.svg",
+                        TripMode::Drive
+                        | TripMode::ParkAndRide
+                        | TripMode::RideHail
+                        | TripMode::Freight => "system/assets/meters/car.svg",
                         TripMode::Transit => "system/assets/meters/bus.svg",
                     },
                 )
@@ -344,6 +347,9 @@ fn bio_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: PersonID)
     for v in &person.vehicles {
         if v.vehicle_type == VehicleType::Bike {
             has_bike = true;
+        } else if v.vehicle_type == VehicleType::Scooter {
+            // Shared scooters aren't really owned by anybody
+            continue;
         } else if app.primary.sim.lookup_parked_car(v.id).is_some() {
             rows.push(
                 ctx.style()
@@ -423,6 +429,7 @@ fn schedule_body(ctx: &mut EventCtx, app: &App, id: PersonID) -> Widget {
             }
             TripEndpoint::Border(_) => "off-map".to_string(),
             TripEndpoint::SuddenlyAppear(_) => "suddenly appear".to_string(),
+            TripEndpoint::Dockless(_) => "a dockless scooter spot".to_string(),
         };
         rows.push(
             Text::from(format!("  Spends {} at {}", trip.departure - last_t, at)).into_widget(ctx),
@@ -448,6 +455,7 @@ fn schedule_body(ctx: &mut EventCtx, app: &App, id: PersonID) -> Widget {
         }
         TripEndpoint::Border(_) => "off-map".to_string(),
         TripEndpoint::SuddenlyAppear(_) => "suddenly disappear".to_string(),
+        TripEndpoint::Dockless(_) => "a dockless scooter spot".to_string(),
     };
     rows.push(
         Text::from(format!(
@@ -627,10 +635,18 @@ fn header(
                         ("walking", Some("system/assets/meters/pedestrian.svg"))
                     }
                     AgentID::Car(c) => match c.vehicle_type {
-                        VehicleType::Car => ("driving", Some("system/assets/meters/car.svg")),
+                        VehicleType::Car | VehicleType::Truck => {
+                            ("driving", Some("system/assets/meters/car.svg"))
+                        }
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
-                        VehicleType::Bus | VehicleType::Train => unreachable!(),
+                        VehicleType::Scooter => ("scooting", Some("system/assets/meters/bike.svg")),
+                        VehicleType::Bus | VehicleType::Train | VehicleType::Tram => {
+                            unreachable!()
+                        }
                     },
+                    AgentID::BusPassenger(_, c) if c.vehicle_type == VehicleType::Car => {
+                        ("riding a ride-hail", Some("system/assets/meters/car.svg"))
+                    }
                     AgentID::BusPassenger(_, _) => {
                         ("riding a bus", Some("system/assets/meters/bus.svg"))
                     }
@@ -640,6 +656,13 @@ fn header(
                 ("...", None)
             },
         ),
+        PersonState::Dockless(pos) => {
+            ctx.canvas.center_on_map_pt(pos.pt(&app.primary.map));
+            (
+                None,
+                ("at a dockless point", Some("system/assets/meters/bike.svg")),
+            )
+        }
         PersonState::OffMap => (None, ("off map", None)),
     };
 
@@ -707,6 +730,11 @@ fn current_status(ctx: &EventCtx, person: &Person, map: &Map) -> Widget {
             format!("Currently inside {}", map.get_b(b).address).text_widget(ctx)
         }
         PersonState::Trip(_) => unreachable!(),
+        PersonState::Dockless(pos) => format!(
+            "Currently at a dockless point along {}",
+            map.get_parent(pos.lane()).get_name(None)
+        )
+        .text_widget(ctx),
         PersonState::OffMap => "Currently outside the map boundaries".text_widget(ctx),
     })
     .margin_vert(16)
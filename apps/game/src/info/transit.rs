@@ -55,6 +55,7 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: TransitSt
 
     let mut boardings: Counter<TransitRouteID> = Counter::new();
     let mut alightings: Counter<TransitRouteID> = Counter::new();
+    let mut left_behind: Counter<TransitRouteID> = Counter::new();
     if let Some(list) = app.primary.sim.get_analytics().passengers_boarding.get(&id) {
         for (_, r, _) in list {
             boardings.inc(*r);
@@ -71,13 +72,25 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: TransitSt
             alightings.inc(*r);
         }
     }
+    if let Some(list) = app
+        .primary
+        .sim
+        .get_analytics()
+        .passengers_left_behind
+        .get(&id)
+    {
+        for (_, r) in list {
+            left_behind.inc(*r);
+        }
+    }
     let mut txt = Text::new();
     txt.add_line("Total");
     txt.append(
         Line(format!(
-            ": {} boardings, {} alightings",
+            ": {} boardings, {} alightings, {} left behind by full vehicles",
             prettyprint_usize(boardings.sum()),
-            prettyprint_usize(alightings.sum())
+            prettyprint_usize(alightings.sum()),
+            prettyprint_usize(left_behind.sum())
         ))
         .secondary(),
     );
@@ -85,9 +98,10 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: TransitSt
         txt.add_line(format!("Route {}", r.short_name));
         txt.append(
             Line(format!(
-                ": {} boardings, {} alightings",
+                ": {} boardings, {} alightings, {} left behind",
                 prettyprint_usize(boardings.get(r.id)),
-                prettyprint_usize(alightings.get(r.id))
+                prettyprint_usize(alightings.get(r.id)),
+                prettyprint_usize(left_behind.get(r.id))
             ))
             .secondary(),
         );
@@ -131,8 +145,9 @@ fn bus_status_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
 
     rows.push(
         Line(format!(
-            "Currently has {} passengers",
+            "Currently has {} passengers, out of {} that fit",
             app.primary.sim.num_transit_passengers(id),
+            app.primary.sim.transit_capacity(id),
         ))
         .into_widget(ctx),
     );
@@ -256,6 +271,28 @@ fn route_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: TransitR
         .into_widget(ctx),
     );
 
+    let now = app.primary.sim.time();
+    let analytics = app.primary.sim.get_analytics();
+    if let Some(headways) = analytics.headway_regularity(now, id) {
+        rows.push(
+            Text::from_all(vec![
+                Line("Headways"),
+                Line(format!(
+                    ": {} on average, varying by {:.0}%. {} of {} arrivals were bunched.",
+                    headways.mean_headway,
+                    100.0 * headways.coefficient_of_variation,
+                    prettyprint_usize(headways.num_bunched),
+                    prettyprint_usize(headways.num_headways)
+                ))
+                .secondary(),
+            ])
+            .wrap_to_pct(ctx, 20)
+            .into_widget(ctx),
+        );
+    }
+    let loads = analytics.load_factor_per_segment(now, id);
+    let adherence = analytics.schedule_adherence(now, id);
+
     rows.push(format!("{} stops", route.stops.len()).text_widget(ctx));
     {
         let i = map.get_i(map.get_l(route.start).src_i);
@@ -273,22 +310,44 @@ fn route_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: TransitR
     for (idx, ts) in route.stops.iter().enumerate() {
         let ts = map.get_ts(*ts);
         let name = format!("Stop {}: {}", idx + 1, ts.name);
+        let mut txt = Text::from_all(vec![
+            Line(&ts.name),
+            Line(format!(
+                ": {} boardings, {} alightings, {} currently waiting",
+                prettyprint_usize(boardings.get(ts.id)),
+                prettyprint_usize(alightings.get(ts.id)),
+                prettyprint_usize(waiting.get(ts.id))
+            ))
+            .secondary(),
+        ]);
+        if let Some((mean, max)) = loads.get(&ts.id) {
+            txt.add_line(
+                Line(format!(
+                    "  Leaving: {:.0}% full on average, {:.0}% at most",
+                    100.0 * mean,
+                    100.0 * max
+                ))
+                .secondary(),
+            );
+        }
+        if let Some(adherence) = adherence.get(&ts.id) {
+            txt.add_line(
+                Line(format!(
+                    "  Delay: {} on average, {} at most. {} of {} arrivals on time",
+                    adherence.mean_delay,
+                    adherence.max_delay,
+                    prettyprint_usize(adherence.num_on_time),
+                    prettyprint_usize(adherence.num_arrivals)
+                ))
+                .secondary(),
+            );
+        }
         rows.push(Widget::row(vec![
             ctx.style()
                 .btn_plain
                 .icon("system/assets/tools/pin.svg")
                 .build_widget(ctx, &name),
-            Text::from_all(vec![
-                Line(&ts.name),
-                Line(format!(
-                    ": {} boardings, {} alightings, {} currently waiting",
-                    prettyprint_usize(boardings.get(ts.id)),
-                    prettyprint_usize(alightings.get(ts.id)),
-                    prettyprint_usize(waiting.get(ts.id))
-                ))
-                .secondary(),
-            ])
-            .into_widget(ctx),
+            txt.into_widget(ctx),
         ]));
         details.warpers.insert(name, ID::TransitStop(ts.id));
     }
@@ -327,6 +327,28 @@ pub fn finished(
             waiting.to_string(&app.opts.units).text_widget(ctx),
         ]));
 
+        // Break down multimodal trips
+        if trip.mode == TripMode::ParkAndRide {
+            let analytics = if open_trips[&id].show_after {
+                app.primary.sim.get_analytics()
+            } else {
+                app.prebaked()
+            };
+            let mut txt = Text::new();
+            for (category, dt) in analytics.time_per_leg(id) {
+                txt.add_line(Line(format!(
+                    "{}: {}",
+                    category,
+                    dt.to_string(&app.opts.units)
+                )));
+            }
+            col.push(Widget::custom_row(vec![
+                Widget::custom_row(vec![Line("Time per leg").secondary().into_widget(ctx)])
+                    .force_width_window_pct(ctx, col_width),
+                txt.into_widget(ctx),
+            ]));
+        }
+
         col.push(Widget::custom_row(vec![
             Widget::custom_row(vec![Line("Purpose").secondary().into_widget(ctx)])
                 .force_width_window_pct(ctx, col_width),
@@ -401,6 +423,7 @@ fn describe_problems(
         TripMode::Walk => {
             let mut arterial_intersection_crossings = 0;
             let mut overcrowding = 0;
+            let mut jaywalking = 0;
             let empty = Vec::new();
             for (_, problem) in analytics.problems_per_trip.get(&id).unwrap_or(&empty) {
                 match problem {
@@ -410,6 +433,9 @@ fn describe_problems(
                     Problem::PedestrianOvercrowding(_) => {
                         overcrowding += 1;
                     }
+                    Problem::Jaywalking(_) | Problem::MidBlockCrossing(_) => {
+                        jaywalking += 1;
+                    }
                     _ => {}
                 }
             }
@@ -424,6 +450,12 @@ fn describe_problems(
                 .secondary(),
             ]);
             txt.add_line(Line(format!("{overcrowding} overcrowded sidewalks crossed")).secondary());
+            txt.add_line(
+                Line(format!(
+                    "{jaywalking} crossings against the signal or mid-block"
+                ))
+                .secondary(),
+            );
 
             Widget::custom_row(vec![
                 Line("Risk Exposure")
@@ -434,9 +466,10 @@ fn describe_problems(
                 txt.into_widget(ctx),
             ])
         }
-        TripMode::Bike => {
+        TripMode::Bike | TripMode::Scooter => {
             let mut count_complex_intersections = 0;
             let mut count_overtakes = 0;
+            let mut count_close_passes = 0;
             let empty = Vec::new();
             for (_, problem) in analytics.problems_per_trip.get(&id).unwrap_or(&empty) {
                 match problem {
@@ -446,6 +479,9 @@ fn describe_problems(
                     Problem::OvertakeDesired(_) => {
                         count_overtakes += 1;
                     }
+                    Problem::ClosePass(_, _) => {
+                        count_close_passes += 1;
+                    }
                     _ => {}
                 }
             }
@@ -468,6 +504,15 @@ fn describe_problems(
                 }
                 .secondary(),
             ]);
+            txt.add_appended(vec![
+                Line(count_close_passes.to_string()),
+                if count_close_passes == 1 {
+                    Line(" close pass")
+                } else {
+                    Line(" close passes")
+                }
+                .secondary(),
+            ]);
 
             Widget::custom_row(vec![
                 Line("Risk Exposure")
@@ -573,6 +618,31 @@ fn draw_problems(
                     (id, *time),
                 ));
             }
+            Problem::ClosePass(on, clearance) => {
+                let pt = on.get_polyline(map).middle();
+                details.draw_extra.unzoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .centered_on(pt)
+                        .color(RewriteColor::ChangeAlpha(0.8)),
+                );
+                details.draw_extra.zoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .scale(0.5)
+                        .color(RewriteColor::ChangeAlpha(0.5))
+                        .centered_on(pt),
+                );
+                details.tooltips.push((
+                    match on {
+                        Traversable::Lane(l) => map.get_parent(*l).get_thick_polygon(),
+                        Traversable::Turn(t) => map.get_i(t.parent).polygon.clone(),
+                    },
+                    Text::from(format!(
+                        "A vehicle passed this cyclist with only {} of room.",
+                        clearance.to_string(&app.opts.units)
+                    )),
+                    (id, *time),
+                ));
+            }
             Problem::ArterialIntersectionCrossing(t) => {
                 let t = map.get_t(*t);
 
@@ -619,6 +689,44 @@ fn draw_problems(
                     (id, *time),
                 ));
             }
+            Problem::Jaywalking(t) => {
+                let geom = map.get_t(*t).geom.make_polygons(Distance::meters(10.0));
+                details.draw_extra.unzoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .centered_on(geom.center())
+                        .color(RewriteColor::ChangeAlpha(0.8)),
+                );
+                details.draw_extra.zoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .scale(0.5)
+                        .color(RewriteColor::ChangeAlpha(0.5))
+                        .centered_on(geom.center()),
+                );
+                details.tooltips.push((
+                    geom,
+                    Text::from("This pedestrian gave up waiting and crossed against the signal."),
+                    (id, *time),
+                ));
+            }
+            Problem::MidBlockCrossing(pos) => {
+                let pt = pos.pt(map);
+                details.draw_extra.unzoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .centered_on(pt)
+                        .color(RewriteColor::ChangeAlpha(0.8)),
+                );
+                details.draw_extra.zoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .scale(0.5)
+                        .color(RewriteColor::ChangeAlpha(0.5))
+                        .centered_on(pt),
+                );
+                details.tooltips.push((
+                    map.get_parent(pos.lane()).get_thick_polygon(),
+                    Text::from("This pedestrian crossed the road away from any crosswalk."),
+                    (id, *time),
+                ));
+            }
         }
     }
 }
@@ -722,6 +830,11 @@ fn make_timeline(
                         "system/assets/timeline/waiting_for_bus.svg"
                     }
                     TripPhaseType::RidingBus(_, _, _) => "system/assets/timeline/riding_bus.svg",
+                    // No dedicated icons yet
+                    TripPhaseType::WaitingForRideHail => {
+                        "system/assets/timeline/waiting_for_bus.svg"
+                    }
+                    TripPhaseType::RidingRideHail(_) => "system/assets/timeline/driving.svg",
                     TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
                     TripPhaseType::DelayedStart => "system/assets/timeline/delayed_start.svg",
                 },
@@ -991,5 +1104,13 @@ fn endpoint(endpt: &TripEndpoint, app: &App) -> (ID, Pt2D, String) {
                 pos.dist_along().to_string(&app.opts.units)
             ),
         ),
+        TripEndpoint::Dockless(pos) => (
+            ID::Lane(pos.lane()),
+            pos.pt(&app.primary.map),
+            format!(
+                "dockless spot {} along",
+                pos.dist_along().to_string(&app.opts.units)
+            ),
+        ),
     }
 }
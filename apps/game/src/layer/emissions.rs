@@ -0,0 +1,211 @@
+use abstutil::Counter;
+use geom::Time;
+use map_gui::tools::{make_heatmap, ColorNetwork, HeatmapOptions};
+use sim::Pollutant;
+use widgetry::mapspace::ToggleZoomed;
+use widgetry::tools::ColorLegend;
+use widgetry::{Choice, EventCtx, GfxCtx, Line, Outcome, Panel, Text, Toggle, Widget};
+
+use crate::app::App;
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+
+// Keep the heatmap responsive on large maps
+const MAX_HEATMAP_PTS: f64 = 50_000.0;
+
+/// Where vehicles have emitted the most of some pollutant since midnight.
+pub struct AirPollution {
+    time: Time,
+    opts: Options,
+    draw: ToggleZoomed,
+    panel: Panel,
+}
+
+impl Layer for AirPollution {
+    fn name(&self) -> Option<&'static str> {
+        Some("air pollution")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        if app.primary.sim.time() != self.time {
+            let mut new = AirPollution::new(ctx, app, self.opts.clone());
+            new.panel.restore(ctx, &self.panel);
+            *self = new;
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            },
+            _ => {
+                let new_opts = self.options();
+                if self.opts != new_opts {
+                    *self = AirPollution::new(ctx, app, new_opts);
+                }
+            }
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+        self.draw.draw(g);
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw.unzoomed);
+    }
+}
+
+impl AirPollution {
+    pub fn new(ctx: &mut EventCtx, app: &App, opts: Options) -> AirPollution {
+        let map = &app.primary.map;
+        let now = app.primary.sim.time();
+        let per_road = app
+            .primary
+            .sim
+            .get_analytics()
+            .emissions
+            .total_per_road(now);
+        let total: f64 = per_road.values().map(|e| e.get(opts.pollutant)).sum();
+
+        let (draw, legend) = if let Some(ref o) = opts.heatmap {
+            // Scatter points along each road, in proportion to what was emitted there
+            let per_pt = (total / MAX_HEATMAP_PTS).max(f64::EPSILON);
+            let mut pts = Vec::new();
+            for (r, amount) in &per_road {
+                let n = (amount.get(opts.pollutant) / per_pt).round() as usize;
+                if n == 0 {
+                    continue;
+                }
+                let pl = &map.get_r(*r).center_pts;
+                let step = pl.length() / (n as f64);
+                for i in 0..n {
+                    pts.push(pl.must_dist_along(step * (i as f64 + 0.5)).0);
+                }
+            }
+            let mut draw = ToggleZoomed::builder();
+            let legend = make_heatmap(ctx, &mut draw.unzoomed, map.get_bounds(), pts, o);
+            (draw.build(ctx), legend)
+        } else {
+            let mut counter = Counter::new();
+            for (r, amount) in &per_road {
+                counter.add(*r, amount.get(opts.pollutant).round() as usize);
+            }
+            let mut colorer = ColorNetwork::new(app);
+            colorer.ranked_roads(counter, &app.cs.good_to_bad_red);
+            (
+                colorer.build(ctx),
+                ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["0", "highest"]),
+            )
+        };
+
+        let panel = make_controls(ctx, app, &opts, total, legend);
+        AirPollution {
+            time: now,
+            opts,
+            draw,
+            panel,
+        }
+    }
+
+    fn options(&self) -> Options {
+        let heatmap = if self.panel.is_checked("Show heatmap") {
+            Some(HeatmapOptions::from_controls(&self.panel))
+        } else {
+            None
+        };
+        Options {
+            heatmap,
+            pollutant: self.panel.dropdown_value("pollutant"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Options {
+    // If None, color roads instead
+    pub heatmap: Option<HeatmapOptions>,
+    pub pollutant: Pollutant,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            heatmap: Some(HeatmapOptions::new()),
+            pollutant: Pollutant::NOx,
+        }
+    }
+}
+
+fn make_controls(
+    ctx: &mut EventCtx,
+    app: &App,
+    opts: &Options,
+    total: f64,
+    legend: Widget,
+) -> Panel {
+    let now = app.primary.sim.time();
+    let mut txt = Text::from(Line(format!(
+        "{} emitted since midnight",
+        describe_amount(opts.pollutant, total)
+    )));
+    if app.has_prebaked().is_some() {
+        // The baseline already ran through the whole current hour. Assume emissions were spread
+        // evenly over it, and only count the part up to now.
+        let per_hour = app.prebaked().emissions.total_per_hour(now);
+        let current_hour = per_hour.len() - 1;
+        let before: f64 = per_hour
+            .into_iter()
+            .enumerate()
+            .map(|(hour, e)| {
+                if hour == current_hour {
+                    e.get(opts.pollutant) * (now.inner_seconds() / 3600.0).fract()
+                } else {
+                    e.get(opts.pollutant)
+                }
+            })
+            .sum();
+        txt.add_line(
+            Line(format!(
+                "{} before any changes",
+                describe_amount(opts.pollutant, before)
+            ))
+            .secondary(),
+        );
+    }
+    txt.add_line(
+        Line("Rough estimates from vehicle speeds; tailpipe and tyre/brake wear only").secondary(),
+    );
+
+    let mut col = vec![
+        header(ctx, "Air pollution"),
+        Widget::dropdown(
+            ctx,
+            "pollutant",
+            opts.pollutant,
+            Pollutant::all()
+                .into_iter()
+                .map(|p| Choice::new(p.name(), p))
+                .collect(),
+        ),
+        txt.wrap_to_pct(ctx, 15).into_widget(ctx),
+        Toggle::switch(ctx, "Show heatmap", None, opts.heatmap.is_some()),
+    ];
+    if let Some(ref o) = opts.heatmap {
+        col.extend(o.to_controls(ctx, legend));
+    } else {
+        col.push(legend);
+    }
+
+    Panel::new_builder(Widget::col(col))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx)
+}
+
+fn describe_amount(pollutant: Pollutant, x: f64) -> String {
+    match pollutant {
+        Pollutant::CO2 => format!("{:.1} kg CO2", x / 1000.0),
+        Pollutant::Energy => format!("{:.1} GJ of energy", x / 1000.0),
+        _ => format!("{:.1} {} {}", x, pollutant.unit(), pollutant.name()),
+    }
+}
@@ -9,8 +9,10 @@ use crate::app::{App, Transition};
 use crate::sandbox::dashboards;
 
 pub mod elevation;
+mod emissions;
 pub mod favorites;
 pub mod map;
+mod noise;
 mod pandemic;
 mod parking;
 mod population;
@@ -110,6 +112,8 @@ impl PickLayer {
                     btn("traffic jams", Key::J),
                     btn("cycling activity", Key::B),
                     btn("pedestrian crowding", Key::C),
+                    btn("air pollution", Key::I),
+                    btn("noise", Key::Q),
                 ]),
                 Widget::col(vec![
                     "Map".text_widget(ctx),
@@ -167,6 +171,13 @@ impl State<App> for PickLayer {
                 "None" => {
                     app.primary.layer = None;
                 }
+                "air pollution" => {
+                    app.primary.layer = Some(Box::new(emissions::AirPollution::new(
+                        ctx,
+                        app,
+                        emissions::Options::new(),
+                    )));
+                }
                 "amenities" => {
                     app.primary.layer = Some(Box::new(map::Static::amenities(ctx, app)));
                 }
@@ -200,6 +211,9 @@ impl State<App> for PickLayer {
                 "favorite buildings" => {
                     app.primary.layer = Some(Box::new(favorites::ShowFavorites::new(ctx, app)));
                 }
+                "noise" => {
+                    app.primary.layer = Some(Box::new(noise::Noise::new(ctx, app)));
+                }
                 "pandemic model" => {
                     app.primary.layer = Some(Box::new(pandemic::Pandemic::new(
                         ctx,
@@ -0,0 +1,214 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use abstutil::prettyprint_usize;
+use geom::{Duration, Time};
+use map_gui::tools::ColorDiscrete;
+use sim::{NoiseLevels, HIGH_NOISE_THRESHOLD, NOISE_THRESHOLD};
+use widgetry::mapspace::ToggleZoomed;
+use widgetry::tools::PopupMsg;
+use widgetry::{Color, EventCtx, GfxCtx, Line, Outcome, Panel, Text, Widget};
+
+use crate::app::{App, Transition};
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+use crate::ID;
+
+// Estimating the noise at every building is slow, so don't redo it constantly while the
+// simulation runs
+const RECALCULATE_EVERY: Duration = Duration::const_seconds(300.0);
+
+/// Road traffic noise along roads and at building facades, and how many residents are exposed.
+pub struct Noise {
+    time: Time,
+    after: NoiseLevels,
+    before: Option<NoiseLevels>,
+    tooltip: Option<Text>,
+    draw: ToggleZoomed,
+    panel: Panel,
+}
+
+impl Layer for Noise {
+    fn name(&self) -> Option<&'static str> {
+        Some("noise")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        let now = app.primary.sim.time();
+        if now < self.time || now - self.time >= RECALCULATE_EVERY {
+            let mut new = Noise::new(ctx, app);
+            new.panel.restore(ctx, &self.panel);
+            *self = new;
+        }
+
+        if ctx.redo_mouseover() {
+            self.tooltip = None;
+            if let Some(ID::Building(b)) = app.mouseover_unzoomed_buildings(ctx) {
+                if let Some(level) = self.after.per_building.get(&b) {
+                    let mut txt = Text::from(format!("{:.0} dB(A)", level));
+                    if let Some(before) = self.before.as_ref().and_then(|x| x.per_building.get(&b))
+                    {
+                        txt.add_line(Line(format!("{:.0} dB(A) before changes", before)));
+                    }
+                    self.tooltip = Some(txt);
+                }
+            }
+        }
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                "Export to CSV" => {
+                    return Some(LayerOutcome::Transition(Transition::Push(
+                        match export_noise(app, &self.after, self.before.as_ref()) {
+                            Ok(path) => PopupMsg::new_state(
+                                ctx,
+                                "Data exported",
+                                vec![format!("Data exported to {}", path)],
+                            ),
+                            Err(err) => {
+                                PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()])
+                            }
+                        },
+                    )));
+                }
+                _ => unreachable!(),
+            }
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+        self.draw.draw(g);
+        if let Some(ref txt) = self.tooltip {
+            g.draw_mouse_tooltip(txt.clone());
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw.unzoomed);
+    }
+}
+
+impl Noise {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> Noise {
+        let map = &app.primary.map;
+        let now = app.primary.sim.time();
+        let (after, before) = ctx.loading_screen("estimate noise", |_, timer| {
+            let after = NoiseLevels::new(app.primary.sim.get_analytics(), map, now, timer);
+            let before = if app.has_prebaked().is_some() {
+                Some(NoiseLevels::new(app.prebaked(), map, now, timer))
+            } else {
+                None
+            };
+            (after, before)
+        });
+
+        let mut colorer = ColorDiscrete::new(
+            app,
+            vec![
+                ("< 50 dB(A)", Color::hex("#A2D9A4")),
+                ("50-55", Color::hex("#F5E17A")),
+                ("55-60", Color::hex("#F0A04B")),
+                ("60-65", Color::hex("#E0602C")),
+                ("65-70", Color::hex("#C02828")),
+                ("> 70 dB(A)", Color::hex("#6E1480")),
+            ],
+        );
+        for (r, level) in &after.per_road {
+            colorer.add_r(*r, bucket(*level));
+        }
+        for (b, level) in &after.per_building {
+            colorer.add_b(*b, bucket(*level));
+        }
+        let (draw, legend) = colorer.build(ctx);
+
+        let mut txt = Text::new();
+        for threshold in [NOISE_THRESHOLD, HIGH_NOISE_THRESHOLD] {
+            txt.add_line(format!(
+                "Residents exposed to {} dB(A) or more: {}",
+                threshold,
+                prettyprint_usize(after.residents_exposed(map, threshold))
+            ));
+            if let Some(ref before) = before {
+                txt.add_line(
+                    Line(format!(
+                        "  {} before changes",
+                        prettyprint_usize(before.residents_exposed(map, threshold))
+                    ))
+                    .secondary(),
+                );
+            }
+        }
+        txt.add_line(
+            Line(
+                "The loudest hour so far, estimated from traffic volumes and speeds. Other \
+                 buildings in the way aren't accounted for.",
+            )
+            .secondary(),
+        );
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            header(ctx, "Road traffic noise"),
+            txt.wrap_to_pct(ctx, 15).into_widget(ctx),
+            legend,
+            ctx.style().btn_plain.text("Export to CSV").build_def(ctx),
+        ]))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx);
+
+        Noise {
+            time: now,
+            after,
+            before,
+            tooltip: None,
+            draw,
+            panel,
+        }
+    }
+}
+
+fn bucket(level: f64) -> &'static str {
+    if level < 50.0 {
+        "< 50 dB(A)"
+    } else if level < 55.0 {
+        "50-55"
+    } else if level < 60.0 {
+        "55-60"
+    } else if level < 65.0 {
+        "60-65"
+    } else if level < 70.0 {
+        "65-70"
+    } else {
+        "> 70 dB(A)"
+    }
+}
+
+fn export_noise(app: &App, after: &NoiseLevels, before: Option<&NoiseLevels>) -> Result<String> {
+    let map = &app.primary.map;
+    let path = format!(
+        "noise_{}_{}.csv",
+        map.get_name().as_filename(),
+        app.primary.sim.time().as_filename()
+    );
+    let mut out = String::new();
+    writeln!(out, "building,osm_url,residents,db_after,db_before")?;
+    let level = |x: Option<&f64>| x.map(|db| format!("{:.1}", db)).unwrap_or_else(String::new);
+    for b in map.all_buildings() {
+        let after = after.per_building.get(&b.id);
+        let before = before.and_then(|x| x.per_building.get(&b.id));
+        if after.is_none() && before.is_none() {
+            continue;
+        }
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            b.id.0,
+            b.orig_id,
+            b.bldg_type.num_residents(),
+            level(after),
+            level(before)
+        )?;
+    }
+    abstio::write_file(path, out)
+}
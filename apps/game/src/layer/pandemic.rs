@@ -102,7 +102,7 @@ impl Pandemic {
                         pts.push(pt);
                     }
                 }
-                PersonState::OffMap => {}
+                PersonState::OffMap | PersonState::Dockless(_) => {}
             }
         }
 
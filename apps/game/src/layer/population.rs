@@ -93,6 +93,9 @@ impl PopulationMap {
                         pts.push(pt);
                     }
                 }
+                PersonState::Dockless(pos) => {
+                    pts.push(pos.pt(&app.primary.map));
+                }
                 PersonState::OffMap => {}
             }
         }
@@ -260,11 +260,16 @@ fn export_raw_problems(app: &App) -> Result<String> {
                 Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                     map.get_i(*i).orig_id.to_string()
                 }
-                Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => match on {
+                Problem::OvertakeDesired(on)
+                | Problem::PedestrianOvercrowding(on)
+                | Problem::ClosePass(on, _) => match on {
                     Traversable::Lane(l) => map.get_r(l.road).orig_id.to_string(),
                     Traversable::Turn(t) => map.get_i(t.parent).orig_id.to_string(),
                 },
-                Problem::ArterialIntersectionCrossing(t) => map.get_i(t.parent).orig_id.to_string(),
+                Problem::ArterialIntersectionCrossing(t) | Problem::Jaywalking(t) => {
+                    map.get_i(t.parent).orig_id.to_string()
+                }
+                Problem::MidBlockCrossing(pos) => map.get_r(pos.lane().road).orig_id.to_string(),
             };
             writeln!(
                 out,
@@ -122,19 +122,22 @@ impl RelativeProblemMap {
                     Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                         intersections.inc(*i);
                     }
-                    Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                        match on {
-                            Traversable::Lane(l) => {
-                                roads.inc(l.road);
-                            }
-                            Traversable::Turn(t) => {
-                                intersections.inc(t.parent);
-                            }
+                    Problem::OvertakeDesired(on)
+                    | Problem::PedestrianOvercrowding(on)
+                    | Problem::ClosePass(on, _) => match on {
+                        Traversable::Lane(l) => {
+                            roads.inc(l.road);
                         }
-                    }
-                    Problem::ArterialIntersectionCrossing(t) => {
+                        Traversable::Turn(t) => {
+                            intersections.inc(t.parent);
+                        }
+                    },
+                    Problem::ArterialIntersectionCrossing(t) | Problem::Jaywalking(t) => {
                         intersections.inc(t.parent);
                     }
+                    Problem::MidBlockCrossing(pos) => {
+                        roads.inc(pos.lane().road);
+                    }
                 }
             };
 
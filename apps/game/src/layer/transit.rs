@@ -1,5 +1,5 @@
 use map_gui::tools::ColorDiscrete;
-use map_model::{PathConstraints, PathStep};
+use map_model::{PathStep, TransitMode};
 use widgetry::mapspace::ToggleZoomed;
 use widgetry::{EventCtx, GfxCtx, Outcome, Panel, Toggle, Widget};
 
@@ -81,10 +81,11 @@ impl TransitNetwork {
         }
         if show_all_routes {
             for tr in map.all_transit_routes() {
-                if !show_buses && tr.route_type == PathConstraints::Bus {
+                // Trams share the road, but they run on rails
+                if !show_buses && tr.mode == TransitMode::Bus {
                     continue;
                 }
-                if !show_trains && tr.route_type == PathConstraints::Train {
+                if !show_trains && tr.mode != TransitMode::Bus {
                     continue;
                 }
                 for path in tr.all_paths(map).unwrap() {
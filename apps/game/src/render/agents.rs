@@ -198,21 +198,21 @@ impl UnzoomedAgents {
 
     fn color(&self, agent: &UnzoomedAgent, color_scheme: &ColorScheme) -> Option<Color> {
         match agent.id.to_vehicle_type() {
-            Some(VehicleType::Car) => {
+            Some(VehicleType::Car) | Some(VehicleType::Truck) => {
                 if self.cars {
                     Some(color_scheme.unzoomed_car)
                 } else {
                     None
                 }
             }
-            Some(VehicleType::Bike) => {
+            Some(VehicleType::Bike) | Some(VehicleType::Scooter) => {
                 if self.bikes {
                     Some(color_scheme.unzoomed_bike)
                 } else {
                     None
                 }
             }
-            Some(VehicleType::Bus) | Some(VehicleType::Train) => {
+            Some(VehicleType::Bus) | Some(VehicleType::Train) | Some(VehicleType::Tram) => {
                 if self.buses_and_trains {
                     Some(color_scheme.unzoomed_bus)
                 } else {
@@ -251,7 +251,9 @@ fn thick_line_from_angle(
 fn zoomed_color_car(input: &DrawCarInput, sim: &Sim, cs: &ColorScheme) -> Color {
     if input.id.vehicle_type == VehicleType::Bus {
         cs.bus_body
-    } else if input.id.vehicle_type == VehicleType::Train {
+    } else if input.id.vehicle_type == VehicleType::Train
+        || input.id.vehicle_type == VehicleType::Tram
+    {
         cs.train_body
     } else {
         let color = match input.status {
@@ -58,7 +58,7 @@ fn draw_vehicle(
     prerender: &Prerender,
     cs: &ColorScheme,
 ) -> Box<dyn GameRenderable> {
-    if input.id.vehicle_type == VehicleType::Bike {
+    if input.id.vehicle_type.rides_bike_lanes() {
         Box::new(DrawBike::new(input, map, sim, prerender, cs))
     } else {
         Box::new(DrawCar::new(input, map, sim, prerender, cs))
@@ -107,7 +107,7 @@ impl CommuterPatterns {
                         continue;
                     }
                 }
-                TripEndpoint::SuddenlyAppear(_) => continue,
+                TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) => continue,
             };
             let block2 = match trip.end {
                 TripEndpoint::Building(b) => bldg_to_block[&b],
@@ -119,7 +119,7 @@ impl CommuterPatterns {
                         continue;
                     }
                 }
-                TripEndpoint::SuddenlyAppear(_) => continue,
+                TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) => continue,
             };
             // Totally ignore trips within the same block
             if block1 != block2 {
@@ -184,7 +184,7 @@ impl CommuterPatterns {
                             count.inc(self.border_to_block[&i]);
                         }
                     }
-                    TripEndpoint::SuddenlyAppear(_) => {}
+                    TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) => {}
                 }
             } else {
                 match trip.start {
@@ -196,7 +196,7 @@ impl CommuterPatterns {
                             count.inc(self.border_to_block[&i]);
                         }
                     }
-                    TripEndpoint::SuddenlyAppear(_) => {}
+                    TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) => {}
                 }
             }
         }
@@ -91,7 +91,9 @@ fn preview_route(g: &mut GfxCtx, app: &App, id: TripID, batch: &mut GeomBatch) {
         match trip.start {
             TripEndpoint::Building(b) => app.primary.map.get_b(b).label_center,
             TripEndpoint::Border(i) => app.primary.map.get_i(i).polygon.center(),
-            TripEndpoint::SuddenlyAppear(pos) => pos.pt(&app.primary.map),
+            TripEndpoint::SuddenlyAppear(pos) | TripEndpoint::Dockless(pos) => {
+                pos.pt(&app.primary.map)
+            }
         },
         5.0,
     ));
@@ -100,7 +102,9 @@ fn preview_route(g: &mut GfxCtx, app: &App, id: TripID, batch: &mut GeomBatch) {
         match trip.end {
             TripEndpoint::Building(b) => app.primary.map.get_b(b).label_center,
             TripEndpoint::Border(i) => app.primary.map.get_i(i).polygon.center(),
-            TripEndpoint::SuddenlyAppear(pos) => pos.pt(&app.primary.map),
+            TripEndpoint::SuddenlyAppear(pos) | TripEndpoint::Dockless(pos) => {
+                pos.pt(&app.primary.map)
+            }
         },
         5.0,
     ));
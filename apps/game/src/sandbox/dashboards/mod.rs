@@ -12,6 +12,7 @@ mod misc;
 mod mode_shift;
 mod parking_overhead;
 mod risks;
+mod safety;
 mod selector;
 mod traffic_signals;
 mod travel_times;
@@ -24,6 +25,7 @@ pub enum DashTab {
     TripTable,
     TravelTimes,
     RiskSummaries,
+    SafetyConflicts,
     ParkingOverhead,
     ActiveTraffic,
     TransitRoutes,
@@ -38,6 +40,7 @@ impl DashTab {
             Choice::new("Trip Table", DashTab::TripTable),
             Choice::new("Travel Times", DashTab::TravelTimes),
             Choice::new("Risk Exposure", DashTab::RiskSummaries),
+            Choice::new("Safety Conflicts", DashTab::SafetyConflicts),
             Choice::new("Parking Overhead", DashTab::ParkingOverhead),
             Choice::new("Active Traffic", DashTab::ActiveTraffic),
             Choice::new("Transit Routes", DashTab::TransitRoutes),
@@ -67,6 +70,7 @@ impl DashTab {
                 travel_times::TravelTimes::new_state(ctx, app, travel_times::Filter::new())
             }
             DashTab::RiskSummaries => risks::RiskSummaries::new_state(ctx, app, false),
+            DashTab::SafetyConflicts => safety::SafetyConflicts::new_state(ctx, app),
             DashTab::ParkingOverhead => parking_overhead::ParkingOverhead::new_state(ctx, app),
             DashTab::ActiveTraffic => misc::ActiveTraffic::new_state(ctx, app),
             DashTab::TransitRoutes => misc::TransitRoutes::new_state(ctx, app),
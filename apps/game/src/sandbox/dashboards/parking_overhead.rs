@@ -1,4 +1,7 @@
-use geom::Duration;
+use std::collections::BTreeSet;
+
+use abstutil::prettyprint_usize;
+use geom::{Distance, Duration};
 use sim::{TripID, TripPhaseType};
 use synthpop::TripEndpoint;
 use widgetry::table::{Col, Filter, Table};
@@ -54,6 +57,7 @@ impl ParkingOverhead {
                     Filler::square_width(ctx, 0.15).named("preview"),
                 ])
                 .evenly_spaced(),
+                zone_summary(app).into_widget(ctx),
                 table.render(ctx, app),
             ])
             .section(ctx),
@@ -173,6 +177,47 @@ fn produce_raw_data(app: &App) -> Vec<Entry> {
     data
 }
 
+/// How much cruising for parking happened and how much revenue was collected in each zone.
+fn zone_summary(app: &App) -> Text {
+    let cruising = app
+        .primary
+        .sim
+        .get_analytics()
+        .cruising_per_zone(app.primary.sim.time());
+    let parking_revenue = app.primary.sim.parking_revenue(&app.primary.map);
+
+    let mut zones: BTreeSet<Option<String>> = cruising.keys().cloned().collect();
+    zones.extend(parking_revenue.keys().cloned().map(Some));
+
+    let mut txt = Text::new();
+    txt.add_line(Line("Parking search and revenue per pricing zone").small_heading());
+    if zones.is_empty() {
+        txt.add_line(Line("Nobody has parked yet"));
+    }
+    for zone in zones {
+        let (count, total) = cruising.get(&zone).cloned().unwrap_or((0, Distance::ZERO));
+        let avg = if count == 0 {
+            Distance::ZERO
+        } else {
+            total / (count as f64)
+        };
+        let revenue = zone
+            .as_ref()
+            .and_then(|z| parking_revenue.get(z))
+            .cloned()
+            .unwrap_or(0.0);
+        txt.add_line(Line(format!(
+            "{}: {} drivers parked after cruising {} in total ({} on average), ${:.2} collected",
+            zone.unwrap_or_else(|| "Unpriced".to_string()),
+            prettyprint_usize(count),
+            total.to_string(&app.opts.units),
+            avg.to_string(&app.opts.units),
+            revenue
+        )));
+    }
+    txt
+}
+
 fn make_table(app: &App) -> Table<App, Entry, Filters> {
     let filter: Filter<App, Entry, Filters> = Filter {
         state: Filters {
@@ -89,6 +89,18 @@ impl RiskSummaries {
                             ),
                         ])
                         .section(ctx),
+                        Widget::col(vec![
+                            Line("Crossing against the signal")
+                                .small_heading()
+                                .into_widget(ctx)
+                                .centered_horiz(),
+                            problem_matrix(
+                                ctx,
+                                app,
+                                ped_filter.trip_problems(app, ProblemType::Jaywalking),
+                            ),
+                        ])
+                        .section(ctx),
                     ],
                 )
                 .margin_above(30),
@@ -134,6 +146,18 @@ impl RiskSummaries {
                             ),
                         ])
                         .section(ctx),
+                        Widget::col(vec![
+                            Line("Close passes of cyclists")
+                                .small_heading()
+                                .into_widget(ctx)
+                                .centered_horiz(),
+                            problem_matrix(
+                                ctx,
+                                app,
+                                bike_filter.trip_problems(app, ProblemType::ClosePass),
+                            ),
+                        ])
+                        .section(ctx),
                     ],
                 )
                 .margin_above(30),
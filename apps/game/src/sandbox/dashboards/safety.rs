@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use abstutil::{prettyprint_usize, Counter};
+use collisions::CollisionDataset;
+use geom::{Distance, Duration, FindClosest};
+use map_model::IntersectionID;
+use sim::SafetySummary;
+use widgetry::tools::PopupMsg;
+use widgetry::{EventCtx, GfxCtx, Line, Outcome, Panel, State, Text, TextExt, Widget};
+
+use crate::app::{App, Transition};
+use crate::info::Tab;
+use crate::sandbox::dashboards::DashTab;
+use crate::sandbox::SandboxMode;
+
+// Only list the intersections with the most conflicts
+const MAX_ROWS: usize = 50;
+
+/// Near-misses between turning vehicles and pedestrians or cyclists, per intersection. Where
+/// collision data for the city exists, shows the number of real crashes alongside, to help judge
+/// whether the simulated conflicts point at the right places.
+pub struct SafetyConflicts {
+    panel: Panel,
+    rows: Vec<Row>,
+}
+
+struct Row {
+    id: IntersectionID,
+    after: Option<SafetySummary>,
+    before: Option<SafetySummary>,
+    collisions: Option<usize>,
+}
+
+impl SafetyConflicts {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let map = &app.primary.map;
+        let now = app.primary.sim.time();
+        let mut after = app.primary.sim.get_analytics().safety_summary(now);
+        let mut before = if app.has_prebaked().is_some() {
+            app.prebaked().safety_summary(now)
+        } else {
+            BTreeMap::new()
+        };
+        let collisions = load_collisions(ctx, app);
+
+        let mut ids: Vec<IntersectionID> = after.keys().chain(before.keys()).cloned().collect();
+        ids.sort();
+        ids.dedup();
+        // Most conflicts after the changes first
+        ids.sort_by_key(|i| {
+            std::cmp::Reverse((
+                after.get(i).map(|s| s.total()).unwrap_or(0),
+                before.get(i).map(|s| s.total()).unwrap_or(0),
+            ))
+        });
+        let rows: Vec<Row> = ids
+            .into_iter()
+            .map(|id| Row {
+                id,
+                after: after.remove(&id),
+                before: before.remove(&id),
+                collisions: collisions.as_ref().map(|c| c.get(id)),
+            })
+            .collect();
+
+        let mut col = vec![
+            DashTab::SafetyConflicts.picker(ctx, app),
+            Line(format!(
+                "Safety conflicts at {} intersections",
+                prettyprint_usize(rows.len())
+            ))
+            .small_heading()
+            .into_widget(ctx),
+            Text::from_multiline(vec![
+                Line(
+                    "A conflict is when a vehicle turning left or right nearly collides with a \
+                     pedestrian or cyclist.",
+                )
+                .secondary(),
+                Line(
+                    "Time-to-collision: how soon they would've collided, if nobody had braked. \
+                     Post-encroachment time: how soon one entered the other's path after it was \
+                     cleared. Lower is more dangerous.",
+                )
+                .secondary(),
+            ])
+            .wrap_to_pct(ctx, 80)
+            .into_widget(ctx),
+        ];
+        if collisions.is_none() {
+            col.push(
+                "No collision data for this city, so crashes can't be compared".text_widget(ctx),
+            );
+        }
+        for row in rows.iter().take(MAX_ROWS) {
+            col.push(Widget::row(vec![
+                ctx.style()
+                    .btn_outline
+                    .text(map.get_i(row.id).name(app.opts.language.as_ref(), map))
+                    .build_widget(ctx, row.id.to_string()),
+                row.describe().text_widget(ctx).centered_vert(),
+            ]));
+        }
+        col.push(ctx.style().btn_plain.text("Export to CSV").build_def(ctx));
+
+        Box::new(SafetyConflicts {
+            panel: Panel::new_builder(Widget::col(col))
+                .exact_size_percent(90, 90)
+                .build(ctx),
+            rows,
+        })
+    }
+}
+
+impl State<App> for SafetyConflicts {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        let id = match self.panel.event(ctx) {
+            Outcome::Clicked(x) => {
+                if let Some(x) = x.strip_prefix("Intersection #") {
+                    IntersectionID(x.parse::<usize>().unwrap())
+                } else if x == "close" {
+                    return Transition::Pop;
+                } else if x == "Export to CSV" {
+                    return Transition::Push(match export_conflicts(app, &self.rows) {
+                        Ok(path) => PopupMsg::new_state(
+                            ctx,
+                            "Data exported",
+                            vec![format!("Data exported to {}", path)],
+                        ),
+                        Err(err) => {
+                            PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()])
+                        }
+                    });
+                } else {
+                    unreachable!()
+                }
+            }
+            Outcome::Changed(_) => {
+                if let Some(t) = DashTab::SafetyConflicts.transition(ctx, app, &self.panel) {
+                    return t;
+                } else {
+                    return Transition::Keep;
+                }
+            }
+            _ => {
+                return Transition::Keep;
+            }
+        };
+
+        Transition::Multi(vec![
+            Transition::Pop,
+            Transition::ModifyState(Box::new(move |state, ctx, app| {
+                let sandbox = state.downcast_mut::<SandboxMode>().unwrap();
+                let mut actions = sandbox.contextual_actions();
+                sandbox.controls.common.as_mut().unwrap().launch_info_panel(
+                    ctx,
+                    app,
+                    Tab::IntersectionInfo(id),
+                    &mut actions,
+                )
+            })),
+        ])
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _app: &App) {
+        self.panel.draw(g);
+    }
+}
+
+impl Row {
+    fn describe(&self) -> String {
+        let mut txt = match self.after {
+            Some(ref s) => format!(
+                "{} with pedestrians, {} with cyclists. Lowest time-to-collision {}, lowest \
+                 post-encroachment time {}",
+                prettyprint_usize(s.pedestrian_conflicts),
+                prettyprint_usize(s.bike_conflicts),
+                describe_min(s.min_time_to_collision),
+                describe_min(s.min_post_encroachment_time)
+            ),
+            None => "No conflicts".to_string(),
+        };
+        if let Some(ref s) = self.before {
+            txt.push_str(&format!(
+                " ({} before changes)",
+                prettyprint_usize(s.total())
+            ));
+        }
+        if let Some(n) = self.collisions {
+            txt.push_str(&format!(", {} reported collisions", prettyprint_usize(n)));
+        }
+        txt
+    }
+}
+
+fn describe_min(x: Option<Duration>) -> String {
+    x.map(|d| d.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+/// If the city has collision data, count the collisions at each intersection.
+fn load_collisions(ctx: &mut EventCtx, app: &App) -> Option<Counter<IntersectionID>> {
+    let map = &app.primary.map;
+    let path = map.get_city_name().input_path("collisions.bin");
+    if !abstio::file_exists(&path) {
+        return None;
+    }
+    let data: CollisionDataset = ctx.loading_screen("load collision data", |_, timer| {
+        abstio::read_binary(path, timer)
+    });
+
+    let mut closest = FindClosest::new();
+    for i in map.all_intersections() {
+        closest.add_polygon(i.id, &i.polygon);
+    }
+    let mut per_intersection = Counter::new();
+    for collision in data.collisions {
+        // Search up to 10m away, like the collisions viewer
+        if let Some((i, _)) = closest.closest_pt(
+            collision.location.to_pt(map.get_gps_bounds()),
+            Distance::meters(10.0),
+        ) {
+            per_intersection.inc(i);
+        }
+    }
+    Some(per_intersection)
+}
+
+fn export_conflicts(app: &App, rows: &[Row]) -> Result<String> {
+    let map = &app.primary.map;
+    let path = format!(
+        "safety_conflicts_{}_{}.csv",
+        map.get_name().as_filename(),
+        app.primary.sim.time().as_filename()
+    );
+    let mut out = String::new();
+    writeln!(
+        out,
+        "intersection,osm_url,pedestrian_conflicts,bike_conflicts,min_ttc_seconds,min_pet_seconds,conflicts_before,collisions"
+    )?;
+    let seconds = |x: Option<Duration>| {
+        x.map(|d| d.inner_seconds().to_string())
+            .unwrap_or_else(String::new)
+    };
+    for row in rows {
+        let (ped, bike, ttc, pet) = match row.after {
+            Some(ref s) => (
+                s.pedestrian_conflicts,
+                s.bike_conflicts,
+                seconds(s.min_time_to_collision),
+                seconds(s.min_post_encroachment_time),
+            ),
+            None => (0, 0, String::new(), String::new()),
+        };
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            row.id.0,
+            map.get_i(row.id).orig_id,
+            ped,
+            bike,
+            ttc,
+            pet,
+            row.before
+                .as_ref()
+                .map(|s| s.total().to_string())
+                .unwrap_or_else(String::new),
+            row.collisions
+                .map(|n| n.to_string())
+                .unwrap_or_else(String::new)
+        )?;
+    }
+    abstio::write_file(path, out)
+}
@@ -199,7 +199,9 @@ impl GameplayMode {
                         return false;
                     }
                 }
-                EditCmd::ChangeRouteSchedule { .. } => {}
+                EditCmd::ChangeRouteSchedule { .. }
+                | EditCmd::ChangeRouteCapacity { .. }
+                | EditCmd::ChangeParkingPricing { .. } => {}
             }
         }
         true
@@ -253,6 +253,12 @@ fn make_agent_toggles(ctx: &mut EventCtx, app: &App, is_enabled: [bool; 4]) -> V
                 prettyprint_usize(counts.sov_drivers)
             ))
             .secondary(),
+            Line(format!(
+                "{} riders in {} ride-hailing vehicles",
+                prettyprint_usize(counts.ride_hail_riders),
+                prettyprint_usize(counts.ride_hail_vehicles)
+            ))
+            .secondary(),
         ]);
         colored_checkbox(
             ctx,
@@ -260,7 +266,7 @@ fn make_agent_toggles(ctx: &mut EventCtx, app: &App, is_enabled: [bool; 4]) -> V
             is_car_enabled,
             app.cs.unzoomed_car,
             "system/assets/meters/car.svg",
-            &prettyprint_usize(counts.sov_drivers),
+            &prettyprint_usize(counts.sov_drivers + counts.ride_hail_vehicles),
             tooltip,
         )
     };
@@ -0,0 +1,27 @@
+use abstutil::Timer;
+use map_model::{Map, MapEdits};
+use sim::assignment::{assign_traffic, AssignmentConfig};
+use synthpop::Scenario;
+
+pub fn run(scenario: String, edits: Option<String>, config: AssignmentConfig) {
+    let mut timer = Timer::new("assign traffic");
+    let scenario: Scenario = abstio::must_read_object(scenario, &mut timer);
+    let mut map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+    if let Some(name) = edits {
+        let edits =
+            MapEdits::load_from_file(&map, abstio::path_edits(map.get_name(), &name), &mut timer)
+                .unwrap();
+        map.must_apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+    }
+
+    let result = assign_traffic(&map, &scenario, &config, &mut timer);
+
+    println!("iteration, driving trips, total cost, best cost, relative gap");
+    for x in &result.iterations {
+        println!(
+            "{}, {}, {}, {}, {:.4}",
+            x.iteration, x.num_trips, x.total_cost, x.best_cost, x.relative_gap
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use map_model::Map;
+use sim::calibrate::{calibrate, CalibrationConfig};
+use synthpop::{Scenario, TrafficCounts, TripMode};
+
+pub fn run(
+    scenario: String,
+    counts: String,
+    config: CalibrationConfig,
+    output: String,
+) -> Result<()> {
+    let mut timer = Timer::new("calibrate scenario");
+    let scenario: Scenario = abstio::read_object(scenario, &mut timer)?;
+    let observed: TrafficCounts = abstio::read_object(counts, &mut timer)?;
+    if observed.map != scenario.map_name {
+        bail!(
+            "The counts are for {}, but the scenario is for {}",
+            observed.map.describe(),
+            scenario.map_name.describe()
+        );
+    }
+    let map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+
+    let mut result = calibrate(&map, scenario, &observed, &config, &mut timer);
+
+    println!("iteration, people, matching locations, RMSE, mean GEH");
+    for x in &result.iterations {
+        println!(
+            "{}, {}, {}/{}, {:.2}, {:.2}",
+            x.iteration, x.num_people, x.num_matching, x.num_locations, x.rmse, x.mean_geh
+        );
+    }
+
+    result.scenario.scenario_name = output;
+    result.scenario.save();
+    println!(
+        "Wrote {}",
+        abstio::path_scenario(&result.scenario.map_name, &result.scenario.scenario_name)
+    );
+    Ok(())
+}
+
+/// Parses a mode, like "drive" or "bike"
+pub fn parse_mode(x: &str) -> Result<TripMode> {
+    for mode in TripMode::all() {
+        if mode.verb() == x || format!("{:?}", mode).to_lowercase() == x {
+            return Ok(mode);
+        }
+    }
+    bail!("Unknown mode {}; try walk, bike, transit, or drive", x)
+}
@@ -4,12 +4,15 @@
 #[macro_use]
 extern crate log;
 
+mod assign_traffic;
 mod augment_scenario;
+mod calibrate_scenario;
 mod clip_osm;
 mod generate_houses;
 mod import_grid2demand;
 mod import_scenario;
 mod one_step_import;
+mod optimize_signals;
 
 use std::io::Write;
 
@@ -214,6 +217,88 @@ enum Command {
         #[structopt(flatten)]
         job: Job,
     },
+    /// Searches for traffic signal timing that reduces the delay at some intersections, by
+    /// repeatedly simulating part of a scenario. The best timing is saved as map edits.
+    OptimizeSignals {
+        /// The path to a scenario file
+        #[structopt(long)]
+        scenario: String,
+        /// The IDs of the traffic signals to optimize, separated by commas
+        #[structopt(long, use_delimiter = true)]
+        intersections: Vec<usize>,
+        /// Only simulate starting from this time, like 07:00:00
+        #[structopt(long, parse(try_from_str = geom::Time::parse))]
+        start: geom::Time,
+        /// Only simulate until this time
+        #[structopt(long, parse(try_from_str = geom::Time::parse))]
+        end: geom::Time,
+        /// How many times to try changing every signal
+        #[structopt(long, default_value = "3")]
+        rounds: usize,
+        /// How many seconds to lengthen or shorten stages by
+        #[structopt(long, default_value = "5")]
+        step_seconds: f64,
+        /// A seed for generating random numbers
+        #[structopt(long, default_value = "42")]
+        rng_seed: u64,
+        /// The name of the map edits to write
+        #[structopt(long, default_value = "optimized signals")]
+        edits_name: String,
+    },
+    /// Adjusts a scenario until the simulated traffic through roads and intersections matches
+    /// observed counts. The calibrated scenario is saved under a new name.
+    CalibrateScenario {
+        /// The path to a scenario file
+        #[structopt(long)]
+        scenario: String,
+        /// The path to a JSON file with observed traffic counts
+        #[structopt(long)]
+        counts: String,
+        /// Which trips are counted and adjusted: walk, bike, transit, or drive
+        #[structopt(long, default_value = "drive", parse(try_from_str = calibrate_scenario::parse_mode))]
+        mode: synthpop::TripMode,
+        /// Stop after this many adjustments, even if the counts don't match yet
+        #[structopt(long, default_value = "5")]
+        max_iterations: usize,
+        /// A location matches if the GEH statistic is below this
+        #[structopt(long, default_value = "5")]
+        max_geh: f64,
+        /// Stop once this fraction of counted locations match
+        #[structopt(long, default_value = "0.85")]
+        target_fraction: f64,
+        /// Between 0 and 1. Lower values adjust the scenario more slowly, avoiding overshooting
+        #[structopt(long, default_value = "0.5")]
+        damping: f64,
+        /// A seed for generating random numbers
+        #[structopt(long, default_value = "42")]
+        rng_seed: u64,
+        /// The name of the calibrated scenario to write
+        #[structopt(long)]
+        output: String,
+    },
+    /// Repeatedly simulates a scenario, routing drivers around the congestion measured in the
+    /// previous day, until routes converge. Prints the relative gap after each iteration.
+    AssignTraffic {
+        /// The path to a scenario file
+        #[structopt(long)]
+        scenario: String,
+        /// The name of map edits to apply first, like road closures
+        #[structopt(long)]
+        edits: Option<String>,
+        /// Stop after this many iterations, even if routes haven't converged
+        #[structopt(long, default_value = "5")]
+        max_iterations: usize,
+        /// Stop once the relative gap is below this
+        #[structopt(long, default_value = "0.01")]
+        max_relative_gap: f64,
+        /// The share of driving trips that switch routes each iteration, between 0 and 1. By
+        /// default, this decreases with each iteration.
+        #[structopt(long)]
+        reroute_share: Option<f64>,
+        /// A seed for generating random numbers
+        #[structopt(long, default_value = "42")]
+        rng_seed: u64,
+    },
     /// Simulate a full day of a scenario, and write the "prebaked results," so the UI can later be
     /// used for A/B testing.
     #[structopt(name = "prebake-scenario")]
@@ -339,6 +424,70 @@ async fn main() -> Result<()> {
         } => importer::regenerate_everything(shard_num, num_shards).await,
         Command::RegenerateEverythingExternally => regenerate_everything_externally()?,
         Command::Import { job } => job.run(&mut Timer::new("import one city")).await,
+        Command::OptimizeSignals {
+            scenario,
+            intersections,
+            start,
+            end,
+            rounds,
+            step_seconds,
+            rng_seed,
+            edits_name,
+        } => optimize_signals::run(
+            scenario,
+            sim::optimize_signals::OptimizerConfig {
+                intersections: intersections
+                    .into_iter()
+                    .map(map_model::IntersectionID)
+                    .collect(),
+                start,
+                end,
+                rounds,
+                step: geom::Duration::seconds(step_seconds),
+            },
+            rng_seed,
+            edits_name,
+        )?,
+        Command::CalibrateScenario {
+            scenario,
+            counts,
+            mode,
+            max_iterations,
+            max_geh,
+            target_fraction,
+            damping,
+            rng_seed,
+            output,
+        } => calibrate_scenario::run(
+            scenario,
+            counts,
+            sim::calibrate::CalibrationConfig {
+                mode,
+                max_iterations,
+                max_geh,
+                target_fraction,
+                damping,
+                rng_seed,
+            },
+            output,
+        )?,
+        Command::AssignTraffic {
+            scenario,
+            edits,
+            max_iterations,
+            max_relative_gap,
+            reroute_share,
+            rng_seed,
+        } => assign_traffic::run(
+            scenario,
+            edits,
+            sim::assignment::AssignmentConfig {
+                max_iterations,
+                max_relative_gap,
+                reroute_share,
+                rng_seed,
+            },
+        ),
         Command::PrebakeScenario { scenario_path } => prebake_scenario(scenario_path),
     }
     Ok(())
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use abstutil::Timer;
+use map_model::Map;
+use sim::optimize_signals::{optimize_signals, OptimizerConfig};
+use sim::{AlertHandler, Sim, SimOptions};
+use synthpop::Scenario;
+
+pub fn run(
+    scenario: String,
+    config: OptimizerConfig,
+    rng_seed: u64,
+    edits_name: String,
+) -> Result<()> {
+    let mut timer = Timer::new("optimize traffic signals");
+    let scenario: Scenario = abstio::read_object(scenario, &mut timer)?;
+    let mut map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+
+    let mut opts = SimOptions::new("optimize_signals");
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(&map, opts);
+    let mut rng = XorShiftRng::seed_from_u64(rng_seed);
+    sim.instantiate(&scenario, &map, &mut rng, &mut timer);
+
+    let result = optimize_signals(&mut map, sim, &config, &mut timer)?;
+    println!(
+        "Delay went from {} to {} after {} simulations",
+        result.baseline_delay, result.best_delay, result.num_simulations
+    );
+
+    let mut edits = result.edits;
+    edits.edits_name = edits_name;
+    map.must_apply_edits(edits, &mut timer);
+    map.save_edits();
+    println!(
+        "Wrote {}",
+        abstio::path_edits(map.get_name(), &map.get_edits().edits_name)
+    );
+    Ok(())
+}
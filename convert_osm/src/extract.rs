@@ -19,6 +19,9 @@ pub struct Extract {
     pub bus_routes_on_roads: MultiMap<WayID, String>,
     /// Crossings located at these points, which should be on a Road's center line
     pub crossing_nodes: HashSet<(HashablePt2D, CrossingType)>,
+    /// The height of the kerb at some of those crossings, and at `barrier=kerb` nodes, which might
+    /// not be on a road at all
+    pub kerb_nodes: Vec<(HashablePt2D, Distance)>,
     /// Some kind of barrier nodes at these points.
     pub barrier_nodes: Vec<(osm::NodeID, HashablePt2D)>,
     pub extra_pois: Vec<ExtraPOI>,
@@ -59,6 +62,7 @@ pub fn extract_osm(
     let mut amenity_points = Vec::new();
     let mut bus_routes_on_roads: MultiMap<WayID, String> = MultiMap::new();
     let mut crossing_nodes = HashSet::new();
+    let mut kerb_nodes = Vec::new();
     let mut barrier_nodes = Vec::new();
     let mut extra_pois = Vec::new();
 
@@ -78,6 +82,15 @@ pub fn extract_osm(
                 CrossingType::Unsignalized
             };
             crossing_nodes.insert((node.pt.to_hashable(), kind));
+            if let Some(height) = get_kerb_height(&node.tags) {
+                kerb_nodes.push((node.pt.to_hashable(), height));
+            }
+        } else if node.tags.is("barrier", "kerb") {
+            // These're usually on the footway leading up to a crossing
+            kerb_nodes.push((
+                node.pt.to_hashable(),
+                get_kerb_height(&node.tags).unwrap_or(RAISED_KERB_HEIGHT),
+            ));
         }
         // TODO Any kind of barrier?
         if node.tags.is("barrier", "bollard") {
@@ -323,11 +336,35 @@ pub fn extract_osm(
         doc,
         bus_routes_on_roads,
         crossing_nodes,
+        kerb_nodes,
         barrier_nodes,
         extra_pois,
     }
 }
 
+const RAISED_KERB_HEIGHT: Distance = Distance::const_meters(0.12);
+
+/// Kerbs are usually only tagged by type, so guess typical heights for those.
+fn get_kerb_height(tags: &Tags) -> Option<Distance> {
+    if let Some(height) = tags.get("kerb:height") {
+        let height = height.trim();
+        if let Some(cm) = height.strip_suffix("cm") {
+            if let Ok(cm) = cm.trim().parse::<f64>() {
+                return Some(Distance::meters(cm / 100.0));
+            }
+        } else if let Ok(meters) = height.trim_end_matches('m').trim().parse::<f64>() {
+            return Some(Distance::meters(meters));
+        }
+    }
+    match tags.get("kerb")?.as_str() {
+        "flush" | "no" => Some(Distance::ZERO),
+        "lowered" => Some(Distance::meters(0.03)),
+        "rolled" => Some(Distance::meters(0.06)),
+        // raised, regular, yes, and anything else unusual
+        _ => Some(RAISED_KERB_HEIGHT),
+    }
+}
+
 fn is_bldg(tags: &Tags) -> bool {
     // Sorry, the towers at Gasworks don't count. :)
     tags.contains_key("building") && !tags.contains_key("abandoned:man_made")
@@ -434,3 +471,59 @@ fn service_road_crosses_parking_lot(map: &RawMap, pts: &[Pt2D], candidates: Vec<
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kerb_height(tags: Vec<(&str, &str)>) -> Option<Distance> {
+        let mut t = Tags::empty();
+        for (k, v) in tags {
+            t.insert(k, v);
+        }
+        get_kerb_height(&t)
+    }
+
+    #[test]
+    fn test_get_kerb_height() {
+        assert_eq!(kerb_height(vec![]), None);
+
+        assert_eq!(
+            kerb_height(vec![("kerb:height", "4cm")]),
+            Some(Distance::meters(0.04))
+        );
+        assert_eq!(
+            kerb_height(vec![("kerb:height", "0.1 m")]),
+            Some(Distance::meters(0.1))
+        );
+        // An explicit height wins over the type
+        assert_eq!(
+            kerb_height(vec![("kerb", "raised"), ("kerb:height", "0.02")]),
+            Some(Distance::meters(0.02))
+        );
+        // Unparseable heights fall back to the type
+        assert_eq!(
+            kerb_height(vec![("kerb", "lowered"), ("kerb:height", "low")]),
+            Some(Distance::meters(0.03))
+        );
+
+        assert_eq!(kerb_height(vec![("kerb", "flush")]), Some(Distance::ZERO));
+        assert_eq!(kerb_height(vec![("kerb", "no")]), Some(Distance::ZERO));
+        assert_eq!(
+            kerb_height(vec![("kerb", "lowered")]),
+            Some(Distance::meters(0.03))
+        );
+        assert_eq!(
+            kerb_height(vec![("kerb", "rolled")]),
+            Some(Distance::meters(0.06))
+        );
+        assert_eq!(
+            kerb_height(vec![("kerb", "raised")]),
+            Some(Distance::meters(0.12))
+        );
+        assert_eq!(
+            kerb_height(vec![("kerb", "yes")]),
+            Some(Distance::meters(0.12))
+        );
+    }
+}
@@ -1,15 +1,17 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use fs_err::File;
 use serde::Deserialize;
 
 use abstutil::MultiMap;
-use geom::{LonLat, PolyLine, Pt2D};
+use geom::{Duration, LonLat, PolyLine, Pt2D, Time};
 use kml::{ExtraShape, ExtraShapes};
 use raw_map::{RawMap, RawTransitRoute, RawTransitStop, RawTransitType};
 
-pub fn import(map: &mut RawMap) -> Result<()> {
+use crate::ServiceDay;
+
+pub fn import(map: &mut RawMap, service_day: &ServiceDay) -> Result<()> {
     // Collect metadata about routes
     for rec in csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/routes.txt"))?)
         .deserialize()
@@ -18,9 +20,9 @@ pub fn import(map: &mut RawMap) -> Result<()> {
         // See https://developers.google.com/transit/gtfs/reference#routestxt
         let route_type = match rec.route_type {
             3 => RawTransitType::Bus,
-            // These aren't distinguished in the map model yet. Trams and streetcars might
-            // particularly mess up...  or just fail to snap to a road later.
-            0 | 1 | 2 => RawTransitType::Train,
+            0 => RawTransitType::Tram,
+            // Subways and intercity rail aren't distinguished in the map model yet
+            1 | 2 => RawTransitType::Train,
             _ => continue,
         };
         map.transit_routes.push(RawTransitRoute {
@@ -34,20 +36,23 @@ pub fn import(map: &mut RawMap) -> Result<()> {
             shape: PolyLine::dummy(),
             stops: Vec::new(),
             route_type,
+            schedule: Vec::new(),
         });
     }
 
-    // Map route_id to shape_id
-    let mut route_to_shapes = MultiMap::new();
-    // Map (route_id, shape_id) to trip_id
-    let mut route_and_shape_to_trips = MultiMap::new();
+    // Every trip of a route
+    let mut route_to_trips = MultiMap::new();
+    let mut trip_to_service = HashMap::new();
+    let mut trip_to_shape = HashMap::new();
     for rec in csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/trips.txt"))?)
         .deserialize()
     {
         let rec: Trip = rec?;
-        route_to_shapes.insert(rec.route_id.clone(), rec.shape_id.clone());
-        route_and_shape_to_trips.insert((rec.route_id, rec.shape_id), rec.trip_id);
+        route_to_trips.insert(rec.route_id.clone(), rec.trip_id.clone());
+        trip_to_service.insert(rec.trip_id.clone(), rec.service_id);
+        trip_to_shape.insert(rec.trip_id, rec.shape_id);
     }
+    let active_services = active_services(map, service_day)?;
 
     // Scrape all shape data. Map from shape_id to points and the sequence number
     //
@@ -66,57 +71,8 @@ pub fn import(map: &mut RawMap) -> Result<()> {
             .push((pt, rec.shape_pt_sequence));
     }
 
-    // Build a PolyLine for every route
-    let mut transit_routes = Vec::new();
-    let mut route_to_shape = HashMap::new();
-    for mut route in map.transit_routes.drain(..) {
-        let shape_ids = route_to_shapes.get(RouteID(route.gtfs_id.clone()));
-        if shape_ids.is_empty() {
-            warn!("Route {} has no shape", route.gtfs_id);
-            continue;
-        }
-        if shape_ids.len() > 1 {
-            warn!(
-                "Route {} has several shapes, choosing one arbitrarily: {:?}",
-                route.gtfs_id, shape_ids
-            );
-        }
-        let shape_id = shape_ids.into_iter().next().unwrap();
-        route_to_shape.insert(RouteID(route.gtfs_id.clone()), shape_id.clone());
-        let mut pts = if let Some(pts) = raw_shapes.remove(shape_id) {
-            pts
-        } else {
-            warn!("Route {} is missing its shape", route.gtfs_id);
-            continue;
-        };
-        // Points are usually sorted, but just in case...
-        pts.sort_by_key(|(_, seq)| *seq);
-        let pts: Vec<Pt2D> = pts.into_iter().map(|(pt, _)| pt).collect();
-        match PolyLine::new(pts) {
-            Ok(pl) => {
-                route.shape = pl;
-                transit_routes.push(route);
-            }
-            Err(err) => {
-                warn!("Route {} has a weird shape: {}", route.gtfs_id, err);
-                continue;
-            }
-        }
-    }
-    map.transit_routes = transit_routes;
-
-    // For now, every route uses exactly one trip ID, and there's no schedule. Just pick an
-    // arbitrary trip per route.
-    let mut route_to_trip = HashMap::new();
-    for (route_id, shape_id) in &route_to_shape {
-        let trips = route_and_shape_to_trips.get((route_id.clone(), shape_id.clone()));
-        if let Some(trip_id) = trips.into_iter().next() {
-            route_to_trip.insert(route_id.clone(), trip_id);
-        }
-    }
-
-    // Scrape the trip ID -> (stop ID, sequence number)
-    let mut trip_to_stops: HashMap<TripID, Vec<(StopID, usize)>> = HashMap::new();
+    // Scrape the trip ID -> (stop ID, sequence number, arrival time)
+    let mut trip_to_stops: HashMap<TripID, Vec<(StopID, usize, Option<Time>)>> = HashMap::new();
     for rec in
         csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/stop_times.txt"))?)
             .deserialize()
@@ -125,20 +81,144 @@ pub fn import(map: &mut RawMap) -> Result<()> {
         trip_to_stops
             .entry(rec.trip_id)
             .or_insert_with(Vec::new)
-            .push((rec.stop_id, rec.stop_sequence));
+            .push((
+                rec.stop_id,
+                rec.stop_sequence,
+                parse_time(&rec.arrival_time),
+            ));
+    }
+    for stops in trip_to_stops.values_mut() {
+        stops.sort_by_key(|(_, seq, _)| *seq);
     }
 
-    // Assign the stops for every route
+    // Some trips are just a template, repeated at some headway. This file is optional.
+    let mut trip_to_frequencies: HashMap<TripID, Vec<(Time, Time, Duration)>> = HashMap::new();
+    let path = map.name.city.input_path("gtfs/frequencies.txt");
+    if abstio::file_exists(&path) {
+        for rec in csv::Reader::from_reader(File::open(path)?).deserialize() {
+            let rec: Frequency = rec?;
+            if let (Some(start), Some(end)) =
+                (parse_time(&rec.start_time), parse_time(&rec.end_time))
+            {
+                if rec.headway_secs > 0 {
+                    trip_to_frequencies
+                        .entry(rec.trip_id)
+                        .or_insert_with(Vec::new)
+                        .push((start, end, Duration::seconds(rec.headway_secs as f64)));
+                }
+            }
+        }
+    }
+
+    // Trips of one route may serve different stops -- short turns, branches, express runs. Each
+    // distinct pattern of stops becomes its own route, following the shape of its first trip.
+    let mut transit_routes = Vec::new();
     let mut stop_ids = HashSet::new();
-    for route in &mut map.transit_routes {
-        let trip_id = route_to_trip[&RouteID(route.gtfs_id.clone())];
-        let mut stops = trip_to_stops.remove(&trip_id).unwrap_or_else(Vec::new);
-        stops.sort_by_key(|(_, seq)| *seq);
-        for (stop_id, _) in stops {
-            route.stops.push(stop_id.0.clone());
-            stop_ids.insert(stop_id);
+    let mut num_trips_dropped = 0;
+    for template in map.transit_routes.drain(..) {
+        let trips = route_to_trips.get(RouteID(template.gtfs_id.clone()));
+        if trips.is_empty() {
+            warn!("Route {} has no trips", template.gtfs_id);
+            continue;
+        }
+
+        // (stops, shape, schedule)
+        let mut patterns: Vec<(Vec<StopID>, ShapeID, Vec<Vec<Time>>)> = Vec::new();
+        for trip_id in trips {
+            if !trip_to_service
+                .get(trip_id)
+                .map(|service| active_services.contains(service))
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let stops = match trip_to_stops.get(trip_id) {
+                Some(stops) => stops,
+                None => {
+                    num_trips_dropped += 1;
+                    continue;
+                }
+            };
+            let times = match fill_times(stops) {
+                Some(times) => times,
+                None => {
+                    warn!("Trip {} has no time at its first stop", trip_id.0);
+                    num_trips_dropped += 1;
+                    continue;
+                }
+            };
+            let pattern: Vec<StopID> = stops.iter().map(|(id, _, _)| id.clone()).collect();
+            let idx = match patterns.iter().position(|(p, _, _)| *p == pattern) {
+                Some(idx) => idx,
+                None => {
+                    patterns.push((pattern, trip_to_shape[trip_id].clone(), Vec::new()));
+                    patterns.len() - 1
+                }
+            };
+            let schedule = &mut patterns[idx].2;
+            if let Some(frequencies) = trip_to_frequencies.get(trip_id) {
+                for (start, end, headway) in frequencies {
+                    let mut depart = *start;
+                    while depart < *end {
+                        let shift = depart - times[0];
+                        schedule.push(times.iter().map(|t| *t + shift).collect());
+                        depart = depart + *headway;
+                    }
+                }
+            } else {
+                schedule.push(times);
+            }
+        }
+
+        if patterns.is_empty() {
+            // Still import the route, following an arbitrary trip. It'll run without a timetable.
+            warn!(
+                "Route {} has no trips on the chosen service day",
+                template.gtfs_id
+            );
+            let trip_id = trips.iter().next().unwrap();
+            if let Some(stops) = trip_to_stops.get(trip_id) {
+                patterns.push((
+                    stops.iter().map(|(id, _, _)| id.clone()).collect(),
+                    trip_to_shape[trip_id].clone(),
+                    Vec::new(),
+                ));
+            }
+        }
+
+        // The pattern with the most trips keeps the route's ID, so edits to it still apply
+        patterns.sort_by_key(|(_, _, schedule)| std::cmp::Reverse(schedule.len()));
+        for (idx, (pattern, shape_id, mut schedule)) in patterns.into_iter().enumerate() {
+            let mut route = template.clone();
+            if idx > 0 {
+                route.gtfs_id = format!("{}:{}", template.gtfs_id, idx + 1);
+            }
+            match make_shape(&raw_shapes, &shape_id) {
+                Ok(pl) => {
+                    route.shape = pl;
+                }
+                Err(err) => {
+                    warn!("Route {} has a bad shape: {}", route.gtfs_id, err);
+                    num_trips_dropped += schedule.len();
+                    continue;
+                }
+            }
+            schedule.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+            route.schedule = schedule;
+            for stop_id in pattern {
+                route.stops.push(stop_id.0.clone());
+                stop_ids.insert(stop_id);
+            }
+            transit_routes.push(route);
         }
     }
+    map.transit_routes = transit_routes;
+    if num_trips_dropped > 0 {
+        warn!(
+            "{} trips on the chosen service day couldn't be imported",
+            num_trips_dropped
+        );
+    }
 
     // Scrape stop metadata
     for rec in csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/stops.txt"))?)
@@ -163,10 +243,19 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     // Make sure all of the stops are valid and used by some route
     let mut used_stops = HashSet::new();
     for route in &mut map.transit_routes {
-        route.stops.retain(|stop_id| {
-            used_stops.insert(stop_id.clone());
-            map.transit_stops.contains_key(stop_id)
-        });
+        let keep: Vec<bool> = route
+            .stops
+            .iter()
+            .map(|stop_id| {
+                used_stops.insert(stop_id.clone());
+                map.transit_stops.contains_key(stop_id)
+            })
+            .collect();
+        route.stops = retain_by_mask(std::mem::take(&mut route.stops), &keep);
+        // The schedule has one time per stop, so drop the same entries
+        for trip in &mut route.schedule {
+            *trip = retain_by_mask(std::mem::take(trip), &keep);
+        }
     }
     map.transit_routes.retain(|route| !route.stops.is_empty());
     map.transit_stops
@@ -187,6 +276,8 @@ struct TripID(String);
 struct StopID(String);
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 struct RouteID(String);
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+struct ServiceID(String);
 
 #[derive(Deserialize)]
 struct Route {
@@ -202,6 +293,7 @@ struct Route {
 #[derive(Deserialize)]
 struct Trip {
     route_id: RouteID,
+    service_id: ServiceID,
     shape_id: ShapeID,
     trip_id: TripID,
 }
@@ -227,6 +319,165 @@ struct StopTime {
     trip_id: TripID,
     stop_id: StopID,
     stop_sequence: usize,
+    // Only required for timepoints
+    #[serde(default)]
+    arrival_time: String,
+}
+
+#[derive(Deserialize)]
+struct Frequency {
+    trip_id: TripID,
+    start_time: String,
+    end_time: String,
+    headway_secs: usize,
+}
+
+#[derive(Deserialize)]
+struct Calendar {
+    service_id: ServiceID,
+    monday: usize,
+    tuesday: usize,
+    wednesday: usize,
+    thursday: usize,
+    friday: usize,
+    saturday: usize,
+    sunday: usize,
+    start_date: String,
+    end_date: String,
+}
+
+impl Calendar {
+    /// 0 is Monday
+    fn runs_on(&self, weekday: usize) -> bool {
+        let flag = match weekday {
+            0 => self.monday,
+            1 => self.tuesday,
+            2 => self.wednesday,
+            3 => self.thursday,
+            4 => self.friday,
+            5 => self.saturday,
+            _ => self.sunday,
+        };
+        flag == 1
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarDate {
+    service_id: ServiceID,
+    date: String,
+    exception_type: usize,
+}
+
+/// Find all services running on the chosen day. Feeds may use calendar.txt, calendar_dates.txt,
+/// or both.
+fn active_services(map: &RawMap, service_day: &ServiceDay) -> Result<HashSet<ServiceID>> {
+    let mut services = HashSet::new();
+    let path = map.name.city.input_path("gtfs/calendar.txt");
+    if abstio::file_exists(&path) {
+        for rec in csv::Reader::from_reader(File::open(path)?).deserialize() {
+            let rec: Calendar = rec?;
+            let runs = match service_day {
+                ServiceDay::Weekday(weekday) => rec.runs_on(*weekday),
+                // Dates are YYYYMMDD, so comparing the strings works
+                ServiceDay::Date(date) => {
+                    rec.start_date <= *date && *date <= rec.end_date && rec.runs_on(weekday(date)?)
+                }
+            };
+            if runs {
+                services.insert(rec.service_id);
+            }
+        }
+    }
+
+    if let ServiceDay::Date(date) = service_day {
+        let path = map.name.city.input_path("gtfs/calendar_dates.txt");
+        if abstio::file_exists(&path) {
+            for rec in csv::Reader::from_reader(File::open(path)?).deserialize() {
+                let rec: CalendarDate = rec?;
+                if rec.date != *date {
+                    continue;
+                }
+                // See https://developers.google.com/transit/gtfs/reference#calendar_datestxt
+                match rec.exception_type {
+                    1 => {
+                        services.insert(rec.service_id);
+                    }
+                    2 => {
+                        services.remove(&rec.service_id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if services.is_empty() {
+        warn!("No GTFS service runs on {:?}", service_day);
+    }
+    Ok(services)
+}
+
+/// Returns the day of the week for a YYYYMMDD date, with 0 being Monday.
+fn weekday(date: &str) -> Result<usize> {
+    if date.len() != 8 {
+        bail!("Bad GTFS service date {}, must be YYYYMMDD", date);
+    }
+    let mut y: usize = date[0..4].parse()?;
+    let m: usize = date[4..6].parse()?;
+    let d: usize = date[6..8].parse()?;
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        bail!("Bad GTFS service date {}, must be YYYYMMDD", date);
+    }
+    // Sakamoto's method, which counts from Sunday
+    let offsets = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    if m < 3 {
+        y -= 1;
+    }
+    let from_sunday = (y + y / 4 - y / 100 + y / 400 + offsets[m - 1] + d) % 7;
+    Ok((from_sunday + 6) % 7)
+}
+
+/// Parses HH:MM:SS. Hours may go past 24 for trips that run after midnight.
+fn parse_time(x: &str) -> Option<Time> {
+    let parts: Vec<&str> = x.trim().split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let mut seconds = 0.0;
+    for part in parts {
+        seconds = 60.0 * seconds + part.parse::<f64>().ok()?;
+    }
+    Some(Time::START_OF_DAY + Duration::seconds(seconds))
+}
+
+/// Stops that aren't timepoints may not have an arrival time. Assume the vehicle reaches them at
+/// the same time as the previous stop. Fails if the first stop has no time.
+fn fill_times(stops: &[(StopID, usize, Option<Time>)]) -> Option<Vec<Time>> {
+    let mut times: Vec<Time> = Vec::new();
+    for (_, _, time) in stops {
+        let time = time.or_else(|| times.last().cloned())?;
+        times.push(time);
+    }
+    Some(times)
+}
+
+fn make_shape(raw_shapes: &HashMap<ShapeID, Vec<(Pt2D, usize)>>, id: &ShapeID) -> Result<PolyLine> {
+    let mut pts = match raw_shapes.get(id) {
+        Some(pts) => pts.clone(),
+        None => bail!("shape {} is missing", id.0),
+    };
+    // Points are usually sorted, but just in case...
+    pts.sort_by_key(|(_, seq)| *seq);
+    PolyLine::new(pts.into_iter().map(|(pt, _)| pt).collect())
+}
+
+fn retain_by_mask<T>(list: Vec<T>, keep: &[bool]) -> Vec<T> {
+    list.into_iter()
+        .zip(keep.iter())
+        .filter(|(_, keep)| **keep)
+        .map(|(x, _)| x)
+        .collect()
 }
 
 fn dump_kml(map: &RawMap) {
@@ -260,3 +511,58 @@ fn dump_kml(map: &RawMap) {
         &ExtraShapes { shapes },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weekday() {
+        // A Wednesday, Saturday, and Sunday
+        assert_eq!(weekday("20240103").unwrap(), 2);
+        assert_eq!(weekday("20240302").unwrap(), 5);
+        assert_eq!(weekday("20240303").unwrap(), 6);
+        // Leap day, and the first of a year where January and February count from the previous
+        assert_eq!(weekday("20240229").unwrap(), 3);
+        assert_eq!(weekday("20230101").unwrap(), 6);
+        assert_eq!(weekday("20000301").unwrap(), 2);
+
+        assert!(weekday("2024-01-03").is_err());
+        assert!(weekday("20241301").is_err());
+        assert!(weekday("20240100").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let hms = |h: f64, m: f64, s: f64| {
+            Some(Time::START_OF_DAY + Duration::seconds(3600.0 * h + 60.0 * m + s))
+        };
+        assert_eq!(parse_time("08:05:30"), hms(8.0, 5.0, 30.0));
+        assert_eq!(parse_time(" 7:00:00"), hms(7.0, 0.0, 0.0));
+        // Service after midnight belongs to the previous day
+        assert_eq!(parse_time("25:10:00"), hms(25.0, 10.0, 0.0));
+
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("08:05"), None);
+        assert_eq!(parse_time("eight:00:00"), None);
+    }
+
+    #[test]
+    fn test_fill_times() {
+        let t = |secs: f64| Time::START_OF_DAY + Duration::seconds(secs);
+        let stops = |times: Vec<Option<Time>>| -> Vec<(StopID, usize, Option<Time>)> {
+            times
+                .into_iter()
+                .enumerate()
+                .map(|(idx, time)| (StopID(format!("stop{}", idx)), idx, time))
+                .collect()
+        };
+
+        assert_eq!(
+            fill_times(&stops(vec![Some(t(10.0)), None, None, Some(t(40.0)), None])),
+            Some(vec![t(10.0), t(10.0), t(10.0), t(40.0), t(40.0)])
+        );
+        assert_eq!(fill_times(&stops(vec![None, Some(t(40.0))])), None);
+        assert_eq!(fill_times(&stops(Vec::new())), Some(Vec::new()));
+    }
+}
@@ -3,11 +3,11 @@ extern crate log;
 
 use std::collections::{HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use abstio::MapName;
 use abstutil::{Tags, Timer};
-use geom::{Distance, HashablePt2D, LonLat, PolyLine, Polygon};
+use geom::{Distance, FindClosest, HashablePt2D, LonLat, PolyLine, Polygon, Pt2D};
 use osm2streets::{osm, MapConfig, Road, RoadID};
 use raw_map::{CrossingType, ExtraRoadData, RawMap};
 
@@ -16,6 +16,9 @@ mod extract;
 mod gtfs;
 mod parking;
 
+/// `barrier=kerb` nodes further than this from any road are ignored
+const KERB_SNAP_DIST: Distance = Distance::const_meters(15.0);
+
 /// Configures the creation of a `RawMap` from OSM and other input data.
 pub struct Options {
     pub map_config: MapConfig,
@@ -27,6 +30,8 @@ pub struct Options {
     pub extra_buildings: Option<String>,
     /// Configure public transit using this URL to a static GTFS feed in .zip format.
     pub gtfs_url: Option<String>,
+    /// Which day's timetable to use from the GTFS feed.
+    pub gtfs_service_day: ServiceDay,
     /// Path to a GeoTIFF file in EPSG:4326 to use for elevation data
     pub elevation_geotiff: Option<String>,
     /// Only include crosswalks that match a `highway=crossing` OSM node.
@@ -42,12 +47,55 @@ impl Options {
             private_offstreet_parking: PrivateOffstreetParking::FixedPerBldg(1),
             extra_buildings: None,
             gtfs_url: None,
+            gtfs_service_day: ServiceDay::Weekday(2),
             elevation_geotiff: None,
             filter_crosswalks: false,
         }
     }
 }
 
+/// Which day's timetable to import from a GTFS feed.
+#[derive(Clone, Debug)]
+pub enum ServiceDay {
+    /// Any service regularly running on this day of the week, ignoring the date ranges and
+    /// exceptions in the feed. 0 is Monday and 6 is Sunday.
+    Weekday(usize),
+    /// A specific date, formatted as YYYYMMDD. The feed's date ranges and exceptions apply.
+    Date(String),
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+impl ServiceDay {
+    /// Parses a day of the week, like "wednesday", or a YYYYMMDD date.
+    pub fn parse(x: &str) -> Result<ServiceDay> {
+        if let Some(idx) = WEEKDAYS.iter().position(|day| x.eq_ignore_ascii_case(day)) {
+            return Ok(ServiceDay::Weekday(idx));
+        }
+        if x.len() == 8 && x.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(ServiceDay::Date(x.to_string()));
+        }
+        bail!("{} isn't a day of the week or a YYYYMMDD date", x)
+    }
+}
+
+impl std::fmt::Display for ServiceDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ServiceDay::Weekday(idx) => write!(f, "{}", WEEKDAYS[*idx]),
+            ServiceDay::Date(date) => write!(f, "{}", date),
+        }
+    }
+}
+
 /// What roads will have on-street parking lanes? Data from
 /// <https://wiki.openstreetmap.org/wiki/Key:parking:lane> is always used if available.
 pub enum OnstreetParking {
@@ -131,6 +179,12 @@ pub fn convert(
     timer.start("use barrier and crossing nodes");
     use_barrier_nodes(&mut map, extract.barrier_nodes, &pt_to_road);
     use_crossing_nodes(&mut map, &extract.crossing_nodes, &pt_to_road);
+    use_kerb_nodes(
+        &mut map,
+        extract.kerb_nodes,
+        &extract.crossing_nodes,
+        &pt_to_road,
+    );
     timer.stop("use barrier and crossing nodes");
 
     if opts.filter_crosswalks {
@@ -149,7 +203,7 @@ pub fn convert(
     }
 
     if opts.gtfs_url.is_some() {
-        gtfs::import(&mut map).unwrap();
+        gtfs::import(&mut map, &opts.gtfs_service_day).unwrap();
     }
 
     timer.start("Add census data");
@@ -345,6 +399,71 @@ fn use_crossing_nodes(
     }
 }
 
+/// Kerbs tagged on a road's own nodes stay there. `barrier=kerb` nodes are usually mapped on the
+/// footway a few meters away from the road, so snap them to a crossing on the closest road, or
+/// failing that, to whichever end of that road is closer.
+fn use_kerb_nodes(
+    map: &mut RawMap,
+    kerb_nodes: Vec<(HashablePt2D, Distance)>,
+    crossing_nodes: &HashSet<(HashablePt2D, CrossingType)>,
+    pt_to_road: &HashMap<HashablePt2D, RoadID>,
+) {
+    let mut crossings_per_road: HashMap<RoadID, Vec<Pt2D>> = HashMap::new();
+    for (pt, _) in crossing_nodes {
+        if let Some(r) = pt_to_road.get(pt) {
+            crossings_per_road
+                .entry(*r)
+                .or_insert_with(Vec::new)
+                .push(pt.to_pt2d());
+        }
+    }
+    let mut closest: FindClosest<RoadID> = FindClosest::new();
+    for r in map.streets.roads.values() {
+        closest.add(r.id, r.reference_line.points());
+    }
+
+    for (pt, height) in kerb_nodes {
+        let snapped = if let Some(r) = pt_to_road.get(&pt) {
+            Some((*r, pt.to_pt2d()))
+        } else {
+            snap_kerb(map, pt.to_pt2d(), &closest, &crossings_per_road)
+        };
+        if let Some((r, pt)) = snapped {
+            if let Some(road) = map.extra_road_data.get_mut(&r) {
+                road.kerb_nodes.push((pt, height));
+            }
+        }
+    }
+}
+
+fn snap_kerb(
+    map: &RawMap,
+    kerb: Pt2D,
+    closest: &FindClosest<RoadID>,
+    crossings_per_road: &HashMap<RoadID, Vec<Pt2D>>,
+) -> Option<(RoadID, Pt2D)> {
+    let (r, _) = closest.closest_pt(kerb, KERB_SNAP_DIST)?;
+    let road = &map.streets.roads[&r];
+    let crossing = crossings_per_road
+        .get(&r)
+        .into_iter()
+        .flatten()
+        .cloned()
+        .filter(|pt| pt.dist_to(kerb) <= KERB_SNAP_DIST)
+        .min_by_key(|pt| pt.dist_to(kerb));
+    if let Some(pt) = crossing {
+        return Some((r, pt));
+    }
+    [
+        road.reference_line.first_pt(),
+        road.reference_line.last_pt(),
+    ]
+    .into_iter()
+    .filter(|pt| pt.dist_to(kerb) <= KERB_SNAP_DIST)
+    .min_by_key(|pt| pt.dist_to(kerb))
+    .map(|pt| (r, pt))
+}
+
 fn filter_crosswalks(
     map: &mut RawMap,
     crosswalks: HashSet<(HashablePt2D, CrossingType)>,
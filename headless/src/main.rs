@@ -9,15 +9,56 @@
 //! it's now 01:01:00.0
 //! > curl http://localhost:1234/data/get-road-thruput
 //! ... huge JSON blob
+//! > curl http://localhost:1234/sim/stream-events?t=02:00:00 -d '{"event_types": ["TripFinished"]}'
+//! ... one JSON event per line, sent as the simulation advances
+//!
+//! Multiple independent simulations can run at once. Every `/sim`, `/traffic-signals`, `/data`,
+//! and `/map` command takes an optional `session` parameter, defaulting to the session created at
+//! startup, called `default`.
+//!
+//! > curl http://localhost:1234/sessions/create?name=experiment -d '{"scenario": "data/system/us/seattle/scenarios/montlake/weekday.bin", "modifiers": [], "sim_flags": ["--infinite-parking"]}'
+//! > curl http://localhost:1234/sim/goto-time?t=01:00:00&session=experiment
+//! > curl http://localhost:1234/sessions/list
+//! ... every session, or just its name if it's busy handling another request or crashed during
+//! an earlier one
+//! > curl http://localhost:1234/sessions/delete?name=experiment
+//!
+//! Traffic signals can also be controlled externally, one stage at a time:
+//!
+//! > curl http://localhost:1234/traffic-signals/set-external?id=67
+//! > curl http://localhost:1234/traffic-signals/get-external-status?id=67
+//! > curl http://localhost:1234/traffic-signals/queue-external-stage?id=67&stage=1&duration=20
+//!
+//! Offsets along a corridor of signals can be coordinated for a two-way green wave. The cycle
+//! length is optional. Afterwards, `/traffic-signals/get-delays` for any of those signals reports
+//! how well traffic progresses along the corridor, before and after.
+//!
+//! > curl http://localhost:1234/traffic-signals/make-green-wave?ids=12,34,56&speed_mps=13.4&cycle_length=90
+//!
+//! Stage durations and orderings can be searched to minimize delay at some signals over a window of
+//! time. This re-simulates the session from its current time many times, so it's slow, and the
+//! window can't start in the past. The response includes map edits with the best timing found.
+//!
+//! > curl http://localhost:1234/traffic-signals/optimize?ids=12,34&t1=07:00:00&t2=08:00:00&rounds=3&step_seconds=5
+//!
+//! Ride-hailing fleets are sized and dispatched with `--ride-hail-fleet-size` and
+//! `--ride-hail-dispatch=nearest|pooled` in a session's `sim_flags`. To see how the fleet did:
+//!
+//! > curl http://localhost:1234/data/get-ride-hail-stats
+//!
+//! Emissions are estimated per road, trip, and hour. The share of electric, petrol, and diesel
+//! cars is set with `--fleet-mix=0.1,0.6,0.3` in a session's `sim_flags`.
+//!
+//! > curl http://localhost:1234/data/get-emissions
 
 #[macro_use]
 extern crate anyhow;
 #[macro_use]
 extern crate log;
 
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::net::IpAddr;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, TryLockError, Weak};
 
 use anyhow::Result;
 use hyper::{Body, Request, Response, Server, StatusCode};
@@ -28,28 +69,65 @@ use structopt::StructOpt;
 
 use abstio::MapName;
 use abstutil::{serialize_btreemap, Timer};
-use geom::{Distance, Duration, FindClosest, LonLat, Time};
+use geom::{Distance, Duration, FindClosest, LonLat, Polygon, Ring, Speed, Time};
 use map_model::{
-    CompressedMovementID, ControlTrafficSignal, EditIntersectionControl, IntersectionID, Map,
-    MovementID, PermanentMapEdits, RoadID, TurnID,
+    make_green_wave, measure_progression, CompressedMovementID, ControlTrafficSignal,
+    CorridorProgression, EditIntersectionControl, IntersectionID, LaneID, Map, MovementID,
+    PermanentMapEdits, RoadID, TurnID,
 };
+use sim::optimize_signals::{optimize_signals, OptimizerConfig};
 use sim::{
-    AgentID, AgentType, DelayCause, PersonID, Sim, SimFlags, SimOptions, TripID, VehicleType,
+    AgentID, AgentType, DelayCause, Emissions, Event, PersonID, SignalController, SignalDecision,
+    SignalStatus, Sim, SimFlags, SimOptions, TripID, VehicleType,
 };
 use synthpop::{ExternalPerson, Scenario, ScenarioModifier, TripMode};
 
 lazy_static::lazy_static! {
-    static ref MAP: RwLock<Map> = RwLock::new(Map::blank());
-    static ref SIM: RwLock<Sim> = RwLock::new(Sim::new(&Map::blank(), SimOptions::new("tmp")));
-    static ref LOAD: RwLock<LoadSim> = RwLock::new({
-        LoadSim {
-            scenario: abstio::path_scenario(&MapName::seattle("montlake"), "weekday"),
-            modifiers: Vec::new(),
-            edits: None,
-            rng_seed: SimFlags::RNG_SEED,
-            opts: SimOptions::default(),
-        }
-    });
+    static ref SESSIONS: RwLock<BTreeMap<String, Arc<Mutex<Session>>>> =
+        RwLock::new(BTreeMap::new());
+    /// Unedited maps, keyed by path, shared between all sessions using them.
+    static ref BASE_MAPS: Mutex<HashMap<String, Weak<Map>>> = Mutex::new(HashMap::new());
+}
+
+const DEFAULT_SESSION: &str = "default";
+/// When an externally controlled signal needs a decision and the client hasn't queued one, keep
+/// the current stage this long before asking again.
+const HOLD_WITHOUT_DECISION: Duration = Duration::const_seconds(1.0);
+
+/// An independent simulation, with its own map edits and options.
+struct Session {
+    /// Sessions share the same map until one of them applies edits.
+    map: Arc<Map>,
+    sim: Sim,
+    load: LoadSim,
+    signals: ApiSignalController,
+    /// Corridors coordinated with `/traffic-signals/make-green-wave`
+    green_waves: Vec<GreenWave>,
+}
+
+/// Lets API clients control traffic signals. Clients queue up decisions for each signal, and the
+/// simulation consumes them whenever a stage ends.
+#[derive(Clone, Default)]
+struct ApiSignalController {
+    decisions: Arc<Mutex<BTreeMap<IntersectionID, VecDeque<SignalDecision>>>>,
+}
+
+impl SignalController for ApiSignalController {
+    fn next_stage(&mut self, _: Time, status: &SignalStatus, _: &Map) -> SignalDecision {
+        self.decisions
+            .lock()
+            .unwrap()
+            .get_mut(&status.id)
+            .and_then(|queue| queue.pop_front())
+            .unwrap_or(SignalDecision {
+                stage: status.current_stage,
+                duration: HOLD_WITHOUT_DECISION,
+            })
+    }
+
+    fn clone_box(&self) -> Box<dyn SignalController> {
+        Box::new(self.clone())
+    }
 }
 
 #[derive(StructOpt)]
@@ -83,16 +161,26 @@ async fn main() {
     let args = Args::from_args();
 
     {
-        let mut load = LOAD.write().unwrap();
-        load.rng_seed = args.rng_seed;
-        load.opts = args.opts;
-        if let Some(path) = args.scenario {
-            load.scenario = path;
-        }
-
+        let load = LoadSim {
+            scenario: args
+                .scenario
+                .unwrap_or_else(|| abstio::path_scenario(&MapName::seattle("montlake"), "weekday")),
+            modifiers: Vec::new(),
+            edits: None,
+            rng_seed: args.rng_seed,
+            opts: args.opts,
+        };
         let (map, sim) = load.setup(&mut Timer::new("setup headless"));
-        *MAP.write().unwrap() = map;
-        *SIM.write().unwrap() = sim;
+        SESSIONS.write().unwrap().insert(
+            DEFAULT_SESSION.to_string(),
+            Arc::new(Mutex::new(Session {
+                map,
+                sim,
+                load,
+                signals: ApiSignalController::default(),
+                green_waves: Vec::new(),
+            })),
+        );
     }
 
     let addr = std::net::SocketAddr::from((args.ip, args.port));
@@ -116,40 +204,275 @@ async fn serve_req(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
             .collect();
     let body = hyper::body::to_bytes(req).await?.to_vec();
     info!("Handling {}", path);
-    Ok(
-        match handle_command(
-            &path,
-            &params,
-            &body,
-            &mut SIM.write().unwrap(),
-            &mut MAP.write().unwrap(),
-            &mut LOAD.write().unwrap(),
-        ) {
-            Ok(resp) => Response::new(Body::from(resp)),
+
+    if path.starts_with("/sessions/") {
+        // Creating a session loads a map and scenario, so keep that off the async runtime
+        let resp = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || handle_session_command(&path, &params, &body)
+        })
+        .await;
+        return Ok(respond(&path, resp));
+    }
+
+    let session = match get_session(&params) {
+        Ok(session) => session,
+        Err(err) => {
+            return Ok(bad_request(&path, err));
+        }
+    };
+
+    // This one doesn't produce a single response, so handle it separately
+    if path == "/sim/stream-events" {
+        // Checking the request waits for the session, which may be busy
+        let resp =
+            tokio::task::spawn_blocking(move || stream_events(&params, &body, session)).await;
+        return Ok(match resp {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(err)) => bad_request(&path, err),
+            Err(err) => internal_error(&path, err),
+        });
+    }
+
+    // Commands like /sim/goto-time or /traffic-signals/optimize can run for a long time. Don't
+    // block the async runtime meanwhile, or every other request stalls too.
+    let resp = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || {
+            let mut session = lock_session(&session)?;
+            handle_command(&path, &params, &body, &mut session)
+        }
+    })
+    .await;
+    Ok(respond(&path, resp))
+}
+
+/// Locks a session for one request. If an earlier request panicked while holding the lock, the
+/// session may be half-updated, so refuse to use it.
+fn lock_session(session: &Mutex<Session>) -> Result<MutexGuard<Session>> {
+    session.lock().map_err(|_| {
+        anyhow!("this session crashed during an earlier request. Delete and recreate it.")
+    })
+}
+
+fn respond(path: &str, resp: Result<Result<String>, tokio::task::JoinError>) -> Response<Body> {
+    match resp {
+        Ok(Ok(resp)) => Response::new(Body::from(resp)),
+        Ok(Err(err)) => bad_request(path, err),
+        Err(err) => internal_error(path, err),
+    }
+}
+
+fn get_session(params: &HashMap<String, String>) -> Result<Arc<Mutex<Session>>> {
+    let name = params
+        .get("session")
+        .map(|x| x.as_str())
+        .unwrap_or(DEFAULT_SESSION);
+    SESSIONS
+        .read()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("no session named {}", name))
+}
+
+fn bad_request(path: &str, err: anyhow::Error) -> Response<Body> {
+    error!("{}: {}", path, err);
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(Body::from(format!("Bad command {}: {}", path, err)))
+        .unwrap()
+}
+
+/// The handler panicked
+fn internal_error(path: &str, err: tokio::task::JoinError) -> Response<Body> {
+    error!("{} crashed: {}", path, err);
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(Body::from(format!("{} crashed: {}", path, err)))
+        .unwrap()
+}
+
+/// Advance the simulation to time `t`, streaming matching events as newline-delimited JSON while
+/// the sim runs. Events are flushed every `step_seconds` of simulated time. The body optionally
+/// contains an `EventFilter`. If the client disconnects, the simulation stops advancing.
+fn stream_events(
+    params: &HashMap<String, String>,
+    body: &[u8],
+    session: Arc<Mutex<Session>>,
+) -> Result<Response<Body>> {
+    let end_time = Time::parse(
+        params
+            .get("t")
+            .ok_or_else(|| anyhow!("missing GET parameter t"))?,
+    )?;
+    let step = match params.get("step_seconds") {
+        Some(x) => Duration::seconds(x.parse::<f64>()?),
+        None => Duration::seconds(10.0),
+    };
+    if step <= Duration::ZERO {
+        bail!("step_seconds must be positive");
+    }
+    let filter: EventFilter = if body.is_empty() {
+        EventFilter::default()
+    } else {
+        abstutil::from_json(body)?
+    };
+    let region = {
+        let session = lock_session(&session)?;
+        if end_time <= session.sim.time() {
+            bail!("{} is in the past. call /sim/reset first?", end_time);
+        }
+        filter.region(&session.map)?
+    };
+
+    let (mut sender, response_body) = Body::channel();
+    let handle = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let mut session = match lock_session(&session) {
+            Ok(session) => session,
             Err(err) => {
-                error!("{}: {}", path, err);
-                Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from(format!("Bad command {}: {}", path, err)))
-                    .unwrap()
+                error!("Can't stream events: {}", err);
+                return;
+            }
+        };
+        let Session { map, sim, .. } = &mut *session;
+        sim.start_buffering_events();
+        while sim.time() < end_time {
+            let remaining = end_time - sim.time();
+            let dt = if remaining < step { remaining } else { step };
+            sim.timed_step(map, dt, &mut None, &mut Timer::throwaway());
+
+            let mut chunk = String::new();
+            for (time, event) in sim.drain_buffered_events() {
+                if filter.matches(&event, map, region.as_ref()) {
+                    chunk.push_str(&serde_json::to_string(&StreamedEvent { time, event }).unwrap());
+                    chunk.push('\n');
+                }
+            }
+            if !chunk.is_empty() && handle.block_on(sender.send_data(chunk.into())).is_err() {
+                info!("Client stopped listening to events at {}", sim.time());
+                break;
+            }
+        }
+        sim.stop_buffering_events();
+    });
+
+    Ok(Response::builder()
+        .header("Content-Type", "application/x-ndjson")
+        .body(response_body)?)
+}
+
+fn handle_session_command(
+    path: &str,
+    params: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<String> {
+    let get = |key: &str| {
+        params
+            .get(key)
+            .ok_or_else(|| anyhow!("missing GET parameter {}", key))
+    };
+
+    match path {
+        "/sessions/list" => {
+            let mut sessions = Vec::new();
+            for (name, session) in SESSIONS.read().unwrap().iter() {
+                // Don't wait on sessions in the middle of a long request, like simulating hours
+                // or optimizing signals
+                sessions.push(match session.try_lock() {
+                    Ok(session) => SessionInfo {
+                        name: name.clone(),
+                        busy: false,
+                        crashed: false,
+                        map: Some(session.map.get_name().clone()),
+                        scenario: Some(session.load.scenario.clone()),
+                        edits: Some(session.map.get_edits().edits_name.clone()),
+                        time: Some(session.sim.time()),
+                    },
+                    Err(TryLockError::WouldBlock) => SessionInfo {
+                        name: name.clone(),
+                        busy: true,
+                        crashed: false,
+                        map: None,
+                        scenario: None,
+                        edits: None,
+                        time: None,
+                    },
+                    // Don't trust anything about a session left half-updated by a panic
+                    Err(TryLockError::Poisoned(_)) => SessionInfo {
+                        name: name.clone(),
+                        busy: false,
+                        crashed: true,
+                        map: None,
+                        scenario: None,
+                        edits: None,
+                        time: None,
+                    },
+                });
+            }
+            Ok(abstutil::to_json(&sessions))
+        }
+        "/sessions/create" => {
+            let name = get("name")?.to_string();
+            if SESSIONS.read().unwrap().contains_key(&name) {
+                bail!("session {} already exists", name);
+            }
+            let args: CreateSession = abstutil::from_json(body)?;
+            let mut load = args.load;
+            load.rng_seed = args.rng_seed.unwrap_or(SimFlags::RNG_SEED);
+            load.opts = SimOptions::from_iter_safe(
+                std::iter::once("headless".to_string()).chain(args.sim_flags),
+            )?;
+
+            // Loading may take a while, so don't hold the lock on all sessions meanwhile
+            let (map, sim) = load.setup(&mut Timer::new(format!("create session {}", name)));
+            let mut sessions = SESSIONS.write().unwrap();
+            if sessions.contains_key(&name) {
+                bail!("session {} already exists", name);
+            }
+            sessions.insert(
+                name.clone(),
+                Arc::new(Mutex::new(Session {
+                    map,
+                    sim,
+                    load,
+                    signals: ApiSignalController::default(),
+                    green_waves: Vec::new(),
+                })),
+            );
+            Ok(format!("session {} created", name))
+        }
+        "/sessions/delete" => {
+            let name = get("name")?;
+            if SESSIONS.write().unwrap().remove(name).is_some() {
+                Ok(format!("session {} deleted", name))
+            } else {
+                bail!("no session named {}", name)
             }
-        },
-    )
+        }
+        _ => Err(anyhow!("Unknown command")),
+    }
 }
 
 fn handle_command(
     path: &str,
     params: &HashMap<String, String>,
     body: &[u8],
-    sim: &mut Sim,
-    map: &mut Map,
-    load: &mut LoadSim,
+    session: &mut Session,
 ) -> Result<String> {
     let get = |key: &str| {
         params
             .get(key)
             .ok_or_else(|| anyhow!("missing GET parameter {}", key))
     };
+    let Session {
+        map,
+        sim,
+        load,
+        signals,
+        green_waves,
+    } = session;
 
     match path {
         // Controlling the simulation
@@ -157,6 +480,8 @@ fn handle_command(
             let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"));
             *map = new_map;
             *sim = new_sim;
+            *signals = ApiSignalController::default();
+            green_waves.clear();
             Ok("sim reloaded".to_string())
         }
         "/sim/load" => {
@@ -170,13 +495,16 @@ fn handle_command(
             let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"));
             *map = new_map;
             *sim = new_sim;
+            *signals = ApiSignalController::default();
+            green_waves.clear();
 
             Ok("flags changed and sim reloaded".to_string())
         }
         "/sim/load-blank" => {
-            *map =
-                Map::load_synchronously(get("map")?.to_string(), &mut Timer::new("load new map"));
-            *sim = Sim::new(&map, SimOptions::default());
+            *map = load_base_map(get("map")?.to_string(), &mut Timer::new("load new map"));
+            *sim = Sim::new(map, SimOptions::default());
+            *signals = ApiSignalController::default();
+            green_waves.clear();
             Ok("map changed, blank simulation".to_string())
         }
         "/sim/get-time" => Ok(sim.time().to_string()),
@@ -230,11 +558,130 @@ fn handle_command(
             edits.commands.push(map.edit_intersection_cmd(id, |new| {
                 new.control = EditIntersectionControl::TrafficSignal(ts.export(map));
             }));
+            // If other sessions share this map, this makes a copy first
+            let map = Arc::make_mut(map);
             map.must_apply_edits(edits, &mut Timer::throwaway());
             map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
 
             Ok(format!("{} has been updated", id))
         }
+        "/traffic-signals/make-green-wave" => {
+            let corridor = get("ids")?
+                .split(',')
+                .map(|x| Ok(IntersectionID(x.parse::<usize>()?)))
+                .collect::<Result<Vec<_>>>()?;
+            let speed = Speed::meters_per_second(get("speed_mps")?.parse::<f64>()?);
+            let cycle_length = match params.get("cycle_length") {
+                Some(x) => Some(Duration::seconds(x.parse::<f64>()?)),
+                None => None,
+            };
+
+            let before = measure_progression(map, &corridor, speed)?;
+            let (new_signals, after) = make_green_wave(map, &corridor, speed, cycle_length)?;
+            let mut edits = map.get_edits().clone();
+            for ts in new_signals {
+                edits.commands.push(map.edit_intersection_cmd(ts.id, |new| {
+                    new.control = EditIntersectionControl::TrafficSignal(ts.export(map));
+                }));
+            }
+            // If other sessions share this map, this makes a copy first
+            let map = Arc::make_mut(map);
+            map.must_apply_edits(edits, &mut Timer::throwaway());
+            map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+
+            // Later green waves over the same signals replace earlier ones
+            green_waves.retain(|gw| !gw.corridor.iter().any(|i| corridor.contains(i)));
+            let green_wave = GreenWave {
+                corridor,
+                speed,
+                before,
+                after,
+            };
+            let result = abstutil::to_json(&green_wave);
+            green_waves.push(green_wave);
+            Ok(result)
+        }
+        "/traffic-signals/optimize" => {
+            let config = OptimizerConfig {
+                intersections: get("ids")?
+                    .split(',')
+                    .map(|x| Ok(IntersectionID(x.parse::<usize>()?)))
+                    .collect::<Result<Vec<_>>>()?,
+                start: Time::parse(get("t1")?)?,
+                end: Time::parse(get("t2")?)?,
+                rounds: match params.get("rounds") {
+                    Some(x) => x.parse::<usize>()?,
+                    None => 3,
+                },
+                step: Duration::seconds(match params.get("step_seconds") {
+                    Some(x) => x.parse::<f64>()?,
+                    None => 5.0,
+                }),
+            };
+            // Start from this session's current map and simulation, without disturbing them
+            let mut scratch_map = Map::clone(map);
+            let result = optimize_signals(
+                &mut scratch_map,
+                sim.clone(),
+                &config,
+                &mut Timer::new("optimize traffic signals"),
+            )?;
+            let mut edits = result.edits;
+            edits.edits_name = "optimized signals".to_string();
+            Ok(abstutil::to_json(&OptimizedSignalsResponse {
+                baseline_delay: result.baseline_delay,
+                best_delay: result.best_delay,
+                num_simulations: result.num_simulations,
+                edits: edits.to_permanent(&scratch_map),
+            }))
+        }
+        "/traffic-signals/set-external" => {
+            let i = IntersectionID(get("id")?.parse::<usize>()?);
+            let external = match params.get("external") {
+                Some(x) => x.parse::<bool>()?,
+                None => true,
+            };
+            if !map.get_i(i).is_traffic_signal() {
+                bail!("{} isn't a traffic signal", i);
+            }
+            sim.set_signal_controller(Box::new(signals.clone()));
+            sim.set_externally_controlled(i, external);
+            if !external {
+                signals.decisions.lock().unwrap().remove(&i);
+            }
+            Ok(format!(
+                "{} is now {}",
+                i,
+                if external {
+                    "controlled externally"
+                } else {
+                    "following its plan"
+                }
+            ))
+        }
+        "/traffic-signals/queue-external-stage" => {
+            let i = IntersectionID(get("id")?.parse::<usize>()?);
+            let stage = get("stage")?.parse::<usize>()?;
+            let duration = Duration::seconds(get("duration")?.parse::<f64>()?);
+            if !sim.is_externally_controlled(i) {
+                bail!("{} isn't externally controlled. Call set-external first", i);
+            }
+            let num_stages = map.get_traffic_signal(i).stages.len();
+            if stage >= num_stages {
+                bail!("{} only has {} stages", i, num_stages);
+            }
+            let mut decisions = signals.decisions.lock().unwrap();
+            let queue = decisions.entry(i).or_insert_with(VecDeque::new);
+            queue.push_back(SignalDecision { stage, duration });
+            Ok(format!("{} decisions queued for {}", queue.len(), i))
+        }
+        "/traffic-signals/get-external-status" => {
+            let i = IntersectionID(get("id")?.parse::<usize>()?);
+            if !map.get_i(i).is_traffic_signal() {
+                bail!("{} isn't a traffic signal", i);
+            }
+            Ok(abstutil::to_json(&sim.get_signal_status(i, map)))
+        }
         "/traffic-signals/get-delays" => {
             let i = map.get_i(IntersectionID(get("id")?.parse::<usize>()?));
             let t1 = Time::parse(get("t1")?)?;
@@ -246,7 +693,14 @@ fn handle_command(
 
             let mut delays = Delays {
                 per_direction: BTreeMap::new(),
+                progression: None,
             };
+            if let Some(gw) = green_waves.iter().find(|gw| gw.corridor.contains(&i.id)) {
+                // The signals may have been edited since the green wave was made
+                let mut current = gw.clone();
+                current.after = measure_progression(map, &gw.corridor, gw.speed)?;
+                delays.progression = Some(current);
+            }
             for m in i.movements.keys() {
                 delays.per_direction.insert(*m, Vec::new());
             }
@@ -328,6 +782,20 @@ fn handle_command(
             }
             Ok(abstutil::to_json(&trips))
         }
+        "/data/get-time-per-leg" => {
+            let analytics = sim.get_analytics();
+            let mut trips = Vec::new();
+            for (_, id, mode, maybe_duration) in &analytics.finished_trips {
+                if maybe_duration.is_some() {
+                    trips.push(TimePerLeg {
+                        id: *id,
+                        mode: *mode,
+                        legs: analytics.time_per_leg(*id),
+                    });
+                }
+            }
+            Ok(abstutil::to_json(&trips))
+        }
         "/data/get-agent-positions" => Ok(abstutil::to_json(&AgentPositions {
             agents: sim
                 .get_unzoomed_agents(map)
@@ -364,6 +832,54 @@ fn handle_command(
                 })
                 .collect(),
         })),
+        "/data/get-ride-hail-stats" => {
+            let analytics = sim.get_analytics();
+            let mut stats = RideHailStats {
+                pickups: analytics.ride_hail_pickups.len(),
+                dropoffs: analytics.ride_hail_dropoffs.len(),
+                still_waiting: sim.num_waiting_for_ride_hail(),
+                mean_wait: Duration::ZERO,
+                max_wait: Duration::ZERO,
+                total_distance: Distance::ZERO,
+                empty_distance: Distance::ZERO,
+                stops_per_lane: BTreeMap::new(),
+            };
+            for (_, l, wait) in &analytics.ride_hail_pickups {
+                stats.mean_wait += *wait;
+                if *wait > stats.max_wait {
+                    stats.max_wait = *wait;
+                }
+                *stats.stops_per_lane.entry(*l).or_insert(0) += 1;
+            }
+            if stats.pickups > 0 {
+                stats.mean_wait = stats.mean_wait / (stats.pickups as f64);
+            }
+            for (_, l) in &analytics.ride_hail_dropoffs {
+                *stats.stops_per_lane.entry(*l).or_insert(0) += 1;
+            }
+            for (_, dist, riders) in &analytics.ride_hail_legs {
+                stats.total_distance += *dist;
+                if *riders == 0 {
+                    stats.empty_distance += *dist;
+                }
+            }
+            Ok(abstutil::to_json(&stats))
+        }
+        "/data/get-emissions" => {
+            let emissions = &sim.get_analytics().emissions;
+            Ok(abstutil::to_json(&EmissionsReport {
+                per_road: emissions
+                    .per_road
+                    .iter()
+                    .map(|((r, hr), amount)| (*r, *hr, *amount))
+                    .collect(),
+                per_trip: emissions.per_trip.clone(),
+                per_hour: emissions.total_per_hour(sim.time()),
+            }))
+        }
+        "/data/get-travel-time-profile" => Ok(abstutil::to_json(
+            &sim.get_analytics().travel_times.to_profile(map),
+        )),
         "/data/trip-time-lower-bound" => {
             let id = TripID(get("id")?.parse::<usize>()?);
             let duration = sim.get_trip_time_lower_bound(map, id)?;
@@ -430,10 +946,41 @@ struct FinishedTrip {
     mode: TripMode,
 }
 
+#[derive(Serialize)]
+struct TimePerLeg {
+    id: TripID,
+    mode: TripMode,
+    /// Each kind of trip phase, like driving or riding transit, and the total time spent in it
+    legs: Vec<(&'static str, Duration)>,
+}
+
 #[derive(Serialize)]
 struct Delays {
     #[serde(serialize_with = "serialize_btreemap")]
     per_direction: BTreeMap<MovementID, Vec<Duration>>,
+    /// If this signal is part of a coordinated corridor, how well traffic progresses along it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progression: Option<GreenWave>,
+}
+
+#[derive(Clone, Serialize)]
+struct GreenWave {
+    /// The signals along the corridor, in order
+    corridor: Vec<IntersectionID>,
+    speed: Speed,
+    /// Progression with the signal timing before the green wave was applied
+    before: CorridorProgression,
+    /// Progression with the current signal timing
+    after: CorridorProgression,
+}
+
+#[derive(Serialize)]
+struct OptimizedSignalsResponse {
+    baseline_delay: Duration,
+    best_delay: Duration,
+    num_simulations: usize,
+    /// Pass these to `/sim/load` to use the optimized signals
+    edits: PermanentMapEdits,
 }
 
 #[derive(Serialize)]
@@ -479,6 +1026,33 @@ struct RoadThroughput {
     counts: Vec<(RoadID, AgentType, usize, usize)>,
 }
 
+#[derive(Serialize)]
+struct EmissionsReport {
+    // (road, hour since midnight, emitted on that road during that one hour period)
+    per_road: Vec<(RoadID, usize, Emissions)>,
+    #[serde(serialize_with = "serialize_btreemap")]
+    per_trip: BTreeMap<TripID, Emissions>,
+    /// Emitted over the whole map during each hour since midnight
+    per_hour: Vec<Emissions>,
+}
+
+#[derive(Serialize)]
+struct RideHailStats {
+    pickups: usize,
+    dropoffs: usize,
+    /// Riders who haven't been assigned a vehicle yet
+    still_waiting: usize,
+    /// From requesting a ride to getting in
+    mean_wait: Duration,
+    max_wait: Duration,
+    total_distance: Distance,
+    /// Driven with nobody on board, to reach a pickup
+    empty_distance: Distance,
+    /// How many times vehicles stopped at the curb of each lane, to pick up or drop off
+    #[serde(serialize_with = "serialize_btreemap")]
+    stops_per_lane: BTreeMap<LaneID, usize>,
+}
+
 #[derive(Serialize)]
 struct TrafficSignalState {
     current_stage_idx: usize,
@@ -497,12 +1071,103 @@ struct BlockedByGraph {
     blocked_by: BTreeMap<AgentID, (Duration, DelayCause, Option<TripID>, Option<PersonID>)>,
 }
 
+#[derive(Serialize)]
+struct StreamedEvent {
+    time: Time,
+    event: Event,
+}
+
+/// Controls which events `/sim/stream-events` sends. Every field is optional; an empty filter
+/// matches everything.
+#[derive(Default, Deserialize)]
+struct EventFilter {
+    /// Only send events of these types, like `TripFinished` or `IntersectionDelayMeasured`.
+    #[serde(default)]
+    event_types: BTreeSet<String>,
+    /// Only send events involving these types of agents. Events not involving exactly one agent,
+    /// like `Alert`, are skipped when this is specified.
+    #[serde(default)]
+    agent_types: BTreeSet<AgentType>,
+    /// Only send events that happen inside this polygon. Events without a location are skipped
+    /// when this is specified.
+    #[serde(default)]
+    region: Option<Vec<LonLat>>,
+}
+
+impl EventFilter {
+    fn region(&self, map: &Map) -> Result<Option<Polygon>> {
+        let mut pts = match self.region {
+            Some(ref pts) => map.get_gps_bounds().convert(pts),
+            None => {
+                return Ok(None);
+            }
+        };
+        // Be forgiving about closing the ring
+        if pts.len() > 1 && pts[0] != *pts.last().unwrap() {
+            pts.push(pts[0]);
+        }
+        Ok(Some(Ring::new(pts)?.into_polygon()))
+    }
+
+    fn matches(&self, event: &Event, map: &Map, region: Option<&Polygon>) -> bool {
+        if !self.event_types.is_empty() && !self.event_types.contains(event.name()) {
+            return false;
+        }
+        if !self.agent_types.is_empty() {
+            match event.agent_type() {
+                Some(agent_type) if self.agent_types.contains(&agent_type) => {}
+                _ => {
+                    return false;
+                }
+            }
+        }
+        if let Some(region) = region {
+            match event.point(map) {
+                Some(pt) if region.contains_pt(pt) => {}
+                _ => {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Serialize)]
+struct SessionInfo {
+    name: String,
+    /// Another request is using the session right now, so the rest is unknown
+    busy: bool,
+    /// An earlier request panicked while using the session. It should be deleted and recreated.
+    crashed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    map: Option<MapName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scenario: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edits: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<Time>,
+}
+
+#[derive(Deserialize)]
+struct CreateSession {
+    #[serde(flatten)]
+    load: LoadSim,
+    /// Defaults to SimFlags::RNG_SEED
+    rng_seed: Option<u64>,
+    /// Options for the simulation, in the same format as the command line flags, like
+    /// `["--infinite-parking"]`
+    #[serde(default)]
+    sim_flags: Vec<String>,
+}
+
 #[derive(Deserialize)]
 struct LoadSim {
     scenario: String,
     modifiers: Vec<ScenarioModifier>,
     edits: Option<PermanentMapEdits>,
-    // These are fixed from the initial command line flags
+    // These are fixed when the session is created
     #[serde(skip_deserializing)]
     rng_seed: u64,
     #[serde(skip_deserializing)]
@@ -510,12 +1175,14 @@ struct LoadSim {
 }
 
 impl LoadSim {
-    fn setup(&self, timer: &mut Timer) -> (Map, Sim) {
+    fn setup(&self, timer: &mut Timer) -> (Arc<Map>, Sim) {
         let mut scenario: Scenario = abstio::must_read_object(self.scenario.clone(), timer);
 
-        let mut map = Map::load_synchronously(scenario.map_name.path(), timer);
+        let mut map = load_base_map(scenario.map_name.path(), timer);
         if let Some(perma) = self.edits.clone() {
-            let edits = perma.into_edits(&map).unwrap();
+            // Don't modify the map other sessions are using
+            let map = Arc::make_mut(&mut map);
+            let edits = perma.into_edits(map).unwrap();
             map.must_apply_edits(edits, timer);
             map.recalculate_pathfinding_after_edits(timer);
         }
@@ -532,6 +1199,17 @@ impl LoadSim {
     }
 }
 
+/// Returns an unedited map, reusing one already loaded by another session if possible.
+fn load_base_map(path: String, timer: &mut Timer) -> Arc<Map> {
+    let mut cache = BASE_MAPS.lock().unwrap();
+    if let Some(map) = cache.get(&path).and_then(|map| map.upgrade()) {
+        return map;
+    }
+    let map = Arc::new(Map::load_synchronously(path.clone(), timer));
+    cache.insert(path, Arc::downgrade(&map));
+    map
+}
+
 fn export_geometry(map: &Map, i: IntersectionID) -> geojson::GeoJson {
     let mut pairs = Vec::new();
 
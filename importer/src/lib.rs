@@ -103,6 +103,10 @@ pub struct Job {
     /// importer/config/$city/.
     #[structopt()]
     pub only_map: Option<String>,
+    /// Which day's timetable to import from GTFS feeds: a day of the week like "saturday", or a
+    /// YYYYMMDD date. If not specified, a typical weekday is used.
+    #[structopt(long, parse(try_from_str = convert_osm::ServiceDay::parse))]
+    pub gtfs_service_day: Option<convert_osm::ServiceDay>,
 
     #[structopt(flatten)]
     pub opts: RawToMapOptions,
@@ -117,6 +121,7 @@ impl Job {
             scenario: false,
             city_overview: false,
             only_map: None,
+            gtfs_service_day: None,
             opts: RawToMapOptions::default(),
         };
         // Only some maps run extra tasks
@@ -157,6 +162,9 @@ impl Job {
         if self.city_overview {
             flags.push("--city-overview".to_string());
         }
+        if let Some(ref day) = self.gtfs_service_day {
+            flags.push(format!("--gtfs-service-day={}", day));
+        }
         if let Some(ref name) = self.only_map {
             flags.push(name.clone());
         }
@@ -211,7 +219,9 @@ impl Job {
             if self.osm_to_raw
                 && (!built_raw_huge_seattle || name != MapName::seattle("huge_seattle"))
             {
-                let raw = utils::osm_to_raw(name.clone(), timer, &config).await;
+                let raw =
+                    utils::osm_to_raw(name.clone(), self.gtfs_service_day.clone(), timer, &config)
+                        .await;
 
                 // The collision data will only cover one part of London, since we don't have a
                 // region-wide map there yet
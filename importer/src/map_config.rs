@@ -77,6 +77,8 @@ pub fn config_for_map(name: &MapName) -> convert_osm::Options {
         } else {
             None
         },
+        // Model a typical weekday
+        gtfs_service_day: convert_osm::ServiceDay::Weekday(2),
         // We only have a few elevation sources working
         elevation_geotiff: if name.city == CityName::new("us", "seattle") {
             Some("data/input/shared/elevation/king_county_2016_lidar.tif".to_string())
@@ -257,8 +257,9 @@ fn get_mode(code: &str) -> TripMode {
         "1.0" => TripMode::Walk,
         "2.0" => TripMode::Bike,
         "3.0" | "4.0" | "5.0" => TripMode::Drive,
-        // TODO Park-and-ride and school bus as walk-to-transit is a little weird.
-        "6.0" | "7.0" | "8.0" => TripMode::Transit,
+        // TODO School bus as walk-to-transit is a little weird.
+        "6.0" | "8.0" => TripMode::Transit,
+        "7.0" => TripMode::ParkAndRide,
         // TODO Invalid code, what's this one mean? I only see a few examples, so just default to
         // walking.
         "0.0" => TripMode::Walk,
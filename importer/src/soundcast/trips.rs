@@ -202,8 +202,11 @@ fn clip_trips(
                 borders.for_mode(orig.mode),
                 match orig.mode {
                     TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                    TripMode::Drive => PathConstraints::Car,
-                    TripMode::Bike => PathConstraints::Bike,
+                    TripMode::Drive | TripMode::ParkAndRide | TripMode::RideHail => {
+                        PathConstraints::Car
+                    }
+                    TripMode::Bike | TripMode::Scooter => PathConstraints::Bike,
+                    TripMode::Freight => PathConstraints::Truck,
                 },
                 maybe_huge_map.as_ref(),
                 only_passthrough_trips,
@@ -293,6 +296,7 @@ pub fn make_scenario(
         map_name: map.get_name().clone(),
         people,
         only_seed_buses: None,
+        deliveries: Vec::new(),
     }
     .remove_weird_schedules(true)
 }
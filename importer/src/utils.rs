@@ -118,16 +118,21 @@ pub fn osmium(
     );
 }
 
-/// Creates a RawMap from OSM and other input data.
+/// Creates a RawMap from OSM and other input data. GTFS feeds use the timetable for
+/// `gtfs_service_day`, or the map's default.
 pub async fn osm_to_raw(
     name: MapName,
+    gtfs_service_day: Option<convert_osm::ServiceDay>,
     timer: &mut abstutil::Timer<'_>,
     config: &ImporterConfiguration,
 ) -> RawMap {
     if name.city == CityName::seattle() {
         crate::seattle::input(config, timer).await;
     }
-    let opts = crate::map_config::config_for_map(&name);
+    let mut opts = crate::map_config::config_for_map(&name);
+    if let Some(day) = gtfs_service_day {
+        opts.gtfs_service_day = day;
+    }
     if let Some(ref url) = opts.gtfs_url {
         download(config, name.city.input_path("gtfs/"), url).await;
     }
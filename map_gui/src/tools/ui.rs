@@ -256,9 +256,11 @@ pub fn cmp_count(txt: &mut Text, before: usize, after: usize) {
 pub fn color_for_mode(app: &dyn AppLike, m: TripMode) -> Color {
     match m {
         TripMode::Walk => app.cs().unzoomed_pedestrian,
-        TripMode::Bike => app.cs().unzoomed_bike,
+        TripMode::Bike | TripMode::Scooter => app.cs().unzoomed_bike,
         TripMode::Transit => app.cs().unzoomed_bus,
-        TripMode::Drive => app.cs().unzoomed_car,
+        TripMode::Drive | TripMode::ParkAndRide | TripMode::RideHail | TripMode::Freight => {
+            app.cs().unzoomed_car
+        }
     }
 }
 
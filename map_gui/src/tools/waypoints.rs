@@ -284,7 +284,7 @@ impl Waypoint {
                     i.name(app.opts().language.as_ref(), map),
                 )
             }
-            TripEndpoint::SuddenlyAppear(pos) => (
+            TripEndpoint::SuddenlyAppear(pos) | TripEndpoint::Dockless(pos) => (
                 pos.pt(map),
                 map.get_parent(pos.lane())
                     .get_name(app.opts().language.as_ref()),
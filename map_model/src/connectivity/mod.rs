@@ -9,7 +9,7 @@ use abstutil::PriorityQueueItem;
 use geom::Duration;
 
 pub use self::walking::{all_walking_costs_from, WalkingOptions};
-pub use crate::pathfind::{vehicle_cost, WalkingNode};
+pub use crate::pathfind::{observed_vehicle_cost, vehicle_cost, WalkingNode};
 use crate::{BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, PathConstraints};
 
 mod walking;
@@ -5,13 +5,15 @@ use geom::{Duration, Speed};
 
 use crate::connectivity::Spot;
 use crate::pathfind::{zone_cost, WalkingNode};
-use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep};
+use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep, WalkingProfile};
 
 #[derive(Clone)]
 pub struct WalkingOptions {
     /// If true, allow walking on shoulders.
     pub allow_shoulders: bool,
     pub walking_speed: Speed,
+    /// If set, avoid sidewalks and crossings that this profile can't use.
+    pub profile: Option<WalkingProfile>,
 }
 
 impl WalkingOptions {
@@ -19,6 +21,7 @@ impl WalkingOptions {
         WalkingOptions {
             allow_shoulders: true,
             walking_speed: WalkingOptions::default_speed(),
+            profile: None,
         }
     }
 
@@ -40,7 +43,8 @@ impl WalkingOptions {
 /// away.
 ///
 /// If all of the start buildings are on the shoulder of a road and `!opts.allow_shoulders`, then
-/// the results will always be empty.
+/// the results will always be empty. Like routing, a start building is connected to the nearest
+/// end of its sidewalk, even if `opts.profile` can't use that sidewalk.
 pub fn all_walking_costs_from(
     map: &Map,
     starts: Vec<Spot>,
@@ -129,7 +133,12 @@ pub fn all_walking_costs_from(
         };
         let lane = map.get_l(r.must_get_sidewalk(map));
         // Cross the lane
-        if opts.allow_shoulders || lane.lane_type != LaneType::Shoulder {
+        let usable = opts
+            .profile
+            .as_ref()
+            .map(|p| p.lane_barrier(lane, map).is_none())
+            .unwrap_or(true);
+        if usable && (opts.allow_shoulders || lane.lane_type != LaneType::Shoulder) {
             let sidewalk_len = lane.length();
             let step = if is_dst_i {
                 PathStep::ContraflowLane(lane.id)
@@ -171,6 +180,11 @@ pub fn all_walking_costs_from(
             if (turn.id.parent == lane.dst_i) != is_dst_i {
                 continue;
             }
+            if let Some(ref profile) = opts.profile {
+                if profile.turn_barrier(turn, map).is_some() {
+                    continue;
+                }
+            }
             queue.push(PriorityQueueItem {
                 cost: current.cost
                     + turn.geom.length()
@@ -273,6 +273,12 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
                 map.transit_routes[id.0].spawn_times = new.clone();
             }
+            EditCmd::ChangeRouteCapacity { id, new, .. } => {
+                map.transit_routes[id.0].capacity = *new;
+            }
+            EditCmd::ChangeParkingPricing { new, .. } => {
+                map.parking_pricing = new.clone();
+            }
         }
     }
 
@@ -293,6 +299,14 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeRouteCapacity { id, old, new } => EditCmd::ChangeRouteCapacity {
+                id,
+                old: new,
+                new: old,
+            },
+            EditCmd::ChangeParkingPricing { old, new } => {
+                EditCmd::ChangeParkingPricing { old: new, new: old }
+            }
         }
     }
 }
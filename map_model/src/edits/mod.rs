@@ -14,8 +14,8 @@ use osm2streets::{get_lane_specs_ltr, RestrictionType};
 pub use self::perma::PermanentMapEdits;
 use crate::{
     AccessRestrictions, ControlStopSign, ControlTrafficSignal, Crossing, DiagonalFilter,
-    IntersectionControl, IntersectionID, LaneID, LaneSpec, Map, MapConfig, ParkingLotID, Road,
-    RoadFilter, RoadID, TransitRouteID, TurnID, TurnType,
+    IntersectionControl, IntersectionID, LaneID, LaneSpec, Map, MapConfig, ParkingLotID,
+    ParkingPricing, Road, RoadFilter, RoadID, TransitRouteID, TurnID, TurnType,
 };
 
 mod apply;
@@ -36,6 +36,10 @@ pub struct MapEdits {
     pub original_roads: BTreeMap<RoadID, EditRoad>,
     pub original_intersections: BTreeMap<IntersectionID, EditIntersection>,
     pub changed_routes: BTreeSet<TransitRouteID>,
+    /// Vehicle capacity of routes before the first change
+    pub original_route_capacities: BTreeMap<TransitRouteID, Option<usize>>,
+    /// Parking prices before the first change, if they've changed
+    pub original_parking_pricing: Option<ParkingPricing>,
 
     /// Some edits are included in the game by default, in data/system/proposals, as "community
     /// proposals." They require a description and may have a link to a write-up.
@@ -60,6 +64,15 @@ pub enum EditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeRouteCapacity {
+        id: TransitRouteID,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
+    ChangeParkingPricing {
+        old: ParkingPricing,
+        new: ParkingPricing,
+    },
 }
 
 pub struct EditEffects {
@@ -198,6 +211,8 @@ impl MapEdits {
             original_roads: BTreeMap::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_route_capacities: BTreeMap::new(),
+            original_parking_pricing: None,
         }
     }
 
@@ -269,6 +284,8 @@ impl MapEdits {
         self.original_roads.clear();
         self.original_intersections.clear();
         self.changed_routes.clear();
+        self.original_route_capacities.clear();
+        self.original_parking_pricing = None;
 
         for cmd in &self.commands {
             match cmd {
@@ -285,6 +302,16 @@ impl MapEdits {
                 EditCmd::ChangeRouteSchedule { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::ChangeRouteCapacity { id, old, .. } => {
+                    if !self.original_route_capacities.contains_key(id) {
+                        self.original_route_capacities.insert(*id, *old);
+                    }
+                }
+                EditCmd::ChangeParkingPricing { ref old, .. } => {
+                    if self.original_parking_pricing.is_none() {
+                        self.original_parking_pricing = Some(old.clone());
+                    }
+                }
             }
         }
 
@@ -296,6 +323,11 @@ impl MapEdits {
             let r = map.get_tr(*br);
             r.spawn_times != r.orig_spawn_times
         });
+        self.original_route_capacities
+            .retain(|r, orig| map.get_tr(*r).capacity != *orig);
+        if self.original_parking_pricing.as_ref() == Some(map.get_parking_pricing()) {
+            self.original_parking_pricing = None;
+        }
     }
 
     /// Assumes update_derived has been called.
@@ -322,6 +354,19 @@ impl MapEdits {
                 old: r.orig_spawn_times.clone(),
             });
         }
+        for (r, old) in &self.original_route_capacities {
+            self.commands.push(EditCmd::ChangeRouteCapacity {
+                id: *r,
+                old: *old,
+                new: map.get_tr(*r).capacity,
+            });
+        }
+        if let Some(ref old) = self.original_parking_pricing {
+            self.commands.push(EditCmd::ChangeParkingPricing {
+                old: old.clone(),
+                new: map.get_parking_pricing().clone(),
+            });
+        }
     }
 
     /// Pick apart changed_roads and figure out if an entire road was edited, or just a few lanes.
@@ -416,6 +461,20 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, .. } => {
                 format!("reschedule route {}", map.get_tr(*id).short_name)
             }
+            EditCmd::ChangeRouteCapacity { id, new, .. } => {
+                let route = map.get_tr(*id);
+                details.push(format!(
+                    "{} people per vehicle",
+                    new.unwrap_or_else(|| route.vehicle_capacity())
+                ));
+                format!("change capacity of route {}", route.short_name)
+            }
+            EditCmd::ChangeParkingPricing { new, .. } => {
+                for z in &new.zones {
+                    details.push(format!("{}: ${:.2}/hour", z.name, z.price_per_hour));
+                }
+                "parking prices".to_string()
+            }
         };
         (summary, details)
     }
@@ -9,6 +9,7 @@ use geom::Time;
 
 use super::perma_traffic_signal;
 use crate::edits::{EditCmd, EditIntersection, EditIntersectionControl, EditRoad, MapEdits};
+use crate::objects::parking_pricing::PermanentParkingPricing;
 use crate::{
     osm, ControlStopSign, DiagonalFilter, IntersectionID, Map, MovementID, OriginalRoad, TurnType,
 };
@@ -74,6 +75,15 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeRouteCapacity {
+        gtfs_id: String,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
+    ChangeParkingPricing {
+        old: PermanentParkingPricing,
+        new: PermanentParkingPricing,
+    },
 }
 
 impl EditCmd {
@@ -96,6 +106,17 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeRouteCapacity { id, old, new } => {
+                PermanentEditCmd::ChangeRouteCapacity {
+                    gtfs_id: map.get_tr(*id).gtfs_id.clone(),
+                    old: *old,
+                    new: *new,
+                }
+            }
+            EditCmd::ChangeParkingPricing { old, new } => PermanentEditCmd::ChangeParkingPricing {
+                old: old.to_permanent(map),
+                new: new.to_permanent(map),
+            },
         }
     }
 }
@@ -146,6 +167,22 @@ impl PermanentEditCmd {
                     .ok_or_else(|| anyhow!("can't find {}", gtfs_id))?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeRouteCapacity { gtfs_id, old, new } => {
+                let id = map
+                    .find_tr_by_gtfs(&gtfs_id)
+                    .ok_or_else(|| anyhow!("can't find {}", gtfs_id))?;
+                Ok(EditCmd::ChangeRouteCapacity { id, old, new })
+            }
+            PermanentEditCmd::ChangeParkingPricing { old, new } => {
+                Ok(EditCmd::ChangeParkingPricing {
+                    old: old
+                        .into_pricing(map)
+                        .context("old ChangeParkingPricing invalid")?,
+                    new: new
+                        .into_pricing(map)
+                        .context("new ChangeParkingPricing invalid")?,
+                })
+            }
         }
     }
 }
@@ -182,6 +219,8 @@ impl PermanentMapEdits {
             original_roads: BTreeMap::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_route_capacities: BTreeMap::new(),
+            original_parking_pricing: None,
         };
         edits.update_derived(map);
         Ok(edits)
@@ -209,6 +248,8 @@ impl PermanentMapEdits {
             original_roads: BTreeMap::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_route_capacities: BTreeMap::new(),
+            original_parking_pricing: None,
         };
         edits.update_derived(map);
         edits
@@ -47,6 +47,25 @@ pub struct Stage {
     pub permitted_turns: BTreeSet<Turn>,
     /// The stage lasts this long before moving to the next one.
     pub stage_type: StageType,
+    /// How the pedestrian signals for protected crosswalks behave. If this is missing, crosswalks
+    /// show WALK for the entire stage.
+    #[serde(default)]
+    pub ped_intervals: Option<PedestrianIntervals>,
+}
+
+/// Pedestrian signal timing during one stage, measured from the start of the stage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PedestrianIntervals {
+    /// Crosswalks show WALK this long before vehicles get a green light. 0 means no leading
+    /// pedestrian interval.
+    pub leading_interval_seconds: usize,
+    /// Then crosswalks show WALK this long. Pedestrians may only start crossing during WALK.
+    pub walk_seconds: usize,
+    /// Then flashing DON'T WALK lasts this long, giving anybody already crossing time to finish.
+    pub flashing_dont_walk_seconds: usize,
+    /// If true, crosswalks only show WALK if a pedestrian pushed the button before the stage
+    /// started.
+    pub call_button: bool,
 }
 
 /// How long a stage lasts before moving to the next one.
@@ -61,6 +80,16 @@ pub enum StageType {
     /// is 20, and additional is 40, the maximum cycle duration is 60.
     /// If there are crosswalks, the minimum is the minimum for the maximum crosswalks
     Variable(usize, usize, usize),
+    /// Green lasts at least `min_seconds`, and is extended by `passage_seconds` each time a
+    /// vehicle is detected on a protected movement, up to `max_seconds`. Detectors sit
+    /// `detector_setback_meters` before the stop line. The stage is skipped if there are no
+    /// vehicles or pedestrians waiting for it.
+    Actuated {
+        min_seconds: f64,
+        max_seconds: f64,
+        passage_seconds: f64,
+        detector_setback_meters: f64,
+    },
 }
 
 /// A movement through an intersection.
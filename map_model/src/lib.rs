@@ -52,6 +52,7 @@ pub use crate::edits::{
     PermanentMapEdits,
 };
 
+pub use crate::make::traffic_signals::{make_green_wave, measure_progression, CorridorProgression};
 pub use crate::make::RawToMapOptions;
 pub use crate::objects::area::{Area, AreaID};
 pub use crate::objects::building::{Building, BuildingID, BuildingType, OffstreetParking};
@@ -60,20 +61,28 @@ pub use crate::objects::lane::{CommonEndpoint, Lane, LaneID, PARKING_LOT_SPOT_LE
 pub use crate::objects::modal_filter::{DiagonalFilter, FilterType, RoadFilter};
 pub use crate::objects::movement::{CompressedMovementID, Movement, MovementID};
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
+pub use crate::objects::parking_pricing::{ParkingPricing, ParkingZone};
 pub use crate::objects::road::{
     Crossing, DirectedRoadID, OriginalRoad, Road, RoadID, RoadSideID, SideOfRoad,
 };
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::objects::traffic_signals::{ControlTrafficSignal, Stage, StageType};
-pub use crate::objects::transit::{TransitRoute, TransitRouteID, TransitStop, TransitStopID};
+pub use crate::objects::traffic_signals::{
+    ControlTrafficSignal, Detector, PedestrianIntervals, PedestrianSignal, Stage, StageType,
+};
+pub use crate::objects::transit::{
+    TransitMode, TransitRoute, TransitRouteID, TransitStop, TransitStopID,
+};
 pub use crate::objects::turn::{Turn, TurnID, TurnPriority, TurnType};
 pub use crate::objects::zone::{AccessRestrictions, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 pub use crate::pathfind::{
-    Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2, Pathfinder, PathfinderCache,
-    PathfinderCaching, RoutingParams,
+    AccessibilityBarrier, Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2,
+    Pathfinder, PathfinderCache, PathfinderCaching, RoutingParams, TravelTimeProfile,
+    WalkingProfile,
+};
+pub use crate::traversable::{
+    Position, Traversable, MAX_BIKE_SPEED, MAX_SCOOTER_SPEED, MAX_TRUCK_SPEED, MAX_WALKING_SPEED,
 };
-pub use crate::traversable::{Position, Traversable, MAX_BIKE_SPEED, MAX_WALKING_SPEED};
 pub use map::turn_type_from_angles;
 
 mod city;
@@ -132,6 +141,9 @@ pub struct Map {
     edits: MapEdits,
     #[serde(skip_serializing, skip_deserializing)]
     edits_generation: usize,
+    /// Only changed through edits, so it isn't part of the map file
+    #[serde(skip_serializing, skip_deserializing)]
+    parking_pricing: ParkingPricing,
     #[serde(skip_serializing, skip_deserializing)]
     road_to_buildings: MultiMap<RoadID, BuildingID>,
 }
@@ -18,7 +18,8 @@ use crate::pathfind::{CreateEngine, Pathfinder};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, ControlStopSign, ControlTrafficSignal,
     Intersection, IntersectionControl, IntersectionID, IntersectionKind, Lane, LaneID, Map,
-    MapEdits, OriginalRoad, PathConstraints, Position, Road, RoadID, RoutingParams, Zone,
+    MapEdits, OriginalRoad, ParkingPricing, PathConstraints, Position, Road, RoadID, RoutingParams,
+    Zone,
 };
 
 mod bridges;
@@ -71,6 +72,7 @@ impl Map {
             name: raw.name.clone(),
             edits: MapEdits::new(),
             edits_generation: 0,
+            parking_pricing: ParkingPricing::default(),
             road_to_buildings: MultiMap::new(),
         };
         map.edits = map.new_edits();
@@ -128,6 +130,7 @@ impl Map {
             let barrier_nodes = snap_nodes_to_line(&extra.barrier_nodes, &r.center_line);
             let crossing_nodes =
                 snap_nodes_with_data_to_line(&extra.crossing_nodes, &r.center_line);
+            let kerb_nodes = snap_nodes_with_data_to_line(&extra.kerb_nodes, &r.center_line);
 
             // TODO Hack. Roads and intersections each may have ZERO or more OSM IDs.
             let orig_id = OriginalRoad {
@@ -193,6 +196,7 @@ impl Map {
                 modal_filter: None,
                 barrier_nodes,
                 crossing_nodes,
+                kerb_nodes,
                 crossings: Vec::new(),
             };
             road.speed_limit = road.speed_limit_from_osm();
@@ -0,0 +1,366 @@
+//! Coordinates the offsets of traffic signals along a corridor, so that vehicles travelling at a
+//! steady speed meet a "green wave" in both directions.
+//!
+//! All of this reasons about the simple duration of each stage, so variable and actuated stages
+//! are treated as if they always last their minimum.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::{ControlTrafficSignal, IntersectionID, Map, RoadID, StageType};
+
+/// How many departures are checked when measuring progression through signals that don't share a
+/// cycle length.
+const MEASURE_HORIZON: Duration = Duration::const_seconds(3600.0);
+/// When the cycle length isn't specified, try lengths up to this much longer than the longest
+/// existing cycle.
+const MAX_CYCLE_STRETCH: f64 = 1.5;
+const CYCLE_STEP: Duration = Duration::const_seconds(5.0);
+
+/// How well vehicles travelling along a corridor progress through its signals.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CorridorProgression {
+    /// The cycle length shared by every signal along the corridor, if there is one. Without a
+    /// common cycle, there can't be a stable green wave.
+    pub cycle_length: Option<Duration>,
+    /// The fraction of departures from the first signal that reach the last without stopping
+    pub outbound: f64,
+    /// The fraction of departures from the last signal that reach the first without stopping
+    pub inbound: f64,
+}
+
+/// Retimes and offsets the signals along a corridor for a two-way green wave. `signals` lists the
+/// signalized intersections in order; they don't have to be adjacent. Vehicles are assumed to
+/// travel at `speed` between them. If `cycle_length` is specified, every signal's fixed stages
+/// are stretched or squeezed to match it; otherwise a common cycle length is picked.
+///
+/// Returns the modified signals, which haven't been applied to the map yet, and the expected
+/// progression.
+pub fn make_green_wave(
+    map: &Map,
+    signals: &[IntersectionID],
+    speed: Speed,
+    cycle_length: Option<Duration>,
+) -> Result<(Vec<ControlTrafficSignal>, CorridorProgression)> {
+    let corridor = Corridor::new(map, signals, speed)?;
+
+    let candidates = if let Some(cycle) = cycle_length {
+        vec![cycle]
+    } else {
+        let longest = signals
+            .iter()
+            .map(|i| map.get_traffic_signal(*i).simple_cycle_duration())
+            .max()
+            .unwrap();
+        let mut list = Vec::new();
+        let mut cycle = longest;
+        while cycle <= longest * MAX_CYCLE_STRETCH {
+            list.push(cycle);
+            cycle += CYCLE_STEP;
+        }
+        list
+    };
+
+    let mut best: Option<(Vec<ControlTrafficSignal>, CorridorProgression)> = None;
+    for cycle in candidates {
+        let mut retimed = Vec::new();
+        for i in signals {
+            match retime(map, map.get_traffic_signal(*i), cycle) {
+                Ok(ts) => retimed.push(ts),
+                Err(err) => {
+                    if cycle_length.is_some() {
+                        return Err(err);
+                    }
+                    retimed.clear();
+                    break;
+                }
+            }
+        }
+        if retimed.is_empty() {
+            continue;
+        }
+
+        // Keep the first signal where it is, then greedily pick the offset of each following
+        // signal to get the best progression so far.
+        for idx in 1..retimed.len() {
+            let mut best_offset = (Duration::ZERO, 0.0, 0.0);
+            for secs in 0..(cycle.inner_seconds() as usize) {
+                retimed[idx].offset = Duration::seconds(secs as f64);
+                let (outbound, inbound) = corridor.progression(&retimed[0..=idx], cycle);
+                let score = (outbound.min(inbound), outbound + inbound);
+                if score > (best_offset.1, best_offset.2) {
+                    best_offset = (retimed[idx].offset, score.0, score.1);
+                }
+            }
+            retimed[idx].offset = best_offset.0;
+        }
+
+        let (outbound, inbound) = corridor.progression(&retimed, cycle);
+        let progression = CorridorProgression {
+            cycle_length: Some(cycle),
+            outbound,
+            inbound,
+        };
+        if best
+            .as_ref()
+            .map(|(_, p)| {
+                (outbound.min(inbound), outbound + inbound)
+                    > (p.outbound.min(p.inbound), p.outbound + p.inbound)
+            })
+            .unwrap_or(true)
+        {
+            best = Some((retimed, progression));
+        }
+    }
+
+    match best {
+        Some(pair) => Ok(pair),
+        None => bail!("None of the signals could be retimed to a common cycle length"),
+    }
+}
+
+/// Measures how well the signals currently in the map support progression along a corridor.
+pub fn measure_progression(
+    map: &Map,
+    signals: &[IntersectionID],
+    speed: Speed,
+) -> Result<CorridorProgression> {
+    let corridor = Corridor::new(map, signals, speed)?;
+    let current: Vec<ControlTrafficSignal> = signals
+        .iter()
+        .map(|i| map.get_traffic_signal(*i).clone())
+        .collect();
+    let cycles: BTreeSet<Duration> = current
+        .iter()
+        .map(|ts| ts.simple_cycle_duration())
+        .collect();
+    let cycle_length = if cycles.len() == 1 {
+        cycles.into_iter().next()
+    } else {
+        None
+    };
+    let horizon = cycle_length.unwrap_or(MEASURE_HORIZON);
+    let (outbound, inbound) = corridor.progression(&current, horizon);
+    Ok(CorridorProgression {
+        cycle_length,
+        outbound,
+        inbound,
+    })
+}
+
+struct Corridor {
+    /// Free-flow travel time from the first signal to each signal
+    arrivals: Vec<Duration>,
+    /// For each signal, the stage serving traffic along the corridor
+    stages: Vec<usize>,
+}
+
+impl Corridor {
+    fn new(map: &Map, signals: &[IntersectionID], speed: Speed) -> Result<Corridor> {
+        if signals.len() < 2 {
+            bail!("A corridor needs at least 2 traffic signals");
+        }
+        if signals.iter().collect::<BTreeSet<_>>().len() != signals.len() {
+            bail!("A corridor can't visit the same intersection twice");
+        }
+        if speed <= Speed::ZERO {
+            bail!("The target speed must be positive");
+        }
+        for i in signals {
+            if map.maybe_get_traffic_signal(*i).is_none() {
+                bail!("{} isn't a traffic signal", i);
+            }
+        }
+
+        let mut arrivals = vec![Duration::ZERO];
+        // For each signal, the roads leading to the previous and next signal
+        let mut corridor_roads: Vec<BTreeSet<RoadID>> = vec![BTreeSet::new(); signals.len()];
+        for (idx, pair) in signals.windows(2).enumerate() {
+            let (roads, _) = match map.simple_path_btwn(pair[0], pair[1]) {
+                Some(path) => path,
+                None => bail!("No path between {} and {}", pair[0], pair[1]),
+            };
+            let mut dist = Distance::ZERO;
+            for r in &roads {
+                dist += map.get_r(*r).length();
+            }
+            arrivals.push(arrivals[idx] + dist / speed);
+            corridor_roads[idx].insert(roads[0]);
+            corridor_roads[idx + 1].insert(*roads.last().unwrap());
+        }
+
+        let mut stages = Vec::new();
+        for (i, roads) in signals.iter().zip(corridor_roads) {
+            let signal = map.get_traffic_signal(*i);
+            // Prefer stages protecting through movements along the corridor, then movements
+            // entering or leaving it.
+            let mut best = (0, 0);
+            for (idx, stage) in signal.stages.iter().enumerate() {
+                let mut score = 0;
+                for m in &stage.protected_movements {
+                    if m.crosswalk {
+                        continue;
+                    }
+                    if roads.contains(&m.from.road) {
+                        score += 1;
+                    }
+                    if roads.contains(&m.to.road) {
+                        score += 1;
+                    }
+                }
+                if score > best.1 {
+                    best = (idx, score);
+                }
+            }
+            stages.push(best.0);
+        }
+
+        Ok(Corridor { arrivals, stages })
+    }
+
+    /// Returns the fraction of outbound and inbound departures, one per second over `horizon`,
+    /// that make it through every signal without stopping. With a common cycle length, checking
+    /// one cycle is enough.
+    fn progression(&self, signals: &[ControlTrafficSignal], horizon: Duration) -> (f64, f64) {
+        let windows: Vec<GreenWindow> = signals
+            .iter()
+            .zip(self.stages.iter())
+            .map(|(ts, idx)| GreenWindow::new(ts, *idx))
+            .collect();
+        let arrivals = &self.arrivals[0..signals.len()];
+        let last = *arrivals.last().unwrap();
+
+        let outbound = fraction_through(&windows, horizon, |idx| arrivals[idx]);
+        // Travelling backwards, the last signal is the first one reached
+        let inbound = fraction_through(&windows, horizon, |idx| last - arrivals[idx]);
+        (outbound, inbound)
+    }
+}
+
+/// When a signal serves the corridor during its cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GreenWindow {
+    start: f64,
+    length: f64,
+    cycle: f64,
+}
+
+impl GreenWindow {
+    fn new(ts: &ControlTrafficSignal, stage: usize) -> GreenWindow {
+        // At time 0, the signal is already `offset` into its cycle.
+        let mut start = Duration::ZERO;
+        for s in &ts.stages[0..stage] {
+            start += s.stage_type.simple_duration();
+        }
+        let cycle = ts.simple_cycle_duration().inner_seconds();
+        GreenWindow {
+            start: (start - ts.offset).inner_seconds().rem_euclid(cycle),
+            length: ts.stages[stage]
+                .stage_type
+                .simple_duration()
+                .inner_seconds(),
+            cycle,
+        }
+    }
+
+    fn is_green(&self, t: f64) -> bool {
+        (t - self.start).rem_euclid(self.cycle) < self.length
+    }
+}
+
+/// Checks departures every second over the horizon, returning the fraction that pass every signal
+/// during its green window.
+fn fraction_through<F: Fn(usize) -> Duration>(
+    windows: &[GreenWindow],
+    horizon: Duration,
+    arrival: F,
+) -> f64 {
+    let departures = horizon.inner_seconds() as usize;
+    if departures == 0 {
+        return 0.0;
+    }
+    let mut ok = 0;
+    for secs in 0..departures {
+        let depart = secs as f64;
+        if windows
+            .iter()
+            .enumerate()
+            .all(|(idx, w)| w.is_green(depart + arrival(idx).inner_seconds()))
+        {
+            ok += 1;
+        }
+    }
+    (ok as f64) / (departures as f64)
+}
+
+/// Stretches or squeezes all stages of a fixed-time signal to match a new cycle length.
+fn retime(map: &Map, ts: &ControlTrafficSignal, cycle: Duration) -> Result<ControlTrafficSignal> {
+    let orig_cycle = ts.simple_cycle_duration();
+    if orig_cycle == cycle {
+        return Ok(ts.clone());
+    }
+    let mut ts = ts.clone();
+    let ratio = cycle / orig_cycle;
+    let mut total = Duration::ZERO;
+    for stage in &mut ts.stages {
+        match stage.stage_type {
+            StageType::Fixed(d) => {
+                let d = Duration::seconds((d.inner_seconds() * ratio).round());
+                stage.stage_type = StageType::Fixed(d);
+                total += d;
+            }
+            _ => bail!(
+                "{} doesn't use fixed timing, so its cycle length can't be changed",
+                ts.id
+            ),
+        }
+    }
+    // Absorb any rounding error in the longest stage
+    if total != cycle {
+        let stage = ts
+            .stages
+            .iter_mut()
+            .max_by_key(|s| s.stage_type.simple_duration())
+            .unwrap();
+        let d = stage.stage_type.simple_duration() + cycle - total;
+        stage.stage_type = StageType::Fixed(d);
+    }
+    ts.validate(map.get_i(ts.id))?;
+    Ok(ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction_through() {
+        // Two signals with a 60s cycle and 30s of green, 20s apart
+        let first = GreenWindow {
+            start: 0.0,
+            length: 30.0,
+            cycle: 60.0,
+        };
+        let arrival = |idx: usize| Duration::seconds(20.0 * idx as f64);
+
+        // Perfectly offset, everybody leaving during the green makes it
+        let second = GreenWindow {
+            start: 20.0,
+            ..first
+        };
+        let pct = fraction_through(&[first, second], Duration::seconds(60.0), arrival);
+        assert_eq!(0.5, pct);
+
+        // Both turn green at the same time, so only the first 10s of departures make it
+        let second = GreenWindow {
+            start: 0.0,
+            ..first
+        };
+        let pct = fraction_through(&[first, second], Duration::seconds(60.0), arrival);
+        assert!((pct - 10.0 / 60.0).abs() < 1e-9);
+    }
+}
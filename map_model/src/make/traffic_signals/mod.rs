@@ -13,6 +13,9 @@ use crate::{
 };
 use geom::Duration;
 
+pub use self::green_wave::{make_green_wave, measure_progression, CorridorProgression};
+
+mod green_wave;
 mod lagging_green;
 
 /// Applies a bunch of heuristics to a single intersection, returning the valid results in
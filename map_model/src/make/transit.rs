@@ -8,7 +8,7 @@ use raw_map::{RawMap, RawTransitRoute, RawTransitStop, RawTransitType};
 
 use crate::make::match_points_to_lanes;
 use crate::{
-    LaneID, Map, PathConstraints, Position, TransitRoute, TransitRouteID, TransitStop,
+    LaneID, Map, PathConstraints, Position, TransitMode, TransitRoute, TransitRouteID, TransitStop,
     TransitStopID,
 };
 
@@ -41,8 +41,11 @@ pub fn finalize_transit(map: &mut Map, raw: &RawMap, timer: &mut Timer) {
     for route in &raw.transit_routes {
         if let Err(err) = create_route(route, map, &gtfs_to_stop_id, &snapper) {
             warn!(
-                "Couldn't snap route {} ({}): {}",
-                route.gtfs_id, route.short_name, err
+                "Couldn't snap route {} ({}), dropping {} trips: {}",
+                route.gtfs_id,
+                route.short_name,
+                route.schedule.len(),
+                err
             );
         }
     }
@@ -148,11 +151,15 @@ fn create_route(
     snapper: &BorderSnapper,
 ) -> Result<()> {
     // TODO At least warn about stops that failed to snap
-    let stops: Vec<TransitStopID> = route
-        .stops
-        .iter()
-        .filter_map(|gtfs_id| gtfs_to_stop_id.get(gtfs_id).cloned())
-        .collect();
+    let mut stops: Vec<TransitStopID> = Vec::new();
+    // Which entries of the raw route's stops (and each trip in its schedule) survived
+    let mut stop_indices = Vec::new();
+    for (idx, gtfs_id) in route.stops.iter().enumerate() {
+        if let Some(id) = gtfs_to_stop_id.get(gtfs_id) {
+            stops.push(*id);
+            stop_indices.push(idx);
+        }
+    }
     if stops.is_empty() {
         bail!("No valid stops");
     }
@@ -169,7 +176,7 @@ fn create_route(
             .get(0)
             .ok_or_else(|| anyhow!("couldn't find where shape enters map"))?;
         // Snap that to a border
-        let borders = if route.route_type == RawTransitType::Bus {
+        let borders = if route.route_type != RawTransitType::Train {
             &snapper.bus_incoming_borders
         } else {
             &snapper.train_incoming_borders
@@ -195,7 +202,7 @@ fn create_route(
             .last()
             .ok_or_else(|| anyhow!("couldn't find where shape leaves map"))?;
         // Snap that to a border
-        let borders = if route.route_type == RawTransitType::Bus {
+        let borders = if route.route_type != RawTransitType::Train {
             &snapper.bus_outgoing_borders
         } else {
             &snapper.train_outgoing_borders
@@ -219,10 +226,32 @@ fn create_route(
         }
     };
 
-    // TODO This'll come from the RawTransitRoute eventually. For now, every 30 minutes.
-    let spawn_times: Vec<Time> = (0..48)
-        .map(|i| Time::START_OF_DAY + (i as f64) * Duration::minutes(30))
-        .collect();
+    let (spawn_times, stop_time_offsets) = if route.schedule.is_empty() {
+        // Without a timetable, just run a vehicle every 30 minutes.
+        let spawn_times: Vec<Time> = (0..48)
+            .map(|i| Time::START_OF_DAY + (i as f64) * Duration::minutes(30))
+            .collect();
+        (spawn_times, Vec::new())
+    } else {
+        // A vehicle spawns when it's due at the first stop.
+        // TODO If the route starts at a border, the vehicle will always be a bit late there.
+        let first = stop_indices[0];
+        let mut trips: Vec<&Vec<Time>> = route.schedule.iter().collect();
+        trips.sort_by(|a, b| a[first].partial_cmp(&b[first]).unwrap());
+        let spawn_times: Vec<Time> = trips.iter().map(|trip| trip[first]).collect();
+        // Spawn times can be edited, so express each trip's schedule relative to them. Running
+        // times between stops vary over the day, so keep every trip's separately.
+        let stop_time_offsets = trips
+            .iter()
+            .map(|trip| {
+                stop_indices
+                    .iter()
+                    .map(|idx| trip[*idx] - trip[first])
+                    .collect()
+            })
+            .collect();
+        (spawn_times, stop_time_offsets)
+    };
 
     let result = TransitRoute {
         id: TransitRouteID(map.transit_routes.len()),
@@ -233,11 +262,18 @@ fn create_route(
         start,
         end_border,
         route_type: match route.route_type {
-            RawTransitType::Bus => PathConstraints::Bus,
+            RawTransitType::Bus | RawTransitType::Tram => PathConstraints::Bus,
             RawTransitType::Train => PathConstraints::Train,
         },
+        mode: match route.route_type {
+            RawTransitType::Bus => TransitMode::Bus,
+            RawTransitType::Tram => TransitMode::Tram,
+            RawTransitType::Train => TransitMode::Train,
+        },
         spawn_times: spawn_times.clone(),
         orig_spawn_times: spawn_times,
+        stop_time_offsets,
+        capacity: None,
     };
 
     // Check that the paths are valid
@@ -22,9 +22,10 @@ use crate::{
     CompressedMovementID, ControlStopSign, ControlTrafficSignal, DirectedRoadID, Direction,
     DrivingSide, ExtraPOI, Intersection, IntersectionControl, IntersectionID, IntersectionKind,
     Lane, LaneID, LaneType, Map, MapConfig, MapEdits, Movement, MovementID, OffstreetParking,
-    OriginalRoad, ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, PathV2, Pathfinder,
-    PathfinderCaching, Position, Road, RoadFilter, RoadID, RoutingParams, TransitRoute,
-    TransitRouteID, TransitStop, TransitStopID, Turn, TurnID, TurnType, Zone,
+    OriginalRoad, ParkingLot, ParkingLotID, ParkingPricing, Path, PathConstraints, PathRequest,
+    PathV2, Pathfinder, PathfinderCaching, Position, Road, RoadFilter, RoadID, RoutingParams,
+    TransitRoute, TransitRouteID, TransitStop, TransitStopID, TravelTimeProfile, Turn, TurnID,
+    TurnType, WalkingProfile, Zone,
 };
 
 impl Map {
@@ -174,6 +175,7 @@ impl Map {
             name: MapName::blank(),
             edits: MapEdits::new(),
             edits_generation: 0,
+            parking_pricing: ParkingPricing::default(),
             road_to_buildings: MultiMap::new(),
         }
     }
@@ -395,6 +397,10 @@ impl Map {
         &self.parking_lots[id.0]
     }
 
+    pub fn get_parking_pricing(&self) -> &ParkingPricing {
+        &self.parking_pricing
+    }
+
     pub fn get_stop_sign(&self, id: IntersectionID) -> &ControlStopSign {
         &self.stop_signs[&id]
     }
@@ -599,6 +605,17 @@ impl Map {
         abstio::write_binary(self.name.path(), self);
     }
 
+    /// Someone picking up or dropping off a dockless scooter or bike along a sidewalk rides from or
+    /// to the returned position, on the closest lane they can use.
+    pub fn find_biking_pos_near_sidewalk(&self, sidewalk_pos: Position) -> Option<Position> {
+        let lane = self
+            .get_parent(sidewalk_pos.lane())
+            .find_closest_lane(sidewalk_pos.lane(), |l| {
+                !l.biking_blackhole && PathConstraints::Bike.can_use(l, self)
+            })?;
+        Some(sidewalk_pos.equiv_pos(lane, self))
+    }
+
     /// Cars trying to park near this building should head for the driving lane returned here, then
     /// start their search. Some parking lanes are connected to driving lanes that're "parking
     /// blackholes" -- if there are no free spots on that lane, then the roads force cars to a
@@ -673,6 +690,28 @@ impl Map {
         self.pathfind_v2_with_params(req, params, cache_custom)?
             .into_v1(self)
     }
+    pub fn pathfind_with_params_at(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        departure: Time,
+        profile: &TravelTimeProfile,
+    ) -> Result<Path> {
+        self.pathfind_v2_with_params_at(req, params, departure, profile)?
+            .into_v1(self)
+    }
+    /// Finds a vehicle path, adding extra delay to cross some roads.
+    pub fn pathfind_with_delays(
+        &self,
+        req: PathRequest,
+        delays: &HashMap<DirectedRoadID, Duration>,
+    ) -> Result<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .pathfind_with_delays(req.clone(), delays, self)
+            .ok_or_else(|| anyhow!("can't fulfill {}", req))?
+            .into_v1(self)
+    }
     pub fn pathfind_v2(&self, req: PathRequest) -> Result<PathV2> {
         assert!(!self.pathfinder_dirty);
         self.pathfinder
@@ -688,15 +727,52 @@ impl Map {
         assert!(!self.pathfinder_dirty);
         self.pathfinder
             .pathfind_with_params(req.clone(), params, cache_custom, self)
+            .ok_or_else(|| match params.walking_profile {
+                // Explain which barriers are in the way
+                Some(ref profile) if req.constraints == PathConstraints::Pedestrian => {
+                    profile.explain_rejection(req, self)
+                }
+                _ => anyhow!("can't fulfill {}", req),
+            })
+    }
+    /// Like `pathfind_v2_with_params`, but the cost of crossing each road depends on the time of
+    /// day it's reached.
+    pub fn pathfind_v2_with_params_at(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        departure: Time,
+        profile: &TravelTimeProfile,
+    ) -> Result<PathV2> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .pathfind_with_params_at(req.clone(), params, departure, profile, self)
             .ok_or_else(|| anyhow!("can't fulfill {}", req))
     }
     pub fn should_use_transit(
         &self,
         start: Position,
         end: Position,
+        profile: Option<&WalkingProfile>,
     ) -> Option<(TransitStopID, Option<TransitStopID>, TransitRouteID)> {
         assert!(!self.pathfinder_dirty);
-        self.pathfinder.should_use_transit(self, start, end)
+        self.pathfinder
+            .should_use_transit(self, start, end, profile)
+    }
+    pub fn should_park_and_ride(
+        &self,
+        start: Position,
+        end: Position,
+        profile: Option<&WalkingProfile>,
+    ) -> Option<(
+        ParkingLotID,
+        TransitStopID,
+        Option<TransitStopID>,
+        TransitRouteID,
+    )> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .should_park_and_ride(self, start, end, profile)
     }
 
     /// Return the cost of a single path, and also a mapping from every directed road to the cost
@@ -827,6 +903,12 @@ impl Map {
         });
     }
 
+    /// Replaces the pathfinder, such as with one using observed costs. The new pathfinder must be
+    /// built from this map.
+    pub fn hack_override_pathfinder(&mut self, pathfinder: Pathfinder) {
+        self.pathfinder = pathfinder;
+    }
+
     /// Normally after applying edits, you must call `recalculate_pathfinding_after_edits`.
     /// Alternatively, you can keep the old pathfinder exactly as it is. Use with caution -- the
     /// pathfinder and the map may be out-of-sync in arbitrary ways.
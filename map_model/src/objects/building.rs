@@ -78,6 +78,14 @@ impl BuildingType {
             BuildingType::Commercial(_) | BuildingType::Empty => false,
         }
     }
+
+    pub fn num_residents(&self) -> usize {
+        match self {
+            BuildingType::Residential { num_residents, .. }
+            | BuildingType::ResidentialCommercial(num_residents, _) => *num_residents,
+            BuildingType::Commercial(_) | BuildingType::Empty => 0,
+        }
+    }
 }
 
 impl Building {
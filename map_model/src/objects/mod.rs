@@ -5,6 +5,7 @@ pub mod lane;
 pub mod modal_filter;
 pub mod movement;
 pub mod parking_lot;
+pub mod parking_pricing;
 pub mod road;
 pub mod stop_signs;
 pub mod traffic_signals;
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::Duration;
+
+use crate::{osm, LaneID, Map, OriginalRoad, ParkingLotID};
+
+/// Prices and time limits for on-street parking and parking lots. Anything not covered by a zone
+/// is free and has no time limit. Private parking in buildings is always free.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParkingPricing {
+    pub zones: Vec<ParkingZone>,
+}
+
+/// A group of parking lanes and lots sharing the same rules. Revenue is reported per zone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParkingZone {
+    pub name: String,
+    /// In dollars
+    pub price_per_hour: f64,
+    /// Drivers planning to stay longer won't park here
+    pub max_stay: Option<Duration>,
+    pub onstreet: BTreeSet<LaneID>,
+    pub lots: BTreeSet<ParkingLotID>,
+}
+
+impl ParkingPricing {
+    pub fn load(path: String, timer: &mut Timer) -> Result<ParkingPricing> {
+        abstio::maybe_read_json(path, timer)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    /// If a lane belongs to multiple zones, the first one wins.
+    pub fn zone_for_lane(&self, l: LaneID) -> Option<&ParkingZone> {
+        self.zones.iter().find(|z| z.onstreet.contains(&l))
+    }
+
+    /// If a lot belongs to multiple zones, the first one wins.
+    pub fn zone_for_lot(&self, pl: ParkingLotID) -> Option<&ParkingZone> {
+        self.zones.iter().find(|z| z.lots.contains(&pl))
+    }
+
+    pub(crate) fn to_permanent(&self, map: &Map) -> PermanentParkingPricing {
+        PermanentParkingPricing {
+            zones: self
+                .zones
+                .iter()
+                .map(|z| PermanentParkingZone {
+                    name: z.name.clone(),
+                    price_per_hour: z.price_per_hour,
+                    max_stay: z.max_stay,
+                    onstreet: z
+                        .onstreet
+                        .iter()
+                        .map(|l| (map.get_r(l.road).orig_id, l.offset))
+                        .collect(),
+                    lots: z.lots.iter().map(|pl| map.get_pl(*pl).osm_id).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// ParkingPricing refers to lanes and lots by IDs that change when the basemap is rebuilt, so
+/// edits refer to them by OSM IDs instead.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PermanentParkingPricing {
+    zones: Vec<PermanentParkingZone>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PermanentParkingZone {
+    name: String,
+    price_per_hour: f64,
+    max_stay: Option<Duration>,
+    /// The road and the index of the lane, from left to right
+    onstreet: Vec<(OriginalRoad, usize)>,
+    lots: Vec<osm::OsmID>,
+}
+
+impl PermanentParkingPricing {
+    pub(crate) fn into_pricing(self, map: &Map) -> Result<ParkingPricing> {
+        let mut zones = Vec::new();
+        for z in self.zones {
+            let mut onstreet = BTreeSet::new();
+            for (orig_id, idx) in z.onstreet {
+                let r = map.get_r(map.find_r_by_osm_id(orig_id)?);
+                match r.lanes.get(idx) {
+                    Some(lane) => {
+                        onstreet.insert(lane.id);
+                    }
+                    None => bail!("{} doesn't have lane {} anymore", orig_id, idx),
+                }
+            }
+            let mut lots = BTreeSet::new();
+            for osm_id in z.lots {
+                match map.all_parking_lots().iter().find(|pl| pl.osm_id == osm_id) {
+                    Some(pl) => {
+                        lots.insert(pl.id);
+                    }
+                    None => bail!("can't find parking lot {}", osm_id),
+                }
+            }
+            zones.push(ParkingZone {
+                name: z.name,
+                price_per_hour: z.price_per_hour,
+                max_stay: z.max_stay,
+                onstreet,
+                lots,
+            });
+        }
+        Ok(ParkingPricing { zones })
+    }
+}
@@ -205,6 +205,9 @@ pub struct Road {
     /// Some kind of crossing this distance along center_pts.
     // TODO Just use Crossing directly?
     pub crossing_nodes: Vec<(Distance, CrossingType)>,
+    /// The height of the kerb at a crossing this distance along center_pts. Only some crossings
+    /// are tagged.
+    pub kerb_nodes: Vec<(Distance, Distance)>,
     /// Sorted by increasing distance
     pub crossings: Vec<Crossing>,
 }
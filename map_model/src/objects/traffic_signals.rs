@@ -8,12 +8,14 @@ use geom::{Distance, Duration, Speed};
 use crate::edits::perma_traffic_signal;
 use crate::make::traffic_signals::get_possible_policies;
 use crate::{
-    Intersection, IntersectionID, Map, Movement, MovementID, RoadID, TurnID, TurnPriority,
+    Intersection, IntersectionID, LaneID, Map, Movement, MovementID, RoadID, TurnID, TurnPriority,
 };
 
 // The pace to use for crosswalk pace in m/s
 // https://en.wikipedia.org/wiki/Preferred_walking_speed
 const CROSSWALK_PACE: Speed = Speed::const_meters_per_second(1.4);
+// The shortest WALK interval that set_ped_intervals will produce
+const MIN_WALK: Duration = Duration::const_seconds(4.0);
 
 /// A traffic signal consists of a sequence of Stages that repeat in a cycle. Most Stages last for a
 /// fixed duration. During a single Stage, some movements are protected (can proceed with the
@@ -32,6 +34,9 @@ pub struct Stage {
     // TODO Not renaming this, because this is going to change radically in
     // https://github.com/a-b-street/abstreet/pull/298 anyway
     pub stage_type: StageType,
+    /// How the pedestrian signals for protected crosswalks behave during this stage. If this is
+    /// missing, crosswalks show WALK for the entire stage.
+    pub ped_intervals: Option<PedestrianIntervals>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -41,6 +46,17 @@ pub enum StageType {
     /// Delay is the elapsed time with no demand that ends a cycle.
     /// Additional is the additional duration for an extended cycle.
     Variable(Duration, Duration, Duration),
+    /// Green is held for at least `min`, then extended by `passage` every time a vehicle crosses a
+    /// detector on one of the protected movements, up to `max` total. If no vehicle arrives within
+    /// `passage`, the stage "gaps out" early. Detectors are placed `detector_setback` before the
+    /// stop line of every incoming lane. If nobody is waiting for the stage -- no vehicles over
+    /// the detectors and no pedestrians pressing the call button -- it's skipped entirely.
+    Actuated {
+        min: Duration,
+        max: Duration,
+        passage: Duration,
+        detector_setback: Distance,
+    },
 }
 
 impl StageType {
@@ -49,8 +65,98 @@ impl StageType {
         match self {
             StageType::Fixed(d) => *d,
             StageType::Variable(duration, _, _) => *duration,
+            StageType::Actuated { min, .. } => *min,
         }
     }
+
+    pub fn to_permanent(&self) -> perma_traffic_signal::StageType {
+        match self {
+            StageType::Fixed(d) => {
+                perma_traffic_signal::StageType::Fixed(d.inner_seconds() as usize)
+            }
+            StageType::Variable(min, delay, additional) => {
+                perma_traffic_signal::StageType::Variable(
+                    min.inner_seconds() as usize,
+                    delay.inner_seconds() as usize,
+                    additional.inner_seconds() as usize,
+                )
+            }
+            StageType::Actuated {
+                min,
+                max,
+                passage,
+                detector_setback,
+            } => perma_traffic_signal::StageType::Actuated {
+                min_seconds: min.inner_seconds(),
+                max_seconds: max.inner_seconds(),
+                passage_seconds: passage.inner_seconds(),
+                detector_setback_meters: detector_setback.inner_meters(),
+            },
+        }
+    }
+
+    pub fn from_permanent(stage_type: perma_traffic_signal::StageType) -> StageType {
+        match stage_type {
+            perma_traffic_signal::StageType::Fixed(d) => {
+                StageType::Fixed(Duration::seconds(d as f64))
+            }
+            perma_traffic_signal::StageType::Variable(min, delay, additional) => {
+                StageType::Variable(
+                    Duration::seconds(min as f64),
+                    Duration::seconds(delay as f64),
+                    Duration::seconds(additional as f64),
+                )
+            }
+            perma_traffic_signal::StageType::Actuated {
+                min_seconds,
+                max_seconds,
+                passage_seconds,
+                detector_setback_meters,
+            } => StageType::Actuated {
+                min: Duration::seconds(min_seconds),
+                max: Duration::seconds(max_seconds),
+                passage: Duration::seconds(passage_seconds),
+                detector_setback: Distance::meters(detector_setback_meters),
+            },
+        }
+    }
+}
+
+/// Pedestrian signal timing for the crosswalks protected during a stage, measured from the start
+/// of the stage. If the stage ends before these intervals do, they're cut short.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PedestrianIntervals {
+    /// Crosswalks show WALK this long before vehicles in the same stage get a green light, so
+    /// pedestrians are already visible in the crosswalk when turning vehicles start. Zero means no
+    /// leading pedestrian interval.
+    pub leading_interval: Duration,
+    /// After the leading interval, crosswalks show WALK this long. Pedestrians may only start
+    /// crossing during WALK.
+    pub walk: Duration,
+    /// Flashing DON'T WALK follows WALK. Nobody may start crossing, but anybody already in the
+    /// crosswalk has this long to finish.
+    pub flashing_dont_walk: Duration,
+    /// If true, crosswalks only show WALK if a pedestrian pushed the button before the stage
+    /// started. Otherwise they show DON'T WALK for the entire stage.
+    pub call_button: bool,
+}
+
+/// What a pedestrian signal head shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PedestrianSignal {
+    Walk,
+    FlashingDontWalk,
+    DontWalk,
+}
+
+/// A loop detector embedded in an incoming lane, reporting when a vehicle is over it or between it
+/// and the stop line.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Detector {
+    pub lane: LaneID,
+    /// Distance along the lane where the detection zone begins. The zone extends to the end of
+    /// the lane.
+    pub dist_along: Distance,
 }
 
 impl ControlTrafficSignal {
@@ -136,10 +242,88 @@ impl ControlTrafficSignal {
                     stage.stage_type.simple_duration()
                 );
             }
+            if let Some(ref p) = stage.ped_intervals {
+                if p.walk <= Duration::ZERO {
+                    bail!("Stage {} needs a positive WALK interval", stage_index);
+                }
+                if p.leading_interval < Duration::ZERO || p.flashing_dont_walk < Duration::ZERO {
+                    bail!("Stage {} has negative pedestrian intervals", stage_index);
+                }
+            }
+            if let StageType::Actuated {
+                min,
+                max,
+                passage,
+                detector_setback,
+            } = stage.stage_type
+            {
+                if min < Duration::seconds(1.0) {
+                    bail!(
+                        "Actuated stage {} must have a minimum green of at least 1s",
+                        stage_index
+                    );
+                }
+                if max < min {
+                    bail!(
+                        "Actuated stage {} has a maximum green {} shorter than the minimum {}",
+                        stage_index,
+                        max,
+                        min
+                    );
+                }
+                if passage <= Duration::ZERO {
+                    bail!(
+                        "Actuated stage {} needs a positive passage time",
+                        stage_index
+                    );
+                }
+                if detector_setback < Distance::ZERO {
+                    bail!(
+                        "Actuated stage {} has a negative detector setback",
+                        stage_index
+                    );
+                }
+            }
         }
         Ok(())
     }
 
+    /// Where the detectors calling a stage are located. Only actuated stages have detectors; they
+    /// cover every incoming driving lane feeding a protected vehicle movement. Short lanes are
+    /// covered entirely.
+    pub fn get_detectors(&self, idx: usize, map: &Map) -> Vec<Detector> {
+        let setback = match self.stages[idx].stage_type {
+            StageType::Actuated {
+                detector_setback, ..
+            } => detector_setback,
+            _ => return Vec::new(),
+        };
+        let i = map.get_i(self.id);
+        let mut lanes = BTreeSet::new();
+        for m in &self.stages[idx].protected_movements {
+            if m.crosswalk {
+                continue;
+            }
+            for t in &i.movements[m].members {
+                lanes.insert(t.src);
+            }
+        }
+        lanes
+            .into_iter()
+            .map(|l| {
+                let len = map.get_l(l).length();
+                Detector {
+                    lane: l,
+                    dist_along: if setback >= len {
+                        Distance::ZERO
+                    } else {
+                        len - setback
+                    },
+                }
+            })
+            .collect()
+    }
+
     /// Move crosswalks from stages, adding them to an all-walk as last stage. This may promote
     /// yields to protected. True is returned if any stages were added or modified.
     pub fn convert_to_ped_scramble(&mut self, i: &Intersection) -> bool {
@@ -201,6 +385,50 @@ impl ControlTrafficSignal {
         self != &orig
     }
 
+    /// Splits every stage with protected crosswalks into pedestrian intervals: an optional leading
+    /// interval, then WALK, then just enough flashing DON'T WALK to cross the longest crosswalk at a
+    /// normal pace. Scrambles never get a leading interval, since there are no vehicles to lead.
+    /// Fails if some stage is too short to fit these intervals.
+    pub fn set_ped_intervals(
+        &mut self,
+        leading_interval: Duration,
+        call_button: bool,
+        i: &Intersection,
+    ) -> Result<()> {
+        for idx in 0..self.stages.len() {
+            if !self.stages[idx]
+                .protected_movements
+                .iter()
+                .any(|m| m.crosswalk)
+            {
+                continue;
+            }
+            let leading_interval = if self.stages[idx].is_ped_scramble() {
+                Duration::ZERO
+            } else {
+                leading_interval
+            };
+            let flashing_dont_walk = self.get_min_crossing_time(idx, i);
+            let duration = self.stages[idx].stage_type.simple_duration();
+            if leading_interval + flashing_dont_walk + MIN_WALK > duration {
+                bail!(
+                    "Stage {} only lasts {}, too short for a leading interval of {} and {} to cross",
+                    idx + 1,
+                    duration,
+                    leading_interval,
+                    flashing_dont_walk
+                );
+            }
+            self.stages[idx].ped_intervals = Some(PedestrianIntervals {
+                leading_interval,
+                walk: duration - leading_interval - flashing_dont_walk,
+                flashing_dont_walk,
+                call_button,
+            });
+        }
+        Ok(())
+    }
+
     /// Modifies the fixed timing of all stages, applying either a major or minor duration,
     /// depending on the relative rank of the roads involved in the intersection. If this
     /// transformation couldn't be applied, returns an error. Even if an error is returned, the
@@ -288,9 +516,45 @@ impl Stage {
             yield_movements: BTreeSet::new(),
             // TODO Set a default
             stage_type: StageType::Fixed(Duration::seconds(30.0)),
+            ped_intervals: None,
+        }
+    }
+
+    /// What protected crosswalks show `elapsed` after the stage starts, assuming somebody pushed
+    /// the call button, if there is one.
+    pub fn ped_signal(&self, elapsed: Duration) -> PedestrianSignal {
+        match self.ped_intervals {
+            None => PedestrianSignal::Walk,
+            Some(ref p) => {
+                let walk_ends = p.leading_interval + p.walk;
+                if elapsed < walk_ends {
+                    PedestrianSignal::Walk
+                } else if elapsed < walk_ends + p.flashing_dont_walk {
+                    PedestrianSignal::FlashingDontWalk
+                } else {
+                    PedestrianSignal::DontWalk
+                }
+            }
         }
     }
 
+    /// How long vehicle movements in this stage are held red at the start of the stage, while
+    /// pedestrians get a head start.
+    pub fn leading_ped_interval(&self) -> Duration {
+        self.ped_intervals
+            .as_ref()
+            .map(|p| p.leading_interval)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Does this stage stop all vehicles and only serve crosswalks, an exclusive pedestrian
+    /// scramble?
+    pub fn is_ped_scramble(&self) -> bool {
+        !self.protected_movements.is_empty()
+            && self.yield_movements.is_empty()
+            && self.protected_movements.iter().all(|m| m.crosswalk)
+    }
+
     pub fn could_be_protected(&self, m1: MovementID, i: &Intersection) -> bool {
         let movement1 = &i.movements[&m1];
         for m2 in &self.protected_movements {
@@ -340,6 +604,17 @@ impl Stage {
                 StageType::Variable(_, delay, additional) => {
                     StageType::Variable(time, delay, additional)
                 }
+                StageType::Actuated {
+                    max,
+                    passage,
+                    detector_setback,
+                    ..
+                } => StageType::Actuated {
+                    min: time,
+                    max: max.max(time),
+                    passage,
+                    detector_setback,
+                },
             };
         }
     }
@@ -385,18 +660,17 @@ impl ControlTrafficSignal {
                             .iter()
                             .map(|mvmnt| mvmnt.to_permanent(map))
                             .collect(),
-                        stage_type: match s.stage_type {
-                            StageType::Fixed(d) => {
-                                perma_traffic_signal::StageType::Fixed(d.inner_seconds() as usize)
-                            }
-                            StageType::Variable(min, delay, additional) => {
-                                perma_traffic_signal::StageType::Variable(
-                                    min.inner_seconds() as usize,
-                                    delay.inner_seconds() as usize,
-                                    additional.inner_seconds() as usize,
-                                )
+                        stage_type: s.stage_type.to_permanent(),
+                        ped_intervals: s.ped_intervals.as_ref().map(|p| {
+                            perma_traffic_signal::PedestrianIntervals {
+                                leading_interval_seconds: p.leading_interval.inner_seconds()
+                                    as usize,
+                                walk_seconds: p.walk.inner_seconds() as usize,
+                                flashing_dont_walk_seconds: p.flashing_dont_walk.inner_seconds()
+                                    as usize,
+                                call_button: p.call_button,
                             }
-                        },
+                        }),
                     })
                     .collect(),
                 offset_seconds: self.offset.inner_seconds() as usize,
@@ -440,18 +714,13 @@ impl ControlTrafficSignal {
                 stages.push(Stage {
                     protected_movements,
                     yield_movements: permitted_movements,
-                    stage_type: match s.stage_type {
-                        perma_traffic_signal::StageType::Fixed(d) => {
-                            StageType::Fixed(Duration::seconds(d as f64))
-                        }
-                        perma_traffic_signal::StageType::Variable(min, delay, additional) => {
-                            StageType::Variable(
-                                Duration::seconds(min as f64),
-                                Duration::seconds(delay as f64),
-                                Duration::seconds(additional as f64),
-                            )
-                        }
-                    },
+                    stage_type: StageType::from_permanent(s.stage_type),
+                    ped_intervals: s.ped_intervals.map(|p| PedestrianIntervals {
+                        leading_interval: Duration::seconds(p.leading_interval_seconds as f64),
+                        walk: Duration::seconds(p.walk_seconds as f64),
+                        flashing_dont_walk: Duration::seconds(p.flashing_dont_walk_seconds as f64),
+                        call_button: p.call_button,
+                    }),
                 });
             } else {
                 bail!("{}", errors.join("; "));
@@ -466,3 +735,177 @@ impl ControlTrafficSignal {
         Ok(ts)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use geom::{Angle, PolyLine, Polygon, Pt2D};
+
+    use super::*;
+    use crate::{osm, DirectedRoadID, Direction, IntersectionControl, IntersectionKind, TurnType};
+
+    #[test]
+    fn test_actuated_stage_round_trip() {
+        // Fractional seconds shouldn't be truncated
+        let stage_type = StageType::Actuated {
+            min: Duration::seconds(7.5),
+            max: Duration::seconds(45.0),
+            passage: Duration::seconds(2.5),
+            detector_setback: Distance::meters(12.5),
+        };
+        assert_eq!(
+            stage_type.to_permanent(),
+            perma_traffic_signal::StageType::Actuated {
+                min_seconds: 7.5,
+                max_seconds: 45.0,
+                passage_seconds: 2.5,
+                detector_setback_meters: 12.5,
+            }
+        );
+        assert_eq!(
+            StageType::from_permanent(stage_type.to_permanent()),
+            stage_type
+        );
+    }
+
+    /// A signal with one 14m crosswalk, taking 10s to cross, and one vehicle movement
+    fn crosswalk_intersection() -> (Intersection, MovementID, MovementID) {
+        let movement = |from: usize, to: usize, crosswalk: bool, length: f64| {
+            let id = MovementID {
+                from: DirectedRoadID {
+                    road: RoadID(from),
+                    dir: Direction::Fwd,
+                },
+                to: DirectedRoadID {
+                    road: RoadID(to),
+                    dir: Direction::Back,
+                },
+                parent: IntersectionID(0),
+                crosswalk,
+            };
+            let m = Movement {
+                id,
+                turn_type: if crosswalk {
+                    TurnType::Crosswalk
+                } else {
+                    TurnType::Straight
+                },
+                members: Vec::new(),
+                geom: PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(length, 0.0)]),
+                angle: Angle::degrees(0.0),
+            };
+            (id, m)
+        };
+        let (crosswalk, crosswalk_movement) = movement(0, 1, true, 14.0);
+        let (vehicle, vehicle_movement) = movement(2, 3, false, 20.0);
+        let i = Intersection {
+            id: IntersectionID(0),
+            polygon: Polygon::rectangle(20.0, 20.0),
+            turns: Vec::new(),
+            elevation: Distance::ZERO,
+            kind: IntersectionKind::Intersection,
+            control: IntersectionControl::Signalled,
+            orig_id: osm::NodeID(0),
+            incoming_lanes: Vec::new(),
+            outgoing_lanes: Vec::new(),
+            roads: vec![RoadID(0), RoadID(1), RoadID(2), RoadID(3)],
+            modal_filter: None,
+            merged: false,
+            movements: vec![(crosswalk, crosswalk_movement), (vehicle, vehicle_movement)]
+                .into_iter()
+                .collect(),
+        };
+        (i, crosswalk, vehicle)
+    }
+
+    fn stage(protected: Vec<MovementID>, yields: Vec<MovementID>, seconds: f64) -> Stage {
+        Stage {
+            protected_movements: protected.into_iter().collect(),
+            yield_movements: yields.into_iter().collect(),
+            stage_type: StageType::Fixed(Duration::seconds(seconds)),
+            ped_intervals: None,
+        }
+    }
+
+    #[test]
+    fn test_ped_signal() {
+        let (_, crosswalk, vehicle) = crosswalk_intersection();
+        let mut s = stage(vec![crosswalk, vehicle], Vec::new(), 30.0);
+        // Without intervals, crosswalks show WALK the whole time
+        assert_eq!(
+            s.ped_signal(Duration::seconds(29.0)),
+            PedestrianSignal::Walk
+        );
+
+        s.ped_intervals = Some(PedestrianIntervals {
+            leading_interval: Duration::seconds(3.0),
+            walk: Duration::seconds(7.0),
+            flashing_dont_walk: Duration::seconds(10.0),
+            call_button: false,
+        });
+        for (elapsed, expected) in [
+            (0.0, PedestrianSignal::Walk),
+            (9.9, PedestrianSignal::Walk),
+            (10.0, PedestrianSignal::FlashingDontWalk),
+            (19.9, PedestrianSignal::FlashingDontWalk),
+            (20.0, PedestrianSignal::DontWalk),
+            (29.0, PedestrianSignal::DontWalk),
+        ] {
+            assert_eq!(s.ped_signal(Duration::seconds(elapsed)), expected);
+        }
+        assert_eq!(s.leading_ped_interval(), Duration::seconds(3.0));
+    }
+
+    #[test]
+    fn test_is_ped_scramble() {
+        let (_, crosswalk, vehicle) = crosswalk_intersection();
+        assert!(!Stage::new().is_ped_scramble());
+        assert!(stage(vec![crosswalk], Vec::new(), 30.0).is_ped_scramble());
+        assert!(!stage(vec![crosswalk, vehicle], Vec::new(), 30.0).is_ped_scramble());
+        assert!(!stage(vec![crosswalk], vec![vehicle], 30.0).is_ped_scramble());
+        assert!(!stage(vec![vehicle], Vec::new(), 30.0).is_ped_scramble());
+    }
+
+    #[test]
+    fn test_set_ped_intervals() {
+        let (i, crosswalk, vehicle) = crosswalk_intersection();
+        let mut signal = ControlTrafficSignal {
+            id: i.id,
+            stages: vec![
+                stage(vec![crosswalk, vehicle], Vec::new(), 30.0),
+                stage(vec![crosswalk], Vec::new(), 20.0),
+                stage(vec![vehicle], Vec::new(), 30.0),
+            ],
+            offset: Duration::ZERO,
+        };
+        signal
+            .set_ped_intervals(Duration::seconds(3.0), true, &i)
+            .unwrap();
+        assert_eq!(
+            signal.stages[0].ped_intervals,
+            Some(PedestrianIntervals {
+                leading_interval: Duration::seconds(3.0),
+                walk: Duration::seconds(17.0),
+                flashing_dont_walk: Duration::seconds(10.0),
+                call_button: true,
+            })
+        );
+        // Scrambles don't get a leading interval
+        assert_eq!(
+            signal.stages[1].ped_intervals,
+            Some(PedestrianIntervals {
+                leading_interval: Duration::ZERO,
+                walk: Duration::seconds(10.0),
+                flashing_dont_walk: Duration::seconds(10.0),
+                call_button: true,
+            })
+        );
+        // No crosswalks, no pedestrian signal
+        assert_eq!(signal.stages[2].ped_intervals, None);
+
+        // 3s leading, 4s of WALK, and 10s to cross don't fit in 15s
+        signal.stages[0].stage_type = StageType::Fixed(Duration::seconds(15.0));
+        assert!(signal
+            .set_ped_intervals(Duration::seconds(3.0), false, &i)
+            .is_err());
+    }
+}
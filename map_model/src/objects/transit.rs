@@ -6,7 +6,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
-use geom::Time;
+use geom::{Duration, Time};
 
 use crate::{LaneID, Map, Path, PathConstraints, PathRequest, Position, RoadID};
 
@@ -51,6 +51,16 @@ pub struct TransitStop {
     pub is_train_stop: bool,
 }
 
+/// What kind of vehicle serves a route.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransitMode {
+    Bus,
+    /// Trams and streetcars share the road with other traffic, so they follow the same lanes as
+    /// buses.
+    Tram,
+    Train,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TransitRoute {
     pub id: TransitRouteID,
@@ -64,14 +74,47 @@ pub struct TransitRoute {
     /// A transit vehicle either vanishes at its last stop or exits the map through this border.
     pub end_border: Option<LaneID>,
     pub route_type: PathConstraints,
+    pub mode: TransitMode,
     /// Non-empty, times in order for one day when a vehicle should begin at start.
     pub spawn_times: Vec<Time>,
     /// Explicitly store whatever the original was, since this can't be reconstructed without side
     /// input.
     pub orig_spawn_times: Vec<Time>,
+    /// If the route is timetabled, how long after spawning each original trip is scheduled to
+    /// reach every stop. Entry i matches `orig_spawn_times[i]`. Empty otherwise.
+    pub stop_time_offsets: Vec<Vec<Duration>>,
+    /// How many people fit on each vehicle, seated and standing. If None, a default for the mode
+    /// is used.
+    pub capacity: Option<usize>,
 }
 
 impl TransitRoute {
+    /// How many people fit on each vehicle serving this route.
+    pub fn vehicle_capacity(&self) -> usize {
+        self.capacity.unwrap_or(match self.mode {
+            TransitMode::Bus => 60,
+            TransitMode::Tram => 120,
+            TransitMode::Train => 200,
+        })
+    }
+
+    /// If the route is timetabled, how long after spawning at this time a vehicle is scheduled to
+    /// reach every stop. Vehicles that don't match an original trip, because the schedule was
+    /// edited, follow the running times of the original trip spawning closest to them.
+    pub fn scheduled_offsets(&self, spawn: Time) -> Option<&Vec<Duration>> {
+        self.orig_spawn_times
+            .iter()
+            .zip(self.stop_time_offsets.iter())
+            .min_by_key(|(t, _)| {
+                if **t > spawn {
+                    **t - spawn
+                } else {
+                    spawn - **t
+                }
+            })
+            .map(|(_, offsets)| offsets)
+    }
+
     fn all_path_requests(&self, map: &Map) -> Vec<PathRequest> {
         let mut steps = vec![PathRequest::vehicle(
             Position::start(self.start),
@@ -139,10 +182,10 @@ impl TransitRoute {
     }
 
     pub fn plural_noun(&self) -> &'static str {
-        if self.route_type == PathConstraints::Bus {
-            "buses"
-        } else {
-            "trains"
+        match self.mode {
+            TransitMode::Bus => "buses",
+            TransitMode::Tram => "trams",
+            TransitMode::Train => "trains",
         }
     }
 }
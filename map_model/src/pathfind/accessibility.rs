@@ -0,0 +1,363 @@
+//! Walking profiles for people who can't use every sidewalk and crossing, like wheelchair users.
+//! Barriers are found from OSM tags and elevation data. Most places have little of this mapped,
+//! so anything untagged is assumed to be usable.
+
+use std::fmt;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use geom::Distance;
+
+use crate::{osm, Direction, Lane, Map, Path, PathRequest, PathStep, Road, Traversable, Turn};
+
+/// Only consider kerbs tagged this close to the end of a road being crossed
+const KERB_SEARCH_DIST: Distance = Distance::const_meters(30.0);
+
+/// Describes what somebody can walk or roll along. Sidewalks and crossings that don't meet all of
+/// these aren't used at all, rather than just being penalized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WalkingProfile {
+    /// Crossings with a kerb higher than this can't be used.
+    pub max_kerb_height: Distance,
+    pub allow_steps: bool,
+    /// The steepest incline that can be used, uphill or downhill. Like `Road::percent_incline`,
+    /// 0.08 is 8%.
+    pub max_incline: f64,
+    /// Sidewalks and footways narrower than this can't be used.
+    pub min_width: Distance,
+    /// Can gravel, cobblestones, grass, and other uneven surfaces be used?
+    pub allow_rough_surfaces: bool,
+}
+
+impl WalkingProfile {
+    /// Roughly following common guidance for wheelchair access: lowered kerbs, no steps, at most
+    /// an 8% incline, and 0.9m of clear width.
+    pub fn wheelchair() -> WalkingProfile {
+        WalkingProfile {
+            max_kerb_height: Distance::const_meters(0.03),
+            allow_steps: false,
+            max_incline: 0.08,
+            min_width: Distance::const_meters(0.9),
+            allow_rough_surfaces: false,
+        }
+    }
+
+    /// Why somebody with this profile can't use a sidewalk, shoulder, or footway.
+    pub fn lane_barrier(&self, lane: &Lane, map: &Map) -> Option<AccessibilityBarrier> {
+        let road = map.get_r(lane.id.road);
+        if !self.allow_steps && road.osm_tags.is(osm::HIGHWAY, "steps") {
+            return Some(AccessibilityBarrier::Steps);
+        }
+        if road.percent_incline.abs() > self.max_incline {
+            return Some(AccessibilityBarrier::Incline(road.percent_incline.abs()));
+        }
+        let width = walkable_tag(road, lane, "width")
+            .and_then(|x| parse_meters(x))
+            .unwrap_or(lane.width);
+        if width < self.min_width {
+            return Some(AccessibilityBarrier::NarrowSidewalk(width));
+        }
+        if !self.allow_rough_surfaces {
+            if let Some(surface) = walkable_tag(road, lane, "surface") {
+                if is_rough_surface(surface) {
+                    return Some(AccessibilityBarrier::RoughSurface(surface.to_string()));
+                }
+            }
+            if let Some(smoothness) = walkable_tag(road, lane, "smoothness") {
+                if is_rough_smoothness(smoothness) {
+                    return Some(AccessibilityBarrier::RoughSurface(smoothness.to_string()));
+                }
+            }
+        }
+        None
+    }
+
+    /// Why somebody with this profile can't use a turn between sidewalks. Only kerbs at crossings
+    /// are checked; corners are assumed to be fine.
+    pub fn turn_barrier(&self, turn: &Turn, map: &Map) -> Option<AccessibilityBarrier> {
+        // Crosswalks over several roads at once don't know which kerbs they use
+        let dr = turn.crosswalk_over_road(map)?;
+        let road = map.get_r(dr.road);
+        let at_end = dr.dir == Direction::Fwd;
+        let height = road
+            .kerb_nodes
+            .iter()
+            .filter(|(dist, _)| {
+                if at_end {
+                    road.length() - *dist <= KERB_SEARCH_DIST
+                } else {
+                    *dist <= KERB_SEARCH_DIST
+                }
+            })
+            .map(|(_, height)| *height)
+            .max()?;
+        if height > self.max_kerb_height {
+            Some(AccessibilityBarrier::Kerb(height))
+        } else {
+            None
+        }
+    }
+
+    /// All of the barriers along a path that somebody with this profile couldn't use.
+    pub fn barriers_along(
+        &self,
+        path: &Path,
+        map: &Map,
+    ) -> Vec<(Traversable, AccessibilityBarrier)> {
+        let mut barriers = Vec::new();
+        for step in path.get_steps() {
+            let barrier = match step {
+                PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                    self.lane_barrier(map.get_l(*l), map)
+                }
+                PathStep::Turn(t) | PathStep::ContraflowTurn(t) => {
+                    self.turn_barrier(map.get_t(*t), map)
+                }
+            };
+            if let Some(barrier) = barrier {
+                barriers.push((step.as_traversable(), barrier));
+            }
+        }
+        barriers
+    }
+
+    /// When no walking path for this profile exists, explain why by describing the barriers along
+    /// the path anybody else would take.
+    pub(crate) fn explain_rejection(&self, req: PathRequest, map: &Map) -> anyhow::Error {
+        let barriers = map
+            .pathfind(req.clone())
+            .map(|path| self.barriers_along(&path, map))
+            .unwrap_or_default();
+        if barriers.is_empty() {
+            return anyhow!("can't fulfill {}", req);
+        }
+        let mut reasons: Vec<String> = Vec::new();
+        for (on, barrier) in barriers {
+            let reason = match on {
+                Traversable::Lane(l) => {
+                    format!("{} on {}", barrier, map.get_r(l.road).get_name(None))
+                }
+                Traversable::Turn(t) => {
+                    format!(
+                        "{} crossing {}",
+                        barrier,
+                        map.get_r(t.src.road).get_name(None)
+                    )
+                }
+            };
+            if !reasons.contains(&reason) {
+                reasons.push(reason);
+            }
+        }
+        anyhow!(
+            "can't fulfill {} with these accessibility needs; the usual route has {}",
+            req,
+            reasons.join(", ")
+        )
+    }
+}
+
+/// Something along a route that a `WalkingProfile` can't use.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AccessibilityBarrier {
+    Steps,
+    Kerb(Distance),
+    Incline(f64),
+    NarrowSidewalk(Distance),
+    /// The surface or smoothness tag
+    RoughSurface(String),
+}
+
+impl fmt::Display for AccessibilityBarrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessibilityBarrier::Steps => write!(f, "steps"),
+            AccessibilityBarrier::Kerb(height) => {
+                write!(f, "a {:.0}cm kerb", height.inner_meters() * 100.0)
+            }
+            AccessibilityBarrier::Incline(pct) => write!(f, "a {:.0}% incline", pct * 100.0),
+            AccessibilityBarrier::NarrowSidewalk(width) => {
+                write!(f, "a {:.1}m wide sidewalk", width.inner_meters())
+            }
+            AccessibilityBarrier::RoughSurface(surface) => write!(f, "a {} surface", surface),
+        }
+    }
+}
+
+/// Sidewalks are usually tagged on the road they're next to, like `sidewalk:left:width`.
+/// Footways and shoulders just use the road's own tags.
+fn walkable_tag<'a>(road: &'a Road, lane: &Lane, key: &str) -> Option<&'a String> {
+    if !lane.is_sidewalk() || road.is_footway() {
+        return road.osm_tags.get(key);
+    }
+    let side = if road.lanes[0].id == lane.id {
+        "left"
+    } else {
+        "right"
+    };
+    road.osm_tags
+        .get(&format!("sidewalk:{}:{}", side, key))
+        .or_else(|| road.osm_tags.get(&format!("sidewalk:both:{}", key)))
+        .or_else(|| road.osm_tags.get(&format!("sidewalk:{}", key)))
+}
+
+fn parse_meters(x: &str) -> Option<Distance> {
+    x.trim_end_matches('m')
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(Distance::meters)
+}
+
+fn is_rough_surface(surface: &str) -> bool {
+    matches!(
+        surface,
+        "unpaved"
+            | "gravel"
+            | "pebblestone"
+            | "cobblestone"
+            | "unhewn_cobblestone"
+            | "sett"
+            | "dirt"
+            | "earth"
+            | "ground"
+            | "grass"
+            | "grass_paver"
+            | "mud"
+            | "sand"
+            | "rock"
+            | "stepping_stones"
+            | "woodchips"
+    )
+}
+
+fn is_rough_smoothness(smoothness: &str) -> bool {
+    matches!(
+        smoothness,
+        "bad" | "very_bad" | "horrible" | "very_horrible" | "impassable"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{RoadID, TurnID, TurnType};
+
+    /// Tag the only road in `Map::almost_blank`. Sidewalks look for `sidewalk:both:*` while other
+    /// lanes use the road's own tags, so set both.
+    fn tag_road(map: &mut Map, key: &str, value: &str) {
+        let tags = &mut map.mut_road(RoadID(0)).osm_tags;
+        tags.insert(key, value);
+        tags.insert(format!("sidewalk:both:{}", key), value);
+    }
+
+    fn first_lane_barrier(map: &Map) -> Option<AccessibilityBarrier> {
+        WalkingProfile::wheelchair().lane_barrier(&map.get_r(RoadID(0)).lanes[0], map)
+    }
+
+    fn crossing(map: &Map, at_end: bool) -> Turn {
+        let road = map.get_r(RoadID(0));
+        Turn {
+            id: TurnID {
+                parent: if at_end { road.dst_i } else { road.src_i },
+                src: road.lanes[0].id,
+                dst: road.lanes.last().unwrap().id,
+            },
+            turn_type: TurnType::Crosswalk,
+            geom: PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(10.0, 0.0)]),
+        }
+    }
+
+    #[test]
+    fn test_parse_meters() {
+        assert_eq!(parse_meters("1.5"), Some(Distance::meters(1.5)));
+        assert_eq!(parse_meters("0.8 m"), Some(Distance::meters(0.8)));
+        // Imperial widths aren't handled, so they're ignored
+        assert_eq!(parse_meters("3'"), None);
+    }
+
+    #[test]
+    fn test_lane_barrier() {
+        let mut map = Map::almost_blank();
+        assert_eq!(first_lane_barrier(&map), None);
+
+        // Untagged lanes use their own width
+        map.mut_road(RoadID(0)).lanes[0].width = Distance::meters(0.5);
+        assert_eq!(
+            first_lane_barrier(&map),
+            Some(AccessibilityBarrier::NarrowSidewalk(Distance::meters(0.5)))
+        );
+        // But a tagged width wins
+        tag_road(&mut map, "width", "0.7");
+        assert_eq!(
+            first_lane_barrier(&map),
+            Some(AccessibilityBarrier::NarrowSidewalk(Distance::meters(0.7)))
+        );
+        tag_road(&mut map, "width", "2");
+        assert_eq!(first_lane_barrier(&map), None);
+
+        tag_road(&mut map, "smoothness", "bad");
+        assert_eq!(
+            first_lane_barrier(&map),
+            Some(AccessibilityBarrier::RoughSurface("bad".to_string()))
+        );
+        // Surface is checked before smoothness
+        tag_road(&mut map, "surface", "gravel");
+        assert_eq!(
+            first_lane_barrier(&map),
+            Some(AccessibilityBarrier::RoughSurface("gravel".to_string()))
+        );
+        let mut rough_ok = WalkingProfile::wheelchair();
+        rough_ok.allow_rough_surfaces = true;
+        assert_eq!(
+            rough_ok.lane_barrier(&map.get_r(RoadID(0)).lanes[0], &map),
+            None
+        );
+
+        map.mut_road(RoadID(0)).percent_incline = -0.1;
+        assert_eq!(
+            first_lane_barrier(&map),
+            Some(AccessibilityBarrier::Incline(0.1))
+        );
+
+        // Steps are reported before anything else
+        tag_road(&mut map, osm::HIGHWAY, "steps");
+        assert_eq!(first_lane_barrier(&map), Some(AccessibilityBarrier::Steps));
+    }
+
+    #[test]
+    fn test_turn_barrier() {
+        let mut map = Map::almost_blank();
+        let profile = WalkingProfile::wheelchair();
+        let start = crossing(&map, false);
+        let end = crossing(&map, true);
+        // Untagged kerbs are assumed to be fine
+        assert_eq!(profile.turn_barrier(&start, &map), None);
+
+        let len = map.get_r(RoadID(0)).length();
+        map.mut_road(RoadID(0)).kerb_nodes = vec![
+            (Distance::meters(1.0), Distance::meters(0.12)),
+            (len - Distance::meters(1.0), Distance::meters(0.03)),
+        ];
+        assert_eq!(
+            profile.turn_barrier(&start, &map),
+            Some(AccessibilityBarrier::Kerb(Distance::meters(0.12)))
+        );
+        // A lowered kerb is just within the limit
+        assert_eq!(profile.turn_barrier(&end, &map), None);
+
+        // Kerbs far from the crossing don't matter
+        map.mut_road(RoadID(0)).kerb_nodes = vec![(len / 2.0, Distance::meters(0.12))];
+        assert_eq!(profile.turn_barrier(&start, &map), None);
+        assert_eq!(profile.turn_barrier(&end, &map), None);
+
+        // Only crossings are checked
+        let mut corner = crossing(&map, false);
+        corner.turn_type = TurnType::SharedSidewalkCorner;
+        map.mut_road(RoadID(0)).kerb_nodes = vec![(Distance::meters(1.0), Distance::meters(0.12))];
+        assert_eq!(profile.turn_barrier(&corner, &map), None);
+    }
+}
@@ -7,17 +7,21 @@ use serde::{Deserialize, Serialize};
 
 use geom::Duration;
 
+pub use self::accessibility::{AccessibilityBarrier, WalkingProfile};
 pub use self::engine::CreateEngine;
 pub use self::pathfinder::{Pathfinder, PathfinderCache, PathfinderCaching};
+pub use self::time_dependent::TravelTimeProfile;
 pub use self::v1::{Path, PathRequest, PathStep};
 pub use self::v2::{PathStepV2, PathV2};
-pub use self::vehicles::vehicle_cost;
+pub use self::vehicles::{observed_vehicle_cost, vehicle_cost};
 pub use self::walking::WalkingNode;
 use crate::{osm, Lane, LaneID, LaneType, Map, MovementID, Road, RoadID, TurnType};
 
+mod accessibility;
 mod engine;
 mod node_map;
 mod pathfinder;
+mod time_dependent;
 // TODO tmp
 pub mod uber_turns;
 mod v1;
@@ -34,6 +38,8 @@ pub enum PathConstraints {
     Bike,
     Bus,
     Train,
+    /// Vans and lorries. They use the same lanes as cars, but some roads ban them.
+    Truck,
 }
 
 impl PathConstraints {
@@ -44,6 +50,7 @@ impl PathConstraints {
             PathConstraints::Bike,
             PathConstraints::Bus,
             PathConstraints::Train,
+            PathConstraints::Truck,
         ]
     }
 
@@ -87,11 +94,14 @@ impl PathConstraints {
             PathConstraints::Train => {
                 return lane.is_light_rail();
             }
+            PathConstraints::Truck => {
+                lane.is_driving() && !map.get_r(lane.id.road).osm_tags.is("hgv", "no")
+            }
         };
         if result {
             return true;
         }
-        // Second chance for cars and bikes trying to use a bus-only lane that also happens to be a
+        // Second chance for cars, bikes, and trucks trying to use a bus-only lane that also happens to be a
         // turn lane.
         //
         // TODO This check could be made stricter in two ways:
@@ -198,6 +208,10 @@ pub struct RoutingParams {
     /// Don't allow movements between these roads at all. Only affects vehicle routing, not
     /// pedestrian.
     pub avoid_movements_between: BTreeSet<(RoadID, RoadID)>,
+
+    /// For pedestrian routing. Sidewalks and crossings this profile can't use are avoided
+    /// entirely, so there's no route at all if it would start or end on one.
+    pub walking_profile: Option<WalkingProfile>,
 }
 
 impl Default for RoutingParams {
@@ -219,6 +233,8 @@ impl Default for RoutingParams {
             avoid_roads: BTreeSet::new(),
             avoid_movements_between: BTreeSet::new(),
             only_use_roads: BTreeSet::new(),
+
+            walking_profile: None,
         }
     }
 }
@@ -1,20 +1,25 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::{Deserialize, Serialize};
 use thread_local::ThreadLocal;
 
 use abstutil::{Timer, VecMap};
-use geom::Duration;
+use geom::{Distance, Duration, Pt2D, Time};
 
 use crate::pathfind::engine::CreateEngine;
+use crate::pathfind::time_dependent;
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::walking::SidewalkPathfinder;
 use crate::{
-    DirectedRoadID, Map, PathConstraints, PathRequest, PathV2, Position, RoutingParams,
-    TransitRouteID, TransitStopID,
+    DirectedRoadID, Map, MovementID, ParkingLotID, PathConstraints, PathRequest, PathV2, Position,
+    RoutingParams, TransitRouteID, TransitStopID, WalkingProfile,
 };
 
+/// Drivers only use park-and-ride lots within this straight-line distance of the first transit
+/// stop.
+const MAX_PARK_AND_RIDE_WALK: Distance = Distance::const_meters(400.0);
+
 #[derive(Serialize, Deserialize)]
 pub struct Pathfinder {
     car_graph: VehiclePathfinder,
@@ -23,6 +28,14 @@ pub struct Pathfinder {
     train_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     walking_with_transit_graph: SidewalkPathfinder,
+    // Few agents are trucks, so this isn't built with the map. The first truck to route builds a
+    // Dijkstra graph and caches it in cached_alternatives.
+    #[serde(
+        skip_serializing,
+        skip_deserializing,
+        default = "VehiclePathfinder::empty"
+    )]
+    truck_graph: VehiclePathfinder,
 
     // These params cover the main graphs
     params: RoutingParams,
@@ -31,6 +44,13 @@ pub struct Pathfinder {
     // TODO VecMap is probably fast enough. RoutingParams is annoying to implement Hash.
     #[serde(skip_serializing, skip_deserializing)]
     cached_alternatives: ThreadLocal<RefCell<VecMap<(PathConstraints, RoutingParams), Pathfinder>>>,
+    // Found the first time somebody considers park-and-ride. Each lot is paired with the transit
+    // stops within walking distance, and lots without any are left out.
+    #[serde(skip_serializing, skip_deserializing)]
+    park_and_ride_lots: ThreadLocal<Vec<(ParkingLotID, Vec<TransitStopID>)>>,
+    // Built the first time somebody with each walking profile considers transit
+    #[serde(skip_serializing, skip_deserializing)]
+    transit_graphs_per_profile: ThreadLocal<RefCell<VecMap<WalkingProfile, SidewalkPathfinder>>>,
 }
 
 /// When pathfinding with different `RoutingParams` is done, a temporary pathfinder must be
@@ -56,8 +76,11 @@ impl Clone for Pathfinder {
             train_graph: self.train_graph.clone(),
             walking_graph: self.walking_graph.clone(),
             walking_with_transit_graph: self.walking_with_transit_graph.clone(),
+            truck_graph: self.truck_graph.clone(),
             params: self.params.clone(),
             cached_alternatives: ThreadLocal::new(),
+            park_and_ride_lots: ThreadLocal::new(),
+            transit_graphs_per_profile: ThreadLocal::new(),
         }
     }
 }
@@ -73,8 +96,11 @@ impl Pathfinder {
             train_graph: VehiclePathfinder::empty(),
             walking_graph: SidewalkPathfinder::empty(),
             walking_with_transit_graph: SidewalkPathfinder::empty(),
+            truck_graph: VehiclePathfinder::empty(),
             params: RoutingParams::default(),
             cached_alternatives: ThreadLocal::new(),
+            park_and_ride_lots: ThreadLocal::new(),
+            transit_graphs_per_profile: ThreadLocal::new(),
         }
     }
 
@@ -115,7 +141,7 @@ impl Pathfinder {
         timer.stop("prepare pathfinding for trains");
 
         timer.start("prepare pathfinding for pedestrians");
-        let walking_graph = SidewalkPathfinder::new(map, None, engine);
+        let walking_graph = SidewalkPathfinder::new(map, None, &params, engine);
         timer.stop("prepare pathfinding for pedestrians");
 
         // Transit routes haven't been created yet, so defer this step
@@ -128,9 +154,12 @@ impl Pathfinder {
             train_graph,
             walking_graph,
             walking_with_transit_graph,
+            truck_graph: VehiclePathfinder::empty(),
 
             params,
             cached_alternatives: ThreadLocal::new(),
+            park_and_ride_lots: ThreadLocal::new(),
+            transit_graphs_per_profile: ThreadLocal::new(),
         }
     }
 
@@ -169,7 +198,7 @@ impl Pathfinder {
             timer.start(format!("prepare pathfinding for just {:?}", constraints));
             match constraints {
                 PathConstraints::Pedestrian => {
-                    p.walking_graph = SidewalkPathfinder::new(map, None, &engine);
+                    p.walking_graph = SidewalkPathfinder::new(map, None, &params, &engine);
                 }
                 PathConstraints::Car => {
                     p.car_graph = VehiclePathfinder::new(map, constraints, &params, &engine);
@@ -183,6 +212,9 @@ impl Pathfinder {
                 PathConstraints::Train => {
                     p.train_graph = VehiclePathfinder::new(map, constraints, &params, &engine);
                 }
+                PathConstraints::Truck => {
+                    p.truck_graph = VehiclePathfinder::new(map, constraints, &params, &engine);
+                }
             }
             timer.stop(format!("prepare pathfinding for just {:?}", constraints));
         }
@@ -190,9 +222,49 @@ impl Pathfinder {
         p
     }
 
+    /// Makes a copy of this pathfinder, where driving uses observed travel times for some
+    /// movements, such as measurements of congestion from a simulation. Other modes are unchanged.
+    /// Applying edits later reverts to ideal costs.
+    pub fn with_observed_driving_costs(
+        &self,
+        map: &Map,
+        costs: &BTreeMap<MovementID, Duration>,
+        timer: &mut Timer,
+    ) -> Pathfinder {
+        timer.start("prepare pathfinding for cars with observed costs");
+        let mut p = self.clone();
+        // The costs are still roughly proportional to the originals, so the node ordering is a
+        // reasonable start.
+        p.car_graph = VehiclePathfinder::new_with_observed_costs(
+            map,
+            PathConstraints::Car,
+            &self.params,
+            Some(costs),
+            &self.car_graph.engine.reuse_ordering(),
+        );
+        timer.stop("prepare pathfinding for cars with observed costs");
+        p
+    }
+
+    /// Builds a driving path along a known sequence of roads, such as one a vehicle actually took,
+    /// instead of searching for one.
+    pub fn path_along_roads(
+        &self,
+        roads: Vec<DirectedRoadID>,
+        req: PathRequest,
+        cost: Duration,
+        map: &Map,
+    ) -> PathV2 {
+        self.car_graph.path_along_roads(roads, req, cost, map)
+    }
+
     pub(crate) fn finalize_transit(&mut self, map: &Map, engine: &CreateEngine) {
-        self.walking_with_transit_graph =
-            SidewalkPathfinder::new(map, Some((&self.bus_graph, &self.train_graph)), engine);
+        self.walking_with_transit_graph = SidewalkPathfinder::new(
+            map,
+            Some((&self.bus_graph, &self.train_graph)),
+            &self.params,
+            engine,
+        );
     }
 
     /// Finds a path from a start to an end for a certain type of agent.
@@ -209,6 +281,9 @@ impl Pathfinder {
             PathConstraints::Bike => self.bike_graph.pathfind(req, map),
             PathConstraints::Bus => self.bus_graph.pathfind(req, map),
             PathConstraints::Train => self.train_graph.pathfind(req, map),
+            PathConstraints::Truck => {
+                self.pathfind_with_params(req, &self.params, PathfinderCaching::CacheDijkstra, map)
+            }
         }
     }
 
@@ -224,15 +299,26 @@ impl Pathfinder {
         map: &Map,
     ) -> Option<PathV2> {
         let constraints = req.constraints;
-        if params == &self.params {
+        if params == &self.params
+            && (constraints != PathConstraints::Truck || !self.truck_graph.is_empty())
+        {
             return match constraints {
                 PathConstraints::Pedestrian => self.walking_graph.pathfind(req, map),
                 PathConstraints::Car => self.car_graph.pathfind(req, map),
                 PathConstraints::Bike => self.bike_graph.pathfind(req, map),
                 PathConstraints::Bus => self.bus_graph.pathfind(req, map),
                 PathConstraints::Train => self.train_graph.pathfind(req, map),
+                PathConstraints::Truck => self.truck_graph.pathfind(req, map),
             };
         }
+        // The truck graph is always built on demand, so don't throw it away
+        let cache_custom = if constraints == PathConstraints::Truck
+            && cache_custom == PathfinderCaching::NoCache
+        {
+            PathfinderCaching::CacheDijkstra
+        } else {
+            cache_custom
+        };
 
         // If the params differ from the ones baked into the map, the CHs won't match. Do we have a
         // cached alternative?
@@ -271,6 +357,51 @@ impl Pathfinder {
         result
     }
 
+    /// Like `pathfind_with_params`, but the cost of crossing each road depends on when it's
+    /// reached, starting from `departure`. Contraction hierarchies can't handle this, so unless the
+    /// profile is flat, vehicle paths are found with a slower search. Pedestrians ignore the
+    /// profile.
+    pub fn pathfind_with_params_at(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        departure: Time,
+        profile: &TravelTimeProfile,
+        map: &Map,
+    ) -> Option<PathV2> {
+        if req.constraints == PathConstraints::Pedestrian || profile.is_flat() {
+            return self.pathfind_with_params(req, params, PathfinderCaching::NoCache, map);
+        }
+        time_dependent::pathfind(
+            req,
+            params,
+            departure,
+            profile,
+            self.car_graph.uber_turns(),
+            map,
+        )
+    }
+
+    /// Finds a vehicle path, adding extra delay to cross some roads. This is useful for avoiding
+    /// current congestion. Like time-dependent routing, this uses a slower search.
+    pub fn pathfind_with_delays(
+        &self,
+        req: PathRequest,
+        delays: &HashMap<DirectedRoadID, Duration>,
+        map: &Map,
+    ) -> Option<PathV2> {
+        if req.constraints == PathConstraints::Pedestrian || delays.is_empty() {
+            return self.pathfind(req, map);
+        }
+        time_dependent::pathfind_with_delays(
+            req,
+            map.routing_params(),
+            delays,
+            self.car_graph.uber_turns(),
+            map,
+        )
+    }
+
     pub fn all_costs_from(
         &self,
         req: PathRequest,
@@ -281,23 +412,97 @@ impl Pathfinder {
             PathConstraints::Pedestrian => self.walking_graph.all_costs_from(req.start, map),
             PathConstraints::Car => self.car_graph.all_costs_from(req.start, map),
             PathConstraints::Bike => self.bike_graph.all_costs_from(req.start, map),
-            PathConstraints::Bus | PathConstraints::Train => unreachable!(),
+            PathConstraints::Bus | PathConstraints::Train | PathConstraints::Truck => {
+                unreachable!()
+            }
         };
         Some((req_cost, all_costs))
     }
 
     // TODO Consider returning the walking-only path in the failure case, to avoid wasting work
+    /// If there's a walking profile, only sidewalks and crossings it can use are considered.
     pub fn should_use_transit(
         &self,
         map: &Map,
         start: Position,
         end: Position,
+        profile: Option<&WalkingProfile>,
     ) -> Option<(TransitStopID, Option<TransitStopID>, TransitRouteID)> {
-        self.walking_with_transit_graph
-            .should_use_transit(map, start, end)
+        let profile = match profile {
+            Some(profile) => profile,
+            None => {
+                return self
+                    .walking_with_transit_graph
+                    .should_use_transit(map, start, end);
+            }
+        };
+        let graphs = self
+            .transit_graphs_per_profile
+            .get_or(|| RefCell::new(VecMap::new()));
+        if let Some(graph) = graphs.borrow().get(profile) {
+            return graph.should_use_transit(map, start, end);
+        }
+        let mut params = self.params.clone();
+        params.walking_profile = Some(profile.clone());
+        let graph = SidewalkPathfinder::new(
+            map,
+            Some((&self.bus_graph, &self.train_graph)),
+            &params,
+            &CreateEngine::Dijkstra,
+        );
+        let result = graph.should_use_transit(map, start, end);
+        graphs.borrow_mut().push(profile.clone(), graph);
+        result
+    }
+
+    /// Considers driving from `start` to a parking lot near a transit stop, then using transit to
+    /// reach the sidewalk position `end`. Of all the lots that work, picks the one with the fastest
+    /// drive.
+    pub fn should_park_and_ride(
+        &self,
+        map: &Map,
+        start: Position,
+        end: Position,
+        profile: Option<&WalkingProfile>,
+    ) -> Option<(
+        ParkingLotID,
+        TransitStopID,
+        Option<TransitStopID>,
+        TransitRouteID,
+    )> {
+        let mut best = None;
+        let lots = self
+            .park_and_ride_lots
+            .get_or(|| find_park_and_ride_lots(map));
+        for (lot, nearby_stops) in lots {
+            let lot = map.get_pl(*lot);
+            // Transit pathfinding is expensive, so only do it for lots near some stop
+            let (stop1, maybe_stop2, route) =
+                match self.should_use_transit(map, lot.sidewalk_pos, end, profile) {
+                    Some(x) => x,
+                    None => continue,
+                };
+            if !nearby_stops.contains(&stop1) {
+                continue;
+            }
+            let cost = match self.pathfind(
+                PathRequest::vehicle(start, lot.driving_pos, PathConstraints::Car),
+                map,
+            ) {
+                Some(path) => path.get_cost(),
+                None => continue,
+            };
+            if best.as_ref().map(|(c, _)| cost < *c).unwrap_or(true) {
+                best = Some((cost, (lot.id, stop1, maybe_stop2, route)));
+            }
+        }
+        best.map(|(_, result)| result)
     }
 
     pub(crate) fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
+        self.park_and_ride_lots = ThreadLocal::new();
+        self.transit_graphs_per_profile = ThreadLocal::new();
+
         timer.start("apply edits to car pathfinding");
         self.car_graph.apply_edits(map);
         timer.stop("apply edits to car pathfinding");
@@ -325,6 +530,28 @@ impl Pathfinder {
     }
 }
 
+/// Pairs every parking lot with the transit stops close enough to walk to, skipping lots with none.
+fn find_park_and_ride_lots(map: &Map) -> Vec<(ParkingLotID, Vec<TransitStopID>)> {
+    let stops: Vec<(TransitStopID, Pt2D)> = map
+        .all_transit_stops()
+        .values()
+        .map(|ts| (ts.id, ts.sidewalk_pos.pt(map)))
+        .collect();
+    let mut lots = Vec::new();
+    for lot in map.all_parking_lots() {
+        let lot_pt = lot.sidewalk_pos.pt(map);
+        let nearby: Vec<TransitStopID> = stops
+            .iter()
+            .filter(|(_, pt)| pt.dist_to(lot_pt) <= MAX_PARK_AND_RIDE_WALK)
+            .map(|(id, _)| *id)
+            .collect();
+        if !nearby.is_empty() {
+            lots.push((lot.id, nearby));
+        }
+    }
+    lots
+}
+
 /// For callers needing to request paths with a variety of RoutingParams. The caller is in charge
 /// of the lifetime, so they can clear it out when appropriate.
 pub struct PathfinderCache {
@@ -0,0 +1,245 @@
+//! Pathfinding for vehicles where the cost of crossing a road depends on the time of day.
+//!
+//! Contraction hierarchies are built for fixed edge weights, so they can't answer these queries.
+//! Instead this runs Dijkstra's algorithm directly over roads, tracking the time each road is
+//! entered. Like the regular vehicle graph, uber-turns through clusters of intersections are
+//! crossed as one step. When a profile doesn't change any costs, the regular pathfinder (and its
+//! contraction hierarchy) is used instead, so the results match.
+//!
+//! The same search is used to route around current congestion, where known delays are added to
+//! the ideal cost of each road.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, MultiMap, Timer};
+use geom::{Duration, Time};
+
+use crate::pathfind::uber_turns::UberTurnV2;
+use crate::pathfind::{round, unround, vehicle_cost};
+use crate::{DirectedRoadID, Map, PathRequest, PathV2, RoutingParams};
+
+/// How much slower than the ideal it is to cross roads at different times of the day.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TravelTimeProfile {
+    /// For each directed road, a multiplier for every hour of the day. This applies to the ideal
+    /// cost of crossing the road and making a movement at the end. Missing roads or hours use 1.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub per_road: BTreeMap<DirectedRoadID, Vec<f64>>,
+}
+
+impl TravelTimeProfile {
+    pub fn load(path: String, timer: &mut Timer) -> Result<TravelTimeProfile> {
+        abstio::maybe_read_json(path, timer)
+    }
+
+    /// The multiplier for entering a road at some time. Times after midnight wrap around to the
+    /// start of the day.
+    pub fn multiplier(&self, dr: DirectedRoadID, time: Time) -> f64 {
+        self.per_road
+            .get(&dr)
+            .and_then(|hours| hours.get(time.get_hours() % 24))
+            .cloned()
+            .unwrap_or(1.0)
+    }
+
+    /// True if no costs change.
+    pub fn is_flat(&self) -> bool {
+        self.per_road
+            .values()
+            .all(|hours| hours.iter().all(|x| (*x - 1.0).abs() < f64::EPSILON))
+    }
+}
+
+/// Finds the fastest path for a vehicle departing at some time. Callers should use the regular
+/// pathfinder for flat profiles and pedestrians.
+pub(crate) fn pathfind(
+    req: PathRequest,
+    params: &RoutingParams,
+    departure: Time,
+    profile: &TravelTimeProfile,
+    uber_turns: &[UberTurnV2],
+    map: &Map,
+) -> Option<PathV2> {
+    dijkstra(req, params, uber_turns, map, |dr, elapsed, ideal| {
+        profile.multiplier(dr, departure + elapsed) * ideal
+    })
+}
+
+/// Finds the fastest path for a vehicle, adding some extra delay to cross each road. Roads missing
+/// from `delays` take their ideal time.
+pub(crate) fn pathfind_with_delays(
+    req: PathRequest,
+    params: &RoutingParams,
+    delays: &HashMap<DirectedRoadID, Duration>,
+    uber_turns: &[UberTurnV2],
+    map: &Map,
+) -> Option<PathV2> {
+    dijkstra(req, params, uber_turns, map, |dr, _, ideal| {
+        ideal + delays.get(&dr).cloned().unwrap_or(Duration::ZERO)
+    })
+}
+
+/// `cost` is given a road, the time spent reaching it, and the ideal cost of crossing it and
+/// making one movement at the end. It returns the actual cost.
+fn dijkstra<F: Fn(DirectedRoadID, Duration, Duration) -> Duration>(
+    req: PathRequest,
+    params: &RoutingParams,
+    uber_turns: &[UberTurnV2],
+    map: &Map,
+    cost: F,
+) -> Option<PathV2> {
+    let constraints = req.constraints;
+    let start = map.get_l(req.start.lane()).get_directed_parent();
+    let end = map.get_l(req.end.lane()).get_directed_parent();
+
+    // Matching the vehicle graph, from some roads, the only way forward is through an uber-turn
+    let mut uber_turn_entrances: MultiMap<DirectedRoadID, usize> = MultiMap::new();
+    for (idx, ut) in uber_turns.iter().enumerate() {
+        if ut
+            .path
+            .iter()
+            .all(|mvmnt| !mvmnt.to.lanes(constraints, map).is_empty())
+        {
+            uber_turn_entrances.insert(ut.entry(), idx);
+        }
+    }
+
+    let mut cost_so_far: HashMap<DirectedRoadID, usize> = HashMap::new();
+    // How each road was reached, and the uber-turn used to get there
+    let mut prev: HashMap<DirectedRoadID, (DirectedRoadID, Option<usize>)> = HashMap::new();
+    let mut queue: BinaryHeap<Reverse<(usize, DirectedRoadID)>> = BinaryHeap::new();
+    cost_so_far.insert(start, 0);
+    queue.push(Reverse((0, start)));
+    if let Some((pos, cost)) = req.alt_start {
+        let alt = map.get_l(pos.lane()).get_directed_parent();
+        let cost = round(cost);
+        if cost < *cost_so_far.get(&alt).unwrap_or(&usize::MAX) {
+            cost_so_far.insert(alt, cost);
+            queue.push(Reverse((cost, alt)));
+        }
+    }
+
+    while let Some(Reverse((so_far, dr))) = queue.pop() {
+        if so_far > cost_so_far[&dr] {
+            continue;
+        }
+        if dr == end {
+            let mut roads = vec![end];
+            let mut used_uber_turns = Vec::new();
+            let mut current = end;
+            while let Some((before, uber_turn)) = prev.get(&current) {
+                if let Some(idx) = uber_turn {
+                    // Walking backwards, add the roads inside the uber-turn
+                    for mvmnt in uber_turns[*idx].path.iter().rev().skip(1) {
+                        roads.push(mvmnt.to);
+                    }
+                    used_uber_turns.push(uber_turns[*idx].clone());
+                }
+                roads.push(*before);
+                current = *before;
+            }
+            roads.reverse();
+            used_uber_turns.reverse();
+            return Some(PathV2::from_roads(
+                roads,
+                req,
+                unround(so_far),
+                used_uber_turns,
+                map,
+            ));
+        }
+        if dr.lanes(constraints, map).is_empty()
+            || (!params.only_use_roads.is_empty() && !params.only_use_roads.contains(&dr.road))
+        {
+            continue;
+        }
+
+        let mut visit = |to: DirectedRoadID, next_cost: usize, uber_turn: Option<usize>| {
+            if next_cost < *cost_so_far.get(&to).unwrap_or(&usize::MAX) {
+                cost_so_far.insert(to, next_cost);
+                prev.insert(to, (dr, uber_turn));
+                queue.push(Reverse((next_cost, to)));
+            }
+        };
+
+        let indices = uber_turn_entrances.get(dr);
+        if indices.is_empty() {
+            for mvmnt in map.get_movements_for(dr, constraints) {
+                if let Some(ideal) = vehicle_cost(dr, mvmnt, constraints, params, map) {
+                    visit(
+                        mvmnt.to,
+                        so_far + round(cost(dr, unround(so_far), ideal)),
+                        None,
+                    );
+                }
+            }
+        } else {
+            for idx in indices {
+                // Cross every road in the uber-turn, or none of them
+                let mut next_cost = Some(so_far);
+                for mvmnt in &uber_turns[*idx].path {
+                    next_cost = next_cost.and_then(|total| {
+                        let ideal = vehicle_cost(mvmnt.from, *mvmnt, constraints, params, map)?;
+                        Some(total + round(cost(mvmnt.from, unround(total), ideal)))
+                    });
+                }
+                if let Some(next_cost) = next_cost {
+                    // The vehicle graph charges 1 more to leave an uber-turn node
+                    visit(uber_turns[*idx].exit(), next_cost + 1, Some(*idx));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Direction, RoadID};
+
+    #[test]
+    fn test_multiplier() {
+        let dr = DirectedRoadID {
+            road: RoadID(0),
+            dir: Direction::Fwd,
+        };
+        let mut profile = TravelTimeProfile::default();
+        assert!(profile.is_flat());
+
+        let mut hours = vec![1.0; 24];
+        hours[17] = 2.5;
+        profile.per_road.insert(dr, hours);
+        assert!(!profile.is_flat());
+        assert_eq!(
+            2.5,
+            profile.multiplier(dr, Time::START_OF_DAY + Duration::hours(17))
+        );
+        assert_eq!(
+            1.0,
+            profile.multiplier(dr, Time::START_OF_DAY + Duration::hours(3))
+        );
+        // The next day wraps around
+        assert_eq!(
+            2.5,
+            profile.multiplier(dr, Time::START_OF_DAY + Duration::hours(41))
+        );
+        // Roads without a profile aren't affected
+        let other = DirectedRoadID {
+            road: RoadID(1),
+            dir: Direction::Back,
+        };
+        assert_eq!(
+            1.0,
+            profile.multiplier(other, Time::START_OF_DAY + Duration::hours(17))
+        );
+    }
+}
@@ -143,6 +143,36 @@ impl PathStep {
             ),
         }
     }
+
+    /// Like `max_speed_and_incline_along`, but for e-scooters and e-bikes. Returns (speed, percent
+    /// incline).
+    pub fn max_scooter_speed_and_incline_along(
+        &self,
+        max_speed_on_flat_ground: Speed,
+        map: &Map,
+    ) -> (Speed, f64) {
+        match self {
+            PathStep::Lane(l) => Traversable::max_scooter_speed_along_road(
+                map.get_l(*l).get_directed_parent(),
+                max_speed_on_flat_ground,
+                map,
+            ),
+            PathStep::ContraflowLane(l) => Traversable::max_scooter_speed_along_road(
+                {
+                    let mut dr = map.get_l(*l).get_directed_parent();
+                    dr.dir = dr.dir.opposite();
+                    dr
+                },
+                max_speed_on_flat_ground,
+                map,
+            ),
+            PathStep::Turn(_) | PathStep::ContraflowTurn(_) => self.max_speed_and_incline_along(
+                Some(max_speed_on_flat_ground),
+                PathConstraints::Bike,
+                map,
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -350,6 +380,69 @@ impl Path {
         }
     }
 
+    /// Replaces everything after the current step with a different route to the same end. The new
+    /// path must start somewhere along the road of the current lane. Fails if the current step
+    /// isn't a lane, this path is in the middle of an uber-turn, or the new path can't be joined.
+    pub fn reroute(&mut self, new: Path, map: &Map) -> Result<()> {
+        if self.currently_inside_ut.is_some() {
+            bail!("can't reroute in the middle of an uber-turn");
+        }
+        let current = match self.steps[0] {
+            PathStep::Lane(l) => l,
+            step => bail!("can't reroute from {:?}", step),
+        };
+        if new.steps.back() != self.steps.back() {
+            bail!(
+                "new path ends at {:?}, not {:?}",
+                new.steps.back(),
+                self.steps.back()
+            );
+        }
+
+        let mut steps = new.steps;
+        let mut uber_turns = new.uber_turns;
+        match steps.pop_front() {
+            Some(PathStep::Lane(l)) if l == current => {}
+            Some(PathStep::Lane(l))
+                if map.get_l(l).get_directed_parent()
+                    == map.get_l(current).get_directed_parent() =>
+            {
+                // The new path starts from a different lane on the same road, so make the first
+                // turn from the current lane instead
+                let orig_turn = match steps.front() {
+                    Some(PathStep::Turn(t)) => *t,
+                    _ => bail!("new path doesn't leave {}", l),
+                };
+                let turn = TurnID {
+                    parent: orig_turn.parent,
+                    src: current,
+                    dst: orig_turn.dst,
+                };
+                if map.maybe_get_t(turn).is_none() {
+                    bail!("{} can't be used to make {}", current, orig_turn);
+                }
+                steps[0] = PathStep::Turn(turn);
+                if let Some(ut) = uber_turns.front_mut() {
+                    if ut.path[0] == orig_turn {
+                        ut.path[0] = turn;
+                    }
+                }
+            }
+            step => bail!("new path starts at {:?}, not {}", step, current),
+        }
+
+        let old_steps: Vec<PathStep> = self.steps.drain(1..).collect();
+        for step in &old_steps {
+            self.total_length -= self.dist_crossed_from_step(map, step);
+        }
+        for step in &steps {
+            self.total_length += self.dist_crossed_from_step(map, step);
+        }
+        self.steps.extend(steps);
+        self.uber_turns = uber_turns;
+        Ok(())
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -612,7 +705,7 @@ impl PathRequest {
         let (start, end) = match constraints {
             PathConstraints::Pedestrian => (from.sidewalk_pos, to.sidewalk_pos),
             PathConstraints::Bike => (from.biking_connection(map)?.0, to.biking_connection(map)?.0),
-            PathConstraints::Car => (
+            PathConstraints::Car | PathConstraints::Truck => (
                 from.driving_connection(map)?.0,
                 to.driving_connection(map)?.0,
             ),
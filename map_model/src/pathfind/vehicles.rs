@@ -1,6 +1,6 @@
-//! Pathfinding for cars, bikes, buses, and trains using contraction hierarchies
+//! Pathfinding for cars, bikes, buses, trains, and trucks using contraction hierarchies
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use fast_paths::InputGraph;
 use serde::{Deserialize, Serialize};
@@ -15,7 +15,7 @@ use crate::pathfind::zone_cost;
 use crate::pathfind::{round, unround};
 use crate::{
     osm, DirectedRoadID, Direction, LaneType, Map, MovementID, PathConstraints, PathRequest,
-    PathV2, Position, RoutingParams, Traversable,
+    PathV2, Position, RoutingParams, Traversable, TurnType,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -45,11 +45,27 @@ impl VehiclePathfinder {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        matches!(self.engine, PathfindEngine::Empty)
+    }
+
     pub fn new(
         map: &Map,
         constraints: PathConstraints,
         params: &RoutingParams,
         engine: &CreateEngine,
+    ) -> VehiclePathfinder {
+        VehiclePathfinder::new_with_observed_costs(map, constraints, params, None, engine)
+    }
+
+    /// Like `new`, but uses observed travel times for some movements, instead of the ideal cost.
+    /// Applying edits later reverts to ideal costs everywhere.
+    pub fn new_with_observed_costs(
+        map: &Map,
+        constraints: PathConstraints,
+        params: &RoutingParams,
+        observed: Option<&BTreeMap<MovementID, Duration>>,
+        engine: &CreateEngine,
     ) -> VehiclePathfinder {
         // Insert every road as a node.
         let mut nodes = NodeMap::new();
@@ -75,7 +91,7 @@ impl VehiclePathfinder {
             }
         }
 
-        let input_graph = make_input_graph(constraints, &nodes, &uber_turns, params, map);
+        let input_graph = make_input_graph(constraints, &nodes, &uber_turns, params, observed, map);
         let engine = engine.create(input_graph);
 
         VehiclePathfinder {
@@ -88,7 +104,7 @@ impl VehiclePathfinder {
     }
 
     pub fn pathfind(&self, req: PathRequest, map: &Map) -> Option<PathV2> {
-        if matches!(self.engine, PathfindEngine::Empty) {
+        if self.is_empty() {
             return None;
         }
 
@@ -137,6 +153,39 @@ impl VehiclePathfinder {
         Some(PathV2::from_roads(road_steps, req, cost, uber_turns, map))
     }
 
+    /// Builds a path along a known sequence of roads, such as one a vehicle actually took, instead
+    /// of searching for one. Any uber-turns crossed along the way are included.
+    pub fn path_along_roads(
+        &self,
+        roads: Vec<DirectedRoadID>,
+        req: PathRequest,
+        cost: Duration,
+        map: &Map,
+    ) -> PathV2 {
+        let mut uber_turns = Vec::new();
+        let mut idx = 0;
+        while idx + 1 < roads.len() {
+            let found = self.uber_turns.iter().find(|ut| {
+                idx + ut.path.len() < roads.len()
+                    && ut.path.iter().enumerate().all(|(i, mvmnt)| {
+                        mvmnt.from == roads[idx + i] && mvmnt.to == roads[idx + i + 1]
+                    })
+            });
+            if let Some(ut) = found {
+                idx += ut.path.len();
+                uber_turns.push(ut.clone());
+            } else {
+                idx += 1;
+            }
+        }
+        PathV2::from_roads(roads, req, cost, uber_turns, map)
+    }
+
+    /// Every uber-turn in the map. These don't depend on the vehicle type.
+    pub(crate) fn uber_turns(&self) -> &[UberTurnV2] {
+        &self.uber_turns
+    }
+
     pub fn apply_edits(&mut self, map: &Map) {
         if matches!(self.engine, PathfindEngine::Empty) {
             return;
@@ -151,6 +200,7 @@ impl VehiclePathfinder {
             &self.nodes,
             &self.uber_turns,
             &self.params,
+            None,
             map,
         );
         let engine = self.engine.reuse_ordering().create(input_graph);
@@ -174,6 +224,7 @@ impl VehiclePathfinder {
                 &self.nodes,
                 &self.uber_turns,
                 &self.params,
+                None,
                 map,
             );
             CreateEngine::Dijkstra
@@ -198,10 +249,18 @@ fn make_input_graph(
     nodes: &NodeMap<Node>,
     uber_turns: &[UberTurnV2],
     params: &RoutingParams,
+    observed: Option<&BTreeMap<MovementID, Duration>>,
     map: &Map,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
 
+    // Observed travel times replace the ideal time to cross a road, but the other routing costs
+    // still apply, and movements that aren't allowed stay that way.
+    let cost = |mvmnt: MovementID| match observed.and_then(|costs| costs.get(&mvmnt)) {
+        Some(time) => observed_vehicle_cost(mvmnt, *time, constraints, params, map),
+        None => vehicle_cost(mvmnt.from, mvmnt, constraints, params, map),
+    };
+
     // From some roads, instead of adding edges to movements, add edges to these (indexed)
     // uber-turns.
     let mut uber_turn_entrances: MultiMap<DirectedRoadID, usize> = MultiMap::new();
@@ -239,9 +298,7 @@ fn make_input_graph(
                 let indices = uber_turn_entrances.get(dr);
                 if indices.is_empty() {
                     for mvmnt in map.get_movements_for(dr, constraints) {
-                        if let Some(cost) =
-                            vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
-                        {
+                        if let Some(cost) = cost(mvmnt) {
                             input_graph.add_edge(
                                 from,
                                 nodes.get(Node::Road(mvmnt.to)),
@@ -256,9 +313,7 @@ fn make_input_graph(
                         let mut sum_cost = Duration::ZERO;
                         let mut ok = true;
                         for mvmnt in &ut.path {
-                            if let Some(cost) =
-                                vehicle_cost(mvmnt.from, *mvmnt, constraints, params, map)
-                            {
+                            if let Some(cost) = cost(*mvmnt) {
                                 sum_cost += cost;
                             } else {
                                 // A vehicle isn't allowed to do one of the steps inside the
@@ -301,6 +356,29 @@ pub fn vehicle_cost(
     constraints: PathConstraints,
     params: &RoutingParams,
     map: &Map,
+) -> Option<Duration> {
+    vehicle_cost_with_time(dr, mvmnt, constraints, params, None, map)
+}
+
+/// Like `vehicle_cost`, but uses an observed time to cross the road and make the movement, such as
+/// one measured in a congested simulation. Penalties from the routing params and zones still apply.
+pub fn observed_vehicle_cost(
+    mvmnt: MovementID,
+    observed: Duration,
+    constraints: PathConstraints,
+    params: &RoutingParams,
+    map: &Map,
+) -> Option<Duration> {
+    vehicle_cost_with_time(mvmnt.from, mvmnt, constraints, params, Some(observed), map)
+}
+
+fn vehicle_cost_with_time(
+    dr: DirectedRoadID,
+    mvmnt: MovementID,
+    constraints: PathConstraints,
+    params: &RoutingParams,
+    observed: Option<Duration>,
+    map: &Map,
 ) -> Option<Duration> {
     if params.avoid_roads.contains(&dr.road)
         || params
@@ -312,18 +390,26 @@ pub fn vehicle_cost(
 
     let road = map.get_r(dr.road);
     let movement = &map.get_i(mvmnt.parent).movements[&mvmnt];
+    // Large vehicles can't turn around in the street
+    if constraints == PathConstraints::Truck && movement.turn_type == TurnType::UTurn {
+        return None;
+    }
     let max_speed = match constraints {
         PathConstraints::Car | PathConstraints::Bus | PathConstraints::Train => None,
         PathConstraints::Bike => Some(crate::MAX_BIKE_SPEED),
+        PathConstraints::Truck => Some(crate::MAX_TRUCK_SPEED),
         PathConstraints::Pedestrian => unreachable!(),
     };
-    let t1 = road.length() / Traversable::max_speed_along_road(dr, max_speed, constraints, map).0;
-
-    let t2 = movement.geom.length()
-        / Traversable::max_speed_along_movement(mvmnt, max_speed, constraints, map);
+    let time = observed.unwrap_or_else(|| {
+        let t1 =
+            road.length() / Traversable::max_speed_along_road(dr, max_speed, constraints, map).0;
+        let t2 = movement.geom.length()
+            / Traversable::max_speed_along_movement(mvmnt, max_speed, constraints, map);
+        t1 + t2
+    });
 
     let base = match constraints {
-        PathConstraints::Car | PathConstraints::Train => t1 + t2,
+        PathConstraints::Car | PathConstraints::Train | PathConstraints::Truck => time,
         PathConstraints::Bike => {
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
@@ -337,7 +423,7 @@ pub fn vehicle_cost(
                 params.driving_lane_penalty
             };
 
-            lt_penalty * (t1 + t2)
+            lt_penalty * time
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
@@ -346,7 +432,7 @@ pub fn vehicle_cost(
             } else {
                 1.1
             };
-            lt_penalty * (t1 + t2)
+            lt_penalty * time
         }
         PathConstraints::Pedestrian => unreachable!(),
     };
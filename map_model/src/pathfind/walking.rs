@@ -11,10 +11,10 @@ use crate::pathfind::engine::{CreateEngine, PathfindEngine};
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::zone_cost;
-use crate::pathfind::{round, unround};
+use crate::pathfind::{round, unround, WalkingProfile};
 use crate::{
     DirectedRoadID, IntersectionID, Map, PathConstraints, PathRequest, PathStep, PathStepV2,
-    PathV2, Position, TransitRoute, TransitRouteID, TransitStopID, TurnType,
+    PathV2, Position, RoutingParams, TransitRoute, TransitRouteID, TransitStopID, TurnType,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -22,6 +22,7 @@ pub struct SidewalkPathfinder {
     #[serde(deserialize_with = "deserialize_nodemap")]
     nodes: NodeMap<WalkingNode>,
     use_transit: bool,
+    profile: Option<WalkingProfile>,
     engine: PathfindEngine,
 }
 
@@ -59,6 +60,7 @@ impl SidewalkPathfinder {
         SidewalkPathfinder {
             nodes: NodeMap::new(),
             use_transit: false,
+            profile: None,
             engine: PathfindEngine::Empty,
         }
     }
@@ -66,6 +68,7 @@ impl SidewalkPathfinder {
     pub fn new(
         map: &Map,
         use_transit: Option<(&VehiclePathfinder, &VehiclePathfinder)>,
+        params: &RoutingParams,
         engine: &CreateEngine,
     ) -> SidewalkPathfinder {
         let mut nodes = NodeMap::new();
@@ -89,12 +92,14 @@ impl SidewalkPathfinder {
             }
         }
 
-        let input_graph = make_input_graph(&nodes, use_transit, map);
+        let profile = params.walking_profile.clone();
+        let input_graph = make_input_graph(&nodes, use_transit, profile.as_ref(), map);
         let engine = engine.create(input_graph);
 
         SidewalkPathfinder {
             nodes,
             use_transit: use_transit.is_some(),
+            profile,
             engine,
         }
     }
@@ -108,7 +113,7 @@ impl SidewalkPathfinder {
             return;
         }
 
-        let input_graph = make_input_graph(&self.nodes, use_transit, map);
+        let input_graph = make_input_graph(&self.nodes, use_transit, self.profile.as_ref(), map);
         let engine = self.engine.reuse_ordering().create(input_graph);
         self.engine = engine;
     }
@@ -118,6 +123,9 @@ impl SidewalkPathfinder {
             return None;
         }
 
+        if !self.endpoints_usable(req.start, req.end, map) {
+            return None;
+        }
         if req.start.lane() == req.end.lane() {
             return Some(one_step_walking_path(req, map));
         }
@@ -134,6 +142,17 @@ impl SidewalkPathfinder {
         Some(PathV2::new(map, steps, req, cost, Vec::new()))
     }
 
+    /// The graph leaves out sidewalks the walking profile can't use, but a route still starts and
+    /// ends somewhere along a sidewalk.
+    fn endpoints_usable(&self, start: Position, end: Position, map: &Map) -> bool {
+        match self.profile {
+            Some(ref profile) => [start, end]
+                .into_iter()
+                .all(|pos| profile.lane_barrier(map.get_l(pos.lane()), map).is_none()),
+            None => true,
+        }
+    }
+
     /// Attempt the pathfinding and see if we should ride public transit. If so, says (stop1,
     /// optional stop 2, route). If there's no stop 2, then ride transit off the border.
     pub fn should_use_transit(
@@ -147,6 +166,9 @@ impl SidewalkPathfinder {
         }
 
         assert!(self.use_transit);
+        if !self.endpoints_usable(start, end, map) {
+            return None;
+        }
 
         let (_, raw_nodes) = self.engine.calculate_path(
             self.nodes.get(WalkingNode::closest(start, map)),
@@ -241,7 +263,7 @@ impl SidewalkPathfinder {
             self.engine.all_costs_from(start)
         } else {
             // The CH engine doesn't support this!
-            let input_graph = make_input_graph(&self.nodes, None, map);
+            let input_graph = make_input_graph(&self.nodes, None, self.profile.as_ref(), map);
             CreateEngine::Dijkstra
                 .create(input_graph)
                 .all_costs_from(start)
@@ -264,13 +286,18 @@ impl SidewalkPathfinder {
 fn make_input_graph(
     nodes: &NodeMap<WalkingNode>,
     use_transit: Option<(&VehiclePathfinder, &VehiclePathfinder)>,
+    profile: Option<&WalkingProfile>,
     map: &Map,
 ) -> InputGraph {
     let max_speed = Some(crate::MAX_WALKING_SPEED);
     let mut input_graph = InputGraph::new();
 
     for l in map.all_lanes() {
-        if l.is_walkable() {
+        if l.is_walkable()
+            && profile
+                .map(|p| p.lane_barrier(l, map).is_none())
+                .unwrap_or(true)
+        {
             // Sidewalks can be crossed in two directions. When there's a steep incline, of course
             // it flips.
             let n1 = nodes.get(WalkingNode::SidewalkEndpoint(
@@ -295,7 +322,11 @@ fn make_input_graph(
     }
 
     for t in map.all_turns() {
-        if t.between_sidewalks() {
+        if t.between_sidewalks()
+            && profile
+                .map(|p| p.turn_barrier(t, map).is_none())
+                .unwrap_or(true)
+        {
             let src = map.get_l(t.id.src);
             let dst = map.get_l(t.id.dst);
             let from = nodes.get(WalkingNode::SidewalkEndpoint(
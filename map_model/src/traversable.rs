@@ -216,8 +216,10 @@ impl Traversable {
             // We assume every pedestrian has a max_speed defined.
             walking_speed_on_incline(max_speed_on_flat_ground.unwrap(), percent_incline)
         } else {
-            debug_assert!(max_speed_on_flat_ground.is_none());
-            // Incline doesn't affect cars, buses, or trains
+            debug_assert!(
+                max_speed_on_flat_ground.is_none() || constraints == PathConstraints::Truck
+            );
+            // Incline doesn't affect cars, buses, trains, or trucks
             road.speed_limit
         };
 
@@ -229,6 +231,24 @@ impl Traversable {
         (speed, percent_incline)
     }
 
+    /// Like `max_speed_along_road`, but for e-scooters and e-bikes. They're routed like bikes, but
+    /// their motor cares much less about hills. Returns (speed, percent incline).
+    pub(crate) fn max_scooter_speed_along_road(
+        dr: DirectedRoadID,
+        max_speed_on_flat_ground: Speed,
+        map: &Map,
+    ) -> (Speed, f64) {
+        let road = map.get_r(dr.road);
+        let percent_incline = if dr.dir == Direction::Fwd {
+            road.percent_incline
+        } else {
+            -road.percent_incline
+        };
+        let speed = scooter_speed_on_incline(max_speed_on_flat_ground, percent_incline)
+            .min(road.speed_limit);
+        (speed, percent_incline)
+    }
+
     /// The single definitive place to determine how fast somebody could go along a single
     /// movement. This should be used for pathfinding and simulation. Ignores elevation.
     pub(crate) fn max_speed_along_movement(
@@ -254,6 +274,10 @@ impl Traversable {
 pub const MAX_BIKE_SPEED: Speed = Speed::const_meters_per_second(4.4704);
 // 3 mph
 pub const MAX_WALKING_SPEED: Speed = Speed::const_meters_per_second(1.34112);
+// 20 kph, the usual cap for shared e-scooters
+pub const MAX_SCOOTER_SPEED: Speed = Speed::const_meters_per_second(5.5556);
+// 90 kph, a common limit for heavy goods vehicles
+pub const MAX_TRUCK_SPEED: Speed = Speed::const_meters_per_second(25.0);
 
 fn bike_speed_on_incline(max_speed: Speed, percent_incline: f64) -> Speed {
     // There doesn't seem to be a straightforward way of calculating how an "average" cyclist's
@@ -298,6 +322,17 @@ fn bike_speed_on_incline(max_speed: Speed, percent_incline: f64) -> Speed {
     0.3 * max_speed
 }
 
+fn scooter_speed_on_incline(max_speed: Speed, percent_incline: f64) -> Speed {
+    // Shared e-scooters and e-bikes have a governor, so they don't go any faster downhill. The
+    // motor handles gentle climbs, but steep ones still slow riders down. There's little data to
+    // go on, so this just flattens out the shape of the bike table.
+    let pct = percent_incline * 100.0;
+    if pct <= 3.0 {
+        return max_speed;
+    }
+    (1.0 - 0.04 * (pct - 3.0)).max(0.5) * max_speed
+}
+
 fn walking_speed_on_incline(max_speed: Speed, percent_incline: f64) -> Speed {
     // https://en.wikipedia.org/wiki/Tobler%27s_hiking_function
     let exp = -3.5 * (percent_incline + 0.05).abs();
@@ -337,6 +372,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_scooter_speed_on_incline() {
+        let base_speed = MAX_SCOOTER_SPEED;
+        assert_approx_eq(base_speed, scooter_speed_on_incline(base_speed, 0.0));
+        assert_approx_eq(base_speed, scooter_speed_on_incline(base_speed, -0.15));
+        assert_approx_eq(0.6 * base_speed, scooter_speed_on_incline(base_speed, 0.13));
+        // Much less sensitive than a bike
+        assert!(
+            scooter_speed_on_incline(base_speed, 0.08) / base_speed
+                > bike_speed_on_incline(MAX_BIKE_SPEED, 0.08) / MAX_BIKE_SPEED
+        );
+    }
+
     #[test]
     fn test_walking_speed_on_incline() {
         let base_speed = MAX_WALKING_SPEED;
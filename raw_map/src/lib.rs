@@ -13,7 +13,7 @@ use abstutil::{
     deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
     Tags,
 };
-use geom::{Distance, PolyLine, Polygon, Pt2D};
+use geom::{Distance, PolyLine, Polygon, Pt2D, Time};
 
 pub use self::types::{Amenity, AmenityType, AreaType};
 
@@ -136,12 +136,16 @@ pub struct RawTransitRoute {
     /// Entries into transit_stops
     pub stops: Vec<String>,
     pub route_type: RawTransitType,
-    // TODO Schedule
+    /// Every trip running on the imported service day, ordered by departure. Each trip lists the
+    /// scheduled arrival at every entry in `stops`. Empty if the feed has no usable timetable.
+    pub schedule: Vec<Vec<Time>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RawTransitType {
     Bus,
+    /// Trams and streetcars run on the street, mixed with other traffic
+    Tram,
     Train,
 }
 
@@ -176,6 +180,8 @@ pub struct ExtraRoadData {
     pub barrier_nodes: Vec<Pt2D>,
     /// Crossing nodes along this road's original center line.
     pub crossing_nodes: Vec<(Pt2D, CrossingType)>,
+    /// The height of the kerb at crossings along this road's original center line, where tagged.
+    pub kerb_nodes: Vec<(Pt2D, Distance)>,
 }
 
 impl ExtraRoadData {
@@ -187,6 +193,7 @@ impl ExtraRoadData {
             crosswalk_backward: true,
             barrier_nodes: Vec::new(),
             crossing_nodes: Vec::new(),
+            kerb_nodes: Vec::new(),
         }
     }
 }
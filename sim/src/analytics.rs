@@ -4,14 +4,19 @@ use std::fmt::Write;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Counter;
-use geom::{Duration, Pt2D, Time};
+use geom::{Distance, Duration, Pt2D, Time};
+use map_model::connectivity::vehicle_cost;
 use map_model::{
-    CompressedMovementID, IntersectionID, LaneID, Map, MovementID, ParkingLotID, Path, PathRequest,
-    RoadID, TransitRouteID, TransitStopID, Traversable, TurnID,
+    CompressedMovementID, DirectedRoadID, IntersectionID, LaneID, Map, MovementID, ParkingLotID,
+    Path, PathConstraints, PathRequest, Position, RoadID, TransitRouteID, TransitStopID,
+    TravelTimeProfile, Traversable, TurnID,
 };
 use synthpop::TripMode;
 
-use crate::{AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripPhaseType};
+use crate::{
+    AgentID, AgentType, AlertLocation, CarID, EmissionsTracker, Event, ParkingSpot, TripID,
+    TripPhaseType, VehicleType,
+};
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
 /// organizing and storing some information from them. The UI queries Analytics to draw time-series
@@ -39,6 +44,13 @@ pub struct Analytics {
     /// For each passenger boarding, how long did they wait at the stop?
     pub passengers_boarding: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID, Duration)>>,
     pub passengers_alighting: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID)>>,
+    /// Every time somebody couldn't board a full vehicle
+    pub passengers_left_behind: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID)>>,
+    /// Per route, every time a vehicle leaves a stop, how many passengers are on board for the
+    /// segment to the next stop, and how many fit
+    pub transit_loads: BTreeMap<TransitRouteID, Vec<(Time, TransitStopID, usize, usize)>>,
+    /// Per timetabled route, how late (or early, if negative) each vehicle reached each stop
+    pub schedule_deviations: BTreeMap<TransitRouteID, Vec<(Time, TransitStopID, Duration)>>,
 
     pub started_trips: BTreeMap<TripID, Time>,
     /// Finish time, ID, mode, trip duration if successful (or None if cancelled)
@@ -53,10 +65,34 @@ pub struct Analytics {
     // TODO Transit riders aren't represented here yet, just the vehicle they're riding.
     /// Only for traffic signals. The u8 is the movement index from a CompressedMovementID.
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(u8, Time, Duration, AgentType)>>,
+    /// Near-misses between turning vehicles and pedestrians or cyclists at each intersection
+    pub safety_conflicts: BTreeMap<IntersectionID, Vec<(Time, SafetyConflict)>>,
 
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    /// Every time a driver parks near a building, how far did they cruise looking for a spot?
+    /// Also the pricing zone of the spot they found, if any.
+    pub parking_cruising: Vec<(Time, Option<String>, Distance)>,
+    /// Per pricing zone, how many dollars have been paid for parking. Drivers pay when they leave
+    /// a spot. Cars seeded before the simulation started pay from when they were seeded. See
+    /// `Sim::parking_revenue` to include cars still parked.
+    pub parking_revenue: BTreeMap<String, f64>,
+    parked_since: BTreeMap<CarID, Time>,
+
+    /// How long cars really took to cross roads, accounting for congestion.
+    pub travel_times: TravelTimes,
+    /// Estimated air pollution and energy use of vehicles, per road and trip
+    pub emissions: EmissionsTracker,
+
+    /// When and where drivers stuck in congestion switched to a different route
+    pub reroutes: Vec<(Time, TripID, LaneID)>,
+
+    /// Where ride-hailing vehicles stopped at the curb, and how long each rider waited for pickup
+    pub ride_hail_pickups: Vec<(Time, LaneID, Duration)>,
+    pub ride_hail_dropoffs: Vec<(Time, LaneID)>,
+    /// Every time a ride-hailing vehicle reaches a stop, how far it drove and with how many riders
+    pub ride_hail_legs: Vec<(Time, Distance, usize)>,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -76,6 +112,12 @@ pub enum Problem {
     OvertakeDesired(Traversable),
     /// Too many people are crossing the same sidewalk or crosswalk at the same time.
     PedestrianOvercrowding(Traversable),
+    /// A vehicle overtook this cyclist, leaving less than 1.5m of room.
+    ClosePass(Traversable, Distance),
+    /// A pedestrian gave up waiting for WALK and crossed against the signal.
+    Jaywalking(TurnID),
+    /// A pedestrian crossed straight over a road, away from any crosswalk.
+    MidBlockCrossing(Position),
 }
 
 impl Problem {
@@ -86,10 +128,13 @@ impl Problem {
             Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                 map.get_i(*i).polygon.center()
             }
-            Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                on.get_polyline(map).middle()
+            Problem::OvertakeDesired(on)
+            | Problem::PedestrianOvercrowding(on)
+            | Problem::ClosePass(on, _) => on.get_polyline(map).middle(),
+            Problem::ArterialIntersectionCrossing(t) | Problem::Jaywalking(t) => {
+                map.get_t(*t).geom.middle()
             }
-            Problem::ArterialIntersectionCrossing(t) => map.get_t(*t).geom.middle(),
+            Problem::MidBlockCrossing(pos) => pos.pt(map),
         }
     }
 }
@@ -101,6 +146,8 @@ pub enum ProblemType {
     OvertakeDesired,
     ArterialIntersectionCrossing,
     PedestrianOvercrowding,
+    ClosePass,
+    Jaywalking,
 }
 
 impl From<&Problem> for ProblemType {
@@ -111,6 +158,8 @@ impl From<&Problem> for ProblemType {
             Problem::OvertakeDesired(_) => Self::OvertakeDesired,
             Problem::ArterialIntersectionCrossing(_) => Self::ArterialIntersectionCrossing,
             Problem::PedestrianOvercrowding(_) => Self::PedestrianOvercrowding,
+            Problem::ClosePass(_, _) => Self::ClosePass,
+            Problem::Jaywalking(_) | Problem::MidBlockCrossing(_) => Self::Jaywalking,
         }
     }
 }
@@ -133,6 +182,8 @@ impl ProblemType {
             ProblemType::OvertakeDesired,
             ProblemType::ArterialIntersectionCrossing,
             ProblemType::PedestrianOvercrowding,
+            ProblemType::ClosePass,
+            ProblemType::Jaywalking,
         ]
     }
 
@@ -147,6 +198,8 @@ impl ProblemType {
                 "where pedestrians cross arterial intersections"
             }
             ProblemType::PedestrianOvercrowding => "where pedestrians are over-crowded",
+            ProblemType::ClosePass => "where cyclists are passed too closely",
+            ProblemType::Jaywalking => "where pedestrians cross against the signal or mid-block",
         }
     }
 }
@@ -161,18 +214,38 @@ impl Analytics {
             bus_arrivals: Vec::new(),
             passengers_boarding: BTreeMap::new(),
             passengers_alighting: BTreeMap::new(),
+            passengers_left_behind: BTreeMap::new(),
+            transit_loads: BTreeMap::new(),
+            schedule_deviations: BTreeMap::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             problems_per_trip: BTreeMap::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            safety_conflicts: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            parking_cruising: Vec::new(),
+            parking_revenue: BTreeMap::new(),
+            parked_since: BTreeMap::new(),
+            travel_times: TravelTimes::default(),
+            emissions: EmissionsTracker::default(),
+            reroutes: Vec::new(),
+            ride_hail_pickups: Vec::new(),
+            ride_hail_dropoffs: Vec::new(),
+            ride_hail_legs: Vec::new(),
             alerts: Vec::new(),
             record_anything,
         }
     }
 
+    /// Seeded cars don't announce parking, so start charging them from when they're seeded.
+    pub(crate) fn record_seeded_parked_car(&mut self, car: CarID, time: Time) {
+        if self.record_anything {
+            self.parked_since.insert(car, time);
+        }
+    }
+
     pub fn event(&mut self, ev: Event, time: Time, map: &Map) {
         if !self.record_anything {
             return;
@@ -227,6 +300,9 @@ impl Analytics {
             _ => {}
         }
 
+        self.travel_times.event(&ev, time, map);
+        self.emissions.event(&ev, time, map);
+
         // Bus arrivals
         if let Event::BusArrivedAtStop(bus, route, stop) = ev {
             self.bus_arrivals.push((time, bus, route, stop));
@@ -245,6 +321,24 @@ impl Analytics {
                 .or_insert_with(Vec::new)
                 .push((time, route));
         }
+        if let Event::PassengerLeftBehind(_, _, route, stop) = ev {
+            self.passengers_left_behind
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route));
+        }
+        if let Event::TransitScheduleDeviation(_, route, stop, delay) = ev {
+            self.schedule_deviations
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, stop, delay));
+        }
+        if let Event::BusDepartedFromStop(_, route, stop, passengers, capacity) = ev {
+            self.transit_loads
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, stop, passengers, capacity));
+        }
 
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
@@ -291,6 +385,13 @@ impl Analytics {
             }
         }
 
+        if let Event::SafetyConflict(conflict) = ev {
+            self.safety_conflicts
+                .entry(conflict.vehicle_turn.parent)
+                .or_insert_with(Vec::new)
+                .push((time, conflict));
+        }
+
         // Parking spot changes
         if let Event::CarReachedParkingSpot(_, spot) = ev {
             if let ParkingSpot::Onstreet(l, _) = spot {
@@ -319,6 +420,26 @@ impl Analytics {
             }
         }
 
+        // Parking search and revenue
+        if let Event::CarReachedParkingSpot(car, _) = ev {
+            self.parked_since.insert(car, time);
+        }
+        if let Event::CarLeftParkingSpot(car, spot) = ev {
+            if let Some(since) = self.parked_since.remove(&car) {
+                if let Some(zone) = spot.pricing_zone(map) {
+                    *self.parking_revenue.entry(zone.name.clone()).or_insert(0.0) +=
+                        zone.price_per_hour * (time - since).inner_seconds() / 3600.0;
+                }
+            }
+        }
+        if let Event::CarCruisedForParking(_, spot, dist) = ev {
+            self.parking_cruising.push((
+                time,
+                spot.pricing_zone(map).map(|z| z.name.clone()),
+                dist,
+            ));
+        }
+
         // Safety metrics
         if let Event::AgentEntersTraversable(a, Some(trip), Traversable::Turn(t), _) = ev {
             if a.to_type() == AgentType::Bike && map.get_i(t.parent).roads.len() > 4 {
@@ -358,6 +479,18 @@ impl Analytics {
             Event::PathAmended(path) => {
                 self.record_demand(&path, map);
             }
+            Event::VehicleRerouted(_, Some(trip), l, _) => {
+                self.reroutes.push((time, trip, l));
+            }
+            Event::RideHailPickup(_, _, pos, waiting) => {
+                self.ride_hail_pickups.push((time, pos.lane(), waiting));
+            }
+            Event::RideHailDropoff(_, _, pos) => {
+                self.ride_hail_dropoffs.push((time, pos.lane()));
+            }
+            Event::RideHailLegFinished(_, dist, riders) => {
+                self.ride_hail_legs.push((time, dist, riders));
+            }
             Event::Alert(loc, msg) => {
                 self.alerts.push((time, loc, msg));
             }
@@ -477,6 +610,46 @@ impl Analytics {
         trips
     }
 
+    /// The time one trip spent in each kind of phase, in the order they first happened. This
+    /// breaks down multimodal trips, like park-and-ride, into time spent driving, parking,
+    /// walking, and waiting for and riding transit. Phases still in progress aren't counted.
+    pub fn time_per_leg(&self, trip: TripID) -> Vec<(&'static str, Duration)> {
+        let mut per_leg: Vec<(&'static str, Duration)> = Vec::new();
+        let mut current: Option<(&'static str, Time)> = None;
+        for (t, id, _, phase_type) in &self.trip_log {
+            if *id != trip {
+                continue;
+            }
+            if let Some((category, start)) = current.take() {
+                if let Some((_, total)) = per_leg.iter_mut().find(|(c, _)| *c == category) {
+                    *total += *t - start;
+                } else {
+                    per_leg.push((category, *t - start));
+                }
+            }
+            if *phase_type == TripPhaseType::Finished || *phase_type == TripPhaseType::Cancelled {
+                break;
+            }
+            current = Some((phase_type.category(), *t));
+        }
+        per_leg
+    }
+
+    /// For finished trips of one mode, the total time spent in each kind of phase. Comparing this
+    /// before and after edits shows which legs of multimodal trips got faster or slower.
+    pub fn time_per_leg_for_mode(&self, mode: TripMode) -> BTreeMap<&'static str, Duration> {
+        let mut results = BTreeMap::new();
+        for (_, id, m, maybe_dt) in &self.finished_trips {
+            if *m != mode || maybe_dt.is_none() {
+                continue;
+            }
+            for (category, dt) in self.time_per_leg(*id) {
+                *results.entry(category).or_insert(Duration::ZERO) += dt;
+            }
+        }
+        results
+    }
+
     pub fn active_agents(&self, now: Time) -> Vec<(Time, usize)> {
         let mut starts_stops: Vec<(Time, bool)> = Vec::new();
         for t in self.started_trips.values() {
@@ -545,6 +718,168 @@ impl Analytics {
         }
     }
 
+    /// For each stop along a route, the mean and max load factor (passengers divided by capacity)
+    /// of vehicles leaving it. Stops that no vehicle has left yet are omitted.
+    pub fn load_factor_per_segment(
+        &self,
+        now: Time,
+        route: TransitRouteID,
+    ) -> BTreeMap<TransitStopID, (f64, f64)> {
+        load_factors(
+            now,
+            self.transit_loads
+                .get(&route)
+                .map(|loads| loads.as_slice())
+                .unwrap_or(&[]),
+        )
+    }
+
+    /// How evenly spaced vehicles on a route are, based on the time between consecutive arrivals
+    /// at each stop. None if no stop has seen two arrivals yet.
+    pub fn headway_regularity(
+        &self,
+        now: Time,
+        route: TransitRouteID,
+    ) -> Option<HeadwayRegularity> {
+        let mut arrivals_per_stop: BTreeMap<TransitStopID, Vec<Time>> = BTreeMap::new();
+        for (t, _, r, stop) in &self.bus_arrivals {
+            if *t > now {
+                break;
+            }
+            if *r == route {
+                arrivals_per_stop
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push(*t);
+            }
+        }
+        // bus_arrivals is in order, so the times per stop are too
+        let mut headways: Vec<Duration> = Vec::new();
+        for times in arrivals_per_stop.values() {
+            for pair in times.windows(2) {
+                headways.push(pair[1] - pair[0]);
+            }
+        }
+        HeadwayRegularity::new(headways)
+    }
+
+    /// For each stop along a timetabled route, how closely vehicles have kept to the schedule.
+    /// Stops that no vehicle has reached yet are omitted.
+    pub fn schedule_adherence(
+        &self,
+        now: Time,
+        route: TransitRouteID,
+    ) -> BTreeMap<TransitStopID, ScheduleAdherence> {
+        let mut per_stop: BTreeMap<TransitStopID, ScheduleAdherence> = BTreeMap::new();
+        for (t, stop, delay) in self.schedule_deviations.get(&route).unwrap_or(&Vec::new()) {
+            if *t > now {
+                break;
+            }
+            let entry = per_stop.entry(*stop).or_insert_with(|| ScheduleAdherence {
+                mean_delay: Duration::ZERO,
+                max_delay: *delay,
+                num_arrivals: 0,
+                num_on_time: 0,
+            });
+            // Accumulate the sum for now; it's divided below
+            entry.mean_delay = entry.mean_delay + *delay;
+            if *delay > entry.max_delay {
+                entry.max_delay = *delay;
+            }
+            entry.num_arrivals += 1;
+            if *delay + ON_TIME_EARLY >= Duration::ZERO && *delay <= ON_TIME_LATE {
+                entry.num_on_time += 1;
+            }
+        }
+        for adherence in per_stop.values_mut() {
+            adherence.mean_delay = adherence.mean_delay / (adherence.num_arrivals as f64);
+        }
+        per_stop
+    }
+
+    /// How long pedestrians have waited to cross at a traffic signal, up to `now`. Returns the mean
+    /// and worst delay and the number of crossings, or None if nobody has crossed yet.
+    pub fn pedestrian_crossing_delay(
+        &self,
+        now: Time,
+        i: IntersectionID,
+    ) -> Option<(Duration, Duration, usize)> {
+        let mut total = Duration::ZERO;
+        let mut worst = Duration::ZERO;
+        let mut count = 0;
+        for (_, t, delay, agent_type) in self.intersection_delays.get(&i)? {
+            if *t > now {
+                break;
+            }
+            if *agent_type != AgentType::Pedestrian {
+                continue;
+            }
+            total = total + *delay;
+            if *delay > worst {
+                worst = *delay;
+            }
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some((total / (count as f64), worst, count))
+    }
+
+    /// Summarizes the near-misses recorded at every intersection up to `now`. Intersections without
+    /// any are omitted.
+    pub fn safety_summary(&self, now: Time) -> BTreeMap<IntersectionID, SafetySummary> {
+        let mut results = BTreeMap::new();
+        for (i, list) in &self.safety_conflicts {
+            let mut summary = SafetySummary {
+                pedestrian_conflicts: 0,
+                bike_conflicts: 0,
+                min_time_to_collision: None,
+                min_post_encroachment_time: None,
+            };
+            for (t, conflict) in list {
+                if *t > now {
+                    break;
+                }
+                if conflict.vulnerable == AgentType::Pedestrian {
+                    summary.pedestrian_conflicts += 1;
+                } else {
+                    summary.bike_conflicts += 1;
+                }
+                let (min, dt) = match conflict.measure {
+                    ConflictMeasure::TimeToCollision(dt) => {
+                        (&mut summary.min_time_to_collision, dt)
+                    }
+                    ConflictMeasure::PostEncroachmentTime(dt) => {
+                        (&mut summary.min_post_encroachment_time, dt)
+                    }
+                };
+                if min.map(|x| dt < x).unwrap_or(true) {
+                    *min = Some(dt);
+                }
+            }
+            if summary.total() > 0 {
+                results.insert(*i, summary);
+            }
+        }
+        results
+    }
+
+    /// Per pricing zone (or None for unpriced spots), how many drivers parked there after
+    /// searching, and how far they cruised in total.
+    pub fn cruising_per_zone(&self, now: Time) -> BTreeMap<Option<String>, (usize, Distance)> {
+        let mut results: BTreeMap<Option<String>, (usize, Distance)> = BTreeMap::new();
+        for (t, zone, dist) in &self.parking_cruising {
+            if *t > now {
+                break;
+            }
+            let entry = results.entry(zone.clone()).or_insert((0, Distance::ZERO));
+            entry.0 += 1;
+            entry.1 += *dist;
+        }
+        results
+    }
+
     fn parking_spot_availability(
         now: Time,
         changes: &[(Time, bool)],
@@ -600,15 +935,18 @@ impl Analytics {
                     Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                         *i
                     }
-                    Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                        match on {
-                            Traversable::Turn(t) => t.parent,
-                            _ => {
-                                continue;
-                            }
+                    Problem::OvertakeDesired(on)
+                    | Problem::PedestrianOvercrowding(on)
+                    | Problem::ClosePass(on, _) => match on {
+                        Traversable::Turn(t) => t.parent,
+                        _ => {
+                            continue;
                         }
+                    },
+                    Problem::ArterialIntersectionCrossing(t) | Problem::Jaywalking(t) => t.parent,
+                    Problem::MidBlockCrossing(_) => {
+                        continue;
                     }
-                    Problem::ArterialIntersectionCrossing(t) => t.parent,
                 };
                 if id == i {
                     raw_per_type
@@ -652,14 +990,14 @@ impl Analytics {
                     break;
                 }
                 let l = match problem {
-                    Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                        match on {
-                            Traversable::Lane(l) => *l,
-                            _ => {
-                                continue;
-                            }
+                    Problem::OvertakeDesired(on)
+                    | Problem::PedestrianOvercrowding(on)
+                    | Problem::ClosePass(on, _) => match on {
+                        Traversable::Lane(l) => *l,
+                        _ => {
+                            continue;
                         }
-                    }
+                    },
                     _ => {
                         continue;
                     }
@@ -695,6 +1033,129 @@ impl Default for Analytics {
     }
 }
 
+/// Vehicles arriving at a stop less than this fraction of the mean headway after the previous one
+/// count as bunched.
+const BUNCHING_RATIO: f64 = 0.5;
+
+/// Arriving at a stop up to this much before or after the timetable counts as on time.
+const ON_TIME_EARLY: Duration = Duration::const_seconds(60.0);
+const ON_TIME_LATE: Duration = Duration::const_seconds(5.0 * 60.0);
+
+/// Summarizes how closely vehicles of one route kept to the timetable at one stop.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScheduleAdherence {
+    /// Negative if vehicles tend to run early
+    pub mean_delay: Duration,
+    pub max_delay: Duration,
+    pub num_arrivals: usize,
+    /// How many arrivals were no more than a minute early or five minutes late
+    pub num_on_time: usize,
+}
+
+/// A turning vehicle and a pedestrian or cyclist nearly collided at an intersection. These are
+/// surrogate safety measures: frequent, low-margin conflicts tend to predict real crashes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SafetyConflict {
+    /// The left or right turn of the vehicle
+    pub vehicle_turn: TurnID,
+    /// The crosswalk or turn used by the pedestrian or cyclist
+    pub vulnerable_turn: TurnID,
+    /// Either a pedestrian or a bike
+    pub vulnerable: AgentType,
+    pub measure: ConflictMeasure,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConflictMeasure {
+    /// When one of the two started their turn, the other was only this far away from reaching
+    /// theirs, and would've collided without braking.
+    TimeToCollision(Duration),
+    /// One of the two started their turn only this long after the other finished a conflicting
+    /// one.
+    PostEncroachmentTime(Duration),
+}
+
+/// Counts the near-misses at one intersection.
+#[derive(Clone, Debug, Serialize)]
+pub struct SafetySummary {
+    pub pedestrian_conflicts: usize,
+    pub bike_conflicts: usize,
+    pub min_time_to_collision: Option<Duration>,
+    pub min_post_encroachment_time: Option<Duration>,
+}
+
+impl SafetySummary {
+    pub fn total(&self) -> usize {
+        self.pedestrian_conflicts + self.bike_conflicts
+    }
+}
+
+/// The mean and max load factor per stop, given (time, stop, passengers, capacity) in time order.
+fn load_factors<K: Copy + Ord>(
+    now: Time,
+    loads: &[(Time, K, usize, usize)],
+) -> BTreeMap<K, (f64, f64)> {
+    let mut per_stop: BTreeMap<K, Vec<f64>> = BTreeMap::new();
+    for (t, stop, passengers, capacity) in loads {
+        if *t > now {
+            break;
+        }
+        per_stop
+            .entry(*stop)
+            .or_insert_with(Vec::new)
+            .push((*passengers as f64) / (*capacity).max(1) as f64);
+    }
+    per_stop
+        .into_iter()
+        .map(|(stop, loads)| {
+            let mean = loads.iter().sum::<f64>() / (loads.len() as f64);
+            let max = loads.into_iter().fold(0.0, f64::max);
+            (stop, (mean, max))
+        })
+        .collect()
+}
+
+/// Summarizes the time between consecutive vehicles of one route reaching the same stop.
+#[derive(Clone, Debug, Serialize)]
+pub struct HeadwayRegularity {
+    pub mean_headway: Duration,
+    /// The standard deviation of headways divided by their mean. 0 means perfectly even spacing.
+    pub coefficient_of_variation: f64,
+    pub num_headways: usize,
+    /// How many headways were less than half of the mean
+    pub num_bunched: usize,
+}
+
+impl HeadwayRegularity {
+    fn new(headways: Vec<Duration>) -> Option<HeadwayRegularity> {
+        if headways.is_empty() {
+            return None;
+        }
+
+        let n = headways.len() as f64;
+        let mean = headways.iter().map(|h| h.inner_seconds()).sum::<f64>() / n;
+        let variance = headways
+            .iter()
+            .map(|h| (h.inner_seconds() - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let bunched = headways
+            .iter()
+            .filter(|h| h.inner_seconds() < BUNCHING_RATIO * mean)
+            .count();
+        Some(HeadwayRegularity {
+            mean_headway: Duration::seconds(mean),
+            coefficient_of_variation: if mean > 0.0 {
+                variance.sqrt() / mean
+            } else {
+                0.0
+            },
+            num_headways: headways.len(),
+            num_bunched: bunched,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct TripPhase {
     pub start_time: Time,
@@ -704,6 +1165,97 @@ pub struct TripPhase {
     pub phase_type: TripPhaseType,
 }
 
+/// The time cars actually take to cross a road and then make a movement at the end of it. This
+/// matches how the pathfinder assigns costs to movements, so it can be used to route around
+/// congestion. Bikes and buses aren't included.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TravelTimes {
+    /// (Movement, hour block the movement finished) -> number of cars and their total travel time
+    pub per_movement: BTreeMap<(MovementID, usize), (usize, Duration)>,
+
+    /// The road each car is currently crossing, and when it started. Cars that spawned or parked
+    /// partway along a road haven't crossed all of it, so they're not tracked until their next
+    /// road.
+    current: BTreeMap<CarID, (DirectedRoadID, Time)>,
+}
+
+impl TravelTimes {
+    fn event(&mut self, ev: &Event, time: Time, map: &Map) {
+        match ev {
+            Event::AgentEntersTraversable(AgentID::Car(car), _, Traversable::Lane(l), _)
+                if car.vehicle_type == VehicleType::Car =>
+            {
+                let dr = map.get_l(*l).get_directed_parent();
+                if let Some((from, start)) = self.current.insert(*car, (dr, time)) {
+                    if from != dr {
+                        let mvmnt = MovementID {
+                            from,
+                            to: dr,
+                            parent: from.dst_i(map),
+                            crosswalk: false,
+                        };
+                        let entry = self
+                            .per_movement
+                            .entry((mvmnt, time.get_hours()))
+                            .or_insert((0, Duration::ZERO));
+                        entry.0 += 1;
+                        entry.1 += time - start;
+                    }
+                }
+            }
+            Event::CarReachedParkingSpot(car, _) => {
+                self.current.remove(car);
+            }
+            Event::PersonLeavesMap(_, Some(AgentID::Car(car)), _)
+            | Event::PersonStopsAtDockless(_, Some(AgentID::Car(car)), _) => {
+                self.current.remove(car);
+            }
+            _ => {}
+        }
+    }
+
+    /// The average travel time through each movement over the whole day.
+    pub fn average_per_movement(&self) -> BTreeMap<MovementID, Duration> {
+        let mut totals: BTreeMap<MovementID, (usize, Duration)> = BTreeMap::new();
+        for ((mvmnt, _), (count, total)) in &self.per_movement {
+            let entry = totals.entry(*mvmnt).or_insert((0, Duration::ZERO));
+            entry.0 += *count;
+            entry.1 += *total;
+        }
+        totals
+            .into_iter()
+            .map(|(mvmnt, (count, total))| (mvmnt, total / (count as f64)))
+            .collect()
+    }
+
+    /// Expresses the measured travel times as a time-of-day profile, comparing each road's
+    /// average to the ideal cost the pathfinder uses.
+    pub fn to_profile(&self, map: &Map) -> TravelTimeProfile {
+        // (directed road, hour) -> sum of ratios and number of cars
+        let mut ratios: BTreeMap<(DirectedRoadID, usize), (f64, usize)> = BTreeMap::new();
+        for ((mvmnt, hour), (count, total)) in &self.per_movement {
+            if let Some(ideal) = vehicle_cost(
+                mvmnt.from,
+                *mvmnt,
+                PathConstraints::Car,
+                map.routing_params(),
+                map,
+            ) {
+                let entry = ratios.entry((mvmnt.from, *hour % 24)).or_insert((0.0, 0));
+                entry.0 += *total / ideal;
+                entry.1 += *count;
+            }
+        }
+
+        let mut profile = TravelTimeProfile::default();
+        for ((dr, hour), (sum, count)) in ratios {
+            profile.per_road.entry(dr).or_insert_with(|| vec![1.0; 24])[hour] =
+                sum / (count as f64);
+        }
+        profile
+    }
+}
+
 /// See https://github.com/a-b-street/abstreet/issues/85
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TimeSeriesCount<X: Ord + Clone> {
@@ -893,3 +1445,55 @@ impl SlidingWindow {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_factors() {
+        let t = |s| Time::START_OF_DAY + Duration::seconds(s);
+        let loads = vec![
+            (t(10.0), 0, 30, 60),
+            (t(20.0), 1, 60, 60),
+            (t(70.0), 0, 60, 60),
+            (t(80.0), 1, 0, 60),
+            // Not yet
+            (t(500.0), 0, 0, 60),
+        ];
+        let result = load_factors(t(100.0), &loads);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[&0], (0.75, 1.0));
+        assert_eq!(result[&1], (0.5, 1.0));
+
+        // A vehicle with no capacity doesn't divide by zero
+        let result = load_factors(t(100.0), &[(t(10.0), 0, 5, 0)]);
+        assert_eq!(result[&0], (5.0, 5.0));
+    }
+
+    #[test]
+    fn test_headway_regularity() {
+        assert!(HeadwayRegularity::new(Vec::new()).is_none());
+
+        // Perfectly even
+        let even = HeadwayRegularity::new(vec![Duration::minutes(10); 4]).unwrap();
+        assert_eq!(even.mean_headway, Duration::minutes(10));
+        assert_eq!(even.coefficient_of_variation, 0.0);
+        assert_eq!(even.num_headways, 4);
+        assert_eq!(even.num_bunched, 0);
+
+        // The mean is 10 minutes, so only the 2 minute gap is bunched. Exactly 5 minutes apart
+        // doesn't count.
+        let headways = vec![
+            Duration::minutes(2),
+            Duration::minutes(5),
+            Duration::minutes(15),
+            Duration::minutes(18),
+        ];
+        let bunched = HeadwayRegularity::new(headways).unwrap();
+        assert_eq!(bunched.mean_headway, Duration::minutes(10));
+        assert_eq!(bunched.num_headways, 4);
+        assert_eq!(bunched.num_bunched, 1);
+        assert!(bunched.coefficient_of_variation > 0.6 && bunched.coefficient_of_variation < 0.7);
+    }
+}
@@ -0,0 +1,289 @@
+//! Dynamic traffic assignment. Normally drivers pick their route assuming free-flow travel times,
+//! so congestion never affects route choice. This simulates the same day over and over, measuring
+//! how long cars really took through each road and movement, and routes the next day using those
+//! measurements, until routes settle down. The measured time replaces the ideal time to cross a
+//! road, but routing penalties, like avoiding main roads or zones with restricted access, still
+//! apply.
+//!
+//! Every driver keeps the route they took the day before, except for a share of them who switch
+//! to the fastest route given the latest measurements. Only moving some of the traffic at a time
+//! avoids everybody piling onto the same alternate route, like the method of successive averages.
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::Serialize;
+
+use abstutil::Timer;
+use geom::Duration;
+use map_model::connectivity::{observed_vehicle_cost, vehicle_cost};
+use map_model::{
+    DirectedRoadID, Map, MovementID, PathConstraints, PathRequest, PathStepV2, PathV2, Pathfinder,
+    Traversable,
+};
+use synthpop::Scenario;
+
+use crate::{
+    AgentID, AlertHandler, Analytics, Event, Sim, SimOptions, TripID, TripPhaseType, VehicleType,
+};
+
+pub struct AssignmentConfig {
+    pub max_iterations: usize,
+    /// Stop once the relative gap is below this
+    pub max_relative_gap: f64,
+    /// The share of driving trips that switch to a new route each iteration, between 0 and 1. If
+    /// this isn't specified, use 1 / (iteration + 2).
+    pub reroute_share: Option<f64>,
+    pub rng_seed: u64,
+}
+
+/// How close to equilibrium one iteration of the assignment got.
+#[derive(Clone, Debug, Serialize)]
+pub struct AssignmentIteration {
+    pub iteration: usize,
+    /// The number of driving trips compared
+    pub num_trips: usize,
+    /// The total time drivers spent on their routes, using the measured costs
+    pub total_cost: Duration,
+    /// The total time if every driver had picked the fastest route, given the measured costs
+    pub best_cost: Duration,
+    /// 0 means nobody could do better by switching routes
+    pub relative_gap: f64,
+}
+
+pub struct AssignmentResults {
+    pub iterations: Vec<AssignmentIteration>,
+    /// The costs measured in the last iteration. These can be passed to
+    /// `Pathfinder::with_observed_driving_costs`.
+    pub costs: BTreeMap<MovementID, Duration>,
+    /// The route each driving trip took in the last iteration
+    pub routes: BTreeMap<TripID, PathV2>,
+    /// From simulating the last iteration
+    pub analytics: Analytics,
+}
+
+/// Repeatedly simulates the scenario on the map, including any edits already applied, feeding
+/// measured congestion back into route choice. The map itself isn't modified.
+pub fn assign_traffic(
+    map: &Map,
+    scenario: &Scenario,
+    config: &AssignmentConfig,
+    timer: &mut Timer,
+) -> AssignmentResults {
+    let mut map = map.clone();
+    let free_flow = map.get_pathfinder().clone();
+    let mut rng = XorShiftRng::seed_from_u64(config.rng_seed);
+    // Trips without a route here pathfind using the map's pathfinder. On the first iteration,
+    // that's everybody, using free-flow costs.
+    let mut routes: BTreeMap<TripID, PathV2> = BTreeMap::new();
+    let mut iterations = Vec::new();
+    let mut iteration = 0;
+    loop {
+        timer.start(format!("assignment iteration {}", iteration));
+        let (analytics, driven) = simulate(&map, scenario, config.rng_seed, &routes, timer);
+        let measured = analytics.travel_times.average_per_movement();
+        let taken = routes_taken(&map, &free_flow, driven);
+        let stats = measure_gap(&map, &free_flow, &taken, &measured, iteration, timer);
+        timer.note(format!(
+            "Iteration {}: relative gap {:.4} over {} driving trips",
+            iteration, stats.relative_gap, stats.num_trips
+        ));
+        timer.stop(format!("assignment iteration {}", iteration));
+
+        let done =
+            stats.relative_gap <= config.max_relative_gap || iteration == config.max_iterations;
+        iterations.push(stats);
+        if done {
+            return AssignmentResults {
+                iterations,
+                costs: measured,
+                routes: taken,
+                analytics,
+            };
+        }
+
+        // Everybody keeps their route, except for the share who'll pathfind again using the
+        // latest measurements
+        let share = config
+            .reroute_share
+            .unwrap_or(1.0 / (iteration as f64 + 2.0))
+            .clamp(0.0, 1.0);
+        routes = taken.into_iter().filter(|_| !rng.gen_bool(share)).collect();
+        let pathfinder = free_flow.with_observed_driving_costs(&map, &measured, timer);
+        map.hack_override_pathfinder(pathfinder);
+        iteration += 1;
+    }
+}
+
+fn simulate(
+    map: &Map,
+    scenario: &Scenario,
+    rng_seed: u64,
+    routes: &BTreeMap<TripID, PathV2>,
+    timer: &mut Timer,
+) -> (Analytics, RoutesDriven) {
+    let mut opts = SimOptions::new("assignment");
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(map, opts);
+    // Trip IDs are assigned the same way every time, since the seed doesn't change
+    sim.set_fixed_routes(routes.clone());
+    let mut rng = XorShiftRng::seed_from_u64(rng_seed);
+    sim.instantiate(scenario, map, &mut rng, timer);
+    sim.start_buffering_events();
+    let mut driven = RoutesDriven::default();
+    // Like prebaking, run a bit past midnight for trips starting late
+    let end = sim.get_end_of_day() + Duration::hours(3);
+    while sim.time() < end {
+        let dt = (end - sim.time()).min(Duration::hours(1));
+        sim.timed_step(map, dt, &mut None, timer);
+        for (_, ev) in sim.drain_buffered_events() {
+            driven.event(&ev, map);
+        }
+    }
+    (sim.get_analytics().clone(), driven)
+}
+
+/// The roads each driving trip actually crossed, including any rerouting along the way. Only the
+/// last time a trip starts driving is kept.
+#[derive(Default)]
+struct RoutesDriven {
+    per_trip: BTreeMap<TripID, (PathRequest, Vec<DirectedRoadID>)>,
+}
+
+impl RoutesDriven {
+    fn event(&mut self, ev: &Event, map: &Map) {
+        match ev {
+            Event::TripPhaseStarting(trip, _, Some(req), TripPhaseType::Driving)
+                if req.constraints == PathConstraints::Car =>
+            {
+                self.per_trip.insert(*trip, (req.clone(), Vec::new()));
+            }
+            // Cars don't announce the lane they start on, so also look at the turn leaving it
+            Event::AgentEntersTraversable(AgentID::Car(car), Some(trip), on, _)
+                if car.vehicle_type == VehicleType::Car =>
+            {
+                if let Some((_, roads)) = self.per_trip.get_mut(trip) {
+                    let dr = match on {
+                        Traversable::Lane(l) => map.get_l(*l).get_directed_parent(),
+                        Traversable::Turn(t) => map.get_l(t.src).get_directed_parent(),
+                    };
+                    if roads.last() != Some(&dr) {
+                        roads.push(dr);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The route each driving trip took, built from the roads the driver actually crossed.
+fn routes_taken(
+    map: &Map,
+    pathfinder: &Pathfinder,
+    driven: RoutesDriven,
+) -> BTreeMap<TripID, PathV2> {
+    let mut routes = BTreeMap::new();
+    for (trip, (req, mut roads)) in driven.per_trip {
+        // A trip that never left the road it started on
+        if roads.is_empty() {
+            roads.push(map.get_l(req.start.lane()).get_directed_parent());
+        }
+        // Skip trips cancelled partway, or drivers who cruised past their destination looking
+        // for parking
+        if roads.last() != Some(&map.get_l(req.end.lane()).get_directed_parent()) {
+            continue;
+        }
+        let mut cost = Duration::ZERO;
+        for pair in roads.windows(2) {
+            if let Some(x) = free_flow_cost(map, movement(pair[0], pair[1], map)) {
+                cost += x;
+            }
+        }
+        routes.insert(trip, pathfinder.path_along_roads(roads, req, cost, map));
+    }
+    routes
+}
+
+fn movement(from: DirectedRoadID, to: DirectedRoadID, map: &Map) -> MovementID {
+    MovementID {
+        from,
+        to,
+        parent: from.dst_i(map),
+        crosswalk: false,
+    }
+}
+
+fn free_flow_cost(map: &Map, mvmnt: MovementID) -> Option<Duration> {
+    vehicle_cost(
+        mvmnt.from,
+        mvmnt,
+        PathConstraints::Car,
+        map.routing_params(),
+        map,
+    )
+}
+
+/// Compares the routes drivers took against the fastest routes they could've taken, with both
+/// judged by the measured costs.
+fn measure_gap(
+    map: &Map,
+    free_flow: &Pathfinder,
+    taken: &BTreeMap<TripID, PathV2>,
+    measured: &BTreeMap<MovementID, Duration>,
+    iteration: usize,
+    timer: &mut Timer,
+) -> AssignmentIteration {
+    let best_pathfinder = free_flow.with_observed_driving_costs(map, measured, timer);
+
+    let mut num_trips = 0;
+    let mut total_cost = Duration::ZERO;
+    let mut best_cost = Duration::ZERO;
+    for path in taken.values() {
+        let best = match best_pathfinder.pathfind_v2(path.get_req().clone(), map) {
+            Some(path) => path,
+            None => continue,
+        };
+        num_trips += 1;
+        total_cost += path_cost(map, path, measured);
+        best_cost += path_cost(map, &best, measured);
+    }
+
+    let relative_gap = if total_cost == Duration::ZERO {
+        0.0
+    } else {
+        // The fastest route could be slightly worse than the taken one, due to rounding costs in
+        // the pathfinder
+        ((total_cost - best_cost) / total_cost).max(0.0)
+    };
+    AssignmentIteration {
+        iteration,
+        num_trips,
+        total_cost,
+        best_cost,
+        relative_gap,
+    }
+}
+
+fn path_cost(map: &Map, path: &PathV2, measured: &BTreeMap<MovementID, Duration>) -> Duration {
+    let mut total = Duration::ZERO;
+    for step in path.get_steps() {
+        if let PathStepV2::Movement(mvmnt) = step {
+            let cost = match measured.get(mvmnt) {
+                Some(time) => observed_vehicle_cost(
+                    *mvmnt,
+                    *time,
+                    PathConstraints::Car,
+                    map.routing_params(),
+                    map,
+                ),
+                None => free_flow_cost(map, *mvmnt),
+            };
+            if let Some(cost) = cost {
+                total += cost;
+            }
+        }
+    }
+    total
+}
@@ -0,0 +1,347 @@
+//! Adjusts a scenario until the simulated throughput along roads and through intersections matches
+//! observed traffic counts.
+//!
+//! Each iteration simulates the full day, compares against the counts, then re-weights people: the
+//! trips of somebody passing through places with too much simulated traffic are less likely to be
+//! kept, and vice versa. Since people entering or leaving through a border are counted at that
+//! border, this also adjusts border demand. Departure times aren't shifted, because the counts
+//! aren't broken down by time.
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use serde::Serialize;
+
+use abstutil::{Counter, Timer};
+use geom::{Duration, Time};
+use map_model::{IntersectionID, Map, RoadID};
+use synthpop::{Scenario, TrafficCounts, TripEndpoint, TripMode};
+
+use crate::{AgentType, AlertHandler, Sim, SimOptions};
+
+/// People can't be scaled by more than this much in a single iteration.
+const MAX_WEIGHT: f64 = 2.0;
+const MIN_WEIGHT: f64 = 0.5;
+
+pub struct CalibrationConfig {
+    /// Only trips using this mode are counted and re-weighted.
+    pub mode: TripMode,
+    pub max_iterations: usize,
+    /// A location matches the observed count if the GEH statistic is below this. 5 is a common
+    /// threshold.
+    pub max_geh: f64,
+    /// Stop once this fraction of counted locations match.
+    pub target_fraction: f64,
+    /// Between 0 and 1. Lower values change the scenario more slowly, but avoid overshooting.
+    pub damping: f64,
+    pub rng_seed: u64,
+}
+
+/// How close one iteration of the calibration got.
+#[derive(Clone, Debug, Serialize)]
+pub struct CalibrationIteration {
+    pub iteration: usize,
+    pub num_people: usize,
+    /// The number of roads and intersections with an observed count
+    pub num_locations: usize,
+    /// The number of those where the simulated count is within tolerance
+    pub num_matching: usize,
+    /// Root mean square error of the counts
+    pub rmse: f64,
+    pub mean_geh: f64,
+}
+
+impl CalibrationIteration {
+    pub fn fraction_matching(&self) -> f64 {
+        if self.num_locations == 0 {
+            1.0
+        } else {
+            (self.num_matching as f64) / (self.num_locations as f64)
+        }
+    }
+}
+
+pub struct CalibrationResults {
+    pub scenario: Scenario,
+    /// The simulated counts using the final scenario
+    pub counts: TrafficCounts,
+    pub iterations: Vec<CalibrationIteration>,
+}
+
+/// Iteratively re-weights people in the scenario until the simulated counts match `observed`, or
+/// the maximum number of iterations is reached. Only roads and intersections present in `observed`
+/// are compared.
+pub fn calibrate(
+    map: &Map,
+    mut scenario: Scenario,
+    observed: &TrafficCounts,
+    config: &CalibrationConfig,
+    timer: &mut Timer,
+) -> CalibrationResults {
+    let mut rng = XorShiftRng::seed_from_u64(config.rng_seed);
+    let mut iterations = Vec::new();
+    let mut iteration = 0;
+    loop {
+        timer.start(format!("calibration iteration {}", iteration));
+        let simulated = simulate(map, &scenario, config, iteration, timer);
+        let factors = compare(observed, &simulated);
+        let stats = CalibrationIteration {
+            iteration,
+            num_people: scenario.people.len(),
+            num_locations: factors.len(),
+            num_matching: factors
+                .iter()
+                .filter(|f| geh(f.observed, f.simulated) < config.max_geh)
+                .count(),
+            rmse: if factors.is_empty() {
+                0.0
+            } else {
+                (factors
+                    .iter()
+                    .map(|f| (f.observed as f64 - f.simulated as f64).powi(2))
+                    .sum::<f64>()
+                    / factors.len() as f64)
+                    .sqrt()
+            },
+            mean_geh: if factors.is_empty() {
+                0.0
+            } else {
+                factors
+                    .iter()
+                    .map(|f| geh(f.observed, f.simulated))
+                    .sum::<f64>()
+                    / factors.len() as f64
+            },
+        };
+        timer.note(format!(
+            "Iteration {}: {}/{} locations match, RMSE {:.2}, mean GEH {:.2}",
+            iteration, stats.num_matching, stats.num_locations, stats.rmse, stats.mean_geh
+        ));
+        timer.stop(format!("calibration iteration {}", iteration));
+
+        let done = stats.fraction_matching() >= config.target_fraction
+            || iteration == config.max_iterations;
+        iterations.push(stats);
+        if done {
+            return CalibrationResults {
+                scenario,
+                counts: simulated,
+                iterations,
+            };
+        }
+
+        scenario = reweight(map, scenario, &factors, config, &mut rng, timer);
+        iteration += 1;
+    }
+}
+
+/// The GEH statistic, commonly used to compare traffic volumes. Unlike a percentage, it's
+/// forgiving for low counts.
+pub fn geh(observed: usize, simulated: usize) -> f64 {
+    let m = simulated as f64;
+    let c = observed as f64;
+    if m + c == 0.0 {
+        return 0.0;
+    }
+    (2.0 * (m - c).powi(2) / (m + c)).sqrt()
+}
+
+fn simulate(
+    map: &Map,
+    scenario: &Scenario,
+    config: &CalibrationConfig,
+    iteration: usize,
+    timer: &mut Timer,
+) -> TrafficCounts {
+    let mut opts = SimOptions::new("calibration");
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(map, opts);
+    let mut rng = XorShiftRng::seed_from_u64(config.rng_seed);
+    sim.instantiate(scenario, map, &mut rng, timer);
+    // Like prebaking, run a bit past midnight for trips starting late
+    sim.timed_step(
+        map,
+        sim.get_end_of_day() - Time::START_OF_DAY + Duration::hours(3),
+        &mut None,
+        timer,
+    );
+
+    let agent_types = vec![match config.mode {
+        TripMode::Walk => AgentType::Pedestrian,
+        TripMode::Bike | TripMode::Scooter => AgentType::Bike,
+        TripMode::Transit => AgentType::TransitRider,
+        TripMode::Drive | TripMode::ParkAndRide | TripMode::RideHail => AgentType::Car,
+        TripMode::Freight => AgentType::Truck,
+    }]
+    .into_iter()
+    .collect();
+    let analytics = sim.get_analytics();
+    TrafficCounts {
+        map: map.get_name().clone(),
+        description: format!("calibration iteration {}", iteration),
+        per_road: analytics.road_thruput.all_total_counts(&agent_types),
+        per_intersection: analytics
+            .intersection_thruput
+            .all_total_counts(&agent_types),
+    }
+}
+
+struct Factor {
+    location: Location,
+    observed: usize,
+    simulated: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Location {
+    Road(RoadID),
+    Intersection(IntersectionID),
+}
+
+impl Factor {
+    /// How much to scale the demand passing through this location. Adding 1 avoids dividing by
+    /// zero and overreacting to tiny counts.
+    fn scale(&self) -> f64 {
+        ((self.observed as f64 + 1.0) / (self.simulated as f64 + 1.0)).clamp(MIN_WEIGHT, MAX_WEIGHT)
+    }
+}
+
+fn compare(observed: &TrafficCounts, simulated: &TrafficCounts) -> Vec<Factor> {
+    let mut factors = Vec::new();
+    for (r, cnt) in observed.per_road.borrow() {
+        factors.push(Factor {
+            location: Location::Road(*r),
+            observed: *cnt,
+            simulated: simulated.per_road.get(*r),
+        });
+    }
+    for (i, cnt) in observed.per_intersection.borrow() {
+        factors.push(Factor {
+            location: Location::Intersection(*i),
+            observed: *cnt,
+            simulated: simulated.per_intersection.get(*i),
+        });
+    }
+    factors
+}
+
+/// Clones or removes people, depending on whether they pass through places that have too little
+/// or too much traffic.
+fn reweight(
+    map: &Map,
+    scenario: Scenario,
+    factors: &[Factor],
+    config: &CalibrationConfig,
+    rng: &mut XorShiftRng,
+    timer: &mut Timer,
+) -> Scenario {
+    let scale: BTreeMap<Location, f64> = factors.iter().map(|f| (f.location, f.scale())).collect();
+
+    let mut people = Vec::new();
+    timer.start_iter("re-weight people", scenario.people.len());
+    for person in scenario.people {
+        timer.next();
+        // Find where this person's trips go, assuming free-flow routes
+        let mut crossed = TrafficCounts::default();
+        for trip in &person.trips {
+            if trip.mode != config.mode {
+                continue;
+            }
+            if let Some(req) = TripEndpoint::path_req(trip.origin, trip.destination, trip.mode, map)
+            {
+                if let Ok(path) = map.pathfind_v2(req) {
+                    crossed.update_with_path(path, 1, map);
+                }
+            }
+        }
+        let weight = person_weight(&crossed.per_road, &crossed.per_intersection, &scale);
+        let weight = weight.powf(config.damping);
+
+        // Keep the whole number of copies, then maybe one more randomly
+        let mut copies = weight.floor() as usize;
+        if rng.gen_bool(weight - weight.floor()) {
+            copies += 1;
+        }
+        for copy in 0..copies {
+            let mut person = person.clone();
+            // The original ID should identify just one person
+            if copy > 0 {
+                person.orig_id = None;
+            }
+            people.push(person);
+        }
+    }
+
+    Scenario { people, ..scenario }
+}
+
+/// The geometric mean of the scale at every counted location this person passes through.
+fn person_weight(
+    roads: &Counter<RoadID>,
+    intersections: &Counter<IntersectionID>,
+    scale: &BTreeMap<Location, f64>,
+) -> f64 {
+    let mut sum = 0.0;
+    let mut n = 0;
+    let locations = roads.borrow().keys().map(|r| Location::Road(*r)).chain(
+        intersections
+            .borrow()
+            .keys()
+            .map(|i| Location::Intersection(*i)),
+    );
+    for loc in locations {
+        if let Some(x) = scale.get(&loc) {
+            sum += x.ln();
+            n += 1;
+        }
+    }
+    if n == 0 {
+        1.0
+    } else {
+        (sum / n as f64).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geh() {
+        assert_eq!(geh(0, 0), 0.0);
+        assert_eq!(geh(100, 100), 0.0);
+        // sqrt(2 * 50^2 / 250)
+        assert!((geh(100, 150) - 20.0_f64.sqrt()).abs() < 1e-9);
+        // Symmetric
+        assert_eq!(geh(100, 150), geh(150, 100));
+        // The same absolute difference matters less for higher counts
+        assert!(geh(1000, 1050) < geh(100, 150));
+    }
+
+    #[test]
+    fn test_person_weight() {
+        let mut scale = BTreeMap::new();
+        scale.insert(Location::Road(RoadID(0)), 2.0);
+        scale.insert(Location::Road(RoadID(1)), 0.5);
+        scale.insert(Location::Intersection(IntersectionID(0)), 2.0);
+
+        let mut roads = Counter::new();
+        let mut intersections = Counter::new();
+        // Not passing through any counted location
+        roads.inc(RoadID(5));
+        assert_eq!(person_weight(&roads, &intersections, &scale), 1.0);
+
+        // The geometric mean of 2 and 0.5
+        roads.inc(RoadID(0));
+        roads.inc(RoadID(1));
+        assert!((person_weight(&roads, &intersections, &scale) - 1.0).abs() < 1e-9);
+
+        // Now the geometric mean of 2, 0.5, and 2. Crossing a location more than once doesn't
+        // count extra.
+        intersections.inc(IntersectionID(0));
+        intersections.inc(IntersectionID(0));
+        assert!((person_weight(&roads, &intersections, &scale) - 2.0_f64.cbrt()).abs() < 1e-9);
+    }
+}
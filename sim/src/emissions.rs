@@ -0,0 +1,336 @@
+//! Rough estimates of the air pollution and energy use of vehicles, derived from how fast they
+//! actually move through the simulation. The factors are simplified from speed-dependent
+//! emissions curves like COPERT and HBEFA. They're meant for comparing scenarios against each
+//! other, not for absolute inventories.
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Duration, Time};
+use map_model::{Map, RoadID, Traversable};
+
+use crate::{AgentID, CarID, Event, TripID, VehicleType};
+
+/// Pollutants emitted and energy used by some vehicles. Only tailpipe and non-exhaust emissions
+/// are counted, so electricity generation doesn't appear here.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    pub co2_grams: f64,
+    pub nox_grams: f64,
+    /// Exhaust plus tyre, brake, and road wear
+    pub pm25_grams: f64,
+    /// Fuel or electricity used
+    pub energy_megajoules: f64,
+}
+
+impl AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        self.co2_grams += other.co2_grams;
+        self.nox_grams += other.nox_grams;
+        self.pm25_grams += other.pm25_grams;
+        self.energy_megajoules += other.energy_megajoules;
+    }
+}
+
+impl Emissions {
+    pub fn get(&self, pollutant: Pollutant) -> f64 {
+        match pollutant {
+            Pollutant::CO2 => self.co2_grams,
+            Pollutant::NOx => self.nox_grams,
+            Pollutant::PM25 => self.pm25_grams,
+            Pollutant::Energy => self.energy_megajoules,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pollutant {
+    CO2,
+    NOx,
+    PM25,
+    Energy,
+}
+
+impl Pollutant {
+    pub fn all() -> Vec<Pollutant> {
+        vec![
+            Pollutant::CO2,
+            Pollutant::NOx,
+            Pollutant::PM25,
+            Pollutant::Energy,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pollutant::CO2 => "CO2",
+            Pollutant::NOx => "NOx",
+            Pollutant::PM25 => "PM2.5",
+            Pollutant::Energy => "energy",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Pollutant::Energy => "MJ",
+            _ => "g",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fuel {
+    Electric,
+    Petrol,
+    Diesel,
+}
+
+/// The share of cars powered by each fuel. Trucks and buses always run on diesel, and trams,
+/// trains, and scooters are electric.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FleetMix {
+    pub electric: f64,
+    pub petrol: f64,
+    pub diesel: f64,
+}
+
+impl FleetMix {
+    /// Roughly the current mix of cars on the road in many European and North American cities.
+    pub fn new() -> FleetMix {
+        FleetMix {
+            electric: 0.05,
+            petrol: 0.75,
+            diesel: 0.2,
+        }
+    }
+
+    /// Deterministically assigns a fuel to a vehicle, so the same vehicle is treated the same way
+    /// across runs.
+    pub fn fuel(&self, car: CarID) -> Fuel {
+        match car.vehicle_type {
+            VehicleType::Car => {}
+            VehicleType::Bus | VehicleType::Truck => {
+                return Fuel::Diesel;
+            }
+            VehicleType::Train | VehicleType::Tram | VehicleType::Scooter | VehicleType::Bike => {
+                return Fuel::Electric;
+            }
+        }
+        // Scatter sequential IDs across [0, 1)
+        let x = ((car.id as u64).wrapping_mul(2_654_435_761) % 10_000) as f64 / 10_000.0
+            * (self.electric + self.petrol + self.diesel);
+        if x < self.electric {
+            Fuel::Electric
+        } else if x < self.electric + self.petrol {
+            Fuel::Petrol
+        } else {
+            Fuel::Diesel
+        }
+    }
+}
+
+impl Default for FleetMix {
+    fn default() -> FleetMix {
+        FleetMix::new()
+    }
+}
+
+/// Estimates what one vehicle emits while covering some distance over some duration, starting at
+/// `prev_speed` (in m/s).
+///
+/// Steady-state energy use follows the usual U-shape: a time-based term for idling and the engine
+/// running, a distance-based term for rolling resistance, and a drag term growing with the square
+/// of speed. On top of that, speeding up costs the gained kinetic energy, divided by the
+/// efficiency of the powertrain.
+pub fn estimate(
+    vehicle_type: VehicleType,
+    fuel: Fuel,
+    dist_meters: f64,
+    duration: Duration,
+    prev_speed: f64,
+) -> Emissions {
+    if vehicle_type == VehicleType::Bike || duration <= Duration::ZERO {
+        return Emissions::default();
+    }
+    let hours = duration.inner_seconds() / 3600.0;
+    let km = dist_meters / 1000.0;
+    let speed = dist_meters / duration.inner_seconds();
+    // Beyond this, the curves aren't meaningful
+    let kmh = (speed * 3.6).min(130.0);
+
+    // Relative to a petrol car. The masses include passengers and an average load.
+    let (scale, mass_kg) = match vehicle_type {
+        VehicleType::Car => (1.0, 1400.0),
+        VehicleType::Truck => (3.3, 7500.0),
+        VehicleType::Bus => (6.0, 13000.0),
+        VehicleType::Tram => (5.0, 40000.0),
+        VehicleType::Train => (8.0, 80000.0),
+        VehicleType::Scooter => (0.04, 100.0),
+        VehicleType::Bike => unreachable!(),
+    };
+    // Energy actually needed relative to petrol, and how much of the energy used ends up as
+    // motion
+    let (relative_energy, efficiency) = match fuel {
+        Fuel::Petrol => (1.0, 0.25),
+        Fuel::Diesel => (0.85, 0.3),
+        Fuel::Electric => (0.3, 0.8),
+    };
+
+    let steady_mj = scale * relative_energy * (25.0 * hours + km * (1.5 + 0.00006 * kmh * kmh));
+    let kinetic_mj = if speed > prev_speed {
+        0.5 * mass_kg * (speed * speed - prev_speed * prev_speed) / efficiency / 1_000_000.0
+    } else {
+        0.0
+    };
+    let energy_megajoules = steady_mj + kinetic_mj;
+
+    // Per MJ of fuel burned
+    let (co2, nox, exhaust_pm25) = match fuel {
+        Fuel::Petrol => (73.3, 0.03, 0.0005),
+        Fuel::Diesel => (74.1, 0.25, 0.004),
+        Fuel::Electric => (0.0, 0.0, 0.0),
+    };
+    // Per km, scaled by weight. Rail wear isn't included.
+    let non_exhaust_pm25 = match vehicle_type {
+        VehicleType::Train | VehicleType::Tram => 0.0,
+        _ => 0.01 * (mass_kg / 1400.0).sqrt(),
+    };
+
+    Emissions {
+        co2_grams: co2 * energy_megajoules,
+        nox_grams: nox * energy_megajoules,
+        pm25_grams: exhaust_pm25 * energy_megajoules + non_exhaust_pm25 * km,
+        energy_megajoules,
+    }
+}
+
+/// Accumulates the emissions of every vehicle as it crosses lanes and turns. Each lane or turn is
+/// estimated using the vehicle's average speed over it. Vehicles that spawned or parked partway
+/// along a lane haven't crossed all of it, so those partial lanes aren't counted.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct EmissionsTracker {
+    pub fleet_mix: FleetMix,
+    /// (Road, hour block) -> everything emitted there. Turns count towards the road they lead
+    /// to.
+    pub per_road: BTreeMap<(RoadID, usize), Emissions>,
+    /// Only trips by vehicle; buses and trains don't belong to a trip.
+    pub per_trip: BTreeMap<TripID, Emissions>,
+
+    /// What each vehicle is currently crossing, when it started, its trip, and its average speed
+    /// over the previous lane or turn.
+    current: BTreeMap<CarID, (Traversable, Time, Option<TripID>, f64)>,
+}
+
+impl EmissionsTracker {
+    pub(crate) fn event(&mut self, ev: &Event, time: Time, map: &Map) {
+        match ev {
+            Event::AgentEntersTraversable(AgentID::Car(car), trip, on, _) => {
+                let prev = self.current.insert(*car, (*on, time, *trip, 0.0));
+                if let Some((from, start, from_trip, prev_speed)) = prev {
+                    let dist = from.get_polyline(map).length().inner_meters();
+                    let duration = time - start;
+                    if duration > Duration::ZERO {
+                        self.current.get_mut(car).unwrap().3 = dist / duration.inner_seconds();
+                    }
+                    let amount = estimate(
+                        car.vehicle_type,
+                        self.fleet_mix.fuel(*car),
+                        dist,
+                        duration,
+                        prev_speed,
+                    );
+                    let road = match from {
+                        Traversable::Lane(l) => l.road,
+                        Traversable::Turn(t) => t.dst.road,
+                    };
+                    *self
+                        .per_road
+                        .entry((road, time.get_hours()))
+                        .or_insert_with(Emissions::default) += amount;
+                    if let Some(id) = from_trip {
+                        *self.per_trip.entry(id).or_insert_with(Emissions::default) += amount;
+                    }
+                }
+            }
+            Event::CarReachedParkingSpot(car, _) => {
+                self.current.remove(car);
+            }
+            Event::PersonLeavesMap(_, Some(AgentID::Car(car)), _)
+            | Event::PersonStopsAtDockless(_, Some(AgentID::Car(car)), _) => {
+                self.current.remove(car);
+            }
+            _ => {}
+        }
+    }
+
+    /// Everything emitted on each road up to some time.
+    pub fn total_per_road(&self, now: Time) -> BTreeMap<RoadID, Emissions> {
+        let mut result = BTreeMap::new();
+        for ((r, hour), amount) in &self.per_road {
+            if *hour <= now.get_hours() {
+                *result.entry(*r).or_insert_with(Emissions::default) += *amount;
+            }
+        }
+        result
+    }
+
+    /// The total emitted over the whole map during each hour, up to some time.
+    pub fn total_per_hour(&self, now: Time) -> Vec<Emissions> {
+        let mut result = vec![Emissions::default(); now.get_hours() + 1];
+        for ((_, hour), amount) in &self.per_road {
+            if *hour <= now.get_hours() {
+                result[*hour] += *amount;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let km = |kmh: f64| {
+            let duration = Duration::seconds(3600.0 / kmh);
+            estimate(
+                VehicleType::Car,
+                Fuel::Petrol,
+                1000.0,
+                duration,
+                1000.0 / duration.inner_seconds(),
+            )
+        };
+        // A typical petrol car in town emits somewhere around 150g CO2 per km
+        let town = km(50.0);
+        assert!(town.co2_grams > 120.0 && town.co2_grams < 200.0);
+        // Crawling in traffic and driving on a motorway both cost more
+        assert!(km(10.0).co2_grams > town.co2_grams);
+        assert!(km(120.0).co2_grams > town.co2_grams);
+
+        // Speeding up costs extra
+        let from_rest = estimate(
+            VehicleType::Car,
+            Fuel::Petrol,
+            1000.0,
+            Duration::seconds(72.0),
+            0.0,
+        );
+        assert!(from_rest.energy_megajoules > town.energy_megajoules);
+
+        // Electric cars have no exhaust, but still wear down their tyres and brakes
+        let electric = estimate(
+            VehicleType::Car,
+            Fuel::Electric,
+            1000.0,
+            Duration::seconds(72.0),
+            0.0,
+        );
+        assert_eq!(electric.co2_grams, 0.0);
+        assert!(electric.pm25_grams > 0.0);
+        assert!(electric.energy_megajoules < from_rest.energy_megajoules);
+    }
+}
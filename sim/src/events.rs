@@ -1,13 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use geom::Duration;
+use geom::{Distance, Duration, Pt2D};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, TransitRouteID, TransitStopID,
-    Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, Position, TransitRouteID,
+    TransitStopID, Traversable, TurnID,
 };
 use synthpop::TripMode;
 
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID};
+use crate::{
+    AgentID, AgentType, CarID, ParkingSpot, PedestrianID, PersonID, Problem, SafetyConflict, TripID,
+};
 
 /// As a simulation runs, different systems emit Events. This cleanly separates the internal
 /// mechanics of the simulation from consumers that just want to know what's happening.
@@ -21,18 +23,39 @@ use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
+    /// A driver is about to park after searching for a spot. The distance is how far they drove
+    /// past their original destination; it's zero if they found a spot there.
+    CarCruisedForParking(CarID, ParkingSpot, Distance),
 
     BusArrivedAtStop(CarID, TransitRouteID, TransitStopID),
-    BusDepartedFromStop(CarID, TransitRouteID, TransitStopID),
+    /// A timetabled vehicle reached a stop this long after it was scheduled to. Negative if it's
+    /// early.
+    TransitScheduleDeviation(CarID, TransitRouteID, TransitStopID, Duration),
+    /// How many passengers are on board when leaving, out of the vehicle's capacity?
+    BusDepartedFromStop(CarID, TransitRouteID, TransitStopID, usize, usize),
     /// How long waiting at the stop?
     PassengerBoardsTransit(PersonID, CarID, TransitRouteID, TransitStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, TransitRouteID, TransitStopID),
+    /// The vehicle was too full to let this person on, so they keep waiting at the stop.
+    PassengerLeftBehind(PedestrianID, CarID, TransitRouteID, TransitStopID),
+
+    /// How long did the rider wait for the vehicle, from requesting it until getting in?
+    RideHailPickup(PersonID, CarID, Position, Duration),
+    RideHailDropoff(PersonID, CarID, Position),
+    /// A ride-hailing vehicle reached a stop, after driving this far with some number of riders.
+    /// With zero riders, the vehicle drove empty to pick somebody up.
+    RideHailLegFinished(CarID, Distance, usize),
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
     /// None if cancelled
     PersonLeavesMap(PersonID, Option<AgentID>, IntersectionID),
     PersonEntersMap(PersonID, AgentID, IntersectionID),
+    /// Somebody leaves a bike or scooter at a dockless point along a sidewalk, ending their trip
+    /// there. None if cancelled.
+    PersonStopsAtDockless(PersonID, Option<AgentID>, Position),
+    /// Somebody starts a trip from a dockless point along a sidewalk.
+    PersonLeavesDockless(PersonID, AgentID, Position),
 
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
 
@@ -45,6 +68,8 @@ pub enum Event {
     AgentEntersTraversable(AgentID, Option<TripID>, Traversable, Option<usize>),
     /// TripID, TurnID (Where the delay was encountered), Time spent waiting at that turn
     IntersectionDelayMeasured(TripID, TurnID, AgentID, Duration),
+    /// A turning vehicle and a pedestrian or cyclist nearly collided.
+    SafetyConflict(SafetyConflict),
 
     TripFinished {
         trip: TripID,
@@ -59,9 +84,149 @@ pub enum Event {
     /// to plumb info into Analytics is Event.
     PathAmended(Path),
 
+    /// A driver stuck in congestion changed their route. The lane is where they were, and the
+    /// duration is how long they'd been stopped so far during the trip.
+    VehicleRerouted(CarID, Option<TripID>, LaneID, Duration),
+
     Alert(AlertLocation, String),
 }
 
+impl Event {
+    /// A short name for the type of event, matching the variant name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::CarReachedParkingSpot(_, _) => "CarReachedParkingSpot",
+            Event::CarLeftParkingSpot(_, _) => "CarLeftParkingSpot",
+            Event::CarCruisedForParking(_, _, _) => "CarCruisedForParking",
+            Event::BusArrivedAtStop(_, _, _) => "BusArrivedAtStop",
+            Event::TransitScheduleDeviation(_, _, _, _) => "TransitScheduleDeviation",
+            Event::BusDepartedFromStop(_, _, _, _, _) => "BusDepartedFromStop",
+            Event::PassengerBoardsTransit(_, _, _, _, _) => "PassengerBoardsTransit",
+            Event::PassengerAlightsTransit(_, _, _, _) => "PassengerAlightsTransit",
+            Event::PassengerLeftBehind(_, _, _, _) => "PassengerLeftBehind",
+            Event::RideHailPickup(_, _, _, _) => "RideHailPickup",
+            Event::RideHailDropoff(_, _, _) => "RideHailDropoff",
+            Event::RideHailLegFinished(_, _, _) => "RideHailLegFinished",
+            Event::PersonEntersBuilding(_, _) => "PersonEntersBuilding",
+            Event::PersonLeavesBuilding(_, _) => "PersonLeavesBuilding",
+            Event::PersonLeavesMap(_, _, _) => "PersonLeavesMap",
+            Event::PersonEntersMap(_, _, _) => "PersonEntersMap",
+            Event::PersonStopsAtDockless(_, _, _) => "PersonStopsAtDockless",
+            Event::PersonLeavesDockless(_, _, _) => "PersonLeavesDockless",
+            Event::PedReachedParkingSpot(_, _) => "PedReachedParkingSpot",
+            Event::BikeStoppedAtSidewalk(_, _) => "BikeStoppedAtSidewalk",
+            Event::ProblemEncountered(_, _) => "ProblemEncountered",
+            Event::AgentEntersTraversable(_, _, _, _) => "AgentEntersTraversable",
+            Event::IntersectionDelayMeasured(_, _, _, _) => "IntersectionDelayMeasured",
+            Event::SafetyConflict(_) => "SafetyConflict",
+            Event::TripFinished { .. } => "TripFinished",
+            Event::TripCancelled(_, _) => "TripCancelled",
+            Event::TripPhaseStarting(_, _, _, _) => "TripPhaseStarting",
+            Event::PathAmended(_) => "PathAmended",
+            Event::VehicleRerouted(_, _, _, _) => "VehicleRerouted",
+            Event::Alert(_, _) => "Alert",
+        }
+    }
+
+    /// The type of agent involved in this event, if there's exactly one.
+    pub fn agent_type(&self) -> Option<AgentType> {
+        match self {
+            Event::CarReachedParkingSpot(car, _)
+            | Event::CarLeftParkingSpot(car, _)
+            | Event::CarCruisedForParking(car, _, _)
+            | Event::BusArrivedAtStop(car, _, _)
+            | Event::TransitScheduleDeviation(car, _, _, _)
+            | Event::BusDepartedFromStop(car, _, _, _, _)
+            | Event::BikeStoppedAtSidewalk(car, _)
+            | Event::VehicleRerouted(car, _, _, _)
+            | Event::RideHailLegFinished(car, _, _) => Some(AgentID::Car(*car).to_type()),
+            Event::PassengerBoardsTransit(_, _, _, _, _)
+            | Event::PassengerAlightsTransit(_, _, _, _)
+            | Event::RideHailPickup(_, _, _, _)
+            | Event::RideHailDropoff(_, _, _) => Some(AgentType::TransitRider),
+            Event::PersonLeavesMap(_, agent, _) | Event::PersonStopsAtDockless(_, agent, _) => {
+                agent.map(|a| a.to_type())
+            }
+            Event::PersonEntersMap(_, a, _)
+            | Event::PersonLeavesDockless(_, a, _)
+            | Event::AgentEntersTraversable(a, _, _, _)
+            | Event::IntersectionDelayMeasured(_, _, a, _) => Some(a.to_type()),
+            Event::PedReachedParkingSpot(_, _) | Event::PassengerLeftBehind(_, _, _, _) => {
+                Some(AgentType::Pedestrian)
+            }
+            Event::TripFinished { mode, .. } | Event::TripCancelled(_, mode) => Some(match mode {
+                TripMode::Walk => AgentType::Pedestrian,
+                TripMode::Bike | TripMode::Scooter => AgentType::Bike,
+                TripMode::Transit => AgentType::TransitRider,
+                TripMode::Drive | TripMode::ParkAndRide | TripMode::RideHail => AgentType::Car,
+                TripMode::Freight => AgentType::Truck,
+            }),
+            Event::PersonEntersBuilding(_, _)
+            | Event::PersonLeavesBuilding(_, _)
+            | Event::ProblemEncountered(_, _)
+            | Event::SafetyConflict(_)
+            | Event::TripPhaseStarting(_, _, _, _)
+            | Event::PathAmended(_)
+            | Event::Alert(_, _) => None,
+        }
+    }
+
+    /// Returns the rough location where the event happened, if it has one. Like
+    /// `Problem::point`, this is only at the granularity of an entire lane, turn, building, or
+    /// intersection.
+    pub fn point(&self, map: &Map) -> Option<Pt2D> {
+        match self {
+            Event::CarReachedParkingSpot(_, spot)
+            | Event::CarLeftParkingSpot(_, spot)
+            | Event::CarCruisedForParking(_, spot, _)
+            | Event::PedReachedParkingSpot(_, spot) => Some(match spot {
+                ParkingSpot::Onstreet(l, _) => map.get_l(*l).lane_center_pts.middle(),
+                ParkingSpot::Offstreet(b, _) => map.get_b(*b).polygon.center(),
+                ParkingSpot::Lot(pl, _) => map.get_pl(*pl).polygon.center(),
+            }),
+            Event::BusArrivedAtStop(_, _, stop)
+            | Event::TransitScheduleDeviation(_, _, stop, _)
+            | Event::BusDepartedFromStop(_, _, stop, _, _)
+            | Event::PassengerBoardsTransit(_, _, _, stop, _)
+            | Event::PassengerAlightsTransit(_, _, _, stop)
+            | Event::PassengerLeftBehind(_, _, _, stop) => {
+                Some(map.get_ts(*stop).driving_pos.pt(map))
+            }
+            Event::RideHailPickup(_, _, pos, _)
+            | Event::RideHailDropoff(_, _, pos)
+            | Event::PersonStopsAtDockless(_, _, pos)
+            | Event::PersonLeavesDockless(_, _, pos) => Some(pos.pt(map)),
+            Event::PersonEntersBuilding(_, b) | Event::PersonLeavesBuilding(_, b) => {
+                Some(map.get_b(*b).polygon.center())
+            }
+            Event::PersonLeavesMap(_, _, i) | Event::PersonEntersMap(_, _, i) => {
+                Some(map.get_i(*i).polygon.center())
+            }
+            Event::BikeStoppedAtSidewalk(_, l) | Event::VehicleRerouted(_, _, l, _) => {
+                Some(map.get_l(*l).lane_center_pts.middle())
+            }
+            Event::ProblemEncountered(_, problem) => Some(problem.point(map)),
+            Event::AgentEntersTraversable(_, _, on, _) => Some(on.get_polyline(map).middle()),
+            Event::IntersectionDelayMeasured(_, t, _, _) => {
+                Some(map.get_i(t.parent).polygon.center())
+            }
+            Event::SafetyConflict(conflict) => {
+                Some(map.get_i(conflict.vehicle_turn.parent).polygon.center())
+            }
+            Event::Alert(loc, _) => match loc {
+                AlertLocation::Intersection(i) => Some(map.get_i(*i).polygon.center()),
+                AlertLocation::Building(b) => Some(map.get_b(*b).polygon.center()),
+                AlertLocation::Nil | AlertLocation::Person(_) => None,
+            },
+            Event::TripFinished { .. }
+            | Event::TripCancelled(_, _)
+            | Event::TripPhaseStarting(_, _, _, _)
+            | Event::PathAmended(_)
+            | Event::RideHailLegFinished(_, _, _) => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum AlertLocation {
     Nil,
@@ -79,6 +244,8 @@ pub enum TripPhaseType {
     WaitingForBus(TransitRouteID, TransitStopID),
     /// What stop did they board at?
     RidingBus(TransitRouteID, TransitStopID, CarID),
+    WaitingForRideHail,
+    RidingRideHail(CarID),
     Cancelled,
     Finished,
     DelayedStart,
@@ -97,9 +264,28 @@ impl TripPhaseType {
             TripPhaseType::RidingBus(r, _, _) => {
                 format!("Riding route {}", map.get_tr(r).long_name)
             }
+            TripPhaseType::WaitingForRideHail => "Waiting for a ride-hail".to_string(),
+            TripPhaseType::RidingRideHail(_) => "Riding in a ride-hail".to_string(),
             TripPhaseType::Cancelled => "Trip was cancelled due to some bug".to_string(),
             TripPhaseType::Finished => "Trip finished".to_string(),
             TripPhaseType::DelayedStart => "Delayed by a previous trip taking too long".to_string(),
         }
     }
+
+    /// Groups phases of the same kind together, no matter the transit route or stop.
+    pub fn category(self) -> &'static str {
+        match self {
+            TripPhaseType::Driving => "driving",
+            TripPhaseType::Walking => "walking",
+            TripPhaseType::Biking => "biking",
+            TripPhaseType::Parking => "parking",
+            TripPhaseType::WaitingForBus(_, _) => "waiting for transit",
+            TripPhaseType::RidingBus(_, _, _) => "riding transit",
+            TripPhaseType::WaitingForRideHail => "waiting for ride-hail",
+            TripPhaseType::RidingRideHail(_) => "riding ride-hail",
+            TripPhaseType::Cancelled => "cancelled",
+            TripPhaseType::Finished => "finished",
+            TripPhaseType::DelayedStart => "delayed start",
+        }
+    }
 }
@@ -22,7 +22,7 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
-use geom::{Distance, Speed, Time};
+use geom::{Distance, Duration, Speed, Time};
 use map_model::{
     BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints, Position,
     TransitRouteID, TransitStopID,
@@ -34,17 +34,29 @@ pub use crate::render::{
     UnzoomedAgent,
 };
 
-pub use self::analytics::{Analytics, Problem, ProblemType, SlidingWindow, TripPhase};
-pub(crate) use self::events::Event;
-pub use self::events::{AlertLocation, TripPhaseType};
+pub use self::analytics::{
+    Analytics, ConflictMeasure, HeadwayRegularity, Problem, ProblemType, SafetyConflict,
+    SafetySummary, ScheduleAdherence, SlidingWindow, TravelTimes, TripPhase,
+};
+pub use self::emissions::{Emissions, EmissionsTracker, FleetMix, Fuel, Pollutant};
+pub use self::events::{AlertLocation, Event, TripPhaseType};
 pub use self::make::SimFlags;
 pub(crate) use self::make::{StartTripArgs, TripSpec};
+pub use self::mechanics::{
+    CarFollowingModel, Gipps, IntelligentDriver, Kinematic, LongitudinalModel, SignalController,
+    SignalDecision, SignalStatus,
+};
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
 };
+pub use self::noise::{NoiseLevels, HIGH_NOISE_THRESHOLD, NOISE_THRESHOLD};
 pub(crate) use self::pandemic::PandemicModel;
 pub use self::prebake::PrebakeSummary;
 pub(crate) use self::recorder::TrafficRecorder;
+pub(crate) use self::ridehail::RideHailSimState;
+pub use self::ridehail::{
+    DispatchPolicy, Dispatcher, FleetVehicle, NearestIdle, Pooled, RideRequest, RideStop,
+};
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{
@@ -52,18 +64,25 @@ pub use self::sim::{
     SimCallback, SimOptions,
 };
 pub(crate) use self::transit::TransitSimState;
+pub use self::transit::MIN_DWELL_TIME;
 pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, TripResult};
 pub(crate) use self::trips::{TripLeg, TripManager};
 pub use synthpop::make::{fork_rng, BorderSpawnOverTime, ScenarioGenerator, SpawnOverTime};
 
 mod analytics;
+pub mod assignment;
+pub mod calibrate;
+mod emissions;
 mod events;
 mod make;
 mod mechanics;
+mod noise;
+pub mod optimize_signals;
 mod pandemic;
 pub mod prebake;
 mod recorder;
 mod render;
+mod ridehail;
 mod router;
 mod scheduler;
 mod sim;
@@ -77,6 +96,14 @@ pub(crate) const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub(crate) const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 pub(crate) const LIGHT_RAIL_LENGTH: Distance = Distance::const_meters(60.0);
+pub(crate) const TRAM_LENGTH: Distance = Distance::const_meters(30.0);
+// A rigid box truck, common for last-mile deliveries
+pub(crate) const TRUCK_LENGTH: Distance = Distance::const_meters(8.0);
+// Most shared e-scooters are a bit over a meter long
+pub(crate) const SCOOTER_LENGTH: Distance = Distance::const_meters(1.2);
+// Only used to judge how close vehicles pass cyclists
+pub(crate) const CAR_WIDTH: Distance = Distance::const_meters(1.75);
+pub(crate) const BIKE_WIDTH: Distance = Distance::const_meters(0.6);
 
 /// At all speeds (including at rest), cars must be at least this far apart, measured from front of
 /// one car to the back of the other.
@@ -105,7 +132,10 @@ impl fmt::Display for CarID {
             VehicleType::Car => write!(f, "Car #{}", self.id),
             VehicleType::Bus => write!(f, "Bus #{}", self.id),
             VehicleType::Train => write!(f, "Train #{}", self.id),
+            VehicleType::Tram => write!(f, "Tram #{}", self.id),
             VehicleType::Bike => write!(f, "Bike #{}", self.id),
+            VehicleType::Truck => write!(f, "Truck #{}", self.id),
+            VehicleType::Scooter => write!(f, "Scooter #{}", self.id),
         }
     }
 }
@@ -148,6 +178,11 @@ impl AgentID {
                 VehicleType::Bike => AgentType::Bike,
                 VehicleType::Bus => AgentType::Bus,
                 VehicleType::Train => AgentType::Train,
+                // Trams run on rails, so count them with trains
+                VehicleType::Tram => AgentType::Train,
+                VehicleType::Truck => AgentType::Truck,
+                // Scooters share bike lanes, so count them with bikes
+                VehicleType::Scooter => AgentType::Bike,
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
             AgentID::BusPassenger(_, _) => AgentType::TransitRider,
@@ -194,12 +229,15 @@ pub enum AgentType {
     Train,
     Pedestrian,
     TransitRider,
+    // Declared last, so serialized results from before trucks were distinguished still load
+    Truck,
 }
 
 impl AgentType {
     pub fn all() -> Vec<AgentType> {
         vec![
             AgentType::Car,
+            AgentType::Truck,
             AgentType::Bike,
             AgentType::Bus,
             AgentType::Train,
@@ -211,6 +249,7 @@ impl AgentType {
     pub fn noun(self) -> &'static str {
         match self {
             AgentType::Car => "Car",
+            AgentType::Truck => "Truck",
             AgentType::Bike => "Bike",
             AgentType::Bus => "Bus",
             AgentType::Train => "Train",
@@ -222,6 +261,7 @@ impl AgentType {
     pub fn plural_noun(self) -> &'static str {
         match self {
             AgentType::Car => "cars",
+            AgentType::Truck => "trucks",
             AgentType::Bike => "bikes",
             AgentType::Bus => "buses",
             AgentType::Train => "trains",
@@ -233,6 +273,7 @@ impl AgentType {
     pub fn ongoing_verb(self) -> &'static str {
         match self {
             AgentType::Car => "driving",
+            AgentType::Truck => "delivering",
             AgentType::Bike => "biking",
             AgentType::Bus | AgentType::Train => unreachable!(),
             AgentType::Pedestrian => "walking",
@@ -278,7 +319,10 @@ pub enum VehicleType {
     Car,
     Bus,
     Train,
+    Tram,
     Bike,
+    Truck,
+    Scooter,
 }
 
 impl fmt::Display for VehicleType {
@@ -287,7 +331,10 @@ impl fmt::Display for VehicleType {
             VehicleType::Car => write!(f, "car"),
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Train => write!(f, "train"),
+            VehicleType::Tram => write!(f, "tram"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Truck => write!(f, "truck"),
+            VehicleType::Scooter => write!(f, "scooter"),
         }
     }
 }
@@ -298,7 +345,11 @@ impl VehicleType {
             VehicleType::Car => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Train => PathConstraints::Train,
+            // Trams run on the street
+            VehicleType::Tram => PathConstraints::Bus,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Truck => PathConstraints::Truck,
+            VehicleType::Scooter => PathConstraints::Bike,
         }
     }
 
@@ -307,9 +358,17 @@ impl VehicleType {
             VehicleType::Car => false,
             VehicleType::Bus => true,
             VehicleType::Train => true,
+            VehicleType::Tram => true,
             VehicleType::Bike => false,
+            VehicleType::Truck => false,
+            VehicleType::Scooter => false,
         }
     }
+
+    /// Bikes and scooters ride in bike lanes and stop along the sidewalk, instead of parking.
+    pub fn rides_bike_lanes(self) -> bool {
+        matches!(self, VehicleType::Bike | VehicleType::Scooter)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -361,13 +420,19 @@ pub struct ParkedCar {
 pub(crate) enum DrivingGoal {
     ParkNear(BuildingID),
     Border(IntersectionID, LaneID),
+    /// Stop in the lane in front of the building for some time, then continue to the next goal
+    Deliver(BuildingID, Duration),
+    /// Leave a bike or scooter at a dockless point along this sidewalk position
+    Dockless(Position),
+    /// Park somewhere in this lot, then continue the trip on foot or by transit
+    ParkInLot(ParkingLotID),
 }
 
 impl DrivingGoal {
     pub fn goal_pos(&self, constraints: PathConstraints, map: &Map) -> Option<Position> {
         match self {
             DrivingGoal::ParkNear(b) => match constraints {
-                PathConstraints::Car => {
+                PathConstraints::Car | PathConstraints::Truck => {
                     let driving_lane = map.find_driving_lane_near_building(*b);
                     let sidewalk_pos = map.get_b(*b).sidewalk_pos;
                     if driving_lane.road == sidewalk_pos.lane().road {
@@ -382,13 +447,28 @@ impl DrivingGoal {
                 }
             },
             DrivingGoal::Border(_, l) => Some(Position::end(*l, map)),
+            DrivingGoal::Deliver(b, _) => DrivingGoal::ParkNear(*b).goal_pos(constraints, map),
+            DrivingGoal::Dockless(pos) => {
+                if constraints == PathConstraints::Bike {
+                    map.find_biking_pos_near_sidewalk(*pos)
+                } else {
+                    None
+                }
+            }
+            DrivingGoal::ParkInLot(lot) => {
+                if constraints == PathConstraints::Car {
+                    Some(map.get_pl(*lot).driving_pos)
+                } else {
+                    None
+                }
+            }
         }
     }
 
     pub fn make_router(&self, owner: CarID, path: Path, map: &Map) -> Router {
         match self {
             DrivingGoal::ParkNear(b) => {
-                if owner.vehicle_type == VehicleType::Bike {
+                if owner.vehicle_type.rides_bike_lanes() {
                     Router::bike_then_stop(owner, path, SidewalkSpot::bike_rack(*b, map).unwrap())
                 } else {
                     Router::park_near(owner, path, *b)
@@ -397,6 +477,11 @@ impl DrivingGoal {
             DrivingGoal::Border(i, last_lane) => {
                 Router::end_at_border(owner, path, map.get_l(*last_lane).length(), *i)
             }
+            DrivingGoal::Deliver(_, dwell) => Router::deliver(owner, path, *dwell),
+            DrivingGoal::Dockless(pos) => {
+                Router::bike_then_stop(owner, path, SidewalkSpot::dockless_rack(*pos, map).unwrap())
+            }
+            DrivingGoal::ParkInLot(lot) => Router::park_in_lot(owner, path, *lot),
         }
     }
 }
@@ -420,6 +505,8 @@ pub(crate) enum SidewalkPOI {
     /// The bikeable position
     BikeRack(Position),
     SuddenlyAppear,
+    /// Somebody starts or ends a trip here, picking up or dropping off a dockless scooter or bike
+    Dockless,
 }
 
 impl SidewalkSpot {
@@ -459,6 +546,23 @@ impl SidewalkSpot {
         })
     }
 
+    /// Where somebody picks up or drops off a dockless scooter or bike
+    pub fn dockless_rack(sidewalk_pos: Position, map: &Map) -> Option<SidewalkSpot> {
+        Some(SidewalkSpot {
+            connection: SidewalkPOI::BikeRack(map.find_biking_pos_near_sidewalk(sidewalk_pos)?),
+            sidewalk_pos,
+        })
+    }
+
+    /// Where somebody walks to or from, at the start or end of a trip beginning or ending at a
+    /// dockless point
+    pub fn dockless(sidewalk_pos: Position) -> SidewalkSpot {
+        SidewalkSpot {
+            connection: SidewalkPOI::Dockless,
+            sidewalk_pos,
+        }
+    }
+
     pub fn bus_stop(stop: TransitStopID, map: &Map) -> SidewalkSpot {
         SidewalkSpot {
             sidewalk_pos: map.get_ts(stop).sidewalk_pos,
@@ -560,6 +664,8 @@ pub(crate) struct CreatePedestrian {
     pub id: PedestrianID,
     pub start: SidewalkSpot,
     pub speed: Speed,
+    /// Riding a scooter along sidewalks, instead of walking
+    pub on_scooter: bool,
     pub goal: SidewalkSpot,
     pub path: Path,
     pub trip: TripID,
@@ -4,7 +4,7 @@ use rand_xorshift::XorShiftRng;
 use structopt::StructOpt;
 
 use abstio::MapName;
-use map_model::{Map, MapEdits};
+use map_model::{EditCmd, Map, MapEdits, ParkingPricing};
 use synthpop::{Scenario, ScenarioModifier};
 
 use crate::{Sim, SimOptions};
@@ -32,6 +32,9 @@ pub struct SimFlags {
     // TODO default_value can only handle strings, so copying SimFlags::RNG_SEED
     #[structopt(long, default_value = "42")]
     pub rng_seed: u64,
+    /// A JSON file with parking prices and time limits, applied to the map as an edit.
+    #[structopt(long)]
+    pub parking_pricing: Option<String>,
     #[structopt(flatten)]
     pub opts: SimOptions,
 }
@@ -61,6 +64,7 @@ impl SimFlags {
             load: MapName::seattle("montlake").path(),
             scenario_modifiers: Vec::new(),
             rng_seed: SimFlags::RNG_SEED,
+            parking_pricing: None,
             opts: SimOptions::new(run_name),
         }
     }
@@ -105,7 +109,8 @@ impl SimFlags {
 
             let mut scenario: Scenario = abstio::must_read_object(self.load.clone(), timer);
 
-            let map = Map::load_synchronously(scenario.map_name.path(), timer);
+            let mut map = Map::load_synchronously(scenario.map_name.path(), timer);
+            self.apply_parking_pricing(&mut map, timer);
 
             for m in &self.scenario_modifiers {
                 scenario = m.apply(&map, scenario, &mut rng);
@@ -121,7 +126,8 @@ impl SimFlags {
         } else if self.load.contains("/raw_maps/") || self.load.contains("/maps/") {
             info!("Loading map {}", self.load);
 
-            let map = Map::load_synchronously(self.load.clone(), timer);
+            let mut map = Map::load_synchronously(self.load.clone(), timer);
+            self.apply_parking_pricing(&mut map, timer);
 
             timer.start("create sim");
             let sim = Sim::new(&map, opts);
@@ -132,4 +138,21 @@ impl SimFlags {
             panic!("Don't know how to load {}", self.load);
         }
     }
+
+    fn apply_parking_pricing(&self, map: &mut Map, timer: &mut abstutil::Timer) {
+        if let Some(ref path) = self.parking_pricing {
+            let pricing = match ParkingPricing::load(path.clone(), timer) {
+                Ok(pricing) => pricing,
+                Err(err) => {
+                    panic!("Couldn't load parking prices from {}: {}", path, err);
+                }
+            };
+            let mut edits = map.get_edits().clone();
+            edits.commands.push(EditCmd::ChangeParkingPricing {
+                old: map.get_parking_pricing().clone(),
+                new: pricing,
+            });
+            map.must_apply_edits(edits, timer);
+        }
+    }
 }
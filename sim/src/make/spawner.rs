@@ -4,10 +4,14 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use map_model::{BuildingID, Map, PathConstraints, Position, TransitRouteID, TransitStopID};
-use synthpop::{TripEndpoint, TripMode};
+use geom::Duration;
+use map_model::{
+    BuildingID, Map, ParkingLotID, PathConstraints, Position, TransitRouteID, TransitStopID,
+    WalkingProfile,
+};
+use synthpop::{DeliveryStop, TripEndpoint, TripMode};
 
-use crate::{CarID, DrivingGoal, SidewalkSpot, TripLeg, VehicleType, SPAWN_DIST};
+use crate::{CarID, DrivingGoal, SidewalkSpot, TripLeg, SPAWN_DIST};
 
 /// We need to remember a few things from scenario instantiation that're used for starting the
 /// trip.
@@ -42,6 +46,8 @@ pub(crate) enum TripSpec {
     JustWalking {
         start: SidewalkSpot,
         goal: SidewalkSpot,
+        /// On some maps, scooters ride along sidewalks like a fast pedestrian
+        scooter: Option<CarID>,
     },
     UsingBike {
         bike: CarID,
@@ -55,6 +61,41 @@ pub(crate) enum TripSpec {
         stop1: TransitStopID,
         maybe_stop2: Option<TransitStopID>,
     },
+    /// Drive to a parking lot, then walk to a nearby stop and ride transit.
+    DriveThenTransit {
+        /// This must be a currently parked vehicle owned by the person.
+        car: CarID,
+        start_bldg: BuildingID,
+        lot: ParkingLotID,
+        goal: SidewalkSpot,
+        route: TransitRouteID,
+        stop1: TransitStopID,
+        maybe_stop2: Option<TransitStopID>,
+    },
+    /// Ride transit back to where the car was left, then drive the rest of the way.
+    TransitThenDrive {
+        start: SidewalkSpot,
+        /// This must be a currently parked vehicle owned by the person.
+        car: CarID,
+        route: TransitRouteID,
+        stop1: TransitStopID,
+        stop2: TransitStopID,
+        goal: DrivingGoal,
+    },
+    /// Trucks don't start parked anywhere; they appear on the road, even when leaving a building.
+    Delivering {
+        start_pos: Position,
+        truck: CarID,
+        stops: Vec<DeliveryStop>,
+        goal: DrivingGoal,
+        retry_if_no_room: bool,
+    },
+    /// Wait inside for an on-demand vehicle, ride it, and get dropped off outside the destination.
+    RideHailing {
+        start_bldg: BuildingID,
+        pickup: Position,
+        dropoff: Position,
+    },
 }
 
 impl TripSpec {
@@ -82,11 +123,7 @@ impl TripSpec {
                     }
                 }
 
-                let constraints = if use_vehicle.vehicle_type == VehicleType::Bike {
-                    PathConstraints::Bike
-                } else {
-                    PathConstraints::Car
-                };
+                let constraints = use_vehicle.vehicle_type.to_constraints();
 
                 legs.push(TripLeg::Drive(*use_vehicle, goal.clone()));
                 if let DrivingGoal::ParkNear(b) = goal {
@@ -114,7 +151,10 @@ impl TripSpec {
                     DrivingGoal::ParkNear(b) => {
                         legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
                     }
-                    DrivingGoal::Border(_, _) => {}
+                    DrivingGoal::Border(_, _)
+                    | DrivingGoal::Deliver(_, _)
+                    | DrivingGoal::Dockless(_)
+                    | DrivingGoal::ParkInLot(_) => {}
                 }
             }
             TripSpec::JustWalking { start, goal, .. } => {
@@ -132,13 +172,21 @@ impl TripSpec {
                     DrivingGoal::ParkNear(b) => Some(TripSpec::JustWalking {
                         start: SidewalkSpot::building(*start, map),
                         goal: SidewalkSpot::building(*b, map),
+                        scooter: None,
                     }),
                     DrivingGoal::Border(i, _) => {
                         SidewalkSpot::end_at_border(*i, map).map(|goal| TripSpec::JustWalking {
                             start: SidewalkSpot::building(*start, map),
                             goal,
+                            scooter: None,
                         })
                     }
+                    DrivingGoal::Dockless(pos) => Some(TripSpec::JustWalking {
+                        start: SidewalkSpot::building(*start, map),
+                        goal: SidewalkSpot::dockless(*pos),
+                        scooter: None,
+                    }),
+                    DrivingGoal::Deliver(_, _) | DrivingGoal::ParkInLot(_) => unreachable!(),
                 };
 
                 if let Some(start_spot) = SidewalkSpot::bike_rack(*start, map) {
@@ -167,7 +215,11 @@ impl TripSpec {
                         DrivingGoal::ParkNear(b) => {
                             legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
                         }
-                        DrivingGoal::Border(_, _) => {}
+                        // The trip ends once the bike or scooter is left at the dockless point
+                        DrivingGoal::Border(_, _)
+                        | DrivingGoal::Deliver(_, _)
+                        | DrivingGoal::Dockless(_)
+                        | DrivingGoal::ParkInLot(_) => {}
                     }
                 } else if let Some(plan) = backup_plan {
                     info!("Can't start biking from {}. Walking instead", start);
@@ -201,6 +253,74 @@ impl TripSpec {
                     legs = vec![TripLeg::Walk(walk_to), TripLeg::RideBus(*route, None)];
                 }
             }
+            TripSpec::DriveThenTransit {
+                car,
+                lot,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+                ..
+            } => {
+                legs.push(TripLeg::Walk(SidewalkSpot::deferred_parking_spot()));
+                legs.push(TripLeg::Drive(*car, DrivingGoal::ParkInLot(*lot)));
+                legs.push(TripLeg::Walk(SidewalkSpot::bus_stop(*stop1, map)));
+                legs.push(TripLeg::RideBus(*route, *maybe_stop2));
+                if maybe_stop2.is_some() {
+                    legs.push(TripLeg::Walk(goal.clone()));
+                }
+            }
+            TripSpec::TransitThenDrive {
+                car,
+                route,
+                stop1,
+                stop2,
+                goal,
+                ..
+            } => {
+                if goal.goal_pos(PathConstraints::Car, map).is_none() {
+                    return TripSpec::SpawningFailure {
+                        use_vehicle: None,
+                        error: format!("goal_pos to {:?} for a car failed", goal),
+                    }
+                    .into_plan(map);
+                }
+
+                legs.push(TripLeg::Walk(SidewalkSpot::bus_stop(*stop1, map)));
+                legs.push(TripLeg::RideBus(*route, Some(*stop2)));
+                // Wherever the car is parked is figured out after getting off transit
+                legs.push(TripLeg::Walk(SidewalkSpot::deferred_parking_spot()));
+                legs.push(TripLeg::Drive(*car, goal.clone()));
+                if let DrivingGoal::ParkNear(b) = goal {
+                    legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
+                }
+            }
+            TripSpec::Delivering {
+                truck, stops, goal, ..
+            } => {
+                for stop in stops {
+                    legs.push(TripLeg::Drive(
+                        *truck,
+                        DrivingGoal::Deliver(stop.building, stop.dwell),
+                    ));
+                }
+                legs.push(TripLeg::Drive(*truck, goal.clone()));
+
+                for leg in &legs {
+                    if let TripLeg::Drive(_, goal) = leg {
+                        if goal.goal_pos(PathConstraints::Truck, map).is_none() {
+                            return TripSpec::SpawningFailure {
+                                use_vehicle: Some(*truck),
+                                error: format!("goal_pos to {:?} for a truck failed", goal),
+                            }
+                            .into_plan(map);
+                        }
+                    }
+                }
+            }
+            TripSpec::RideHailing { dropoff, .. } => {
+                legs.push(TripLeg::RideHail(*dropoff));
+            }
         };
 
         (self, legs)
@@ -213,20 +333,19 @@ impl TripSpec {
         to: TripEndpoint,
         mode: TripMode,
         use_vehicle: Option<CarID>,
+        delivery_stops: &[DeliveryStop],
         retry_if_no_room: bool,
+        walking_profile: Option<&WalkingProfile>,
         map: &Map,
     ) -> Result<TripSpec> {
         Ok(match mode {
-            TripMode::Drive | TripMode::Bike => {
-                let constraints = if mode == TripMode::Drive {
-                    PathConstraints::Car
-                } else {
-                    PathConstraints::Bike
-                };
+            // Park-and-ride is planned separately; without any parking lots, it's just driving
+            TripMode::Drive | TripMode::Bike | TripMode::Scooter | TripMode::ParkAndRide => {
+                let constraints = use_vehicle.unwrap().vehicle_type.to_constraints();
                 let goal = driving_goal(to, constraints, map)?;
                 match from {
                     TripEndpoint::Building(start_bldg) => {
-                        if mode == TripMode::Drive {
+                        if constraints == PathConstraints::Car {
                             TripSpec::UsingParkedCar {
                                 start_bldg,
                                 goal,
@@ -263,17 +382,48 @@ impl TripSpec {
                         use_vehicle: use_vehicle.unwrap(),
                         retry_if_no_room,
                     },
+                    // Pick up the bike or scooter right there
+                    TripEndpoint::Dockless(pos) => TripSpec::VehicleAppearing {
+                        start_pos: dockless_start_pos(pos, constraints, map)?,
+                        goal,
+                        use_vehicle: use_vehicle.unwrap(),
+                        retry_if_no_room,
+                    },
                 }
             }
+            TripMode::Freight => delivering(
+                from,
+                to,
+                use_vehicle.unwrap(),
+                delivery_stops,
+                retry_if_no_room,
+                map,
+            )?,
+            TripMode::RideHail => match (from, to) {
+                (TripEndpoint::Building(start_bldg), TripEndpoint::Building(goal_bldg)) => {
+                    let pickup = curbside_pos(start_bldg, map)?;
+                    let dropoff = curbside_pos(goal_bldg, map)?;
+                    if pickup == dropoff {
+                        bail!("{} and {} share the same curb", start_bldg, goal_bldg);
+                    }
+                    TripSpec::RideHailing {
+                        start_bldg,
+                        pickup,
+                        dropoff,
+                    }
+                }
+                _ => bail!("ride-hailing only goes between buildings"),
+            },
             TripMode::Walk => TripSpec::JustWalking {
                 start: start_sidewalk_spot(from, map)?,
                 goal: end_sidewalk_spot(to, map)?,
+                scooter: None,
             },
             TripMode::Transit => {
                 let start = start_sidewalk_spot(from, map)?;
                 let goal = end_sidewalk_spot(to, map)?;
                 if let Some((stop1, maybe_stop2, route)) =
-                    map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
+                    map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos, walking_profile)
                 {
                     TripSpec::UsingTransit {
                         start,
@@ -285,11 +435,161 @@ impl TripSpec {
                 } else {
                     //warn!("{:?} not actually using transit, because pathfinding didn't find any
                     // useful route", trip);
-                    TripSpec::JustWalking { start, goal }
+                    TripSpec::JustWalking {
+                        start,
+                        goal,
+                        scooter: None,
+                    }
                 }
             }
         })
     }
+
+    /// On maps where scooters ride along sidewalks, the rider is simulated like a fast pedestrian
+    /// going straight from the start to the end.
+    pub fn scooting_on_sidewalks(
+        from: TripEndpoint,
+        to: TripEndpoint,
+        scooter: CarID,
+        map: &Map,
+    ) -> Result<TripSpec> {
+        Ok(TripSpec::JustWalking {
+            start: start_sidewalk_spot(from, map)?,
+            goal: end_sidewalk_spot(to, map)?,
+            scooter: Some(scooter),
+        })
+    }
+
+    /// A park-and-ride trip drives to a parking lot near transit, then rides from there. The trip
+    /// back rides transit to wherever the car was left, then drives the rest of the way. Without a
+    /// useful lot or transit route, the person just drives.
+    pub fn park_and_ride(
+        from: TripEndpoint,
+        to: TripEndpoint,
+        car: CarID,
+        returning: bool,
+        car_parked_at: Option<SidewalkSpot>,
+        walking_profile: Option<&WalkingProfile>,
+        map: &Map,
+    ) -> Result<TripSpec> {
+        if returning {
+            let car_parked_at = car_parked_at
+                .ok_or_else(|| anyhow!("{} isn't parked anywhere to return to", car))?;
+            let start = start_sidewalk_spot(from, map)?;
+            let goal = driving_goal(to, PathConstraints::Car, map)?;
+            if let Some((stop1, Some(stop2), route)) = map.should_use_transit(
+                start.sidewalk_pos,
+                car_parked_at.sidewalk_pos,
+                walking_profile,
+            ) {
+                return Ok(TripSpec::TransitThenDrive {
+                    start,
+                    car,
+                    route,
+                    stop1,
+                    stop2,
+                    goal,
+                });
+            }
+            // Just walk back to the car
+            return match from {
+                TripEndpoint::Building(start_bldg) => Ok(TripSpec::UsingParkedCar {
+                    car,
+                    start_bldg,
+                    goal,
+                }),
+                _ => bail!("can't get back to {} from {:?}", car, from),
+            };
+        }
+
+        let start_bldg = match from {
+            TripEndpoint::Building(b) => b,
+            _ => bail!(
+                "park-and-ride trips must start from a building, not {:?}",
+                from
+            ),
+        };
+        let goal = end_sidewalk_spot(to, map)?;
+        if let Some((lot, stop1, maybe_stop2, route)) = DrivingGoal::ParkNear(start_bldg)
+            .goal_pos(PathConstraints::Car, map)
+            .and_then(|start_pos| {
+                map.should_park_and_ride(start_pos, goal.sidewalk_pos, walking_profile)
+            })
+        {
+            return Ok(TripSpec::DriveThenTransit {
+                car,
+                start_bldg,
+                lot,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            });
+        }
+        Ok(TripSpec::UsingParkedCar {
+            car,
+            start_bldg,
+            goal: driving_goal(to, PathConstraints::Car, map)?,
+        })
+    }
+}
+
+fn delivering(
+    from: TripEndpoint,
+    to: TripEndpoint,
+    truck: CarID,
+    stops: &[DeliveryStop],
+    retry_if_no_room: bool,
+    map: &Map,
+) -> Result<TripSpec> {
+    let constraints = PathConstraints::Truck;
+    let start_pos = match from {
+        // Leave from the front of the depot
+        TripEndpoint::Building(b) => {
+            let pos = DrivingGoal::ParkNear(b)
+                .goal_pos(constraints, map)
+                .ok_or_else(|| anyhow!("can't start a truck from {}", b))?;
+            // Like at borders, don't start with the front of the vehicle right at the lane's start
+            if pos.dist_along() < SPAWN_DIST {
+                Position::new(pos.lane(), SPAWN_DIST)
+            } else {
+                pos
+            }
+        }
+        TripEndpoint::Border(i) => map
+            .get_i(i)
+            .some_outgoing_road(map)
+            .and_then(|dr| dr.lanes(constraints, map).pop())
+            .map(|l| Position::new(l, SPAWN_DIST))
+            .ok_or_else(|| anyhow!("can't start a truck from {}", i))?,
+        TripEndpoint::SuddenlyAppear(pos) => pos,
+        TripEndpoint::Dockless(_) => bail!("trucks can't start from a dockless point"),
+    };
+    let goal = match to {
+        // Pull into the depot, without needing to find parking
+        TripEndpoint::Building(b) => DrivingGoal::Deliver(b, Duration::ZERO),
+        _ => driving_goal(to, constraints, map)?,
+    };
+    Ok(TripSpec::Delivering {
+        start_pos,
+        truck,
+        stops: stops.to_vec(),
+        goal,
+        retry_if_no_room,
+    })
+}
+
+/// Where on the road a ride-hailing vehicle stops in front of a building
+fn curbside_pos(b: BuildingID, map: &Map) -> Result<Position> {
+    let pos = DrivingGoal::ParkNear(b)
+        .goal_pos(PathConstraints::Car, map)
+        .ok_or_else(|| anyhow!("no road to pick up or drop off at {}", b))?;
+    // Like at borders, don't stop with the front of the vehicle right at the lane's start
+    Ok(if pos.dist_along() < SPAWN_DIST {
+        Position::new(pos.lane(), SPAWN_DIST)
+    } else {
+        pos
+    })
 }
 
 fn start_sidewalk_spot(endpt: TripEndpoint, map: &Map) -> Result<SidewalkSpot> {
@@ -298,6 +598,7 @@ fn start_sidewalk_spot(endpt: TripEndpoint, map: &Map) -> Result<SidewalkSpot> {
         TripEndpoint::Border(i) => SidewalkSpot::start_at_border(i, map)
             .ok_or_else(|| anyhow!("can't start walking from {}", i)),
         TripEndpoint::SuddenlyAppear(pos) => Ok(SidewalkSpot::suddenly_appear(pos, map)),
+        TripEndpoint::Dockless(pos) => Ok(SidewalkSpot::dockless(pos)),
     }
 }
 
@@ -307,10 +608,35 @@ fn end_sidewalk_spot(endpt: TripEndpoint, map: &Map) -> Result<SidewalkSpot> {
         TripEndpoint::Border(i) => {
             SidewalkSpot::end_at_border(i, map).ok_or_else(|| anyhow!("can't end walking at {}", i))
         }
+        TripEndpoint::Dockless(pos) => Ok(SidewalkSpot::dockless(pos)),
         TripEndpoint::SuddenlyAppear(_) => unreachable!(),
     }
 }
 
+fn dockless_start_pos(
+    sidewalk_pos: Position,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Result<Position> {
+    if constraints != PathConstraints::Bike {
+        bail!("only bikes and scooters can start from a dockless point");
+    }
+    let pos = map
+        .find_biking_pos_near_sidewalk(sidewalk_pos)
+        .ok_or_else(|| {
+            anyhow!(
+                "no lane to ride from the dockless point at {}",
+                sidewalk_pos
+            )
+        })?;
+    // Like at borders, don't start with the front of the vehicle right at the lane's start
+    Ok(if pos.dist_along() < SPAWN_DIST {
+        Position::new(pos.lane(), SPAWN_DIST)
+    } else {
+        pos
+    })
+}
+
 fn driving_goal(
     endpt: TripEndpoint,
     constraints: PathConstraints,
@@ -332,6 +658,13 @@ fn driving_goal(
                 }
             })
             .ok_or_else(|| anyhow!("can't end at {} for {:?}", i, constraints)),
+        TripEndpoint::Dockless(pos) => {
+            if constraints == PathConstraints::Bike {
+                Ok(DrivingGoal::Dockless(pos))
+            } else {
+                bail!("only bikes and scooters can end at a dockless point")
+            }
+        }
         TripEndpoint::SuddenlyAppear(_) => unreachable!(),
     }
 }
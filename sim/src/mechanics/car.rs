@@ -2,12 +2,12 @@ use std::collections::{BTreeSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration, PolyLine, Time, EPSILON_DIST};
+use geom::{Distance, Duration, PolyLine, Speed, Time, EPSILON_DIST};
 use map_model::{Direction, LaneID, Map, Traversable};
 
 use crate::{
-    CarID, CarStatus, DistanceInterval, DrawCarInput, Intent, ParkingSpot, PersonID, Router,
-    TimeInterval, TransitSimState, TripID, Vehicle, VehicleType,
+    CarFollowingModel, CarID, CarStatus, DistanceInterval, DrawCarInput, Intent, ParkingSpot,
+    PersonID, Router, TimeInterval, TransitSimState, TripID, Vehicle, VehicleType,
 };
 
 /// Represents a single vehicle. Note "car" is a misnomer; it could also be a bus or bike.
@@ -26,9 +26,18 @@ pub(crate) struct Car {
     /// vehicle.length.
     pub last_steps: VecDeque<Traversable>,
 
-    /// Since lane over-taking isn't implemented yet, a vehicle tends to be stuck behind a slow
-    /// leader for a while. Avoid duplicate events.
+    /// A vehicle may be stuck behind a slow leader for a while before there's a chance to
+    /// overtake. Avoid duplicate events.
     pub wants_to_overtake: BTreeSet<CarID>,
+
+    /// The value of total_blocked_time when this car last considered rerouting. Avoids rerouting
+    /// over and over in the same jam.
+    pub blocked_time_at_last_reroute: Duration,
+
+    /// How this vehicle speeds up
+    pub car_following: CarFollowingModel,
+    /// How fast the vehicle was going at the end of its last crossing
+    pub speed: Speed,
 }
 
 impl Car {
@@ -56,20 +65,50 @@ impl Car {
         start_time: Time,
         map: &Map,
     ) -> CarState {
-        let (speed, percent_incline) = self
-            .router
-            .get_path()
-            .current_step()
-            .max_speed_and_incline_along(
+        let step = self.router.get_path().current_step();
+        let (speed, percent_incline) = if self.vehicle.vehicle_type == VehicleType::Scooter {
+            step.max_scooter_speed_and_incline_along(self.vehicle.max_speed.unwrap(), map)
+        } else {
+            step.max_speed_and_incline_along(
                 self.vehicle.max_speed,
                 self.vehicle.vehicle_type.to_constraints(),
                 map,
+            )
+        };
+        let (dt, end_speed) = self
+            .car_following
+            .for_vehicle(self.vehicle.vehicle_type)
+            .cross(
+                dist_int.end - dist_int.start,
+                self.initial_speed(start_time),
+                speed,
             );
-        let dt = (dist_int.end - dist_int.start) / speed;
         CarState::Crossing {
             time_int: TimeInterval::new(start_time, start_time + dt),
             dist_int,
             steep_uphill: percent_incline >= 0.08,
+            end_speed,
+        }
+    }
+
+    /// How fast is the vehicle going when it starts a new crossing? This depends on the state it's
+    /// leaving.
+    fn initial_speed(&self, start_time: Time) -> Speed {
+        match self.state {
+            CarState::Crossing { end_speed, .. } => end_speed,
+            CarState::Queued { blocked_since, .. }
+            | CarState::WaitingToAdvance { blocked_since } => {
+                // Only vehicles that actually stopped have to start again from rest
+                if blocked_since < start_time {
+                    Speed::ZERO
+                } else {
+                    self.speed
+                }
+            }
+            CarState::ChangingLanes { .. } | CarState::Passing { .. } => self.speed,
+            CarState::Unparking { .. }
+            | CarState::Parking(_, _, _)
+            | CarState::IdlingAtStop(_, _) => Speed::ZERO,
         }
     }
 
@@ -173,6 +212,26 @@ impl Car {
                     }
                 }
             }
+            CarState::Passing { oncoming, .. } => {
+                // Stay over in the oncoming lane for the whole pass
+                let current = map.get_l(self.router.head().as_lane());
+                let mut diff = (oncoming.offset as isize) - (current.id.offset as isize);
+                if current.dir == Direction::Back {
+                    diff *= -1;
+                }
+                // TODO Same width caveat as parking
+                let width = map.get_l(oncoming).width * (diff as f64);
+                match raw_body.shift_right(width) {
+                    Ok(pl) => pl,
+                    Err(err) => {
+                        println!(
+                            "Body for passing {} at {} broken: {}",
+                            self.vehicle.id, now, err
+                        );
+                        raw_body
+                    }
+                }
+            }
             CarState::Unparking {
                 ref spot,
                 ref time_int,
@@ -276,6 +335,7 @@ impl Car {
                 CarState::WaitingToAdvance { .. } => CarStatus::Moving,
                 CarState::Crossing { .. } => CarStatus::Moving,
                 CarState::ChangingLanes { .. } => CarStatus::Moving,
+                CarState::Passing { .. } => CarStatus::Moving,
                 CarState::Unparking { .. } => CarStatus::Moving,
                 CarState::Parking(_, _, _) => CarStatus::Moving,
                 // Changing color for idling buses is helpful
@@ -293,9 +353,7 @@ impl Car {
             },
             on: self.router.head(),
             partly_on,
-            label: if self.vehicle.vehicle_type == VehicleType::Bus
-                || self.vehicle.vehicle_type == VehicleType::Train
-            {
+            label: if self.vehicle.vehicle_type.is_transit() {
                 Some(
                     map.get_tr(transit.bus_route(self.vehicle.id))
                         .short_name
@@ -325,6 +383,8 @@ pub(crate) enum CarState {
         time_int: TimeInterval,
         dist_int: DistanceInterval,
         steep_uphill: bool,
+        /// How fast the vehicle will be going at the end
+        end_speed: Speed,
     },
     ChangingLanes {
         from: LaneID,
@@ -335,6 +395,13 @@ pub(crate) enum CarState {
         // How long does the lane-changing itself last? This must end before new_time_int does.
         lc_time: TimeInterval,
     },
+    /// Overtaking through an empty oncoming lane. The car is already in front of the vehicle it's
+    /// passing in the queue, but doesn't bound it until pulling back in.
+    Passing {
+        time_int: TimeInterval,
+        dist_int: DistanceInterval,
+        oncoming: LaneID,
+    },
     Queued {
         blocked_since: Time,
         want_to_change_lanes: Option<LaneID>,
@@ -361,6 +428,7 @@ impl CarState {
             CarState::WaitingToAdvance { .. } => unreachable!(),
             // Note this state lasts for lc_time, NOT for new_time.
             CarState::ChangingLanes { ref lc_time, .. } => lc_time.end,
+            CarState::Passing { ref time_int, .. } => time_int.end,
             CarState::Unparking { ref time_int, .. } => time_int.end,
             CarState::Parking(_, _, ref time_int) => time_int.end,
             CarState::IdlingAtStop(_, ref time_int) => time_int.end,
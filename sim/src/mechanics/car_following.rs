@@ -0,0 +1,244 @@
+//! Longitudinal models decide how vehicles speed up. The simulation is event-driven, so these
+//! models never step every vehicle through time. Instead, they predict how long it takes to cross
+//! a lane or turn, given how fast a vehicle starts. Interactions with the vehicle in front are
+//! still handled by queues: a follower can't get closer than `FOLLOWING_DISTANCE` to their leader,
+//! and a vehicle that had to stop starts again from rest. Deceleration is instantaneous.
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::VehicleType;
+
+/// Describes how a vehicle accelerates freely towards the fastest it can go.
+pub trait LongitudinalModel {
+    /// The speed after accelerating for `dt`, starting at `speed` and aiming for `max_speed`.
+    fn accelerate(&self, speed: Speed, max_speed: Speed, dt: Duration) -> Speed;
+
+    /// How often `accelerate` is evaluated when predicting a crossing.
+    fn time_step(&self) -> Duration;
+
+    /// How long it takes to cross some distance, and the speed at the end. A vehicle starting
+    /// faster than `max_speed` immediately slows down.
+    fn cross(&self, dist: Distance, initial_speed: Speed, max_speed: Speed) -> (Duration, Speed) {
+        let max = max_speed.inner_meters_per_second();
+        let mut v = initial_speed.inner_meters_per_second().min(max);
+        let mut remaining = dist.inner_meters();
+        let mut t = 0.0;
+        let dt = self.time_step();
+        loop {
+            if remaining <= 0.0 {
+                return (Duration::seconds(t), Speed::meters_per_second(v));
+            }
+            // Once the vehicle is almost at top speed, finish at a constant speed
+            if v >= NEARLY_AT_MAX * max {
+                return (
+                    Duration::seconds(t + remaining / v),
+                    Speed::meters_per_second(v),
+                );
+            }
+            let next = self
+                .accelerate(Speed::meters_per_second(v), max_speed, dt)
+                .inner_meters_per_second()
+                .min(max);
+            let step_dist = (v + next) / 2.0 * dt.inner_seconds();
+            if step_dist >= remaining {
+                // Finish partway through this step, assuming constant speed for the remainder
+                let avg = (v + next) / 2.0;
+                return (
+                    Duration::seconds(t + remaining / avg),
+                    Speed::meters_per_second(v + (next - v) * remaining / step_dist),
+                );
+            }
+            remaining -= step_dist;
+            t += dt.inner_seconds();
+            v = next;
+        }
+    }
+}
+
+/// Past this fraction of the max speed, vehicles are treated as cruising. Some models only
+/// approach the max speed asymptotically.
+const NEARLY_AT_MAX: f64 = 0.99;
+
+/// Which longitudinal model vehicles use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CarFollowingModel {
+    /// Vehicles instantly reach the fastest speed they can go. This is the original behavior.
+    Kinematic,
+    /// The Intelligent Driver Model from Treiber, Hennecke, and Helbing
+    IntelligentDriver,
+    /// The model from Gipps (1981)
+    Gipps,
+}
+
+impl CarFollowingModel {
+    /// Parameters for each model depend on the type of vehicle.
+    pub fn for_vehicle(self, vehicle_type: VehicleType) -> &'static dyn LongitudinalModel {
+        match self {
+            CarFollowingModel::Kinematic => &Kinematic,
+            CarFollowingModel::IntelligentDriver => match vehicle_type {
+                VehicleType::Car => &IntelligentDriver::CAR,
+                VehicleType::Bus => &IntelligentDriver::BUS,
+                VehicleType::Train => &IntelligentDriver::TRAIN,
+                // Trams mix with traffic, so they drive like buses
+                VehicleType::Tram => &IntelligentDriver::BUS,
+                VehicleType::Bike => &IntelligentDriver::BIKE,
+                VehicleType::Truck => &IntelligentDriver::TRUCK,
+                VehicleType::Scooter => &IntelligentDriver::SCOOTER,
+            },
+            CarFollowingModel::Gipps => match vehicle_type {
+                VehicleType::Car => &Gipps::CAR,
+                VehicleType::Bus => &Gipps::BUS,
+                VehicleType::Train => &Gipps::TRAIN,
+                VehicleType::Tram => &Gipps::BUS,
+                VehicleType::Bike => &Gipps::BIKE,
+                VehicleType::Truck => &Gipps::TRUCK,
+                VehicleType::Scooter => &Gipps::SCOOTER,
+            },
+        }
+    }
+}
+
+/// Vehicles always go as fast as they can, with instantaneous acceleration.
+pub struct Kinematic;
+
+impl LongitudinalModel for Kinematic {
+    fn accelerate(&self, _: Speed, max_speed: Speed, _: Duration) -> Speed {
+        max_speed
+    }
+
+    fn time_step(&self) -> Duration {
+        Duration::seconds(1.0)
+    }
+
+    fn cross(&self, dist: Distance, _: Speed, max_speed: Speed) -> (Duration, Speed) {
+        (dist / max_speed, max_speed)
+    }
+}
+
+/// The free-road part of the Intelligent Driver Model.
+pub struct IntelligentDriver {
+    /// In m/s^2
+    pub max_accel: f64,
+    /// How quickly acceleration drops off when approaching the max speed. 4 is typical.
+    pub exponent: f64,
+}
+
+impl IntelligentDriver {
+    pub const CAR: IntelligentDriver = IntelligentDriver {
+        max_accel: 1.5,
+        exponent: 4.0,
+    };
+    pub const BUS: IntelligentDriver = IntelligentDriver {
+        max_accel: 1.0,
+        exponent: 4.0,
+    };
+    pub const TRAIN: IntelligentDriver = IntelligentDriver {
+        max_accel: 0.8,
+        exponent: 4.0,
+    };
+    pub const BIKE: IntelligentDriver = IntelligentDriver {
+        max_accel: 1.0,
+        exponent: 4.0,
+    };
+    pub const TRUCK: IntelligentDriver = IntelligentDriver {
+        max_accel: 0.9,
+        exponent: 4.0,
+    };
+    pub const SCOOTER: IntelligentDriver = IntelligentDriver {
+        max_accel: 1.5,
+        exponent: 4.0,
+    };
+}
+
+impl LongitudinalModel for IntelligentDriver {
+    fn accelerate(&self, speed: Speed, max_speed: Speed, dt: Duration) -> Speed {
+        let ratio = speed.inner_meters_per_second() / max_speed.inner_meters_per_second();
+        let accel = self.max_accel * (1.0 - ratio.powf(self.exponent));
+        Speed::meters_per_second(
+            speed.inner_meters_per_second() + accel.max(0.0) * dt.inner_seconds(),
+        )
+    }
+
+    fn time_step(&self) -> Duration {
+        Duration::seconds(0.1)
+    }
+}
+
+/// The acceleration part of Gipps' model. Speed is updated once per reaction time.
+pub struct Gipps {
+    /// In m/s^2
+    pub max_accel: f64,
+    pub reaction_time: Duration,
+}
+
+impl Gipps {
+    pub const CAR: Gipps = Gipps {
+        max_accel: 1.7,
+        reaction_time: Duration::const_seconds(0.66),
+    };
+    pub const BUS: Gipps = Gipps {
+        max_accel: 1.0,
+        reaction_time: Duration::const_seconds(0.66),
+    };
+    pub const TRAIN: Gipps = Gipps {
+        max_accel: 0.8,
+        reaction_time: Duration::const_seconds(1.0),
+    };
+    pub const BIKE: Gipps = Gipps {
+        max_accel: 1.0,
+        reaction_time: Duration::const_seconds(0.66),
+    };
+    pub const TRUCK: Gipps = Gipps {
+        max_accel: 0.9,
+        reaction_time: Duration::const_seconds(0.66),
+    };
+    pub const SCOOTER: Gipps = Gipps {
+        max_accel: 1.5,
+        reaction_time: Duration::const_seconds(0.66),
+    };
+}
+
+impl LongitudinalModel for Gipps {
+    fn accelerate(&self, speed: Speed, max_speed: Speed, dt: Duration) -> Speed {
+        let ratio = speed.inner_meters_per_second() / max_speed.inner_meters_per_second();
+        let dv = 2.5 * self.max_accel * dt.inner_seconds() * (1.0 - ratio) * (0.025 + ratio).sqrt();
+        Speed::meters_per_second(speed.inner_meters_per_second() + dv.max(0.0))
+    }
+
+    fn time_step(&self) -> Duration {
+        self.reaction_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross() {
+        let dist = Distance::meters(100.0);
+        let max_speed = Speed::meters_per_second(10.0);
+
+        let (dt, end_speed) = Kinematic.cross(dist, Speed::ZERO, max_speed);
+        assert_eq!(dt, Duration::seconds(10.0));
+        assert_eq!(end_speed, max_speed);
+
+        for model in [
+            CarFollowingModel::IntelligentDriver,
+            CarFollowingModel::Gipps,
+        ] {
+            let model = model.for_vehicle(VehicleType::Car);
+            // Starting from rest is slower than the kinematic model, but not absurdly so
+            let (from_rest, end_speed) = model.cross(dist, Speed::ZERO, max_speed);
+            assert!(from_rest > Duration::seconds(10.0));
+            assert!(from_rest < Duration::seconds(20.0));
+            assert!(end_speed <= max_speed);
+
+            // Already going at top speed, there's no difference
+            let (cruising, _) = model.cross(dist, max_speed, max_speed);
+            assert!((cruising.inner_seconds() - 10.0).abs() < 0.1);
+        }
+    }
+}
@@ -3,21 +3,32 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey};
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{DrivingSide, IntersectionID, LaneID, Map, Path, PathStep, Position, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{
+    Detector, DirectedRoadID, DrivingSide, IntersectionID, LaneID, Map, Path, PathConstraints,
+    PathStep, Position, Traversable,
+};
 
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::queue::{Queue, QueueEntry, Queued};
 use crate::sim::Ctx;
 use crate::{
-    ActionAtEnd, AgentID, AgentProperties, CarID, CarStatus, Command, CreateCar, DelayCause,
-    DistanceInterval, DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSim,
-    ParkingSpot, PersonID, Problem, SimOptions, TimeInterval, TransitSimState, TripID, TripManager,
-    UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
+    ActionAtEnd, AgentID, AgentProperties, CarFollowingModel, CarID, CarStatus, Command, CreateCar,
+    DelayCause, DistanceInterval, DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSim,
+    ParkingSpot, PersonID, Problem, RideHailSimState, SimOptions, TimeInterval, TransitSimState,
+    TripID, TripManager, UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, BIKE_WIDTH,
+    CAR_WIDTH, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
 };
 
-const TIME_TO_WAIT_AT_BUS_STOP: Duration = Duration::const_seconds(10.0);
 const TIME_TO_CHANGE_LANES: Duration = Duration::const_seconds(1.0);
+/// Drivers don't consider rerouting until they've been stopped for at least this long since the
+/// last time they considered it.
+const MIN_DELAY_BEFORE_REROUTING: Duration = Duration::const_seconds(60.0);
+/// When estimating congestion, each stopped vehicle delays the ones behind it by roughly this much.
+const SATURATION_HEADWAY: Duration = Duration::const_seconds(2.0);
+/// Overtaking a cyclist with less room than this counts as a close pass. Many places require
+/// drivers to leave at least 1.5m.
+const CLOSE_PASS_DISTANCE: Distance = Distance::const_meters(1.5);
 
 // TODO Do something else.
 pub const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -45,6 +56,10 @@ pub(crate) struct DrivingSimState {
 
     recalc_lanechanging: bool,
     handle_uber_turns: bool,
+    reroute_delay_ratio: Option<f64>,
+    overtake_using_oncoming_lane: bool,
+    min_bike_passing_distance: Distance,
+    car_following: CarFollowingModel,
 
     time_to_unpark_onstreet: Duration,
     time_to_park_onstreet: Duration,
@@ -61,6 +76,13 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging: !opts.dont_recalc_lanechanging,
             handle_uber_turns: !opts.dont_handle_uber_turns,
+            reroute_delay_ratio: opts.reroute_delay_ratio,
+            overtake_using_oncoming_lane: opts.overtake_using_oncoming_lane,
+            min_bike_passing_distance: opts
+                .min_bike_passing_distance
+                .map(Distance::meters)
+                .unwrap_or(Distance::ZERO),
+            car_following: opts.car_following,
             waiting_to_spawn: BTreeMap::new(),
 
             time_to_unpark_onstreet: Duration::seconds(10.0),
@@ -191,6 +213,9 @@ impl DrivingSimState {
                 total_blocked_time: Duration::ZERO,
                 trip_and_person: params.trip_and_person,
                 wants_to_overtake: BTreeSet::new(),
+                blocked_time_at_last_reroute: Duration::ZERO,
+                car_following: self.car_following,
+                speed: Speed::ZERO,
             };
             let mut start_crossing = false;
             if let Some(p) = params.maybe_parked_car {
@@ -296,6 +321,11 @@ impl DrivingSimState {
         self.waiting_to_spawn.insert(id, (pos, person));
     }
 
+    /// A vehicle that was waiting to spawn won't be retried.
+    pub fn vehicle_gave_up_spawning(&mut self, id: CarID) {
+        self.waiting_to_spawn.remove(&id);
+    }
+
     /// State transitions for this car:
     ///
     /// Crossing -> Queued or WaitingToAdvance
@@ -323,6 +353,7 @@ impl DrivingSimState {
         ctx: &mut Ctx,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ride_hail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) {
         let mut need_distances = {
@@ -360,9 +391,9 @@ impl DrivingSimState {
             // checker, temporarily move one of them out of the map.
             let mut car = self.cars.remove(&id).unwrap();
             // Responsibility of update_car_with_distances to manage scheduling stuff!
-            if self
-                .update_car_with_distances(&mut car, &dists, idx, now, ctx, trips, transit, walking)
-            {
+            if self.update_car_with_distances(
+                &mut car, &dists, idx, now, ctx, trips, transit, ride_hail, walking,
+            ) {
                 self.cars.insert(id, car);
             } else {
                 self.delete_car_internal(&mut car, dists, idx, now, ctx);
@@ -381,7 +412,8 @@ impl DrivingSimState {
         transit: &mut TransitSimState,
     ) -> bool {
         match car.state {
-            CarState::Crossing { .. } => {
+            CarState::Crossing { end_speed, .. } => {
+                car.speed = end_speed;
                 car.state = CarState::Queued {
                     blocked_since: now,
                     want_to_change_lanes: None,
@@ -409,8 +441,8 @@ impl DrivingSimState {
 
                     // Record when a vehicle wants to pass a bike
                     if first_conflict
-                        && slow_leader.vehicle_type == VehicleType::Bike
-                        && car.vehicle.vehicle_type != VehicleType::Bike
+                        && slow_leader.vehicle_type.rides_bike_lanes()
+                        && !car.vehicle.vehicle_type.rides_bike_lanes()
                     {
                         self.events.push(Event::ProblemEncountered(
                             self.cars[&slow_leader].trip_and_person.unwrap().0,
@@ -418,10 +450,11 @@ impl DrivingSimState {
                         ));
                     }
 
-                    if let Some(target_lane) = self.pick_overtaking_lane(car, ctx.map) {
-                        // We need the current position of the car to see if lane-changing is
-                        // actually feasible right now, so record our intention and trigger
-                        // update_car_with_distances.
+                    if let Some(target_lane) = self.pick_overtaking_lane(car, slow_leader, ctx.map)
+                    {
+                        // We need the current position of the car to see if lane-changing (or
+                        // passing through the oncoming lane) is actually feasible right now, so
+                        // record our intention and trigger update_car_with_distances.
                         car.state = CarState::Queued {
                             blocked_since: now,
                             want_to_change_lanes: Some(target_lane),
@@ -532,6 +565,9 @@ impl DrivingSimState {
                     &mut self.events,
                 );
                 car.total_blocked_time += now - blocked_since;
+                if let Traversable::Lane(_) = goto {
+                    self.maybe_reroute(car, now, ctx);
+                }
                 car.state = car.crossing_state(Distance::ZERO, now, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -582,6 +618,7 @@ impl DrivingSimState {
                     time_int: new_time,
                     dist_int: new_dist,
                     steep_uphill: false,
+                    end_speed: car.speed,
                 };
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -601,6 +638,28 @@ impl DrivingSimState {
                     .unwrap()
                     .clear_dynamic_blockage(car.vehicle.id, idx);
             }
+            CarState::Passing { dist_int, .. } => {
+                // Pull back in, ahead of whoever we passed
+                car.state = car.crossing_state(dist_int.end, now, ctx.map);
+                ctx.scheduler
+                    .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                self.new_crossing_state(ctx, car);
+
+                // Now we bound the vehicle we passed. Like the Unparking case, temporarily make
+                // this car visible to calculate positions.
+                self.cars.insert(car.vehicle.id, car.clone());
+                let dists = self.queues[&car.router.head()].get_car_positions(
+                    now,
+                    &self.cars,
+                    &self.queues,
+                );
+                self.cars.remove(&car.vehicle.id);
+                let idx = dists
+                    .iter()
+                    .position(|entry| entry.member == Queued::Vehicle(car.vehicle.id))
+                    .unwrap();
+                self.update_follower(idx, &dists, now, ctx);
+            }
             CarState::Queued { .. } => unreachable!(),
             CarState::Parking(_, _, _) => unreachable!(),
             CarState::IdlingAtStop(_, _) => unreachable!(),
@@ -608,6 +667,120 @@ impl DrivingSimState {
         false
     }
 
+    /// Drivers stuck in congestion for much longer than they expected may look for a faster way to
+    /// finish their trip, avoiding roads where vehicles are currently queued.
+    fn maybe_reroute(&mut self, car: &mut Car, now: Time, ctx: &mut Ctx) {
+        let ratio = match self.reroute_delay_ratio {
+            Some(ratio) => ratio,
+            None => {
+                return;
+            }
+        };
+        if car.vehicle.vehicle_type != VehicleType::Car
+            || car.router.last_step()
+            || car.router.is_parking()
+        {
+            return;
+        }
+        if car.total_blocked_time - car.blocked_time_at_last_reroute < MIN_DELAY_BEFORE_REROUTING {
+            return;
+        }
+        let moving_time = (now - car.started_at) - car.total_blocked_time;
+        if car.total_blocked_time < ratio * moving_time {
+            return;
+        }
+
+        car.blocked_time_at_last_reroute = car.total_blocked_time;
+        let delays = self.congestion_delays(car.router.get_path(), ctx.map);
+        if car.router.reroute(&delays, ctx.map) {
+            self.events.push(Event::VehicleRerouted(
+                car.vehicle.id,
+                car.trip_and_person.map(|(t, _)| t),
+                car.router.head().as_lane(),
+                car.total_blocked_time,
+            ));
+        }
+    }
+
+    /// Roughly how long it currently takes to get through the queued vehicles on each road left
+    /// along a path, spread across all of the lanes cars can use. Drivers only know about the jams
+    /// on their own route, not everywhere else.
+    fn congestion_delays(&self, path: &Path, map: &Map) -> HashMap<DirectedRoadID, Duration> {
+        let mut delays = HashMap::new();
+        for step in path.get_steps() {
+            let dr = match step {
+                PathStep::Lane(l) => map.get_l(*l).get_directed_parent(),
+                _ => continue,
+            };
+            if delays.contains_key(&dr) {
+                continue;
+            }
+            let lanes = dr.lanes(PathConstraints::Car, map);
+            let stopped: usize = lanes
+                .iter()
+                .filter_map(|l| self.queues.get(&Traversable::Lane(*l)))
+                .map(|q| q.num_stopped_vehicles(&self.cars))
+                .sum();
+            delays.insert(
+                dr,
+                (stopped as f64) / (lanes.len().max(1) as f64) * SATURATION_HEADWAY,
+            );
+        }
+        delays.retain(|_, delay| *delay > Duration::ZERO);
+        delays
+    }
+
+    /// After a live edit changes part of a car's remaining path, look for a new way to finish the
+    /// trip. This only happens when en-route rerouting is enabled, and the car isn't on any
+    /// edited lane itself. Returns true if the car has a new path.
+    pub fn reroute_after_live_edits(
+        &mut self,
+        id: CarID,
+        edited_lanes: &BTreeSet<LaneID>,
+        intersections: &mut IntersectionSimState,
+        map: &Map,
+    ) -> bool {
+        if self.reroute_delay_ratio.is_none() {
+            return false;
+        }
+        let car = match self.cars.get_mut(&id) {
+            Some(car) => car,
+            None => {
+                return false;
+            }
+        };
+        let current_lane = match car.router.head() {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(_) => {
+                return false;
+            }
+        };
+        if !car.last_steps.is_empty() || edited_lanes.contains(&current_lane) {
+            return false;
+        }
+        // Cars queued at the end of the lane may have already asked the intersection for a turn
+        // on the old path, so only reroute cars still moving along the lane.
+        if !matches!(car.state, CarState::Crossing { .. }) {
+            return false;
+        }
+        if !car.router.reroute_after_live_edits(map) {
+            return false;
+        }
+        // The intersection ahead may expect the leader to make a turn that's no longer on the path
+        if self.queues[&car.router.head()].is_car_at_front(id) {
+            if let Some(Traversable::Turn(turn)) = car.router.maybe_next() {
+                intersections.approaching_leader(AgentID::Car(id), turn, car.state.get_end_time());
+            }
+        }
+        self.events.push(Event::VehicleRerouted(
+            id,
+            car.trip_and_person.map(|(t, _)| t),
+            current_lane,
+            car.total_blocked_time,
+        ));
+        true
+    }
+
     // Returns true if the car survives.
     fn update_car_with_distances(
         &mut self,
@@ -618,6 +791,7 @@ impl DrivingSimState {
         ctx: &mut Ctx,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        ride_hail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) -> bool {
         let our_dist = dists[idx].front;
@@ -626,7 +800,8 @@ impl DrivingSimState {
             CarState::Crossing { .. }
             | CarState::Unparking { .. }
             | CarState::WaitingToAdvance { .. }
-            | CarState::ChangingLanes { .. } => unreachable!(),
+            | CarState::ChangingLanes { .. }
+            | CarState::Passing { .. } => unreachable!(),
             CarState::Queued {
                 blocked_since,
                 want_to_change_lanes,
@@ -634,7 +809,12 @@ impl DrivingSimState {
                 // Two totally different reasons we'll wind up here: we want to lane-change, and
                 // we're on our last step.
                 if let Some(target_lane) = want_to_change_lanes {
-                    self.try_start_lc(car, our_dist, idx, target_lane, now, ctx);
+                    let current_lane = car.router.head().as_lane();
+                    if ctx.map.get_l(target_lane).dir == ctx.map.get_l(current_lane).dir {
+                        self.try_start_lc(car, our_dist, idx, target_lane, now, ctx);
+                    } else {
+                        self.try_pass_using_oncoming_lane(car, dists, idx, target_lane, now, ctx);
+                    }
                     return true;
                 }
 
@@ -714,10 +894,12 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         car.total_blocked_time += now - blocked_since;
-                        if transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx) {
+                        if let Some(dwell) =
+                            transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx)
+                        {
                             car.state = CarState::IdlingAtStop(
                                 our_dist,
-                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_BUS_STOP),
+                                TimeInterval::new(now, now + dwell),
                             );
                             ctx.scheduler
                                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -727,6 +909,15 @@ impl DrivingSimState {
                             false
                         }
                     }
+                    Some(ActionAtEnd::Deliver(dwell)) => {
+                        car.total_blocked_time += now - blocked_since;
+                        // Double-park, blocking the lane for anyone behind
+                        car.state =
+                            CarState::IdlingAtStop(our_dist, TimeInterval::new(now, now + dwell));
+                        ctx.scheduler
+                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                        true
+                    }
                     None => {
                         ctx.scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -773,7 +964,53 @@ impl DrivingSimState {
                 false
             }
             CarState::IdlingAtStop(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                let pos = Position::new(car.router.head().as_lane(), dist);
+                if car.vehicle.vehicle_type == VehicleType::Truck {
+                    match trips.truck_finished_delivery(
+                        now,
+                        car.vehicle.id,
+                        pos,
+                        car.total_blocked_time,
+                        car.router.get_path().total_length(),
+                        ctx,
+                    ) {
+                        Some(router) => {
+                            car.router = router;
+                            // The trip already counted these
+                            car.total_blocked_time = Duration::ZERO;
+                            car.blocked_time_at_last_reroute = Duration::ZERO;
+                        }
+                        None => {
+                            return false;
+                        }
+                    }
+                } else if car.vehicle.vehicle_type == VehicleType::Car {
+                    // Only ride-hailing vehicles idle at stops
+                    match ride_hail.vehicle_finished_stop(
+                        now,
+                        car.vehicle.id,
+                        pos,
+                        car.router.get_path().total_length(),
+                        trips,
+                        ctx,
+                    ) {
+                        Some(router) => {
+                            car.router = router;
+                            car.total_blocked_time = Duration::ZERO;
+                            car.blocked_time_at_last_reroute = Duration::ZERO;
+                        }
+                        None => {
+                            return false;
+                        }
+                    }
+                } else if let Some(extra) = transit.take_extra_dwell(car.vehicle.id) {
+                    car.state = CarState::IdlingAtStop(dist, TimeInterval::new(now, now + extra));
+                    ctx.scheduler
+                        .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                    return true;
+                } else {
+                    car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                }
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
                 car.state = car.crossing_state(dist, now, ctx.map);
@@ -950,10 +1187,11 @@ impl DrivingSimState {
                         lc_time,
                     };
                 }
-                // They weren't blocked
+                // They weren't blocked. Somebody passing will check when they pull back in.
                 CarState::Unparking { .. }
                 | CarState::Parking(_, _, _)
-                | CarState::IdlingAtStop(_, _) => {}
+                | CarState::IdlingAtStop(_, _)
+                | CarState::Passing { .. } => {}
                 CarState::WaitingToAdvance { .. } => unreachable!(),
             }
         }
@@ -1090,6 +1328,7 @@ impl DrivingSimState {
                             // jump forwards here; the leader vanished from the end of the traversable.
                             CarState::Crossing { .. }
                             | CarState::ChangingLanes { .. }
+                            | CarState::Passing { .. }
                             | CarState::Unparking { .. }
                             | CarState::Parking(_, _, _)
                             | CarState::IdlingAtStop(_, _) => {}
@@ -1105,11 +1344,12 @@ impl DrivingSimState {
     }
 
     /// If the car wants to over-take somebody, what adjacent lane should they use?
-    /// - The lane must be in the same direction as the current; no support for crossing the road's
-    ///   yellow line yet.
+    /// - Prefer a lane in the same direction as the current one. If there's none and it's
+    ///   allowed, briefly use the oncoming lane.
     /// - Prefer passing on the left (for DrivingSide::Right)
+    /// - When passing a cyclist, leave at least min_bike_passing_distance.
     /// For now, just pick one candidate lane, even if both might be usable.
-    fn pick_overtaking_lane(&self, car: &Car, map: &Map) -> Option<LaneID> {
+    fn pick_overtaking_lane(&self, car: &Car, leader: CarID, map: &Map) -> Option<LaneID> {
         // Don't overtake in the middle of a turn!
         let current_lane = map.get_l(car.router.head().maybe_lane()?);
         let road = map.get_parent(current_lane.id);
@@ -1126,20 +1366,22 @@ impl DrivingSimState {
             candidates.reverse();
         }
 
+        let constraints = car.vehicle.vehicle_type.to_constraints();
+        let enough_room = |l: LaneID| {
+            !leader.vehicle_type.rides_bike_lanes()
+                || passing_clearance(current_lane.id, l, map) >= self.min_bike_passing_distance
+        };
+        let mut oncoming = None;
         for l in candidates {
             let target_lane = map.get_l(l);
-            // Must be the same direction -- no crossing into oncoming traffic yet
-            if current_lane.dir != target_lane.dir {
-                continue;
-            }
             // The lane types can differ, as long as the vehicle can use the target. Imagine
             // overtaking a slower cyclist in a bike lane using the rest of the road.
-            if !car
-                .vehicle
-                .vehicle_type
-                .to_constraints()
-                .can_use(target_lane, map)
-            {
+            if !constraints.can_use(target_lane, map) || !enough_room(l) {
+                continue;
+            }
+            if current_lane.dir != target_lane.dir {
+                // This must be the lane on the other side of the center line
+                oncoming = Some(l);
                 continue;
             }
             // Is this other lane compatible with the path? We won't make any attempts to return to the
@@ -1153,9 +1395,146 @@ impl DrivingSimState {
             return Some(target_lane.id);
         }
 
+        // Don't bother passing right before the end of the trip
+        if self.overtake_using_oncoming_lane && !car.router.last_step() {
+            return oncoming;
+        }
         None
     }
 
+    /// Pass the leader by crossing into the oncoming lane, then pulling back in front of them. The
+    /// pass lasts as long as it takes to gain enough distance on the leader at full speed. This
+    /// only happens when the oncoming lane is empty and nobody will reach it before the pass is
+    /// done, and there's room in front of the leader.
+    fn try_pass_using_oncoming_lane(
+        &mut self,
+        car: &mut Car,
+        dists: &[QueueEntry],
+        idx: usize,
+        oncoming_lane: LaneID,
+        now: Time,
+        ctx: &mut Ctx,
+    ) {
+        // Same as lane-changing, don't start if our back is still in another lane or turn
+        if !car.last_steps.is_empty() || idx == 0 {
+            return;
+        }
+        let leader = match dists[idx - 1].member {
+            Queued::Vehicle(id) => id,
+            _ => {
+                return;
+            }
+        };
+        if !self.queues[&Traversable::Lane(oncoming_lane)].is_empty() {
+            return;
+        }
+        // Only one vehicle at a time passes through the oncoming lane
+        if dists.iter().any(|entry| match entry.member {
+            Queued::Vehicle(id) => self
+                .cars
+                .get(&id)
+                .map(|c| matches!(c.state, CarState::Passing { .. }))
+                .unwrap_or(false),
+            _ => false,
+        }) {
+            return;
+        }
+
+        // How long will the pass take? Assume the leader keeps going at their current speed.
+        let current_lane = car.router.head().as_lane();
+        let our_speed = PathStep::Lane(current_lane).max_speed_along(
+            car.vehicle.max_speed,
+            car.vehicle.vehicle_type.to_constraints(),
+            ctx.map,
+        );
+        let leader_speed = match self.cars[&leader].state {
+            CarState::Crossing {
+                ref time_int,
+                ref dist_int,
+                ..
+            } if time_int.end > time_int.start => {
+                dist_int.length() / (time_int.end - time_int.start)
+            }
+            _ => Speed::ZERO,
+        };
+        if our_speed <= leader_speed {
+            return;
+        }
+        let our_front = dists[idx].front;
+        let gap = dists[idx - 1].front + FOLLOWING_DISTANCE + car.vehicle.length - our_front;
+        let duration = gap / (our_speed - leader_speed);
+        let new_front = our_front + our_speed * duration;
+
+        // Where does there have to be room for us?
+        let room = if idx >= 2 {
+            dists[idx - 2].back - FOLLOWING_DISTANCE
+        } else if self.queues[&car.router.head()].laggy_head.is_some() {
+            // Hard to say exactly where the laggy head's back is, so don't risk it
+            return;
+        } else {
+            ctx.map.get_l(current_lane).length()
+        };
+        if new_front > room {
+            return;
+        }
+
+        // The oncoming lane has to stay clear for the whole pass. Anybody crossing a turn into it
+        // could show up any moment; otherwise, check when vehicles approaching it will reach the
+        // end of their lane.
+        let done = now + duration;
+        for turn in ctx.map.get_turns_to_lane(oncoming_lane) {
+            if self
+                .queues
+                .get(&Traversable::Turn(turn.id))
+                .map(|q| !q.get_active_cars().is_empty())
+                .unwrap_or(false)
+            {
+                return;
+            }
+            if turn.id.src == current_lane {
+                continue;
+            }
+            if let Some(queue) = self.queues.get(&Traversable::Lane(turn.id.src)) {
+                for id in queue.get_active_cars() {
+                    let eta = match self.cars.get(&id).map(|c| &c.state) {
+                        Some(CarState::Crossing { ref time_int, .. })
+                        | Some(CarState::ChangingLanes {
+                            new_time: ref time_int,
+                            ..
+                        }) => time_int.end,
+                        _ => now,
+                    };
+                    if eta < done {
+                        return;
+                    }
+                }
+            }
+        }
+
+        self.queues
+            .get_mut(&car.router.head())
+            .unwrap()
+            .swap_with_leader(car.vehicle.id, idx);
+        if let CarState::Queued { blocked_since, .. } = car.state {
+            car.total_blocked_time += now - blocked_since;
+        }
+        car.speed = our_speed;
+        car.state = CarState::Passing {
+            time_int: TimeInterval::new(now, done),
+            dist_int: DistanceInterval::new_driving(our_front, new_front),
+            oncoming: oncoming_lane,
+        };
+        ctx.scheduler
+            .update(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+        self.maybe_record_close_pass(leader, current_lane, oncoming_lane, ctx.map);
+
+        // Whoever was behind us now follows the vehicle we're passing. The queue order changed,
+        // so fix up the positions before waking them up.
+        let mut dists = dists.to_vec();
+        dists.swap(idx - 1, idx);
+        self.update_follower(idx, &dists, now, ctx);
+    }
+
     fn try_start_lc(
         &mut self,
         car: &mut Car,
@@ -1220,6 +1599,10 @@ impl DrivingSimState {
                 );
             }
 
+            if let Some(leader) = self.queues[&car.router.head()].get_leader(car.vehicle.id) {
+                self.maybe_record_close_pass(leader, current_lane, target_lane, ctx.map);
+            }
+
             // Exit the old queue (leaving a dynamic blockage in place)
             self.queues
                 .get_mut(&car.router.head())
@@ -1248,6 +1631,29 @@ impl DrivingSimState {
         }
     }
 
+    /// When a vehicle overtakes a cyclist using a lane that's too close, record a problem for the
+    /// cyclist.
+    fn maybe_record_close_pass(
+        &mut self,
+        leader: CarID,
+        leader_lane: LaneID,
+        passing_lane: LaneID,
+        map: &Map,
+    ) {
+        if !leader.vehicle_type.rides_bike_lanes() {
+            return;
+        }
+        let clearance = passing_clearance(leader_lane, passing_lane, map);
+        if clearance < CLOSE_PASS_DISTANCE {
+            if let Some((trip, _)) = self.cars[&leader].trip_and_person {
+                self.events.push(Event::ProblemEncountered(
+                    trip,
+                    Problem::ClosePass(Traversable::Lane(leader_lane), clearance),
+                ));
+            }
+        }
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
@@ -1590,6 +1996,38 @@ impl DrivingSimState {
         affected
     }
 
+    /// Like `find_vehicles_affected_by_live_edits`, but for vehicles without a trip, like
+    /// ride-hailing fleet vehicles. Since nobody else tracks their route, this checks the path
+    /// ahead too.
+    pub fn find_tripless_vehicles_affected_by_live_edits(
+        &self,
+        closed_intersections: &HashSet<IntersectionID>,
+        edited_lanes: &BTreeSet<LaneID>,
+    ) -> Vec<CarID> {
+        let affected = |step: Traversable| match step {
+            Traversable::Lane(l) => edited_lanes.contains(&l),
+            Traversable::Turn(t) => {
+                closed_intersections.contains(&t.parent)
+                    || edited_lanes.contains(&t.src)
+                    || edited_lanes.contains(&t.dst)
+            }
+        };
+        self.cars
+            .values()
+            .filter(|car| {
+                car.trip_and_person.is_none()
+                    && (car.last_steps.iter().any(|step| affected(*step))
+                        || car
+                            .router
+                            .get_path()
+                            .get_steps()
+                            .iter()
+                            .any(|step| affected(step.as_traversable())))
+            })
+            .map(|car| car.vehicle.id)
+            .collect()
+    }
+
     pub fn all_waiting_people(&self, now: Time, delays: &mut BTreeMap<PersonID, Duration>) {
         for c in self.cars.values() {
             if let Some((_, person)) = c.trip_and_person {
@@ -1606,6 +2044,19 @@ impl DrivingSimState {
         Some((queue.reserved_length, queue.geom_len))
     }
 
+    /// Is any vehicle currently over a detector or between it and the stop line?
+    pub fn detector_occupied(&self, now: Time, detector: &Detector) -> bool {
+        match self.queues.get(&Traversable::Lane(detector.lane)) {
+            Some(q) => q
+                .get_car_positions(now, &self.cars, &self.queues)
+                .into_iter()
+                .any(|entry| {
+                    matches!(entry.member, Queued::Vehicle(_)) && entry.front >= detector.dist_along
+                }),
+            None => false,
+        }
+    }
+
     pub fn get_blocked_by_graph(
         &self,
         now: Time,
@@ -1684,16 +2135,20 @@ impl DrivingSimState {
             _ => {}
         }
 
-        // TODO Check if there's room in front of them to pass. Handle passing two bikes?
-        // TODO Check remaining distance before next intersection
+        // TODO Handle passing two bikes?
         // TODO Check relative speed difference, and time to pass them
-        // TODO Eventually, check if there's room to do the maneuever (lane-changing to
-        // the left only, or popping into an oncoming lane!
 
         Some(leader.vehicle.id)
     }
 }
 
+/// Roughly how much room there is between a cyclist riding in the middle of one lane and a car
+/// passing them in the middle of an adjacent lane.
+fn passing_clearance(bike_lane: LaneID, passing_lane: LaneID, map: &Map) -> Distance {
+    let between_centers = (map.get_l(bike_lane).width + map.get_l(passing_lane).width) / 2.0;
+    between_centers - (CAR_WIDTH + BIKE_WIDTH) / 2.0
+}
+
 // This implementation relies on the fact that car IDs are unique just by their number. Vehicle
 // type is also in there, but during lookup, it'll be ignored!
 impl IndexableKey for CarID {
@@ -5,18 +5,25 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_btreemap, prettyprint_usize, serialize_btreemap, FixedMap};
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, StageType,
-    Traversable, TurnID, TurnPriority, TurnType, UberTurn,
+    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map,
+    PedestrianSignal, StageType, Traversable, Turn, TurnID, TurnPriority, TurnType, UberTurn,
 };
 
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
-    AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    AgentID, AgentType, AlertLocation, CarID, Command, ConflictMeasure, DelayCause,
+    DrivingSimState, Event, PedestrianID, SafetyConflict, Scheduler, SignalController,
+    SignalStatus, SimOptions, Speed,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+// A pedestrian ready to jaywalk who sees a vehicle coming looks again after this long.
+const JAYWALK_RETRY: Duration = Duration::const_seconds(2.0);
+// Near-misses with a bigger margin than these aren't recorded as safety conflicts.
+const MAX_TIME_TO_COLLISION: Duration = Duration::const_seconds(3.0);
+const MAX_POST_ENCROACHMENT_TIME: Duration = Duration::const_seconds(5.0);
 
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
 /// maybe_start_turn to make a Request. Based on the intersection type (stop sign, traffic signal,
@@ -34,6 +41,10 @@ pub(crate) struct IntersectionSimState {
     break_turn_conflict_cycles: bool,
     handle_uber_turns: bool,
     disable_turn_conflicts: bool,
+    jaywalk_after_waiting: Option<Duration>,
+    // Pedestrians who were just allowed to cross against the signal. The walking sim claims these
+    // right after starting the turn.
+    jaywalkers: BTreeSet<PedestrianID>,
     // (x, y) means x is blocked by y. It's a many-to-many relationship. TODO Better data
     // structure.
     blocked_by: BTreeSet<(CarID, CarID)>,
@@ -45,6 +56,13 @@ pub(crate) struct IntersectionSimState {
     total_repeat_requests: usize,
     not_allowed_requests: usize,
     blocked_by_someone_requests: usize,
+
+    // Traffic signals that ask signal_controller what to do, instead of following their plan.
+    // The controller can't be serialized, so after loading a savestate, these fall back to their
+    // plan until a new controller is set.
+    external_signals: BTreeSet<IntersectionID>,
+    #[serde(skip_serializing, skip_deserializing)]
+    signal_controller: Option<Box<dyn SignalController>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -66,6 +84,11 @@ struct State {
     // In some cases, a turn completing at one intersection may affect agents waiting to start an
     // uber-turn at nearby intersections.
     uber_turn_neighbors: Vec<IntersectionID>,
+    // Turns finished within the last MAX_POST_ENCROACHMENT_TIME, to measure near-misses
+    recently_finished: Vec<(AgentID, TurnID, Time)>,
+    // Pairs of agents with a safety conflict recorded within the last MAX_POST_ENCROACHMENT_TIME,
+    // so the same near-miss isn't counted by both measures. The lower AgentID is first.
+    recent_conflicts: Vec<(AgentID, AgentID, Time)>,
 
     // This is keyed by the lane the agent is approaching from. Note that:
     // 1) the turn in the request may change by the time the leader arrives -- they might decide to
@@ -87,8 +110,14 @@ struct SignalState {
     current_stage: usize,
     // The time when the signal is checked for advancing
     stage_ends_at: Time,
-    // The number of times a variable signal has been extended during the current stage.
+    // The number of times a variable or actuated signal has been extended during the current
+    // stage.
     extensions_count: usize,
+    // When the current stage began, not counting extensions
+    stage_started_at: Time,
+    // Do crosswalks in the current stage get WALK? Only false when the stage has a call button and
+    // no pedestrian pressed it before the stage started.
+    walk_granted: bool,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -107,12 +136,17 @@ impl IntersectionSimState {
             break_turn_conflict_cycles: !opts.dont_break_turn_conflict_cycles,
             handle_uber_turns: !opts.dont_handle_uber_turns,
             disable_turn_conflicts: opts.disable_turn_conflicts,
+            jaywalk_after_waiting: opts.jaywalk_after_waiting.map(Duration::seconds),
+            jaywalkers: BTreeSet::new(),
             blocked_by: BTreeSet::new(),
             events: Vec::new(),
 
             total_repeat_requests: 0,
             not_allowed_requests: 0,
             blocked_by_someone_requests: 0,
+
+            external_signals: BTreeSet::new(),
+            signal_controller: None,
         };
         if sim.disable_turn_conflicts {
             sim.use_freeform_policy_everywhere = true;
@@ -125,6 +159,8 @@ impl IntersectionSimState {
                 waiting: BTreeMap::new(),
                 reserved: BTreeSet::new(),
                 uber_turn_neighbors: Vec::new(),
+                recently_finished: Vec::new(),
+                recent_conflicts: Vec::new(),
                 signal: None,
                 leader_eta: BTreeMap::new(),
             };
@@ -154,6 +190,10 @@ impl IntersectionSimState {
 
         state.reserved.remove(&Request { agent, turn });
         if !handling_live_edits && map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner {
+            state
+                .recently_finished
+                .retain(|(_, _, t)| now - *t <= MAX_POST_ENCROACHMENT_TIME);
+            state.recently_finished.push((agent, turn, now));
             self.wakeup_waiting(now, turn.parent, scheduler, map);
         }
         if self.break_turn_conflict_cycles {
@@ -290,6 +330,7 @@ impl IntersectionSimState {
         now: Time,
         id: IntersectionID,
         map: &Map,
+        driving: &DrivingSimState,
         scheduler: &mut Scheduler,
     ) {
         let i = map.get_i(id);
@@ -300,6 +341,8 @@ impl IntersectionSimState {
             signal: &ControlTrafficSignal,
             i: &Intersection,
             allow_crosswalk_skip: bool,
+            has_call: &dyn Fn(usize) -> bool,
+            ped_call: &dyn Fn(usize) -> bool,
         ) -> Duration {
             signal_state.current_stage = (signal_state.current_stage + 1) % signal.stages.len();
             let stage = &signal.stages[signal_state.current_stage];
@@ -312,10 +355,38 @@ impl IntersectionSimState {
                         (signal_state.current_stage + 1) % signal.stages.len();
                 }
             }
+            // Skip over actuated stages that nobody has called, and pedestrian scrambles with a
+            // call button nobody has pushed. If nobody is waiting anywhere, just serve the next
+            // stage.
+            let next = signal_state.current_stage;
+            for offset in 0..signal.stages.len() {
+                let idx = (next + offset) % signal.stages.len();
+                let stage = &signal.stages[idx];
+                let is_actuated = matches!(stage.stage_type, StageType::Actuated { .. });
+                let uncalled_scramble = stage.is_ped_scramble()
+                    && stage
+                        .ped_intervals
+                        .as_ref()
+                        .map_or(false, |p| p.call_button)
+                    && !ped_call(idx);
+                if (!is_actuated || has_call(idx)) && !uncalled_scramble {
+                    signal_state.current_stage = idx;
+                    break;
+                }
+            }
             signal.stages[signal_state.current_stage]
                 .stage_type
                 .simple_duration()
         }
+        let external_status =
+            if self.external_signals.contains(&id) && self.signal_controller.is_some() {
+                Some(self.signal_status(now, id, map))
+            } else {
+                None
+            };
+
+        let externally_controlled = external_status.is_some();
+
         let state = self.state.get_mut(&id).unwrap();
         let signal_state = state.signal.as_mut().unwrap();
         let signal = map.get_traffic_signal(id);
@@ -325,53 +396,141 @@ impl IntersectionSimState {
             }
             false
         });
-        let duration: Duration;
+        // A stage is called by a vehicle over one of its detectors, or by any agent (like a
+        // pedestrian pressing the call button) already waiting for one of its protected movements.
+        let waiting = &state.waiting;
+        let vehicle_detected = |idx: usize| {
+            signal
+                .get_detectors(idx, map)
+                .iter()
+                .any(|d| driving.detector_occupied(now, d))
+        };
+        let has_call = |idx: usize| {
+            vehicle_detected(idx)
+                || waiting.keys().any(|req| {
+                    signal.stages[idx].get_priority_of_turn(req.turn, i) == TurnPriority::Protected
+                })
+        };
+        let ped_call = |idx: usize| {
+            waiting.keys().any(|req| {
+                req.agent.is_pedestrian()
+                    && signal.stages[idx].get_priority_of_turn(req.turn, i)
+                        == TurnPriority::Protected
+            })
+        };
+        let mut stage_changed = true;
+        let mut duration: Duration;
         // Switch to a new stage?
         assert_eq!(now, signal_state.stage_ends_at);
         let old_stage = &signal.stages[signal_state.current_stage];
-        match old_stage.stage_type {
-            StageType::Fixed(_) => {
-                duration = advance(signal_state, signal, i, !ped_waiting);
+        if let Some(status) = external_status {
+            let decision = self
+                .signal_controller
+                .as_mut()
+                .unwrap()
+                .next_stage(now, &status, map);
+            if decision.stage < signal.stages.len() {
+                // Holding the same stage is just an extension
+                stage_changed = decision.stage != signal_state.current_stage;
+                signal_state.current_stage = decision.stage;
+            } else {
+                stage_changed = false;
+                self.events.push(Event::Alert(
+                    AlertLocation::Intersection(id),
+                    format!(
+                        "External controller picked stage {}, but there are only {}",
+                        decision.stage,
+                        signal.stages.len()
+                    ),
+                ));
             }
-            StageType::Variable(min, delay, additional) => {
-                // test if anyone is waiting in current stage, and if so, extend the signal cycle.
-                // Filter out pedestrians, as they've had their chance and the delay
-                // could be short enough to keep them on the curb.
-                let delay = std::cmp::max(Duration::const_seconds(1.0), delay);
-                // Only extend for the fixed additional time
-                if signal_state.extensions_count as f64 * delay.inner_seconds()
-                    >= additional.inner_seconds()
-                {
-                    self.events.push(Event::Alert(
-                        AlertLocation::Intersection(id),
-                        format!(
-                            "exhausted a variable stage {},{},{},{}",
-                            min, delay, additional, signal_state.extensions_count
-                        ),
-                    ));
-                    duration = advance(signal_state, signal, i, !ped_waiting);
-                    signal_state.extensions_count = 0;
-                } else if state.waiting.keys().all(|req| {
-                    if let AgentID::Pedestrian(_) = req.agent {
-                        return true;
+            signal_state.extensions_count = 0;
+            duration = std::cmp::max(Duration::const_seconds(1.0), decision.duration);
+        } else {
+            match old_stage.stage_type {
+                StageType::Fixed(_) => {
+                    duration = advance(signal_state, signal, i, !ped_waiting, &has_call, &ped_call);
+                }
+                StageType::Variable(min, delay, additional) => {
+                    // test if anyone is waiting in current stage, and if so, extend the signal cycle.
+                    // Filter out pedestrians, as they've had their chance and the delay
+                    // could be short enough to keep them on the curb.
+                    let delay = std::cmp::max(Duration::const_seconds(1.0), delay);
+                    // Only extend for the fixed additional time
+                    if signal_state.extensions_count as f64 * delay.inner_seconds()
+                        >= additional.inner_seconds()
+                    {
+                        self.events.push(Event::Alert(
+                            AlertLocation::Intersection(id),
+                            format!(
+                                "exhausted a variable stage {},{},{},{}",
+                                min, delay, additional, signal_state.extensions_count
+                            ),
+                        ));
+                        duration =
+                            advance(signal_state, signal, i, !ped_waiting, &has_call, &ped_call);
+                        signal_state.extensions_count = 0;
+                    } else if state.waiting.keys().all(|req| {
+                        if let AgentID::Pedestrian(_) = req.agent {
+                            return true;
+                        }
+                        // Should we only allow protected to extend or any not banned?
+                        // currently only the protected demand control extended.
+                        old_stage.get_priority_of_turn(req.turn, i) != TurnPriority::Protected
+                    }) {
+                        signal_state.extensions_count = 0;
+                        duration =
+                            advance(signal_state, signal, i, !ped_waiting, &has_call, &ped_call);
+                    } else {
+                        signal_state.extensions_count += 1;
+                        stage_changed = false;
+                        duration = delay;
+                        self.events.push(Event::Alert(
+                            AlertLocation::Intersection(id),
+                            format!(
+                                "Extending a variable stage {},{},{},{}",
+                                min, delay, additional, signal_state.extensions_count
+                            ),
+                        ));
                     }
-                    // Should we only allow protected to extend or any not banned?
-                    // currently only the protected demand control extended.
-                    old_stage.get_priority_of_turn(req.turn, i) != TurnPriority::Protected
-                }) {
-                    signal_state.extensions_count = 0;
-                    duration = advance(signal_state, signal, i, !ped_waiting);
-                } else {
-                    signal_state.extensions_count += 1;
-                    duration = delay;
-                    self.events.push(Event::Alert(
-                        AlertLocation::Intersection(id),
-                        format!(
-                            "Extending a variable stage {},{},{},{}",
-                            min, delay, additional, signal_state.extensions_count
-                        ),
-                    ));
                 }
+                StageType::Actuated {
+                    min, max, passage, ..
+                } => {
+                    let elapsed = min + passage * (signal_state.extensions_count as f64);
+                    if elapsed >= max {
+                        // Max-out
+                        duration =
+                            advance(signal_state, signal, i, !ped_waiting, &has_call, &ped_call);
+                        signal_state.extensions_count = 0;
+                    } else if vehicle_detected(signal_state.current_stage) {
+                        signal_state.extensions_count += 1;
+                        stage_changed = false;
+                        duration = std::cmp::min(passage, max - elapsed);
+                    } else {
+                        // Gap-out
+                        duration =
+                            advance(signal_state, signal, i, !ped_waiting, &has_call, &ped_call);
+                        signal_state.extensions_count = 0;
+                    }
+                }
+            }
+        }
+
+        if stage_changed {
+            signal_state.stage_started_at = now;
+            let stage = &signal.stages[signal_state.current_stage];
+            signal_state.walk_granted = match stage.ped_intervals {
+                Some(ref p) if p.call_button => ped_call(signal_state.current_stage),
+                _ => true,
+            };
+            // Without a call, vehicles don't wait out the leading pedestrian interval, so the
+            // stage ends that much sooner. External controllers choose their own durations.
+            if !signal_state.walk_granted && !externally_controlled {
+                duration = std::cmp::max(
+                    Duration::const_seconds(1.0),
+                    duration - stage.leading_ped_interval(),
+                );
             }
         }
 
@@ -581,6 +740,10 @@ impl IntersectionSimState {
             }
         }
 
+        if !shared_sidewalk_corner {
+            self.record_safety_conflicts(&req, speed, now, map);
+        }
+
         // TODO For now, we're only interested in signals, and there's too much raw data to store
         // for stop signs too.
         let state = self.state.get_mut(&turn.parent).unwrap();
@@ -594,10 +757,29 @@ impl IntersectionSimState {
         true
     }
 
+    /// True if this pedestrian just started their turn by crossing against the signal. Only
+    /// answers once per crossing.
+    pub fn claim_jaywalking(&mut self, ped: PedestrianID) -> bool {
+        self.jaywalkers.remove(&ped)
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
 
+    pub fn set_signal_controller(&mut self, controller: Box<dyn SignalController>) {
+        self.signal_controller = Some(controller);
+    }
+
+    /// The change takes effect the next time the current stage ends.
+    pub fn set_externally_controlled(&mut self, id: IntersectionID, external: bool) {
+        if external {
+            self.external_signals.insert(id);
+        } else {
+            self.external_signals.remove(&id);
+        }
+    }
+
     pub fn handle_live_edited_traffic_signals(
         &mut self,
         now: Time,
@@ -625,6 +807,7 @@ impl IntersectionSimState {
                 }
                 (None, Some(_)) => {
                     state.signal = None;
+                    self.external_signals.remove(&state.id);
                     scheduler.cancel(Command::UpdateIntersection(state.id));
                 }
                 (None, None) => {}
@@ -755,6 +938,33 @@ impl IntersectionSimState {
         (state.current_stage, state.stage_ends_at - now)
     }
 
+    /// Summarize the agents waiting at a traffic signal, for external controllers.
+    pub fn signal_status(&self, now: Time, id: IntersectionID, map: &Map) -> SignalStatus {
+        let state = &self.state[&id];
+        let i = map.get_i(id);
+        let mut waiting: BTreeMap<_, Vec<(AgentID, Duration)>> = BTreeMap::new();
+        for m in i.movements.keys() {
+            waiting.insert(*m, Vec::new());
+        }
+        for (req, (started_at, _)) in &state.waiting {
+            if let Some(m) = i.movements.values().find(|m| m.members.contains(&req.turn)) {
+                waiting
+                    .get_mut(&m.id)
+                    .unwrap()
+                    .push((req.agent, now - *started_at));
+            }
+        }
+        SignalStatus {
+            id,
+            current_stage: state.signal.as_ref().unwrap().current_stage,
+            waiting,
+        }
+    }
+
+    pub fn is_externally_controlled(&self, id: IntersectionID) -> bool {
+        self.external_signals.contains(&id)
+    }
+
     pub fn describe_stats(&self) -> Vec<String> {
         vec![
             "intersection stats".to_string(),
@@ -932,10 +1142,30 @@ impl IntersectionSimState {
         let full_stage_duration = stage.stage_type.simple_duration();
         let remaining_stage_time = signal_state.stage_ends_at - now;
         let (our_time, _) = state.waiting[req];
+        let time_to_cross = turn.geom.length() / speed;
 
-        // Can't go at all this stage.
         let our_priority = stage.get_priority_of_turn(req.turn, map.get_i(state.id));
-        if our_priority == TurnPriority::Banned {
+        let elapsed = now - signal_state.stage_started_at;
+        if turn.turn_type.pedestrian_crossing() {
+            // Pedestrians only start crossing during WALK.
+            if our_priority != TurnPriority::Protected
+                || !signal_state.walk_granted
+                || stage.ped_signal(elapsed) != PedestrianSignal::Walk
+            {
+                return self.maybe_jaywalk(req, map, time_to_cross, now, scheduler);
+            }
+        } else if our_priority == TurnPriority::Banned {
+            // Can't go at all this stage.
+            return false;
+        } else if signal_state.walk_granted && elapsed < stage.leading_ped_interval() {
+            // Vehicles wait for the leading pedestrian interval to finish. If nobody pushed the
+            // call button, there's no interval.
+            if let Some(s) = scheduler {
+                s.update(
+                    signal_state.stage_started_at + stage.leading_ped_interval(),
+                    Command::update_agent(req.agent),
+                );
+            }
             return false;
         }
 
@@ -964,7 +1194,6 @@ impl IntersectionSimState {
         // Optimistically if nobody else is in the way, this is how long it'll take to finish the
         // turn. Don't start the turn if we won't finish by the time the light changes. If we get
         // it wrong, that's fine -- block the box a bit.
-        let time_to_cross = turn.geom.length() / speed;
         if time_to_cross > remaining_stage_time {
             // Signals enforce a minimum crosswalk time, but some pedestrians are configured to
             // walk very slowly. In that case, allow them to go anyway and wind up in the crosswalk
@@ -977,6 +1206,125 @@ impl IntersectionSimState {
         true
     }
 
+    // Called when a pedestrian isn't allowed to cross right now. If jaywalking is enabled and
+    // they've waited long enough, they'll cross anyway when no vehicle is about to use a
+    // conflicting movement. Turns already accepted were checked before this.
+    fn maybe_jaywalk(
+        &mut self,
+        req: &Request,
+        map: &Map,
+        time_to_cross: Duration,
+        now: Time,
+        scheduler: Option<&mut Scheduler>,
+    ) -> bool {
+        let patience = match self.jaywalk_after_waiting {
+            Some(x) => x,
+            None => {
+                return false;
+            }
+        };
+        let ped = match req.agent {
+            AgentID::Pedestrian(p) => p,
+            _ => unreachable!(),
+        };
+        let state = &self.state[&req.turn.parent];
+        let (our_time, _) = state.waiting[req];
+        if now < our_time + patience {
+            if let Some(s) = scheduler {
+                s.update(our_time + patience, Command::update_agent(req.agent));
+            }
+            return false;
+        }
+
+        let turn = map.get_t(req.turn);
+        let vehicle_coming = state
+            .waiting
+            .keys()
+            .filter(|other| !other.agent.is_pedestrian())
+            .any(|other| turn.conflicts_with(map.get_t(other.turn)))
+            || state.leader_eta.values().any(|(other, eta)| {
+                *eta <= now + time_to_cross && turn.conflicts_with(map.get_t(other.turn))
+            });
+        if vehicle_coming {
+            if let Some(s) = scheduler {
+                s.update(now + JAYWALK_RETRY, Command::update_agent(req.agent));
+            }
+            return false;
+        }
+
+        self.jaywalkers.insert(ped);
+        true
+    }
+
+    // As this request starts its turn, measure near-misses between turning vehicles and
+    // pedestrians or cyclists. Conflicting turns never overlap in the simulation, so these measure
+    // how close they came. Only vehicles report when they're approaching an intersection, so
+    // time-to-collision isn't measured for pedestrians walking up to a crosswalk.
+    fn record_safety_conflicts(&mut self, req: &Request, speed: Speed, now: Time, map: &Map) {
+        let turn = map.get_t(req.turn);
+        let state = self.state.get_mut(&req.turn.parent).unwrap();
+        let mut conflicts = Vec::new();
+
+        // Did somebody just clear a conflicting path?
+        state
+            .recently_finished
+            .retain(|(_, _, t)| now - *t <= MAX_POST_ENCROACHMENT_TIME);
+        for (agent, other_turn, finished_at) in &state.recently_finished {
+            if *agent == req.agent {
+                continue;
+            }
+            conflicts.push((
+                *agent,
+                map.get_t(*other_turn),
+                ConflictMeasure::PostEncroachmentTime(now - *finished_at),
+            ));
+        }
+
+        // Will somebody reach a conflicting path before we clear ours?
+        let time_to_cross = turn.geom.length() / speed;
+        for (other, eta) in state.leader_eta.values() {
+            if other.agent == req.agent
+                || *eta < now
+                || *eta - now > MAX_TIME_TO_COLLISION
+                || *eta - now > time_to_cross
+            {
+                continue;
+            }
+            conflicts.push((
+                other.agent,
+                map.get_t(other.turn),
+                ConflictMeasure::TimeToCollision(*eta - now),
+            ));
+        }
+
+        // A vehicle that saw a pedestrian coming (time-to-collision) is often also the one they
+        // follow across (post-encroachment time). Only count each pair once.
+        state
+            .recent_conflicts
+            .retain(|(_, _, t)| now - *t <= MAX_POST_ENCROACHMENT_TIME);
+        for (other, other_turn, measure) in conflicts {
+            if let Some((vehicle_turn, vulnerable_turn, vulnerable)) =
+                vulnerable_conflict((req.agent, turn), (other, other_turn))
+            {
+                let pair = (req.agent.min(other), req.agent.max(other));
+                if state
+                    .recent_conflicts
+                    .iter()
+                    .any(|(a, b, _)| (*a, *b) == pair)
+                {
+                    continue;
+                }
+                state.recent_conflicts.push((pair.0, pair.1, now));
+                self.events.push(Event::SafetyConflict(SafetyConflict {
+                    vehicle_turn,
+                    vulnerable_turn,
+                    vulnerable,
+                    measure,
+                }));
+            }
+        }
+    }
+
     // If true, the request can go.
     fn handle_accepted_conflicts(
         &mut self,
@@ -1109,6 +1457,8 @@ impl SignalState {
             current_stage: 0,
             stage_ends_at: now,
             extensions_count: 0,
+            stage_started_at: now,
+            walk_granted: true,
         };
 
         let signal = map.get_traffic_signal(id);
@@ -1126,6 +1476,12 @@ impl SignalState {
                 }
             } else {
                 state.stage_ends_at = now + dt - offset;
+                // Don't go back before midnight; just shorten the pedestrian intervals then.
+                state.stage_started_at = if now - Time::START_OF_DAY >= offset {
+                    now - offset
+                } else {
+                    Time::START_OF_DAY
+                };
                 break;
             }
         }
@@ -1134,6 +1490,31 @@ impl SignalState {
     }
 }
 
+// If one of these is a vehicle turning left or right across the path of a pedestrian or cyclist,
+// returns the vehicle's turn, the other turn, and who's vulnerable.
+fn vulnerable_conflict(
+    a: (AgentID, &Turn),
+    b: (AgentID, &Turn),
+) -> Option<(TurnID, TurnID, AgentType)> {
+    for ((vehicle, vehicle_turn), (other, other_turn)) in [(a, b), (b, a)] {
+        if !matches!(vehicle_turn.turn_type, TurnType::Left | TurnType::Right)
+            || !matches!(
+                vehicle.to_type(),
+                AgentType::Car | AgentType::Truck | AgentType::Bus | AgentType::Train
+            )
+        {
+            continue;
+        }
+        let vulnerable = other.to_type();
+        if matches!(vulnerable, AgentType::Pedestrian | AgentType::Bike)
+            && vehicle_turn.conflicts_with(other_turn)
+        {
+            return Some((vehicle_turn.id, other_turn.id, vulnerable));
+        }
+    }
+    None
+}
+
 fn allow_block_the_box(i: &Intersection) -> bool {
     // Degenerate intersections are often just artifacts of how roads are split up in OSM. Allow
     // vehicles to get stuck in them, since the only possible thing they could block is pedestrians
@@ -1173,3 +1554,89 @@ fn allow_block_the_box(i: &Intersection) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use geom::{PolyLine, Pt2D};
+    use map_model::RoadID;
+
+    use super::*;
+    use crate::VehicleType;
+
+    fn turn(id: usize, turn_type: TurnType, from: (f64, f64), to: (f64, f64)) -> Turn {
+        Turn {
+            id: TurnID {
+                parent: IntersectionID(0),
+                src: LaneID {
+                    road: RoadID(id),
+                    offset: 0,
+                },
+                dst: LaneID {
+                    road: RoadID(id),
+                    offset: 1,
+                },
+            },
+            turn_type,
+            geom: PolyLine::must_new(vec![Pt2D::new(from.0, from.1), Pt2D::new(to.0, to.1)]),
+        }
+    }
+
+    fn car(id: usize, vehicle_type: VehicleType) -> AgentID {
+        AgentID::Car(CarID { id, vehicle_type })
+    }
+
+    #[test]
+    fn test_vulnerable_conflict() {
+        let left = turn(0, TurnType::Left, (0.0, -10.0), (0.0, 10.0));
+        let straight = turn(1, TurnType::Straight, (0.0, -10.0), (0.0, 10.0));
+        let crosswalk = turn(2, TurnType::Crosswalk, (-10.0, 0.0), (10.0, 0.0));
+        let bike_lane = turn(3, TurnType::Straight, (-10.0, 0.0), (10.0, 0.0));
+        let far_crosswalk = turn(4, TurnType::Crosswalk, (-10.0, 20.0), (10.0, 20.0));
+        let ped = AgentID::Pedestrian(PedestrianID(0));
+
+        // Either order works
+        for vehicle_type in [VehicleType::Car, VehicleType::Truck, VehicleType::Bus] {
+            let vehicle = car(1, vehicle_type);
+            let expected = Some((left.id, crosswalk.id, AgentType::Pedestrian));
+            assert_eq!(
+                vulnerable_conflict((vehicle, &left), (ped, &crosswalk)),
+                expected
+            );
+            assert_eq!(
+                vulnerable_conflict((ped, &crosswalk), (vehicle, &left)),
+                expected
+            );
+        }
+        assert_eq!(
+            vulnerable_conflict(
+                (car(1, VehicleType::Truck), &left),
+                (car(2, VehicleType::Bike), &bike_lane)
+            ),
+            Some((left.id, bike_lane.id, AgentType::Bike))
+        );
+
+        // Vehicles going straight aren't turning across anybody's path
+        assert_eq!(
+            vulnerable_conflict((car(1, VehicleType::Car), &straight), (ped, &crosswalk)),
+            None
+        );
+        // Turning bikes aren't the ones putting others at risk
+        assert_eq!(
+            vulnerable_conflict((car(1, VehicleType::Bike), &left), (ped, &crosswalk)),
+            None
+        );
+        // Two vehicles don't count
+        assert_eq!(
+            vulnerable_conflict(
+                (car(1, VehicleType::Truck), &left),
+                (car(2, VehicleType::Car), &bike_lane)
+            ),
+            None
+        );
+        // Paths that don't cross
+        assert_eq!(
+            vulnerable_conflict((car(1, VehicleType::Car), &left), (ped, &far_crosswalk)),
+            None
+        );
+    }
+}
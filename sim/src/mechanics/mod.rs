@@ -1,12 +1,18 @@
+pub use self::car_following::{
+    CarFollowingModel, Gipps, IntelligentDriver, Kinematic, LongitudinalModel,
+};
 pub(crate) use self::driving::DrivingSimState;
 pub(crate) use self::intersection::IntersectionSimState;
-pub(crate) use self::parking::{ParkingSim, ParkingSimState};
+pub(crate) use self::parking::{is_acceptable_cost, parking_cost, ParkingSim, ParkingSimState};
 pub(crate) use self::queue::Queue;
+pub use self::signal_control::{SignalController, SignalDecision, SignalStatus};
 pub(crate) use self::walking::WalkingSimState;
 
 mod car;
+mod car_following;
 mod driving;
 mod intersection;
 mod parking;
 mod queue;
+mod signal_control;
 mod walking;
@@ -9,10 +9,10 @@ use abstutil::{
     deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
     Timer,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, Duration, PolyLine, Pt2D};
 use map_model::{
-    BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
-    PathStep, Position, Traversable, TurnID,
+    BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, ParkingZone,
+    PathConstraints, PathStep, Position, Traversable, TurnID, MAX_WALKING_SPEED,
 };
 
 use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
@@ -64,6 +64,8 @@ pub trait ParkingSim {
     /// them there, producing some nice, realistic churn if there's too much contention. But
     /// the implementation has some internal jitter between different vehicles, to discourage
     /// everybody near one spot from all competing for it.
+    /// Drivers look for a spot whose price and walk to the target they find acceptable, and only
+    /// settle for the nearest free spot if there's nothing acceptable anywhere.
     /// Note the first PathStep is the turn after start, NOT PathStep::Lane(start).
     fn path_to_free_parking_spot(
         &self,
@@ -579,31 +581,44 @@ impl ParkingSim for NormalParkingSimState {
         let mut rng =
             XorShiftRng::seed_from_u64((vehicle.id.id + start.encode_u32() as usize) as u64);
 
+        // If every free spot costs more than the driver wants to pay, they'll settle for the first
+        // one found
+        let mut fallback: Option<(LaneID, ParkingSpot, Position)> = None;
+
         while !queue.is_empty() {
             let (dist_so_far, current) = queue.pop().unwrap();
-            // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
-            // opens up on the 'start' lane, but behind the car.
+            // Don't search the whole map for an acceptable spot; settle for one found nearby
+            if fallback.is_some() && Distance::ZERO - dist_so_far > MAX_SEARCH_FOR_ACCEPTABLE_SPOT {
+                break;
+            }
+            // If the current lane has an acceptable spot open, we wouldn't be asking. This can
+            // happen if a spot opens up on the 'start' lane, but behind the car.
             if current != start {
-                // Pick the closest to the start of the lane, since that's closest to where we came
-                // from
-                if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
-                    .into_iter()
-                    .min_by_key(|(_, pos)| pos.dist_along())
+                let candidates =
+                    self.get_all_free_spots(Position::start(current), vehicle, target, map);
+                if let Some((spot, pos, _)) = candidates
+                    .iter()
+                    .filter_map(|(spot, pos)| {
+                        let cost = parking_cost(
+                            *spot,
+                            self.spot_to_sidewalk_pos(*spot, map),
+                            target,
+                            map,
+                        )?;
+                        is_acceptable_cost(cost).then_some((*spot, *pos, cost))
+                    })
+                    .min_by_key(|(_, _, cost)| *cost)
                 {
-                    let mut steps = vec![PathStep::Lane(current)];
-                    let mut current = current;
-                    loop {
-                        if current == start {
-                            // Don't include PathStep::Lane(start)
-                            steps.pop();
-                            steps.reverse();
-                            return Some((steps, spot, pos));
-                        }
-                        let turn = backrefs[&current];
-                        steps.push(PathStep::Turn(turn));
-                        steps.push(PathStep::Lane(turn.src));
-                        current = turn.src;
+                    return Some((backtrack(start, current, &backrefs), spot, pos));
+                }
+                if fallback.is_none() {
+                    // Pick the closest to the start of the lane, since that's closest to where we
+                    // came from
+                    if let Some((spot, pos)) = candidates
+                        .into_iter()
+                        .min_by_key(|(_, pos)| pos.dist_along())
+                    {
+                        fallback = Some((current, spot, pos));
                     }
                 }
             }
@@ -625,7 +640,8 @@ impl ParkingSim for NormalParkingSimState {
             }
         }
 
-        None
+        let (current, spot, pos) = fallback?;
+        Some((backtrack(start, current, &backrefs), spot, pos))
     }
 
     fn collect_events(&mut self) -> Vec<Event> {
@@ -1012,20 +1028,7 @@ impl ParkingSim for InfiniteParkingSimState {
                     .into_iter()
                     .min_by_key(|(_, pos)| pos.dist_along())
                 {
-                    let mut steps = vec![PathStep::Lane(current)];
-                    let mut current = current;
-                    loop {
-                        if current == start {
-                            // Don't include PathStep::Lane(start)
-                            steps.pop();
-                            steps.reverse();
-                            return Some((steps, spot, pos));
-                        }
-                        let turn = backrefs[&current];
-                        steps.push(PathStep::Turn(turn));
-                        steps.push(PathStep::Lane(turn.src));
-                        current = turn.src;
-                    }
+                    return Some((backtrack(start, current, &backrefs), spot, pos));
                 }
             }
             for turn in map.get_turns_for(current, PathConstraints::Car) {
@@ -1070,3 +1073,167 @@ impl ParkingSim for InfiniteParkingSimState {
         cars
     }
 }
+
+/// Follows the turns found by a search back to `start`. The first PathStep is the turn after start,
+/// NOT PathStep::Lane(start).
+fn backtrack(start: LaneID, end: LaneID, backrefs: &HashMap<LaneID, TurnID>) -> Vec<PathStep> {
+    let mut steps = vec![PathStep::Lane(end)];
+    let mut current = end;
+    while current != start {
+        let turn = backrefs[&current];
+        steps.push(PathStep::Turn(turn));
+        steps.push(PathStep::Lane(turn.src));
+        current = turn.src;
+    }
+    // Don't include PathStep::Lane(start)
+    steps.pop();
+    steps.reverse();
+    steps
+}
+
+/// When comparing spots, drivers assume they'll stay this long.
+const EXPECTED_STAY: Duration = Duration::const_seconds(2.0 * 3600.0);
+/// How much drivers value the time spent walking between their car and their destination, in
+/// dollars per hour.
+const VALUE_OF_WALKING_TIME: f64 = 20.0;
+/// Drivers would rather cruise for a better spot than accept one costing more than this, in cents.
+const MAX_ACCEPTABLE_COST: usize = 1500;
+/// Past this (jittered) distance, drivers stop searching for an acceptable spot and settle for
+/// the first free one found.
+const MAX_SEARCH_FOR_ACCEPTABLE_SPOT: Distance = Distance::const_meters(1000.0);
+
+/// How much a driver heading to some building dislikes a spot, in cents. This weighs the price of
+/// parking against walking there and back. None means the spot's time limit is too short.
+pub(crate) fn parking_cost(
+    spot: ParkingSpot,
+    sidewalk_pos: Position,
+    target: BuildingID,
+    map: &Map,
+) -> Option<usize> {
+    let walk = sidewalk_pos.pt(map).dist_to(map.get_b(target).label_center);
+    cost_of_walk_and_zone(walk, spot.pricing_zone(map))
+}
+
+/// The arithmetic behind `parking_cost`, given the straight-line walk one way.
+fn cost_of_walk_and_zone(walk: Distance, zone: Option<&ParkingZone>) -> Option<usize> {
+    let walk_hours = 2.0 * (walk / MAX_WALKING_SPEED).inner_seconds() / 3600.0;
+    let mut dollars = VALUE_OF_WALKING_TIME * walk_hours;
+    if let Some(zone) = zone {
+        if zone
+            .max_stay
+            .map(|max| max < EXPECTED_STAY)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        dollars += zone.price_per_hour * EXPECTED_STAY.inner_seconds() / 3600.0;
+    }
+    Some((100.0 * dollars) as usize)
+}
+
+pub(crate) fn is_acceptable_cost(cost: usize) -> bool {
+    cost <= MAX_ACCEPTABLE_COST
+}
+
+impl ParkingSpot {
+    /// The zone setting the price and time limit of this spot, if any.
+    pub fn pricing_zone(self, map: &Map) -> Option<&ParkingZone> {
+        match self {
+            ParkingSpot::Onstreet(l, _) => map.get_parking_pricing().zone_for_lane(l),
+            ParkingSpot::Offstreet(_, _) => None,
+            ParkingSpot::Lot(pl, _) => map.get_parking_pricing().zone_for_lot(pl),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use map_model::{IntersectionID, RoadID};
+
+    use super::*;
+
+    fn zone(price_per_hour: f64, max_stay: Option<Duration>) -> ParkingZone {
+        ParkingZone {
+            name: "downtown".to_string(),
+            price_per_hour,
+            max_stay,
+            onstreet: BTreeSet::new(),
+            lots: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_parking_cost() {
+        // Free and right at the door
+        assert_eq!(cost_of_walk_and_zone(Distance::ZERO, None), Some(0));
+
+        // The price is paid for EXPECTED_STAY
+        assert_eq!(
+            cost_of_walk_and_zone(Distance::ZERO, Some(&zone(2.5, None))),
+            Some(500)
+        );
+        assert_eq!(
+            cost_of_walk_and_zone(Distance::ZERO, Some(&zone(2.5, Some(EXPECTED_STAY)))),
+            Some(500)
+        );
+
+        // Walking 15 minutes there and back is valued at VALUE_OF_WALKING_TIME. Allow a cent of
+        // rounding.
+        let walk = MAX_WALKING_SPEED * Duration::minutes(15);
+        let cost = cost_of_walk_and_zone(walk, None).unwrap();
+        assert!((999..=1000).contains(&cost), "walking cost {}", cost);
+        let cost = cost_of_walk_and_zone(walk, Some(&zone(2.5, None))).unwrap();
+        assert!(
+            (1499..=1500).contains(&cost),
+            "walking and paying cost {}",
+            cost
+        );
+
+        // Time limits too short for the stay rule the spot out entirely
+        assert_eq!(
+            cost_of_walk_and_zone(Distance::ZERO, Some(&zone(0.0, Some(Duration::hours(1))))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_acceptable_cost() {
+        assert!(is_acceptable_cost(0));
+        assert!(is_acceptable_cost(MAX_ACCEPTABLE_COST));
+        assert!(!is_acceptable_cost(MAX_ACCEPTABLE_COST + 1));
+    }
+
+    #[test]
+    fn test_backtrack() {
+        let lane = |r| LaneID {
+            road: RoadID(r),
+            offset: 0,
+        };
+        let turn = |i, src, dst| TurnID {
+            parent: IntersectionID(i),
+            src: lane(src),
+            dst: lane(dst),
+        };
+
+        // 0 -> 1 -> 2, plus a dead end off 1 that shouldn't show up
+        let mut backrefs = HashMap::new();
+        backrefs.insert(lane(1), turn(0, 0, 1));
+        backrefs.insert(lane(2), turn(1, 1, 2));
+        backrefs.insert(lane(3), turn(1, 1, 3));
+
+        assert_eq!(
+            backtrack(lane(0), lane(2), &backrefs),
+            vec![
+                PathStep::Turn(turn(0, 0, 1)),
+                PathStep::Lane(lane(1)),
+                PathStep::Turn(turn(1, 1, 2)),
+                PathStep::Lane(lane(2)),
+            ]
+        );
+        assert_eq!(
+            backtrack(lane(1), lane(2), &backrefs),
+            vec![PathStep::Turn(turn(1, 1, 2)), PathStep::Lane(lane(2))]
+        );
+        assert!(backtrack(lane(0), lane(0), &backrefs).is_empty());
+    }
+}
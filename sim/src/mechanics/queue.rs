@@ -7,7 +7,7 @@ use geom::{Distance, Time};
 use map_model::{Map, Position, Traversable};
 
 use crate::mechanics::car::{Car, CarState};
-use crate::{CarID, VehicleType, FOLLOWING_DISTANCE};
+use crate::{CarID, FOLLOWING_DISTANCE};
 
 /// A Queue of vehicles on a single lane or turn. This is where
 /// https://a-b-street.github.io/docs/tech/trafficsim/discrete_event.html#exact-positions is
@@ -245,6 +245,11 @@ impl Queue {
                             // Same as the Crossing logic
                             new_dist.lerp(new_time.percent_clamp_end(now)).min(bound)
                         }
+                        CarState::Passing {
+                            ref time_int,
+                            ref dist_int,
+                            ..
+                        } => dist_int.lerp(time_int.percent_clamp_end(now)).min(bound),
                         CarState::Unparking { front, .. } => front,
                         CarState::Parking(front, _, _) => front,
                         CarState::IdlingAtStop(front, _) => front,
@@ -276,7 +281,12 @@ impl Queue {
             if let Some(ref mut intermediate_results) = intermediate_results {
                 intermediate_results.push(entry.clone());
             }
-            previous = Some(entry);
+            // Somebody passing through the oncoming lane is beside the vehicle behind them, so
+            // they don't bound it yet
+            let passing = matches!(entry.member, Queued::Vehicle(id) if matches!(cars[&id].state, CarState::Passing { .. }));
+            if !passing {
+                previous = Some(entry);
+            }
         }
         // Enable to detect possible bugs, but save time otherwise
         if false {
@@ -425,10 +435,10 @@ impl Queue {
         let bike_cost = if self
             .members
             .iter()
-            .any(|x| matches!(x, Queued::Vehicle(c) if c.vehicle_type == VehicleType::Bike))
+            .any(|x| matches!(x, Queued::Vehicle(c) if c.vehicle_type.rides_bike_lanes()))
             || self
                 .laggy_head
-                .map(|c| c.vehicle_type == VehicleType::Bike)
+                .map(|c| c.vehicle_type.rides_bike_lanes())
                 .unwrap_or(false)
         {
             1
@@ -439,6 +449,35 @@ impl Queue {
         (num_vehicles, bike_cost)
     }
 
+    /// True if nothing is in this queue or about to enter it.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+            && self.laggy_head.is_none()
+            && self.reserved_length == Distance::ZERO
+    }
+
+    /// Record that a vehicle at some index passed the vehicle immediately in front of it.
+    pub fn swap_with_leader(&mut self, car: CarID, idx: usize) {
+        assert_eq!(self.members[idx], Queued::Vehicle(car));
+        assert!(matches!(self.members[idx - 1], Queued::Vehicle(_)));
+        self.members.swap(idx - 1, idx);
+    }
+
+    /// The number of vehicles waiting in this queue, not counting blockages. Vehicles not in
+    /// `cars` are skipped.
+    pub fn num_stopped_vehicles(&self, cars: &FixedMap<CarID, Car>) -> usize {
+        self.members
+            .iter()
+            .filter(|x| match x {
+                Queued::Vehicle(c) => matches!(
+                    cars.get(c).map(|car| &car.state),
+                    Some(CarState::Queued { .. }) | Some(CarState::WaitingToAdvance { .. })
+                ),
+                _ => false,
+            })
+            .count()
+    }
+
     /// Find the vehicle in front of the specified input. None if the specified vehicle isn't
     /// ACTIVE (not a blockage) in the queue at all, or they're the front (with or without a laggy
     /// head).
@@ -599,6 +638,16 @@ fn dump_cars(dists: &[QueueEntry], cars: &FixedMap<CarID, Car>, id: Traversable,
                         new_dist.start, new_dist.end, new_time.start, new_time.end
                     );
                 }
+                CarState::Passing {
+                    ref time_int,
+                    ref dist_int,
+                    oncoming,
+                } => {
+                    println!(
+                        "  Going {} .. {} during {} .. {}, passing through {}",
+                        dist_int.start, dist_int.end, time_int.start, time_int.end, oncoming
+                    );
+                }
                 CarState::Queued { .. } => {
                     println!("  Queued currently");
                 }
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use abstutil::serialize_btreemap;
+use geom::{Duration, Time};
+use map_model::{IntersectionID, Map, MovementID};
+
+use crate::AgentID;
+
+/// Something outside the simulation that decides how some traffic signals behave, instead of
+/// following the fixed or variable timing in their `ControlTrafficSignal`. This is useful for
+/// benchmarking adaptive controllers against the existing plans.
+///
+/// The controller is consulted every time the current stage of an externally controlled signal
+/// ends.
+pub trait SignalController: Send + Sync {
+    /// Pick the next stage to serve and for how long, based on the current state of the
+    /// intersection.
+    fn next_stage(&mut self, now: Time, status: &SignalStatus, map: &Map) -> SignalDecision;
+
+    /// The simulation can be cloned, so controllers must be too.
+    fn clone_box(&self) -> Box<dyn SignalController>;
+}
+
+impl Clone for Box<dyn SignalController> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A snapshot of a traffic signal and the agents waiting at it.
+#[derive(Clone, Debug, Serialize)]
+pub struct SignalStatus {
+    pub id: IntersectionID,
+    /// The stage currently being served, zero based
+    pub current_stage: usize,
+    /// For every movement at the intersection, the agents waiting to start it and how long
+    /// they've waited so far. The number of agents is the queue length at the stop line.
+    #[serde(serialize_with = "serialize_btreemap")]
+    pub waiting: BTreeMap<MovementID, Vec<(AgentID, Duration)>>,
+}
+
+impl SignalStatus {
+    /// The total time all agents have spent waiting so far.
+    pub fn total_waiting_time(&self) -> Duration {
+        let mut total = Duration::ZERO;
+        for agents in self.waiting.values() {
+            for (_, dt) in agents {
+                total += *dt;
+            }
+        }
+        total
+    }
+}
+
+/// What an external controller wants a traffic signal to do next.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SignalDecision {
+    /// Which stage to serve, zero based. If this is the current stage, it's extended.
+    pub stage: usize,
+    /// How long to serve the stage before asking the controller again. This is clamped to at
+    /// least one second.
+    pub duration: Duration,
+}
@@ -5,8 +5,8 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_multimap, serialize_multimap, FixedMap, IndexableKey, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed, Time};
 use map_model::{
-    BuildingID, DrivingSide, IntersectionID, Map, ParkingLotID, Path, PathConstraints, PathStep,
-    RoadID, TransitRouteID, Traversable,
+    osm, BuildingID, DrivingSide, IntersectionID, Map, ParkingLotID, Path, PathConstraints,
+    PathStep, Position, RoadID, TransitRouteID, Traversable,
 };
 
 use crate::sim::Ctx;
@@ -14,8 +14,8 @@ use crate::{
     pedestrian_body_radius, AgentID, AgentProperties, Command, CommutersVehiclesCounts,
     CreatePedestrian, DistanceInterval, DrawPedCrowdInput, DrawPedestrianInput, Event, Intent,
     IntersectionSimState, ParkedCar, ParkingSpot, PedCrowdLocation, PedestrianID, PersonID,
-    Problem, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval, TransitSimState, TripID,
-    TripManager, UnzoomedAgent,
+    Problem, Scheduler, SidewalkPOI, SidewalkSpot, SimOptions, TimeInterval, TransitSimState,
+    TripID, TripManager, UnzoomedAgent,
 };
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
@@ -33,14 +33,18 @@ pub(crate) struct WalkingSimState {
     )]
     peds_per_traversable: MultiMap<Traversable, PedestrianID>,
     events: Vec<Event>,
+    // If set, pedestrians cross mid-block instead of walking to a crosswalk and back, when that
+    // saves them more than this much time.
+    jaywalk_after_waiting: Option<Duration>,
 }
 
 impl WalkingSimState {
-    pub fn new() -> WalkingSimState {
+    pub fn new(opts: &SimOptions) -> WalkingSimState {
         WalkingSimState {
             peds: FixedMap::new(),
             peds_per_traversable: MultiMap::new(),
             events: Vec::new(),
+            jaywalk_after_waiting: opts.jaywalk_after_waiting.map(Duration::seconds),
         }
     }
 
@@ -70,6 +74,7 @@ impl WalkingSimState {
                 steep_uphill: false,
             },
             speed: params.speed,
+            on_scooter: params.on_scooter,
             total_blocked_time: Duration::ZERO,
             started_at: now,
             path: params.path,
@@ -100,6 +105,7 @@ impl WalkingSimState {
                 now,
                 map,
                 &mut self.events,
+                self.jaywalk_after_waiting,
             ),
         };
 
@@ -217,6 +223,18 @@ impl WalkingSimState {
                             ctx.scheduler
                                 .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
                         }
+                        SidewalkPOI::Dockless => {
+                            self.peds_per_traversable
+                                .remove(ped.path.current_step().as_traversable(), ped.id);
+                            trips.ped_reached_dockless_point(
+                                now,
+                                ped.id,
+                                ped.total_blocked_time,
+                                ped.path.total_length(),
+                                ctx,
+                            );
+                            self.peds.remove(&id);
+                        }
                         SidewalkPOI::SuddenlyAppear => unreachable!(),
                         SidewalkPOI::DeferredParkingSpot => unreachable!(),
                     }
@@ -241,6 +259,7 @@ impl WalkingSimState {
                         &mut self.peds_per_traversable,
                         &mut self.events,
                         ctx.scheduler,
+                        self.jaywalk_after_waiting,
                     ) {
                         ctx.scheduler
                             .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -258,6 +277,7 @@ impl WalkingSimState {
                     &mut self.peds_per_traversable,
                     &mut self.events,
                     ctx.scheduler,
+                    self.jaywalk_after_waiting,
                 ) {
                     ctx.scheduler
                         .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -277,6 +297,7 @@ impl WalkingSimState {
                     now,
                     ctx.map,
                     &mut self.events,
+                    self.jaywalk_after_waiting,
                 );
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -301,6 +322,7 @@ impl WalkingSimState {
                     now,
                     ctx.map,
                     &mut self.events,
+                    self.jaywalk_after_waiting,
                 );
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -341,7 +363,33 @@ impl WalkingSimState {
                     now,
                     ctx.map,
                     &mut self.events,
+                    self.jaywalk_after_waiting,
+                );
+                ctx.scheduler
+                    .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
+            }
+            PedState::CrossingMidBlock(_, _, to_dist) => {
+                // Skip the crosswalk and continue along the other sidewalk
+                self.peds_per_traversable
+                    .remove(ped.path.current_step().as_traversable(), ped.id);
+                ped.path.shift(ctx.map);
+                ped.path.shift(ctx.map);
+                ped.state = ped.crossing_state(
+                    &self.peds_per_traversable,
+                    to_dist,
+                    now,
+                    ctx.map,
+                    &mut self.events,
+                    None,
                 );
+                self.peds_per_traversable
+                    .insert(ped.path.current_step().as_traversable(), ped.id);
+                self.events.push(Event::AgentEntersTraversable(
+                    AgentID::Pedestrian(ped.id),
+                    Some(ped.trip),
+                    ped.path.current_step().as_traversable(),
+                    None,
+                ));
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
@@ -407,7 +455,8 @@ impl WalkingSimState {
             // connection
             PedState::LeavingBuilding(_, _)
             | PedState::LeavingParkingLot(_, _)
-            | PedState::FinishingBiking(_, _, _) => Distance::ZERO,
+            | PedState::FinishingBiking(_, _, _)
+            | PedState::CrossingMidBlock(_, _, _) => Distance::ZERO,
             // In all of these cases, we haven't shifted the PathStep that led us to this state yet
             PedState::WaitingToTurn(_, _)
             | PedState::EnteringBuilding(_, _)
@@ -508,7 +557,8 @@ impl WalkingSimState {
                 }
                 PedState::StartingToBike(_, _, _)
                 | PedState::FinishingBiking(_, _, _)
-                | PedState::WaitingForBus(_, _) => {
+                | PedState::WaitingForBus(_, _)
+                | PedState::CrossingMidBlock(_, _, _) => {
                     // The backwards half of the sidewalk is closer to the road.
                     backwards.push((*id, dist));
                 }
@@ -598,6 +648,10 @@ impl WalkingSimState {
 
     pub fn populate_commuter_counts(&self, cnts: &mut CommutersVehiclesCounts) {
         for p in self.peds.values() {
+            if p.on_scooter {
+                cnts.cyclists += 1;
+                continue;
+            }
             match p.goal.connection {
                 SidewalkPOI::ParkingSpot(_) | SidewalkPOI::DeferredParkingSpot => {
                     cnts.walking_to_from_car += 1;
@@ -661,6 +715,7 @@ struct Pedestrian {
     id: PedestrianID,
     state: PedState,
     speed: Speed,
+    on_scooter: bool,
     total_blocked_time: Duration,
     // TODO organize analytics better.
     started_at: Time,
@@ -680,7 +735,21 @@ impl Pedestrian {
         start_time: Time,
         map: &Map,
         events: &mut Vec<Event>,
+        jaywalk_after_waiting: Option<Duration>,
     ) -> PedState {
+        if let Some(state) = jaywalk_after_waiting
+            .and_then(|patience| self.mid_block_crossing(start_dist, start_time, patience, map))
+        {
+            events.push(Event::ProblemEncountered(
+                self.trip,
+                Problem::MidBlockCrossing(Position::new(
+                    self.path.current_step().as_lane(),
+                    start_dist,
+                )),
+            ));
+            return state;
+        }
+
         let end_dist = if self.path.is_last_step() {
             self.goal.sidewalk_pos.dist_along()
         } else {
@@ -706,11 +775,12 @@ impl Pedestrian {
         }
 
         let dist_int = DistanceInterval::new_walking(start_dist, end_dist);
-        let (speed, percent_incline) = self.path.current_step().max_speed_and_incline_along(
-            Some(self.speed),
-            PathConstraints::Pedestrian,
-            map,
-        );
+        let step = self.path.current_step();
+        let (speed, percent_incline) = if self.on_scooter {
+            step.max_scooter_speed_and_incline_along(self.speed, map)
+        } else {
+            step.max_speed_and_incline_along(Some(self.speed), PathConstraints::Pedestrian, map)
+        };
         let time_int = TimeInterval::new(
             start_time,
             start_time + dist_int.length() / (speed_penalty * speed),
@@ -722,6 +792,77 @@ impl Pedestrian {
         }
     }
 
+    // If the path walks along a sidewalk to a crosswalk over the same road, then comes back along
+    // the other side, cutting straight across might save a lot of time. Vehicles don't see or yield
+    // to somebody crossing mid-block, and highways are never crossed this way.
+    fn mid_block_crossing(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        patience: Duration,
+        map: &Map,
+    ) -> Option<PedState> {
+        if self.on_scooter {
+            return None;
+        }
+        let steps = self.path.get_steps();
+        if steps.len() < 3 {
+            return None;
+        }
+        let (from, turn, to) = match (steps[0], steps[1], steps[2]) {
+            (
+                PathStep::Lane(from) | PathStep::ContraflowLane(from),
+                PathStep::Turn(turn) | PathStep::ContraflowTurn(turn),
+                PathStep::Lane(to) | PathStep::ContraflowLane(to),
+            ) => (from, turn, to),
+            _ => {
+                return None;
+            }
+        };
+        if from.road != to.road
+            || !map.get_t(turn).turn_type.pedestrian_crossing()
+            || map.get_r(from.road).get_rank() == osm::RoadRank::Highway
+        {
+            return None;
+        }
+
+        let from_len = map.get_l(from).length();
+        let to_len = map.get_l(to).length();
+        let to_dist = Position::new(from, start_dist)
+            .equiv_pos(to, map)
+            .dist_along();
+        // Where this step would start and end on the other sidewalk
+        let (to_entry, to_exit) = match steps[2] {
+            PathStep::Lane(_) => (Distance::ZERO, to_len),
+            _ => (to_len, Distance::ZERO),
+        };
+        let to_exit = if steps.len() == 3 {
+            self.goal.sidewalk_pos.dist_along()
+        } else {
+            to_exit
+        };
+        let to_crosswalk = match steps[0] {
+            PathStep::Lane(_) => from_len - start_dist,
+            _ => start_dist,
+        };
+
+        let line = Line::new(
+            Position::new(from, start_dist).pt(map),
+            Position::new(to, to_dist).pt(map),
+        )
+        .ok()?;
+        let detour = to_crosswalk + map.get_t(turn).geom.length() + (to_exit - to_entry).abs();
+        let direct = line.length() + (to_exit - to_dist).abs();
+        if detour <= direct || (detour - direct) / self.speed < patience {
+            return None;
+        }
+        Some(PedState::CrossingMidBlock(
+            line.clone(),
+            TimeInterval::new(start_time, start_time + line.length() / self.speed),
+            to_dist,
+        ))
+    }
+
     fn get_dist_along(&self, now: Time, map: &Map) -> Distance {
         match self.state {
             PedState::Crossing {
@@ -739,6 +880,10 @@ impl Pedestrian {
             PedState::StartingToBike(ref spot, _, _) => spot.sidewalk_pos.dist_along(),
             PedState::FinishingBiking(ref spot, _, _) => spot.sidewalk_pos.dist_along(),
             PedState::WaitingForBus(_, _) => self.goal.sidewalk_pos.dist_along(),
+            PedState::CrossingMidBlock(ref line, _, _) => map
+                .get_l(self.path.current_step().as_lane())
+                .dist_along_of_point(line.pt1())
+                .unwrap_or(Distance::ZERO),
         }
     }
 
@@ -838,7 +983,8 @@ impl Pedestrian {
                     .unwrap_or_else(|_| line.pt1()),
                 line.angle(),
             ),
-            PedState::FinishingBiking(_, ref line, ref time_int) => (
+            PedState::FinishingBiking(_, ref line, ref time_int)
+            | PedState::CrossingMidBlock(ref line, ref time_int, _) => (
                 line.percent_along(time_int.percent(now))
                     .unwrap_or_else(|_| line.pt1()),
                 line.angle(),
@@ -881,6 +1027,7 @@ impl Pedestrian {
         peds_per_traversable: &mut MultiMap<Traversable, PedestrianID>,
         events: &mut Vec<Event>,
         scheduler: &mut Scheduler,
+        jaywalk_after_waiting: Option<Duration>,
     ) -> bool {
         if let PathStep::Turn(t) | PathStep::ContraflowTurn(t) = self.path.next_step() {
             if !intersections.maybe_start_turn(
@@ -898,6 +1045,9 @@ impl Pedestrian {
             ) {
                 return false;
             }
+            if intersections.claim_jaywalking(self.id) {
+                events.push(Event::ProblemEncountered(self.trip, Problem::Jaywalking(t)));
+            }
         }
 
         peds_per_traversable.remove(self.path.current_step().as_traversable(), self.id);
@@ -908,7 +1058,14 @@ impl Pedestrian {
             PathStep::Turn(_) => Distance::ZERO,
             PathStep::ContraflowTurn(t) => map.get_t(t).geom.length(),
         };
-        self.state = self.crossing_state(peds_per_traversable, start_dist, now, map, events);
+        self.state = self.crossing_state(
+            peds_per_traversable,
+            start_dist,
+            now,
+            map,
+            events,
+            jaywalk_after_waiting,
+        );
         peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
@@ -936,6 +1093,9 @@ enum PedState {
     StartingToBike(SidewalkSpot, Line, TimeInterval),
     FinishingBiking(SidewalkSpot, Line, TimeInterval),
     WaitingForBus(TransitRouteID, Time),
+    /// Crossing straight over the road, away from any crosswalk. The Distance is where they'll be
+    /// on the sidewalk across the road, which is two steps further along the path.
+    CrossingMidBlock(Line, TimeInterval, Distance),
 }
 
 impl PedState {
@@ -949,6 +1109,7 @@ impl PedState {
             PedState::EnteringParkingLot(_, ref time_int) => time_int.end,
             PedState::StartingToBike(_, _, ref time_int) => time_int.end,
             PedState::FinishingBiking(_, _, ref time_int) => time_int.end,
+            PedState::CrossingMidBlock(_, ref time_int, _) => time_int.end,
             PedState::WaitingForBus(_, _) => unreachable!(),
         }
     }
@@ -0,0 +1,214 @@
+//! Estimates road traffic noise from simulated volumes and speeds, following the UK's Calculation
+//! of Road Traffic Noise (CRTN, 1988). Only the basic level, the speed and heavy vehicle
+//! corrections, and the distance correction are used. Screening by other buildings, ground
+//! absorption, and road gradient and surface are ignored, so buildings set back behind others are
+//! overestimated.
+
+use std::collections::BTreeMap;
+
+use abstutil::Timer;
+use geom::{Distance, FindClosest, Time};
+use map_model::{Building, BuildingID, Map, RoadID};
+
+use crate::{AgentType, Analytics};
+
+/// Where the WHO and the EU's Environmental Noise Directive start to report exposure, in dB(A)
+pub const NOISE_THRESHOLD: f64 = 55.0;
+/// Above this, noise is usually considered a significant adverse effect, in dB(A)
+pub const HIGH_NOISE_THRESHOLD: f64 = 65.0;
+
+/// Roads further away from a building than this don't affect it
+const MAX_PROPAGATION_DIST: Distance = Distance::const_meters(200.0);
+/// CRTN isn't valid for traffic moving slower than this, in km/h
+const MIN_SPEED_KMH: f64 = 20.0;
+/// Sound reflecting off the facade itself adds this much, in dB
+const FACADE_CORRECTION: f64 = 2.5;
+
+/// Noise levels as LAeq over one hour, in dB(A). Each road is measured during its loudest hour so
+/// far.
+#[derive(Clone)]
+pub struct NoiseLevels {
+    /// 10m from the kerb
+    pub per_road: BTreeMap<RoadID, f64>,
+    /// At the most exposed facade, from all nearby roads
+    pub per_building: BTreeMap<BuildingID, f64>,
+}
+
+impl NoiseLevels {
+    pub fn new(analytics: &Analytics, map: &Map, now: Time, timer: &mut Timer) -> NoiseLevels {
+        let per_road = road_noise(analytics, map, now, |_| true);
+        let closest = index_roads(map, &per_road);
+        let per_building = timer
+            .parallelize(
+                "estimate noise at buildings",
+                map.all_buildings().iter().collect(),
+                |b| {
+                    let nearby = closest
+                        .all_close_pts(b.polygon.center(), MAX_PROPAGATION_DIST)
+                        .into_iter()
+                        .map(|(r, _, _)| r);
+                    facade_level(map, &per_road, nearby, b).map(|level| (b.id, level))
+                },
+            )
+            .into_iter()
+            .flatten()
+            .collect();
+
+        NoiseLevels {
+            per_road,
+            per_building,
+        }
+    }
+
+    /// Just calculates the noise at one building, which is much faster than everywhere. Only
+    /// roads within range of the building are estimated.
+    pub fn for_building(analytics: &Analytics, map: &Map, now: Time, b: BuildingID) -> Option<f64> {
+        let b = map.get_b(b);
+        let center = b.polygon.center();
+        let per_road = road_noise(analytics, map, now, |r| {
+            let pl = &map.get_r(r).center_pts;
+            center.dist_to(pl.project_pt(center)) <= MAX_PROPAGATION_DIST
+        });
+        facade_level(map, &per_road, per_road.keys().cloned(), b)
+    }
+
+    /// How many residents live in buildings at least this loud
+    pub fn residents_exposed(&self, map: &Map, threshold: f64) -> usize {
+        self.per_building
+            .iter()
+            .filter(|(_, level)| **level >= threshold)
+            .map(|(b, _)| map.get_b(*b).bldg_type.num_residents())
+            .sum()
+    }
+}
+
+/// The loudest hourly level along each road passing `include`, 10m from the kerb.
+fn road_noise<F: Fn(RoadID) -> bool>(
+    analytics: &Analytics,
+    map: &Map,
+    now: Time,
+    include: F,
+) -> BTreeMap<RoadID, f64> {
+    // Checking a road may be expensive, so only do it once
+    let mut included: BTreeMap<RoadID, bool> = BTreeMap::new();
+    let mut is_included = |r: RoadID| *included.entry(r).or_insert_with(|| include(r));
+
+    // (Road, hour) -> number of all motor vehicles, and just the heavy ones
+    let mut flows: BTreeMap<(RoadID, usize), (usize, usize)> = BTreeMap::new();
+    for ((r, agent_type, hour), count) in &analytics.road_thruput.counts {
+        if *hour > now.get_hours() || !is_included(*r) {
+            continue;
+        }
+        let entry = flows.entry((*r, *hour)).or_insert((0, 0));
+        match agent_type {
+            AgentType::Car => {
+                entry.0 += *count;
+            }
+            AgentType::Bus | AgentType::Truck => {
+                entry.0 += *count;
+                entry.1 += *count;
+            }
+            _ => {}
+        }
+    }
+
+    // (Road, hour) -> total meters and seconds cars took to cross it. This includes waiting at
+    // the end of the road, which is also when traffic is quieter.
+    let mut speeds: BTreeMap<(RoadID, usize), (f64, f64)> = BTreeMap::new();
+    for ((mvmnt, hour), (count, total)) in &analytics.travel_times.per_movement {
+        let r = mvmnt.from.road;
+        if !is_included(r) {
+            continue;
+        }
+        let entry = speeds.entry((r, *hour)).or_insert((0.0, 0.0));
+        entry.0 += map.get_r(r).length().inner_meters() * (*count as f64);
+        entry.1 += total.inner_seconds();
+    }
+
+    let mut result = BTreeMap::new();
+    for ((r, hour), (flow, heavy)) in flows {
+        if flow == 0 {
+            continue;
+        }
+        let mut speed = map.get_r(r).speed_limit.inner_meters_per_second();
+        if let Some((meters, seconds)) = speeds.get(&(r, hour)) {
+            if *seconds > 0.0 {
+                speed = speed.min(meters / seconds);
+            }
+        }
+        let level = crtn_level(flow, heavy, speed * 3.6);
+        let entry = result.entry(r).or_insert(level);
+        *entry = entry.max(level);
+    }
+    result
+}
+
+/// LAeq over one hour, 10m from the kerb.
+fn crtn_level(flow: usize, heavy: usize, kmh: f64) -> f64 {
+    let q = flow as f64;
+    let v = kmh.max(MIN_SPEED_KMH);
+    let pct_heavy = 100.0 * (heavy as f64) / q;
+    let l10 = 42.2
+        + 10.0 * q.log10()
+        + 33.0 * (v + 40.0 + 500.0 / v).log10()
+        + 10.0 * (1.0 + 5.0 * pct_heavy / v).log10()
+        - 68.8;
+    // For free-flowing traffic, LAeq is about 3 dB below L10
+    l10 - 3.0
+}
+
+fn index_roads(map: &Map, per_road: &BTreeMap<RoadID, f64>) -> FindClosest<RoadID> {
+    let mut closest = FindClosest::new();
+    for r in per_road.keys() {
+        closest.add(*r, map.get_r(*r).center_pts.points());
+    }
+    closest
+}
+
+/// Combines the noise from roads near a building, each at the facade closest to it.
+fn facade_level<I: Iterator<Item = RoadID>>(
+    map: &Map,
+    per_road: &BTreeMap<RoadID, f64>,
+    nearby: I,
+    b: &Building,
+) -> Option<f64> {
+    let mut energy = 0.0;
+    for r in nearby {
+        let level = per_road[&r];
+        let road = map.get_r(r);
+        let mut dist = f64::MAX;
+        for pt in b.polygon.get_outer_ring().points() {
+            dist = dist.min(pt.dist_to(road.center_pts.project_pt(*pt)).inner_meters());
+        }
+        if dist > MAX_PROPAGATION_DIST.inner_meters() {
+            continue;
+        }
+        // CRTN measures from the nearside kerb, and the slant distance from a source 0.5m up to a
+        // receiver 1.5m up
+        let kerb_dist = (dist - road.get_half_width().inner_meters()).max(1.0);
+        let slant_dist = ((kerb_dist + 3.5).powi(2) + 1.0).sqrt();
+        let at_facade = level - 10.0 * (slant_dist / 13.5).log10() + FACADE_CORRECTION;
+        energy += 10.0_f64.powf(at_facade / 10.0);
+    }
+    if energy == 0.0 {
+        None
+    } else {
+        Some(10.0 * energy.log10())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crtn_level() {
+        // The basic hourly L10 for 1,000 vehicles/hour at 75 km/h with no heavy vehicles is
+        // 72.2 dB(A), and the speed correction there is about zero
+        assert!((crtn_level(1000, 0, 75.0) - 69.2).abs() < 0.05);
+        // 10% heavy vehicles at 50 km/h adds 0.2 dB(A)
+        assert!((crtn_level(1000, 100, 50.0) - 69.4).abs() < 0.05);
+        // Slow traffic is treated like it's moving at the minimum speed
+        assert_eq!(crtn_level(500, 0, 5.0), crtn_level(500, 0, MIN_SPEED_KMH));
+    }
+}
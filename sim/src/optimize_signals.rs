@@ -0,0 +1,316 @@
+//! Searches for traffic signal timing that reduces the delay measured at some intersections. The
+//! same window of time is simulated over and over from an in-memory copy of the simulation, trying
+//! small changes to stage durations and orderings, and keeping the ones that help.
+
+use anyhow::Result;
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{
+    ControlTrafficSignal, EditIntersectionControl, IntersectionID, Map, MapEdits, StageType,
+};
+
+use crate::Sim;
+
+pub struct OptimizerConfig {
+    /// The traffic signals to retime. Delay is measured only at these.
+    pub intersections: Vec<IntersectionID>,
+    /// Only simulate from `start` to `end`
+    pub start: Time,
+    pub end: Time,
+    /// How many times to try changing every signal. The search stops early when nothing improves.
+    pub rounds: usize,
+    /// How much to lengthen or shorten stages by
+    pub step: Duration,
+}
+
+pub struct OptimizedSignals {
+    /// The total delay at the intersections with the original timing
+    pub baseline_delay: Duration,
+    /// The total delay with the best timing found
+    pub best_delay: Duration,
+    pub num_simulations: usize,
+    /// The map's existing edits, plus changes to the optimized signals. Nothing is added for
+    /// signals that didn't change.
+    pub edits: MapEdits,
+}
+
+/// `sim` must already be instantiated with a scenario, and not past `config.start`; it'll be run
+/// until then and copied for every attempt. The map is temporarily modified while trying different
+/// timing, but restored before returning.
+pub fn optimize_signals(
+    map: &mut Map,
+    mut sim: Sim,
+    config: &OptimizerConfig,
+    timer: &mut Timer,
+) -> Result<OptimizedSignals> {
+    if config.intersections.is_empty() {
+        bail!("No intersections to optimize");
+    }
+    if config.start >= config.end {
+        bail!(
+            "The time window {} to {} is empty",
+            config.start,
+            config.end
+        );
+    }
+    if config.step <= Duration::ZERO {
+        bail!("The step must be positive");
+    }
+    for i in &config.intersections {
+        if map.maybe_get_traffic_signal(*i).is_none() {
+            bail!("{} isn't a traffic signal", i);
+        }
+    }
+    if sim.time() > config.start {
+        bail!("The simulation is already past {}", config.start);
+    }
+
+    timer.start("simulate until the start of the window");
+    sim.timed_step(map, config.start - sim.time(), &mut None, timer);
+    timer.stop("simulate until the start of the window");
+
+    let original: Vec<ControlTrafficSignal> = config
+        .intersections
+        .iter()
+        .map(|i| map.get_traffic_signal(*i).clone())
+        .collect();
+    let mut optimizer = Optimizer {
+        config,
+        snapshot: sim,
+        num_simulations: 0,
+    };
+
+    let baseline_delay = optimizer.evaluate(map, &original);
+    timer.note(format!("Baseline delay is {}", baseline_delay));
+    let mut best = original.clone();
+    let mut best_delay = baseline_delay;
+
+    for round in 0..config.rounds {
+        let mut improved = false;
+        timer.start_iter(&format!("round {}", round + 1), best.len());
+        for idx in 0..best.len() {
+            timer.next();
+            for candidate in neighbors(map, &best[idx], config.step) {
+                let mut attempt = best.clone();
+                attempt[idx] = candidate;
+                let delay = optimizer.evaluate(map, &attempt);
+                if delay < best_delay {
+                    best = attempt;
+                    best_delay = delay;
+                    improved = true;
+                }
+            }
+        }
+        timer.note(format!(
+            "After round {}, the best delay is {}",
+            round + 1,
+            best_delay
+        ));
+        if !improved {
+            break;
+        }
+    }
+
+    // Restore the map before recording edits, so the commands have the right "old" state
+    for ts in original.clone() {
+        map.incremental_edit_traffic_signal(ts);
+    }
+    let mut edits = map.get_edits().clone();
+    for (orig, ts) in original.into_iter().zip(best) {
+        if orig != ts {
+            edits.commands.push(map.edit_intersection_cmd(ts.id, |new| {
+                new.control = EditIntersectionControl::TrafficSignal(ts.export(map));
+            }));
+        }
+    }
+
+    Ok(OptimizedSignals {
+        baseline_delay,
+        best_delay,
+        num_simulations: optimizer.num_simulations,
+        edits,
+    })
+}
+
+struct Optimizer<'a> {
+    config: &'a OptimizerConfig,
+    /// The simulation at the start of the window
+    snapshot: Sim,
+    num_simulations: usize,
+}
+
+impl<'a> Optimizer<'a> {
+    /// Simulates the window with some signal timing, returning the total delay.
+    fn evaluate(&mut self, map: &mut Map, signals: &[ControlTrafficSignal]) -> Duration {
+        for ts in signals {
+            map.incremental_edit_traffic_signal(ts.clone());
+        }
+        let mut sim = self.snapshot.clone();
+        // The new timing takes effect when each signal's current stage ends
+        sim.handle_live_edited_traffic_signals(map);
+        sim.timed_step(
+            map,
+            self.config.end - self.config.start,
+            &mut None,
+            &mut Timer::throwaway(),
+        );
+        self.num_simulations += 1;
+
+        let mut total = Duration::ZERO;
+        let analytics = sim.get_analytics();
+        for i in &self.config.intersections {
+            if let Some(list) = analytics.intersection_delays.get(i) {
+                for (_, t, dt, _) in list {
+                    if *t >= self.config.start && *t <= self.config.end {
+                        total += *dt;
+                    }
+                }
+            }
+            // Agents still waiting haven't recorded their delay yet. Without counting them, a plan
+            // that never serves some movement would look great.
+            total += sim.get_signal_status(*i, map).total_waiting_time();
+        }
+        total
+    }
+}
+
+/// All valid variations of a signal with one stage lengthened, shortened, or swapped with the next.
+fn neighbors(map: &Map, ts: &ControlTrafficSignal, step: Duration) -> Vec<ControlTrafficSignal> {
+    let mut results = Vec::new();
+    for idx in 0..ts.stages.len() {
+        for delta in [step, Duration::ZERO - step] {
+            if let Some(stage_type) = adjust(&ts.stages[idx].stage_type, delta) {
+                let mut candidate = ts.clone();
+                candidate.stages[idx].stage_type = stage_type;
+                results.push(candidate);
+            }
+        }
+        if idx + 1 < ts.stages.len() {
+            let mut candidate = ts.clone();
+            candidate.stages.swap(idx, idx + 1);
+            results.push(candidate);
+        }
+    }
+    let i = map.get_i(ts.id);
+    results.retain(|candidate| candidate.validate(i).is_ok());
+    results
+}
+
+fn adjust(stage_type: &StageType, delta: Duration) -> Option<StageType> {
+    let min = Duration::seconds(1.0);
+    match stage_type {
+        StageType::Fixed(d) => {
+            let d = *d + delta;
+            (d >= min).then_some(StageType::Fixed(d))
+        }
+        StageType::Variable(d, delay, additional) => {
+            let d = *d + delta;
+            (d >= min).then_some(StageType::Variable(d, *delay, *additional))
+        }
+        // The minimum green is usually set by crosswalks, so tune the maximum
+        StageType::Actuated {
+            min: min_green,
+            max,
+            passage,
+            detector_setback,
+        } => {
+            let max = *max + delta;
+            (max >= *min_green).then_some(StageType::Actuated {
+                min: *min_green,
+                max,
+                passage: *passage,
+                detector_setback: *detector_setback,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Distance;
+    use map_model::Stage;
+
+    use super::*;
+
+    fn fixed(seconds: f64) -> StageType {
+        StageType::Fixed(Duration::seconds(seconds))
+    }
+
+    #[test]
+    fn test_adjust() {
+        let step = Duration::seconds(5.0);
+        assert_eq!(adjust(&fixed(30.0), step), Some(fixed(35.0)));
+        assert_eq!(
+            adjust(&fixed(30.0), Duration::ZERO - step),
+            Some(fixed(25.0))
+        );
+        // Stages can't get shorter than 1s
+        assert_eq!(adjust(&fixed(5.0), Duration::ZERO - step), None);
+        assert_eq!(adjust(&fixed(6.0), Duration::ZERO - step), Some(fixed(1.0)));
+
+        let variable = StageType::Variable(
+            Duration::seconds(20.0),
+            Duration::seconds(2.0),
+            Duration::seconds(10.0),
+        );
+        assert_eq!(
+            adjust(&variable, step),
+            Some(StageType::Variable(
+                Duration::seconds(25.0),
+                Duration::seconds(2.0),
+                Duration::seconds(10.0),
+            ))
+        );
+
+        // Only the maximum green of actuated stages changes, and it can't go below the minimum
+        let actuated = |max: f64| StageType::Actuated {
+            min: Duration::seconds(10.0),
+            max: Duration::seconds(max),
+            passage: Duration::seconds(3.0),
+            detector_setback: Distance::meters(30.0),
+        };
+        assert_eq!(adjust(&actuated(40.0), step), Some(actuated(45.0)));
+        assert_eq!(
+            adjust(&actuated(40.0), Duration::ZERO - step),
+            Some(actuated(35.0))
+        );
+        assert_eq!(adjust(&actuated(12.0), Duration::ZERO - step), None);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        // The borders of this map have no movements, so any stages are valid there
+        let map = Map::almost_blank();
+        let mut ts = ControlTrafficSignal {
+            id: IntersectionID(0),
+            stages: vec![Stage::new(), Stage::new()],
+            offset: Duration::ZERO,
+        };
+        ts.stages[0].stage_type = fixed(30.0);
+        ts.stages[1].stage_type = fixed(3.0);
+
+        let results: Vec<Vec<StageType>> = neighbors(&map, &ts, Duration::seconds(5.0))
+            .into_iter()
+            .map(|candidate| {
+                candidate
+                    .stages
+                    .into_iter()
+                    .map(|stage| stage.stage_type)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                // Lengthen and shorten the first stage
+                vec![fixed(35.0), fixed(3.0)],
+                vec![fixed(25.0), fixed(3.0)],
+                // Swap the two
+                vec![fixed(3.0), fixed(30.0)],
+                // The second stage can only get longer
+                vec![fixed(30.0), fixed(8.0)],
+            ]
+        );
+    }
+}
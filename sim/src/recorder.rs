@@ -85,6 +85,10 @@ impl TrafficRecorder {
                 TripEndpoint::Border(exit_intersection),
                 if car.vehicle_type == VehicleType::Bike {
                     TripMode::Bike
+                } else if car.vehicle_type == VehicleType::Scooter {
+                    TripMode::Scooter
+                } else if car.vehicle_type == VehicleType::Truck {
+                    TripMode::Freight
                 } else {
                     TripMode::Drive
                 },
@@ -110,6 +114,7 @@ impl TrafficRecorder {
                 })
                 .collect::<Vec<_>>(),
             only_seed_buses: None,
+            deliveries: Vec::new(),
         }
         .save();
     }
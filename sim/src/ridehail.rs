@@ -0,0 +1,445 @@
+//! On-demand vehicles, like taxis, carry riders from door to door. Unlike transit, they don't follow
+//! a fixed route; a dispatcher assigns each request to some vehicle in the fleet. Idle vehicles wait
+//! off the road wherever they last stopped, and they double-park at the curb to pick up and drop
+//! off.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Time};
+use map_model::{Map, PathConstraints, PathRequest, Position};
+
+use crate::sim::Ctx;
+use crate::{
+    CarID, Command, CreateCar, Event, PersonID, Router, TripID, TripManager, Vehicle, VehicleSpec,
+    VehicleType, MIN_CAR_LENGTH, SPAWN_DIST,
+};
+
+/// How long a vehicle blocks the lane while somebody gets in or out
+const TIME_TO_BOARD: Duration = Duration::const_seconds(30.0);
+
+/// Somebody asking for a ride
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RideRequest {
+    pub trip: TripID,
+    pub person: PersonID,
+    pub pickup: Position,
+    pub dropoff: Position,
+    pub requested_at: Time,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum RideStop {
+    Pickup(RideRequest),
+    Dropoff(RideRequest),
+}
+
+impl RideStop {
+    pub fn pos(&self) -> Position {
+        match self {
+            RideStop::Pickup(req) => req.pickup,
+            RideStop::Dropoff(req) => req.dropoff,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FleetVehicle {
+    pub vehicle: Vehicle,
+    /// Where the vehicle last stopped. Idle vehicles wait here, off the road.
+    pub pos: Position,
+    /// The first stop is where the vehicle is heading, or where it's stopped right now.
+    pub stops: VecDeque<RideStop>,
+    pub riders: Vec<RideRequest>,
+    on_road: bool,
+}
+
+impl FleetVehicle {
+    pub fn is_idle(&self) -> bool {
+        self.stops.is_empty()
+    }
+
+    /// Riders on board, plus the ones waiting to be picked up
+    pub fn seats_claimed(&self) -> usize {
+        self.riders.len()
+            + self
+                .stops
+                .iter()
+                .filter(|stop| matches!(stop, RideStop::Pickup(_)))
+                .count()
+    }
+
+    /// Where the vehicle will stop next
+    pub fn next_pos(&self) -> Position {
+        self.stops
+            .front()
+            .map(|stop| stop.pos())
+            .unwrap_or(self.pos)
+    }
+}
+
+/// Decides which vehicle serves a new ride request.
+pub trait Dispatcher {
+    /// Returns the vehicle to send, or None if the rider should keep waiting. Idle vehicles get the
+    /// pickup and dropoff as their only stops. Busy vehicles pick up the new rider after their next
+    /// stop, and drop them off after everybody else.
+    fn assign(&self, req: &RideRequest, vehicles: &[&FleetVehicle], map: &Map) -> Option<CarID>;
+}
+
+/// Which dispatcher assigns ride-hailing vehicles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DispatchPolicy {
+    /// Send the closest idle vehicle. Every rider has a vehicle to themselves.
+    NearestIdle,
+    /// Riders may share a vehicle that's already heading nearby.
+    Pooled,
+}
+
+impl DispatchPolicy {
+    pub fn dispatcher(self) -> &'static dyn Dispatcher {
+        match self {
+            DispatchPolicy::NearestIdle => &NearestIdle,
+            DispatchPolicy::Pooled => &Pooled::SHARED_TAXI,
+        }
+    }
+}
+
+/// Sends the idle vehicle closest to the pickup, as the crow flies.
+pub struct NearestIdle;
+
+impl Dispatcher for NearestIdle {
+    fn assign(&self, req: &RideRequest, vehicles: &[&FleetVehicle], map: &Map) -> Option<CarID> {
+        let pickup = req.pickup.pt(map);
+        vehicles
+            .iter()
+            .filter(|v| v.is_idle())
+            .min_by_key(|v| v.pos.pt(map).dist_to(pickup))
+            .map(|v| v.vehicle.id)
+    }
+}
+
+/// Sends the closest vehicle with a free seat, as long as a busy vehicle isn't too far out of its
+/// way.
+pub struct Pooled {
+    pub seats: usize,
+    /// A busy vehicle only picks somebody up this close to its next stop, as the crow flies
+    pub max_detour: Distance,
+}
+
+impl Pooled {
+    pub const SHARED_TAXI: Pooled = Pooled {
+        seats: 3,
+        max_detour: Distance::const_meters(1000.0),
+    };
+}
+
+impl Dispatcher for Pooled {
+    fn assign(&self, req: &RideRequest, vehicles: &[&FleetVehicle], map: &Map) -> Option<CarID> {
+        let pickup = req.pickup.pt(map);
+        vehicles
+            .iter()
+            .filter_map(|v| {
+                let dist = v.next_pos().pt(map).dist_to(pickup);
+                if v.is_idle() || (v.seats_claimed() < self.seats && dist <= self.max_detour) {
+                    Some((v.vehicle.id, dist))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(id, _)| id)
+    }
+}
+
+/// Manages the fleet of ride-hailing vehicles and the people waiting for them.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct RideHailSimState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, FleetVehicle>,
+    /// Requests that no vehicle could take yet, oldest first
+    waiting: VecDeque<RideRequest>,
+    policy: DispatchPolicy,
+
+    events: Vec<Event>,
+}
+
+impl RideHailSimState {
+    /// Spreads the fleet evenly over all driving lanes.
+    pub fn new(
+        fleet_size: usize,
+        policy: DispatchPolicy,
+        trips: &mut TripManager,
+        map: &Map,
+    ) -> RideHailSimState {
+        let lanes: Vec<_> = map.all_lanes().filter(|l| l.is_driving()).collect();
+        let mut vehicles = BTreeMap::new();
+        if !lanes.is_empty() {
+            for idx in 0..fleet_size {
+                let lane = lanes[(idx * lanes.len() / fleet_size) % lanes.len()];
+                let id = CarID {
+                    id: trips.new_car_id(),
+                    vehicle_type: VehicleType::Car,
+                };
+                let vehicle = VehicleSpec {
+                    vehicle_type: VehicleType::Car,
+                    length: MIN_CAR_LENGTH,
+                    max_speed: None,
+                }
+                .make(id, None);
+                vehicles.insert(
+                    id,
+                    FleetVehicle {
+                        vehicle,
+                        pos: Position::new(lane.id, lane.length() / 2.0),
+                        stops: VecDeque::new(),
+                        riders: Vec::new(),
+                        on_road: false,
+                    },
+                );
+            }
+        }
+
+        RideHailSimState {
+            vehicles,
+            waiting: VecDeque::new(),
+            policy,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn request_ride(
+        &mut self,
+        now: Time,
+        req: RideRequest,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) {
+        if self.vehicles.is_empty() {
+            trips.cancel_ride_hail(
+                now,
+                req.trip,
+                None,
+                "there are no ride-hailing vehicles".to_string(),
+                ctx,
+            );
+            return;
+        }
+        if !self.dispatch(now, req.clone(), trips, ctx) {
+            self.waiting.push_back(req);
+        }
+    }
+
+    /// Returns false if nobody can serve the request yet.
+    fn dispatch(
+        &mut self,
+        now: Time,
+        req: RideRequest,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> bool {
+        let car = {
+            let candidates: Vec<&FleetVehicle> = self.vehicles.values().collect();
+            match self.policy.dispatcher().assign(&req, &candidates, ctx.map) {
+                Some(car) => car,
+                None => {
+                    return false;
+                }
+            }
+        };
+
+        let vehicle = self.vehicles.get_mut(&car).unwrap();
+        if vehicle.is_idle() {
+            vehicle.stops.push_back(RideStop::Pickup(req.clone()));
+            vehicle.stops.push_back(RideStop::Dropoff(req));
+            // If the vehicle is still on the road, it just finished a stop, and the caller will
+            // route it.
+            if !vehicle.on_road {
+                self.start_vehicle(now, car, trips, ctx);
+            }
+        } else {
+            vehicle.stops.insert(1, RideStop::Pickup(req.clone()));
+            vehicle.stops.push_back(RideStop::Dropoff(req));
+        }
+        true
+    }
+
+    fn dispatch_waiting(&mut self, now: Time, trips: &mut TripManager, ctx: &mut Ctx) {
+        let mut still_waiting = VecDeque::new();
+        while let Some(req) = self.waiting.pop_front() {
+            if !self.dispatch(now, req.clone(), trips, ctx) {
+                still_waiting.push_back(req);
+            }
+        }
+        self.waiting = still_waiting;
+    }
+
+    /// An idle vehicle pulls out onto the road, heading for its first stop.
+    fn start_vehicle(&mut self, now: Time, car: CarID, trips: &mut TripManager, ctx: &mut Ctx) {
+        let vehicle = self.vehicles.get_mut(&car).unwrap();
+        // Like at borders, don't start with the front of the vehicle right at the lane's start
+        let start = if vehicle.pos.dist_along() < SPAWN_DIST {
+            Position::new(vehicle.pos.lane(), SPAWN_DIST)
+        } else {
+            vehicle.pos
+        };
+        let req = PathRequest::vehicle(start, vehicle.next_pos(), PathConstraints::Car);
+        match ctx.map.pathfind(req) {
+            Ok(path) => {
+                vehicle.on_road = true;
+                ctx.scheduler.push(
+                    now,
+                    Command::SpawnCar(
+                        CreateCar {
+                            vehicle: vehicle.vehicle.clone(),
+                            router: Router::deliver(car, path, TIME_TO_BOARD),
+                            maybe_parked_car: None,
+                            trip_and_person: None,
+                            maybe_route: None,
+                        },
+                        true,
+                    ),
+                );
+            }
+            Err(err) => {
+                self.abandon_riders(now, car, err.to_string(), trips, ctx);
+            }
+        }
+    }
+
+    /// A vehicle waiting to pull out or already driving can't use its route anymore, because of
+    /// live map edits. It goes idle at its last stop, and everybody it was serving gives up.
+    pub fn vehicle_cant_continue(
+        &mut self,
+        now: Time,
+        car: CarID,
+        reason: String,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) {
+        self.abandon_riders(now, car, reason, trips, ctx);
+        self.vehicles.get_mut(&car).unwrap().on_road = false;
+    }
+
+    /// A vehicle finished letting somebody in or out. Returns the route to the next stop, or None
+    /// if the vehicle is idle and should leave the road.
+    pub fn vehicle_finished_stop(
+        &mut self,
+        now: Time,
+        car: CarID,
+        pos: Position,
+        distance_crossed: Distance,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) -> Option<Router> {
+        let vehicle = self.vehicles.get_mut(&car).unwrap();
+        vehicle.pos = pos;
+        self.events.push(Event::RideHailLegFinished(
+            car,
+            distance_crossed,
+            vehicle.riders.len(),
+        ));
+
+        // Several riders might get in or out at the same curb
+        let here = vehicle.next_pos();
+        loop {
+            let vehicle = self.vehicles.get_mut(&car).unwrap();
+            match vehicle.stops.pop_front().unwrap() {
+                RideStop::Pickup(req) => {
+                    let waiting = now - req.requested_at;
+                    self.events
+                        .push(Event::RideHailPickup(req.person, car, pos, waiting));
+                    trips.ride_hail_pickup(req.trip, car, waiting);
+                    vehicle.riders.push(req);
+                }
+                RideStop::Dropoff(req) => {
+                    vehicle.riders.retain(|r| r.trip != req.trip);
+                    self.events
+                        .push(Event::RideHailDropoff(req.person, car, pos));
+                    trips.ride_hail_dropoff(now, req.trip, car, req.dropoff, ctx);
+                }
+            }
+
+            // Maybe somebody's been waiting for a vehicle to free up
+            self.dispatch_waiting(now, trips, ctx);
+
+            let vehicle = self.vehicles.get_mut(&car).unwrap();
+            if vehicle.is_idle() {
+                vehicle.on_road = false;
+                return None;
+            }
+            if vehicle.next_pos() != here {
+                break;
+            }
+        }
+
+        let vehicle = &self.vehicles[&car];
+        let req = PathRequest::vehicle(pos, vehicle.next_pos(), PathConstraints::Car);
+        match ctx.map.pathfind(req) {
+            Ok(path) => Some(Router::deliver(car, path, TIME_TO_BOARD)),
+            Err(err) => {
+                self.abandon_riders(now, car, err.to_string(), trips, ctx);
+                self.vehicles.get_mut(&car).unwrap().on_road = false;
+                None
+            }
+        }
+    }
+
+    /// The vehicle can't reach its next stop, so cancel the trips of everybody it's serving.
+    fn abandon_riders(
+        &mut self,
+        now: Time,
+        car: CarID,
+        reason: String,
+        trips: &mut TripManager,
+        ctx: &mut Ctx,
+    ) {
+        let vehicle = self.vehicles.get_mut(&car).unwrap();
+        let on_board: BTreeSet<TripID> = vehicle.riders.drain(..).map(|r| r.trip).collect();
+        let mut cancel: BTreeSet<TripID> = on_board.clone();
+        for stop in vehicle.stops.drain(..) {
+            if let RideStop::Pickup(req) = stop {
+                cancel.insert(req.trip);
+            }
+        }
+        for trip in cancel {
+            let boarded = if on_board.contains(&trip) {
+                Some(car)
+            } else {
+                None
+            };
+            trips.cancel_ride_hail(now, trip, boarded, reason.clone(), ctx);
+        }
+    }
+
+    pub fn is_fleet_vehicle(&self, car: CarID) -> bool {
+        self.vehicles.contains_key(&car)
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// (Vehicles on the road, vehicles carrying somebody)
+    pub fn active_vehicles(&self) -> (usize, usize) {
+        let mut on_road = 0;
+        let mut occupied = 0;
+        for vehicle in self.vehicles.values() {
+            if vehicle.on_road {
+                on_road += 1;
+            }
+            if !vehicle.riders.is_empty() {
+                occupied += 1;
+            }
+        }
+        (on_road, occupied)
+    }
+
+    pub fn num_waiting(&self) -> usize {
+        self.waiting.len()
+    }
+}
@@ -5,16 +5,16 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, Turn, TurnID,
+    BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints,
+    PathRequest, PathStep, Position, RoadID, Traversable, Turn, TurnID,
 };
 
-use crate::mechanics::Queue;
+use crate::mechanics::{is_acceptable_cost, parking_cost, Queue};
 use crate::{
     AlertLocation, CarID, Event, ParkingSim, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot,
-    TripID, TripPhaseType, Vehicle, VehicleType,
+    TripID, TripPhaseType, Vehicle,
 };
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -33,6 +33,8 @@ pub(crate) enum ActionAtEnd {
     StopBiking(SidewalkSpot),
     BusAtStop,
     GiveUpOnParking,
+    /// Wait in the lane for this long
+    Deliver(Duration),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -44,17 +46,73 @@ enum Goal {
         /// No parking available at all!
         stuck_end_dist: Option<Distance>,
         started_looking: bool,
+        cruising: Cruising,
     },
     EndAtBorder {
         end_dist: Distance,
         i: IntersectionID,
     },
+    /// Park in one particular lot, like at a park-and-ride. The path ends at the lot's driveway.
+    ParkInLot {
+        lot: ParkingLotID,
+        spot: Option<ParkingSpot>,
+        /// The lot is full!
+        stuck_end_dist: Option<Distance>,
+    },
     BikeThenStop {
         goal: SidewalkSpot,
     },
     FollowTransitRoute {
         end_dist: Distance,
     },
+    Deliver {
+        end_dist: Distance,
+        dwell: Duration,
+    },
+}
+
+/// How far a driver has gone past the original destination, searching for a parking spot
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Cruising {
+    total: Distance,
+    /// `total` counts up to this distance along the last lane. If the spot there is taken before
+    /// the driver reaches it, searching again picks up from here.
+    counted_to: Option<Distance>,
+}
+
+impl Cruising {
+    fn new() -> Cruising {
+        Cruising {
+            total: Distance::ZERO,
+            counted_to: None,
+        }
+    }
+
+    /// A free spot turned up further along the current lane, at `dist`.
+    fn spot_ahead(&mut self, dist: Distance) {
+        // Nothing to count if the driver hasn't left the destination's lane yet
+        if let Some(counted_to) = self.counted_to.as_mut() {
+            self.total += dist - *counted_to;
+            *counted_to = dist;
+        }
+    }
+
+    /// The driver at `front` along a lane of `lane_length` heads somewhere else, covering `detour`
+    /// (ending with the full length of the new lane) to reach a spot at `new_dist`.
+    fn search_elsewhere(
+        &mut self,
+        front: Distance,
+        lane_length: Distance,
+        detour: Distance,
+        new_lane_length: Distance,
+        new_dist: Distance,
+    ) {
+        // Don't count the rest of this lane twice, if an earlier search already headed down it
+        self.total += lane_length - self.counted_to.unwrap_or(front);
+        self.total += detour;
+        self.total -= new_lane_length - new_dist;
+        self.counted_to = Some(new_dist);
+    }
 }
 
 impl Router {
@@ -79,6 +137,19 @@ impl Router {
                 spot: None,
                 stuck_end_dist: None,
                 started_looking: false,
+                cruising: Cruising::new(),
+            },
+            owner,
+        }
+    }
+
+    pub fn park_in_lot(owner: CarID, path: Path, lot: ParkingLotID) -> Router {
+        Router {
+            path,
+            goal: Goal::ParkInLot {
+                lot,
+                spot: None,
+                stuck_end_dist: None,
             },
             owner,
         }
@@ -102,6 +173,17 @@ impl Router {
         }
     }
 
+    pub fn deliver(owner: CarID, path: Path, dwell: Duration) -> Router {
+        Router {
+            goal: Goal::Deliver {
+                end_dist: path.get_req().end.dist_along(),
+                dwell,
+            },
+            path,
+            owner,
+        }
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -128,8 +210,12 @@ impl Router {
                 stuck_end_dist,
                 ..
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
+            Goal::ParkInLot { stuck_end_dist, .. } => {
+                stuck_end_dist.unwrap_or_else(|| self.path.get_req().end.dist_along())
+            }
             Goal::BikeThenStop { ref goal } => goal.sidewalk_pos.dist_along(),
             Goal::FollowTransitRoute { end_dist } => end_dist,
+            Goal::Deliver { end_dist, .. } => end_dist,
         }
     }
 
@@ -200,6 +286,7 @@ impl Router {
                 ref mut stuck_end_dist,
                 target,
                 ref mut started_looking,
+                ref mut cruising,
             } => {
                 if let Some(d) = stuck_end_dist {
                     if *d == front {
@@ -222,26 +309,45 @@ impl Router {
                         target,
                         map,
                     );
-                    let best =
-                        if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map) {
-                            if driving_pos.lane() == current_lane {
-                                let target_dist = driving_pos.dist_along();
-                                // Closest to the building
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
-                            } else {
-                                // Closest to the road endpoint, I guess
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| pos.dist_along())
-                            }
-                        } else {
-                            // Closest to the road endpoint, I guess
-                            candidates
+                    let is_acceptable = |spot: ParkingSpot| {
+                        parking_cost(spot, parking.spot_to_sidewalk_pos(spot, map), target, map)
+                            .map(is_acceptable_cost)
+                            .unwrap_or(false)
+                    };
+                    // Weigh the price against the walk to the building
+                    let mut best = candidates
+                        .iter()
+                        .filter_map(|(spot, pos)| {
+                            let cost = parking_cost(
+                                *spot,
+                                parking.spot_to_sidewalk_pos(*spot, map),
+                                target,
+                                map,
+                            )?;
+                            is_acceptable_cost(cost).then_some((*spot, *pos, cost))
+                        })
+                        .min_by_key(|(_, _, cost)| *cost)
+                        .map(|(spot, pos, _)| (spot, pos));
+                    let mut search = None;
+                    if best.is_none() {
+                        search =
+                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map);
+                        // If nothing acceptable is free anywhere, don't bother driving somewhere
+                        // else to settle
+                        let settling = search
+                            .as_ref()
+                            .map(|(_, spot, _)| !is_acceptable(*spot))
+                            .unwrap_or(true);
+                        if settling {
+                            if let Some(here) = candidates
                                 .into_iter()
                                 .min_by_key(|(_, pos)| pos.dist_along())
-                        };
+                            {
+                                best = Some(here);
+                                search = None;
+                            }
+                        }
+                    }
                     if let Some((new_spot, new_pos)) = best {
                         if let Some((t, p)) = trip_and_person {
                             events.push(Event::TripPhaseStarting(
@@ -257,15 +363,23 @@ impl Router {
                         }
                         assert_eq!(new_pos.lane(), current_lane);
                         assert!(new_pos.dist_along() >= front);
+                        cruising.spot_ahead(new_pos.dist_along());
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
-                        {
+                        if let Some((new_path_steps, new_spot, new_pos)) = search {
                             assert!(!new_path_steps.is_empty());
+                            let mut detour = Distance::ZERO;
                             for step in new_path_steps {
+                                detour += step.as_traversable().get_polyline(map).length();
                                 self.path.add(step, map);
                             }
+                            cruising.search_elsewhere(
+                                front,
+                                map.get_l(current_lane).length(),
+                                detour,
+                                map.get_l(new_pos.lane()).length(),
+                                new_pos.dist_along(),
+                            );
                             *spot = Some((new_spot, new_pos.dist_along()));
                             events.push(Event::PathAmended(self.path.clone()));
                             // TODO This path might not be the same as the one found here...
@@ -299,11 +413,54 @@ impl Router {
                 }
 
                 if spot.unwrap().1 == front {
+                    events.push(Event::CarCruisedForParking(
+                        vehicle.id,
+                        spot.unwrap().0,
+                        cruising.total,
+                    ));
                     Some(ActionAtEnd::StartParking(spot.unwrap().0))
                 } else {
                     None
                 }
             }
+            Goal::ParkInLot {
+                lot,
+                ref mut spot,
+                ref mut stuck_end_dist,
+            } => {
+                if let Some(d) = stuck_end_dist {
+                    if *d == front {
+                        return Some(ActionAtEnd::GiveUpOnParking);
+                    } else {
+                        return None;
+                    }
+                }
+
+                let need_new_spot = match spot {
+                    Some(s) => !parking.is_free(*s),
+                    None => true,
+                };
+                if need_new_spot {
+                    *spot = parking.get_free_lot_spots(lot).into_iter().next();
+                    if spot.is_none() {
+                        if let Some((_, p)) = trip_and_person {
+                            events.push(Event::Alert(
+                                AlertLocation::Person(p),
+                                format!("{} is full, so {} can't park there", lot, vehicle.id),
+                            ));
+                        }
+                        let current_lane = self.path.current_step().as_lane();
+                        *stuck_end_dist = Some(map.get_l(current_lane).length());
+                        return Some(ActionAtEnd::GotoLaneEnd);
+                    }
+                }
+
+                if self.path.get_req().end.dist_along() == front {
+                    Some(ActionAtEnd::StartParking(spot.unwrap()))
+                } else {
+                    None
+                }
+            }
             Goal::BikeThenStop { ref goal } => {
                 if goal.sidewalk_pos.dist_along() == front {
                     Some(ActionAtEnd::StopBiking(goal.clone()))
@@ -318,6 +475,13 @@ impl Router {
                     None
                 }
             }
+            Goal::Deliver { end_dist, dwell } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::Deliver(dwell))
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -372,7 +536,7 @@ impl Router {
                 // make our choice based on each penalty in order, breaking ties by moving onto the
                 // next thing. With one exception: To produce more realistic behavior, we combine
                 // `vehicles + lc` as one score to avoid switching lanes just to get around one car.
-                if self.owner.vehicle_type == VehicleType::Bike {
+                if self.owner.vehicle_type.rides_bike_lanes() {
                     bike = 0;
                 } else {
                     slow_lane = 0;
@@ -483,6 +647,61 @@ impl Router {
         self.path.modify_step(1, PathStep::Turn(turn), map);
     }
 
+    /// Looks for a different way to finish the trip from the start of the current lane, adding
+    /// `delays` to the time needed to cross some roads. Returns true if the driver switched to a
+    /// route using different roads.
+    pub fn reroute(&mut self, delays: &HashMap<DirectedRoadID, Duration>, map: &Map) -> bool {
+        let req = PathRequest::vehicle(
+            Position::start(self.head().as_lane()),
+            self.path.get_req().end,
+            self.owner.vehicle_type.to_constraints(),
+        );
+        let new_path = match map.pathfind_with_delays(req, delays) {
+            Ok(path) => path,
+            Err(_) => {
+                return false;
+            }
+        };
+        let roads = |path: &Path| -> Vec<RoadID> {
+            path.get_steps()
+                .iter()
+                .filter_map(|step| match step {
+                    PathStep::Lane(l) => Some(l.road),
+                    _ => None,
+                })
+                .collect()
+        };
+        if roads(&new_path) == roads(&self.path) {
+            return false;
+        }
+        if let Err(err) = self.path.reroute(new_path, map) {
+            warn!("{} couldn't reroute: {}", self.owner, err);
+            return false;
+        }
+        true
+    }
+
+    /// After live edits changed part of the path ahead, finds a new way to finish the trip from
+    /// the start of the current lane. Returns false if there's none.
+    pub fn reroute_after_live_edits(&mut self, map: &Map) -> bool {
+        let req = PathRequest::vehicle(
+            Position::start(self.head().as_lane()),
+            self.path.get_req().end,
+            self.owner.vehicle_type.to_constraints(),
+        );
+        let new_path = match map.pathfind(req) {
+            Ok(path) => path,
+            Err(_) => {
+                return false;
+            }
+        };
+        if let Err(err) = self.path.reroute(new_path, map) {
+            warn!("{} couldn't reroute around live edits: {}", self.owner, err);
+            return false;
+        }
+        true
+    }
+
     pub fn is_parking(&self) -> bool {
         match self.goal {
             Goal::ParkNearBuilding {
@@ -495,7 +714,35 @@ impl Router {
     pub fn get_parking_spot_goal(&self) -> Option<&ParkingSpot> {
         match self.goal {
             Goal::ParkNearBuilding { ref spot, .. } => spot.as_ref().map(|(s, _)| s),
+            Goal::ParkInLot { ref spot, .. } => spot.as_ref(),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cruising() {
+        let m = Distance::meters;
+
+        // Finding a spot on the destination's own lane isn't cruising
+        let mut cruising = Cruising::new();
+        cruising.spot_ahead(m(30.0));
+        assert_eq!(cruising.total, Distance::ZERO);
+
+        // From 20m along a 100m lane, cross a 10m turn and stop 40m into a 50m lane
+        cruising.search_elsewhere(m(20.0), m(100.0), m(60.0), m(50.0), m(40.0));
+        assert_eq!(cruising.total, m(80.0 + 60.0 - 10.0));
+
+        // The spot is taken; a later one on the same lane only adds the extra distance
+        cruising.spot_ahead(m(45.0));
+        assert_eq!(cruising.total, m(135.0));
+
+        // That's taken too. Searching again from 42m along shouldn't recount up to 45m.
+        cruising.search_elsewhere(m(42.0), m(50.0), m(25.0), m(20.0), m(5.0));
+        assert_eq!(cruising.total, m(135.0 + 5.0 + 25.0 - 15.0));
+    }
+}
@@ -8,7 +8,8 @@ use geom::{Duration, Histogram, Time};
 use map_model::{IntersectionID, TransitRouteID};
 
 use crate::{
-    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, StartTripArgs, TripID,
+    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, RideRequest,
+    StartTripArgs, TripID,
 };
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -26,6 +27,8 @@ pub(crate) enum Command {
     Pandemic(pandemic::Cmd),
     /// The Time is redundant, just used to dedupe commands
     StartBus(TransitRouteID, Time),
+    /// Somebody asks the ride-hailing fleet for a vehicle
+    RequestRide(RideRequest),
 }
 
 impl Command {
@@ -49,6 +52,7 @@ impl Command {
             Command::Callback(_) => CommandType::Callback,
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
             Command::StartBus(r, t) => CommandType::StartBus(*r, *t),
+            Command::RequestRide(ref req) => CommandType::RequestRide(req.trip),
         }
     }
 
@@ -64,6 +68,7 @@ impl Command {
             Command::Callback(_) => SimpleCommandType::Callback,
             Command::Pandemic(_) => SimpleCommandType::Pandemic,
             Command::StartBus(_, _) => SimpleCommandType::StartBus,
+            Command::RequestRide(_) => SimpleCommandType::RequestRide,
         }
     }
 }
@@ -80,6 +85,7 @@ enum CommandType {
     Callback,
     Pandemic(pandemic::Cmd),
     StartBus(TransitRouteID, Time),
+    RequestRide(TripID),
 }
 
 /// A more compressed form of CommandType, just used for keeping stats on event processing.
@@ -93,6 +99,7 @@ enum SimpleCommandType {
     Callback,
     Pandemic,
     StartBus,
+    RequestRide,
 }
 
 /// The priority queue driving the discrete event simulation. Different pieces of the simulation
@@ -1,6 +1,6 @@
 // This file has a jumbled mess of queries, setup, and mutating methods.
 
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use anyhow::Result;
 use instant::Instant;
@@ -13,8 +13,8 @@ use abstio::{CityName, MapName};
 use abstutil::{prettyprint_usize, serialized_size_bytes, Timer};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints, PathRequest,
-    Position, TransitRoute, Traversable,
+    BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathRequest, PathV2, Position,
+    TransitMode, TransitRoute, TravelTimeProfile, Traversable,
 };
 use synthpop::OrigPersonID;
 
@@ -22,11 +22,12 @@ pub use self::queries::{AgentProperties, DelayCause};
 // TODO Super weird for both of these to wind up here
 pub use self::scenario::{count_parked_cars_per_bldg, rand_dist};
 use crate::{
-    AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrivingSimState, Event,
-    IntersectionSimState, PandemicModel, ParkedCar, ParkingSim, ParkingSimState, ParkingSpot,
-    Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
+    AgentID, AlertLocation, Analytics, CarFollowingModel, CarID, Command, CreateCar,
+    DispatchPolicy, DrivingSimState, Event, FleetMix, IntersectionSimState, PandemicModel,
+    ParkedCar, ParkingSim, ParkingSimState, ParkingSpot, Person, PersonID, RideHailSimState,
+    Router, Scheduler, SidewalkPOI, SidewalkSpot, SignalController, StartTripArgs, TrafficRecorder,
     TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
+    VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH, TRAM_LENGTH,
 };
 
 mod queries;
@@ -43,6 +44,7 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    ride_hail: RideHailSimState,
     trips: TripManager,
     #[serde(skip_serializing, skip_deserializing)]
     pandemic: Option<PandemicModel>,
@@ -65,6 +67,11 @@ pub struct Sim {
 
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
+
+    /// If something outside the sim subscribes to the raw stream of events, copies accumulate here
+    /// until they're drained.
+    #[serde(skip_serializing, skip_deserializing)]
+    event_buffer: Option<Vec<(Time, Event)>>,
 }
 
 pub(crate) struct Ctx<'a> {
@@ -127,6 +134,55 @@ pub struct SimOptions {
     /// quickly.
     #[structopt(long)]
     pub skip_analytics: bool,
+    /// Let drivers stuck in congestion look for a faster route to finish their trip. A driver
+    /// reconsiders once the time they've spent stopped exceeds this ratio of the time spent moving.
+    /// For example, 0.5 means a driver reroutes when their trip is taking 50% longer than expected.
+    /// When this is set, drivers also reroute when live edits change the road ahead of them,
+    /// instead of having their trip cancelled.
+    #[structopt(long)]
+    pub reroute_delay_ratio: Option<f64>,
+    /// Let vehicles overtake slower ones by briefly crossing into the oncoming lane, when nothing
+    /// is coming. Normally vehicles only overtake using another lane in the same direction.
+    #[structopt(long)]
+    pub overtake_using_oncoming_lane: bool,
+    /// Vehicles won't overtake a cyclist unless they can leave at least this much room, in meters.
+    #[structopt(long)]
+    pub min_bike_passing_distance: Option<f64>,
+    /// How vehicles accelerate. The default kinematic model has vehicles instantly reach their
+    /// top speed. Must be kinematic|idm|gipps
+    #[structopt(long, parse(try_from_str = parse_car_following), default_value = "kinematic")]
+    pub car_following: CarFollowingModel,
+    /// Scooters ride along sidewalks with pedestrians, instead of using bike lanes and roads.
+    ///
+    /// If this isn't set, it's on just for maps without any bike lanes.
+    #[structopt(long)]
+    pub scooters_on_sidewalks: Option<bool>,
+    /// How many ride-hailing vehicles wait around the map for riders. With none, every ride-hailing
+    /// trip is cancelled.
+    #[structopt(long, default_value = "0")]
+    pub ride_hail_fleet_size: usize,
+    /// How ride-hailing vehicles are assigned to riders. Must be nearest|pooled
+    #[structopt(long, parse(try_from_str = parse_dispatch_policy), default_value = "nearest")]
+    pub ride_hail_dispatch: DispatchPolicy,
+    /// Let pedestrians cross against the signal once they've waited this many seconds at a
+    /// crosswalk, if no vehicle is about to use a conflicting movement. Each of these crossings is
+    /// counted as a problem for the trip. By default, pedestrians always wait for WALK. Pedestrians
+    /// also cross mid-block, straight over a non-highway road, when that saves them at least this
+    /// many seconds over walking to the crosswalk and back. Vehicles don't yield to them.
+    #[structopt(long)]
+    pub jaywalk_after_waiting: Option<f64>,
+    /// The share of cars running on electric, petrol, and diesel, used to estimate emissions.
+    /// Given as "electric,petrol,diesel", like 0.1,0.6,0.3
+    #[structopt(long, parse(try_from_str = parse_fleet_mix), default_value = "0.05,0.75,0.2")]
+    pub fleet_mix: FleetMix,
+    /// The share of people, from 0 to 1, who use a wheelchair. They only walk along sidewalks and
+    /// crossings without steps, high kerbs, steep inclines, rough surfaces, or narrow widths.
+    #[structopt(long, default_value = "0")]
+    pub wheelchair_users: f64,
+    /// Route cars and trucks using travel times that vary over the day, read from this JSON file.
+    /// The headless API's /data/get-travel-time-profile produces one from a previous run.
+    #[structopt(long)]
+    pub travel_time_profile: Option<String>,
 }
 
 impl SimOptions {
@@ -143,6 +199,17 @@ impl SimOptions {
             infinite_parking: false,
             disable_turn_conflicts: false,
             skip_analytics: false,
+            reroute_delay_ratio: None,
+            overtake_using_oncoming_lane: false,
+            min_bike_passing_distance: None,
+            car_following: CarFollowingModel::Kinematic,
+            scooters_on_sidewalks: None,
+            ride_hail_fleet_size: 0,
+            ride_hail_dispatch: DispatchPolicy::NearestIdle,
+            jaywalk_after_waiting: None,
+            fleet_mix: FleetMix::new(),
+            wheelchair_users: 0.0,
+            travel_time_profile: None,
         }
     }
 }
@@ -183,6 +250,44 @@ fn parse_alert_handler(x: &str) -> Result<AlertHandler> {
     }
 }
 
+fn parse_car_following(x: &str) -> Result<CarFollowingModel> {
+    match x {
+        "kinematic" => Ok(CarFollowingModel::Kinematic),
+        "idm" => Ok(CarFollowingModel::IntelligentDriver),
+        "gipps" => Ok(CarFollowingModel::Gipps),
+        _ => bail!("Bad --car-following={}. Must be kinematic|idm|gipps", x),
+    }
+}
+
+fn parse_dispatch_policy(x: &str) -> Result<DispatchPolicy> {
+    match x {
+        "nearest" => Ok(DispatchPolicy::NearestIdle),
+        "pooled" => Ok(DispatchPolicy::Pooled),
+        _ => bail!("Bad --ride-hail-dispatch={}. Must be nearest|pooled", x),
+    }
+}
+
+fn parse_fleet_mix(x: &str) -> Result<FleetMix> {
+    let shares = x
+        .split(',')
+        .map(|share| share.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if shares.len() != 3
+        || shares.iter().any(|share| *share < 0.0)
+        || shares.iter().sum::<f64>() == 0.0
+    {
+        bail!(
+            "Bad --fleet-mix={}. Must be the electric,petrol,diesel shares, like 0.1,0.6,0.3",
+            x
+        );
+    }
+    Ok(FleetMix {
+        electric: shares[0],
+        petrol: shares[1],
+        diesel: shares[2],
+    })
+}
+
 // Setup
 impl Sim {
     pub fn new(map: &Map, mut opts: SimOptions) -> Sim {
@@ -206,13 +311,38 @@ impl Sim {
             opts.allow_block_the_box = true;
         }
 
+        // Without anywhere safer to go, scooter riders take to the sidewalks.
+        let scooters_on_sidewalks = opts
+            .scooters_on_sidewalks
+            .unwrap_or_else(|| !map.all_lanes().any(|l| l.is_biking()));
+
+        let travel_time_profile = opts.travel_time_profile.as_ref().map(|path| {
+            TravelTimeProfile::load(path.clone(), &mut timer)
+                .unwrap_or_else(|err| panic!("Couldn't load travel time profile {}: {}", path, err))
+        });
+        let mut trips = TripManager::new(
+            scooters_on_sidewalks,
+            opts.wheelchair_users,
+            travel_time_profile,
+        );
+        let ride_hail = RideHailSimState::new(
+            opts.ride_hail_fleet_size,
+            opts.ride_hail_dispatch,
+            &mut trips,
+            map,
+        );
+
+        let mut analytics = Analytics::new(!opts.skip_analytics);
+        analytics.emissions.fleet_mix = opts.fleet_mix.clone();
+
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, &mut timer),
-            walking: WalkingSimState::new(),
+            walking: WalkingSimState::new(&opts),
             intersections: IntersectionSimState::new(map, &mut scheduler, &opts),
             transit: TransitSimState::new(map),
-            trips: TripManager::new(),
+            ride_hail,
+            trips,
             pandemic: opts.enable_pandemic_model.map(PandemicModel::new),
             scheduler,
             time: Time::START_OF_DAY,
@@ -224,8 +354,9 @@ impl Sim {
             highlighted_people: None,
             alerts: opts.alerts,
 
-            analytics: Analytics::new(!opts.skip_analytics),
+            analytics,
             recorder: None,
+            event_buffer: None,
         }
     }
 
@@ -321,6 +452,8 @@ impl Sim {
     }
     pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
         self.parking.reserve_spot(spot, vehicle.id);
+        self.analytics
+            .record_seeded_parked_car(vehicle.id, self.time);
         self.parking.add_parked_car(ParkedCar {
             vehicle,
             spot,
@@ -340,10 +473,10 @@ impl Sim {
 
         // For now, no desire for randomness. Caller can pass in list of specs if that ever
         // changes.
-        let (vehicle_type, length) = match route.route_type {
-            PathConstraints::Bus => (VehicleType::Bus, BUS_LENGTH),
-            PathConstraints::Train => (VehicleType::Train, LIGHT_RAIL_LENGTH),
-            _ => unreachable!(),
+        let (vehicle_type, length) = match route.mode {
+            TransitMode::Bus => (VehicleType::Bus, BUS_LENGTH),
+            TransitMode::Tram => (VehicleType::Tram, TRAM_LENGTH),
+            TransitMode::Train => (VehicleType::Train, LIGHT_RAIL_LENGTH),
         };
         let vehicle = VehicleSpec {
             vehicle_type,
@@ -357,6 +490,8 @@ impl Sim {
             },
             None,
         );
+        // StartBus fires exactly at the scheduled time
+        self.transit.bus_scheduled(vehicle.id, self.time);
 
         self.scheduler.push(
             self.time,
@@ -373,6 +508,13 @@ impl Sim {
         );
     }
 
+    /// Cars on these trips follow the given route instead of pathfinding when they start driving.
+    /// If the trip winds up asking for a different path, like from a different parking spot, it
+    /// pathfinds normally.
+    pub fn set_fixed_routes(&mut self, routes: BTreeMap<TripID, PathV2>) {
+        self.trips.set_fixed_routes(routes);
+    }
+
     pub fn set_run_name(&mut self, name: String) {
         self.run_name = name;
     }
@@ -470,13 +612,29 @@ impl Sim {
                     }
                 }
                 if !ok {
-                    self.trips.cancel_trip(
-                        self.time,
-                        create_car.trip_and_person.unwrap().0,
-                        "path is no longer valid after map edits".to_string(),
-                        Some(create_car.vehicle),
-                        &mut ctx,
-                    );
+                    let id = create_car.vehicle.id;
+                    let reason = "path is no longer valid after map edits".to_string();
+                    self.driving.vehicle_gave_up_spawning(id);
+                    if let Some((trip, _)) = create_car.trip_and_person {
+                        self.trips.cancel_trip(
+                            self.time,
+                            trip,
+                            reason,
+                            Some(create_car.vehicle),
+                            &mut ctx,
+                        );
+                    } else if create_car.maybe_route.is_some() {
+                        // Nobody's aboard a bus before it spawns, so just drop it
+                        self.transit.bus_abandoned(id);
+                    } else {
+                        self.ride_hail.vehicle_cant_continue(
+                            self.time,
+                            id,
+                            reason,
+                            &mut self.trips,
+                            &mut ctx,
+                        );
+                    }
                 } else {
                     // create_car contains a Path, which is expensive to clone. We need different
                     // parts of create_car after attempting start_car_on_lane.
@@ -529,10 +687,10 @@ impl Sim {
                                 trip,
                                 person,
                                 Some(req),
-                                if id.vehicle_type == VehicleType::Car {
-                                    TripPhaseType::Driving
-                                } else {
+                                if id.vehicle_type.rides_bike_lanes() {
                                     TripPhaseType::Biking
+                                } else {
+                                    TripPhaseType::Driving
                                 },
                             ));
                         }
@@ -547,7 +705,7 @@ impl Sim {
                             self.parking.remove_parked_car(parked_car);
                         }
                         if let Some(route) = maybe_route {
-                            self.transit.bus_created(id, route);
+                            self.transit.bus_created(self.time, id, route);
                         }
                         self.analytics
                             .record_demand(self.driving.get_path(id).unwrap(), map);
@@ -599,6 +757,7 @@ impl Sim {
                     &mut ctx,
                     &mut self.trips,
                     &mut self.transit,
+                    &mut self.ride_hail,
                     &mut self.walking,
                 );
             }
@@ -615,8 +774,13 @@ impl Sim {
                 );
             }
             Command::UpdateIntersection(i) => {
-                self.intersections
-                    .update_intersection(self.time, i, map, &mut self.scheduler);
+                self.intersections.update_intersection(
+                    self.time,
+                    i,
+                    map,
+                    &self.driving,
+                    &mut self.scheduler,
+                );
             }
             Command::Callback(frequency) => {
                 self.scheduler
@@ -634,6 +798,10 @@ impl Sim {
             Command::StartBus(r, _) => {
                 self.start_bus(map.get_tr(r), map);
             }
+            Command::RequestRide(req) => {
+                self.ride_hail
+                    .request_ride(self.time, req, &mut self.trips, &mut ctx);
+            }
         }
 
         // Record events at precisely the time they occur.
@@ -645,6 +813,7 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.ride_hail.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
@@ -656,6 +825,12 @@ impl Sim {
             if let Some(ref mut r) = self.recorder {
                 r.handle_event(self.time, &ev, map, &self.driving);
             }
+            if let Some(ref mut buffer) = self.event_buffer {
+                // Paths are expensive to copy, and this event is just internal plumbing anyway
+                if !matches!(ev, Event::PathAmended(_)) {
+                    buffer.push((self.time, ev.clone()));
+                }
+            }
 
             self.analytics.event(ev, self.time, map);
         }
@@ -800,6 +975,10 @@ impl Sim {
                 "- transit: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.transit))
             );
+            println!(
+                "- ride_hail: {} bytes",
+                prettyprint_usize(serialized_size_bytes(&self.ride_hail))
+            );
             println!(
                 "- trips: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.trips))
@@ -841,9 +1020,11 @@ impl Sim {
     pub fn handle_live_edits(&mut self, map: &Map, timer: &mut Timer) -> (usize, usize) {
         self.edits_name = map.get_edits().edits_name.clone();
 
-        let (affected, num_parked_cars) = self.find_trips_affected_by_live_edits(map, timer);
+        let (affected, fleet_vehicles, num_parked_cars) =
+            self.find_trips_affected_by_live_edits(map, timer);
         let num_trips_cancelled = affected.len();
-        let affected_agents: BTreeSet<AgentID> = affected.iter().map(|(a, _)| *a).collect();
+        let mut affected_agents: BTreeSet<AgentID> = affected.iter().map(|(a, _)| *a).collect();
+        affected_agents.extend(fleet_vehicles.iter().map(|c| AgentID::Car(*c)));
 
         // V1: Just cancel every trip crossing an affected area.
         // (V2 is probably rerouting everyone, only cancelling when that fails)
@@ -884,20 +1065,33 @@ impl Sim {
                 AgentID::BusPassenger(_, _) => unreachable!(),
             }
         }
+        // Ride-hailing vehicles leave the road, and everybody they're serving gives up
+        for car in fleet_vehicles {
+            self.driving.delete_car(car, self.time, &mut ctx);
+            self.ride_hail.vehicle_cant_continue(
+                self.time,
+                car,
+                "map edited without reset".to_string(),
+                &mut self.trips,
+                &mut ctx,
+            );
+        }
 
         self.driving.handle_live_edits(map);
         self.intersections.handle_live_edits(map);
+        self.transit.handle_live_edits(map);
 
         (num_trips_cancelled, num_parked_cars)
     }
 
-    /// Returns (trips affected, number of parked cars displaced)
+    /// Returns (trips affected, ride-hailing vehicles affected, number of parked cars displaced)
     fn find_trips_affected_by_live_edits(
         &mut self,
         map: &Map,
         timer: &mut Timer,
-    ) -> (BTreeSet<(AgentID, TripID)>, usize) {
+    ) -> (BTreeSet<(AgentID, TripID)>, Vec<CarID>, usize) {
         let mut affected: BTreeSet<(AgentID, TripID)> = BTreeSet::new();
+        let mut fleet_vehicles = Vec::new();
 
         // TODO Handle changes to access restrictions
 
@@ -929,10 +1123,30 @@ impl Sim {
                 }
             }
 
+            // If drivers can reroute en-route, those not yet on anything edited look for another
+            // way instead of having their trip cancelled
+            let driving = &mut self.driving;
+            let intersections = &mut self.intersections;
+            affected.retain(|(a, _)| match a {
+                AgentID::Car(car) => {
+                    !driving.reroute_after_live_edits(*car, &edited_lanes, intersections, map)
+                }
+                _ => true,
+            });
+
             affected.extend(
                 self.driving
                     .find_vehicles_affected_by_live_edits(&closed_intersections, &edited_lanes),
             );
+            for car in self
+                .driving
+                .find_tripless_vehicles_affected_by_live_edits(&closed_intersections, &edited_lanes)
+            {
+                // TODO Buses still aren't handled
+                if self.ride_hail.is_fleet_vehicle(car) {
+                    fleet_vehicles.push(car);
+                }
+            }
         }
 
         let num_evicted = {
@@ -955,7 +1169,7 @@ impl Sim {
             num_evicted
         };
 
-        (affected, num_evicted)
+        (affected, fleet_vehicles, num_evicted)
     }
 }
 
@@ -1025,6 +1239,44 @@ impl Sim {
     }
 }
 
+// Controlling traffic signals externally
+impl Sim {
+    /// Use this controller for all traffic signals marked by `set_externally_controlled`. This
+    /// replaces any previous controller.
+    pub fn set_signal_controller(&mut self, controller: Box<dyn SignalController>) {
+        self.intersections.set_signal_controller(controller);
+    }
+
+    /// Make a traffic signal ask the external controller which stage to serve next, instead of
+    /// following its plan. The change takes effect when the current stage ends.
+    pub fn set_externally_controlled(&mut self, i: IntersectionID, external: bool) {
+        self.intersections.set_externally_controlled(i, external);
+    }
+}
+
+// Subscribing to events
+impl Sim {
+    /// Start copying every Event emitted by the simulation, so that `drain_buffered_events` can
+    /// return them later. Only one subscriber at a time is supported.
+    pub fn start_buffering_events(&mut self) {
+        self.event_buffer = Some(Vec::new());
+    }
+
+    /// Stop copying events, discarding anything not yet drained.
+    pub fn stop_buffering_events(&mut self) {
+        self.event_buffer = None;
+    }
+
+    /// Returns all events emitted since the last call, along with the time they occurred. Returns
+    /// nothing if `start_buffering_events` hasn't been called.
+    pub fn drain_buffered_events(&mut self) -> Vec<(Time, Event)> {
+        self.event_buffer
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
 // Managing highlighted people
 impl Sim {
     pub fn set_highlighted_people(&mut self, people: BTreeSet<PersonID>) {
@@ -16,7 +16,8 @@ use crate::analytics::SlidingWindow;
 use crate::{
     AgentID, AgentType, Analytics, CarID, CommutersVehiclesCounts, DrawCarInput, DrawPedCrowdInput,
     DrawPedestrianInput, PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person, PersonID,
-    PersonState, Sim, TripEndpoint, TripID, TripInfo, TripResult, UnzoomedAgent, VehicleType,
+    PersonState, SignalStatus, Sim, TripEndpoint, TripID, TripInfo, TripResult, UnzoomedAgent,
+    VehicleType,
 };
 
 // TODO Many of these just delegate to an inner piece. This is unorganized and hard to maintain.
@@ -38,11 +39,15 @@ impl Sim {
         self.trips.num_trips()
     }
     pub fn num_agents(&self) -> Counter<AgentType> {
-        self.trips.num_agents(&self.transit)
+        self.trips.num_agents(&self.transit, &self.ride_hail)
     }
     pub fn num_commuters_vehicles(&self) -> CommutersVehiclesCounts {
         self.trips
-            .num_commuters_vehicles(&self.transit, &self.walking)
+            .num_commuters_vehicles(&self.transit, &self.ride_hail, &self.walking)
+    }
+    /// How many people have asked for a ride-hailing vehicle, but aren't assigned one yet
+    pub fn num_waiting_for_ride_hail(&self) -> usize {
+        self.ride_hail.num_waiting()
     }
     /// (total number of people, just in buildings, just off map)
     pub fn num_ppl(&self) -> (usize, usize, usize) {
@@ -100,10 +105,13 @@ impl Sim {
         self.transit.get_passengers(car).len()
     }
 
+    /// How many passengers fit on a bus or train
+    pub fn transit_capacity(&self, car: CarID) -> usize {
+        self.transit.bus_capacity(car)
+    }
+
     pub fn bus_route_id(&self, maybe_bus: CarID) -> Option<TransitRouteID> {
-        if maybe_bus.vehicle_type == VehicleType::Bus
-            || maybe_bus.vehicle_type == VehicleType::Train
-        {
+        if maybe_bus.vehicle_type.is_transit() {
             Some(self.transit.bus_route(maybe_bus))
         } else {
             None
@@ -174,7 +182,7 @@ impl Sim {
                 match self.trips.get_person(owner)?.state {
                     PersonState::Inside(b) => Some((car_pos, map.get_b(b).sidewalk_pos)),
                     PersonState::Trip(_) => None,
-                    PersonState::OffMap => None,
+                    PersonState::OffMap | PersonState::Dockless(_) => None,
                 }
             })
             .collect()
@@ -204,6 +212,9 @@ impl Sim {
             VehicleType::Bike,
             VehicleType::Bus,
             VehicleType::Train,
+            VehicleType::Tram,
+            VehicleType::Truck,
+            VehicleType::Scooter,
         ] {
             let id = CarID {
                 id: idx,
@@ -261,6 +272,7 @@ impl Sim {
         match self.trips.get_person(p)?.state {
             PersonState::Inside(b) => Some(map.get_b(b).polygon.center()),
             PersonState::Trip(t) => self.get_canonical_pt_per_trip(t, map).ok(),
+            PersonState::Dockless(pos) => Some(pos.pt(map)),
             PersonState::OffMap => None,
         }
     }
@@ -313,6 +325,21 @@ impl Sim {
         &self.analytics
     }
 
+    /// Per pricing zone, how many dollars have been collected for parking so far. Cars still
+    /// parked are charged up to now, on top of what drivers paid when leaving.
+    pub fn parking_revenue(&self, map: &Map) -> BTreeMap<String, f64> {
+        let mut revenue = self.analytics.parking_revenue.clone();
+        for spot in self.parking.get_all_parking_spots().0 {
+            if let (Some(zone), Some(car)) =
+                (spot.pricing_zone(map), self.parking.get_car_at_spot(spot))
+            {
+                *revenue.entry(zone.name.clone()).or_insert(0.0) +=
+                    zone.price_per_hour * (self.time - car.parked_since).inner_seconds() / 3600.0;
+            }
+        }
+        revenue
+    }
+
     /// For intersections with an agent waiting beyond some threshold, return when they started
     /// waiting. Sorted by earliest waiting (likely the root cause of gridlock).
     pub fn delayed_intersections(&self, threshold: Duration) -> Vec<(IntersectionID, Time)> {
@@ -343,6 +370,15 @@ impl Sim {
             .current_stage_and_remaining_time(self.time, i)
     }
 
+    /// Describe the agents waiting at a traffic signal, as external controllers see it.
+    pub fn get_signal_status(&self, i: IntersectionID, map: &Map) -> SignalStatus {
+        self.intersections.signal_status(self.time, i, map)
+    }
+
+    pub fn is_externally_controlled(&self, i: IntersectionID) -> bool {
+        self.intersections.is_externally_controlled(i)
+    }
+
     // TODO This is an awkward copy of raw_throughput
     // TODO And it does NOT count buses/trains spawning
     pub fn all_arrivals_at_border(
@@ -427,7 +463,7 @@ impl Sim {
                 let max_speed = match info.mode {
                     TripMode::Walk | TripMode::Transit => Some(person.ped_speed),
                     // TODO We should really search the vehicles and grab it from there
-                    TripMode::Drive => None,
+                    TripMode::Drive | TripMode::ParkAndRide | TripMode::RideHail => None,
                     // Assume just one bike
                     TripMode::Bike => {
                         person
@@ -437,6 +473,22 @@ impl Sim {
                             .unwrap()
                             .max_speed
                     }
+                    TripMode::Scooter => {
+                        person
+                            .vehicles
+                            .iter()
+                            .find(|v| v.vehicle_type == VehicleType::Scooter)
+                            .unwrap()
+                            .max_speed
+                    }
+                    TripMode::Freight => {
+                        person
+                            .vehicles
+                            .iter()
+                            .find(|v| v.vehicle_type == VehicleType::Truck)
+                            .unwrap()
+                            .max_speed
+                    }
                 };
                 Ok(path.estimate_duration(map, max_speed))
             }
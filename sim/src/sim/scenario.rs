@@ -8,11 +8,11 @@ use abstutil::{prettyprint_usize, Counter, Timer};
 use geom::{Distance, Speed};
 use map_model::{BuildingID, Map, OffstreetParking, RoadID};
 use synthpop::make::fork_rng;
-use synthpop::{PersonSpec, Scenario, TripEndpoint, TripMode};
+use synthpop::{PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
 
 use crate::{
     ParkingSpot, Sim, StartTripArgs, TripInfo, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH,
-    MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    MAX_CAR_LENGTH, MIN_CAR_LENGTH, SCOOTER_LENGTH, TRUCK_LENGTH,
 };
 
 impl Sim {
@@ -85,6 +85,7 @@ impl Sim {
                         } else {
                             None
                         },
+                        delivery_stops: Vec::new(),
                     },
                     StartTripArgs {
                         retry_if_no_room,
@@ -94,6 +95,34 @@ impl Sim {
             }
         }
 
+        timer.start_iter("delivery tours", scenario.deliveries.len());
+        for tour in &scenario.deliveries {
+            timer.next();
+
+            if let Err(err) = tour.check() {
+                panic!("{}", err);
+            }
+
+            let person = self.new_person(None, rand_ped_speed(rng), vec![truck()]);
+            schedule_trips.push((
+                person.id,
+                TripInfo {
+                    departure: tour.depart,
+                    mode: TripMode::Freight,
+                    start: tour.origin,
+                    end: tour.destination,
+                    purpose: TripPurpose::Delivery,
+                    modified: false,
+                    cancellation_reason: None,
+                    delivery_stops: tour.stops.clone(),
+                },
+                StartTripArgs {
+                    retry_if_no_room,
+                    use_vehicle: Some(person.vehicles[0].id),
+                },
+            ));
+        }
+
         // parked_cars is stable over map edits, so don't fork.
         parked_cars.shuffle(rng);
         seed_parked_cars(parked_cars, self, map, rng, timer);
@@ -116,13 +145,19 @@ fn get_vehicles(
     let mut vehicle_foreach_trip = Vec::new();
 
     let mut bike_idx = None;
+    // Shared scooters are interchangeable, so one is enough
+    let mut scooter_idx = None;
+    let mut truck_idx = None;
     // For each indexed car, is it parked somewhere, or off-map?
     let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
+    // A car left at a park-and-ride lot, waiting for the trip back
+    let mut park_and_ride_car = None;
 
     // TODO If the trip is cancelled, this should be affected...
     for trip in &person.trips {
         let use_for_trip = match trip.mode {
-            TripMode::Walk | TripMode::Transit => None,
+            // Ride-hailing vehicles belong to the fleet, not the rider
+            TripMode::Walk | TripMode::Transit | TripMode::RideHail => None,
             TripMode::Bike => {
                 if bike_idx.is_none() {
                     bike_idx = Some(vehicle_specs.len());
@@ -130,14 +165,35 @@ fn get_vehicles(
                 }
                 bike_idx
             }
-            TripMode::Drive => {
+            TripMode::Scooter => {
+                if scooter_idx.is_none() {
+                    scooter_idx = Some(vehicle_specs.len());
+                    vehicle_specs.push(scooter());
+                }
+                scooter_idx
+            }
+            TripMode::Freight => {
+                if truck_idx.is_none() {
+                    truck_idx = Some(vehicle_specs.len());
+                    vehicle_specs.push(truck());
+                }
+                truck_idx
+            }
+            TripMode::Drive | TripMode::ParkAndRide => {
                 let need_parked_at = match trip.origin {
                     TripEndpoint::Building(b) => Some(b),
                     _ => None,
                 };
+                let returning = if trip.mode == TripMode::ParkAndRide {
+                    park_and_ride_car.take()
+                } else {
+                    None
+                };
 
                 // Any available cars in the right spot?
-                let idx = if let Some(idx) = car_locations
+                let idx = if let Some(idx) = returning {
+                    idx
+                } else if let Some(idx) = car_locations
                     .iter()
                     .find(|(_, parked_at)| *parked_at == need_parked_at)
                     .map(|(idx, _)| *idx)
@@ -155,12 +211,19 @@ fn get_vehicles(
 
                 // Where does this car wind up?
                 car_locations.retain(|(i, _)| idx != *i);
-                match trip.destination {
-                    TripEndpoint::Building(b) => {
-                        car_locations.push((idx, Some(b)));
-                    }
-                    TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => {
-                        car_locations.push((idx, None));
+                if trip.mode == TripMode::ParkAndRide && returning.is_none() {
+                    // At the lot until the trip back
+                    park_and_ride_car = Some(idx);
+                } else {
+                    match trip.destination {
+                        TripEndpoint::Building(b) => {
+                            car_locations.push((idx, Some(b)));
+                        }
+                        TripEndpoint::Border(_)
+                        | TripEndpoint::SuddenlyAppear(_)
+                        | TripEndpoint::Dockless(_) => {
+                            car_locations.push((idx, None));
+                        }
                     }
                 }
 
@@ -176,6 +239,9 @@ fn get_vehicles(
         if bike_idx.is_some() {
             n -= 1;
         }
+        if scooter_idx.is_some() {
+            n -= 1;
+        }
         if n > 1 {
             println!("Someone needs {} cars", n);
         }
@@ -210,6 +276,22 @@ fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
     }
 }
 
+fn scooter() -> VehicleSpec {
+    VehicleSpec {
+        vehicle_type: VehicleType::Scooter,
+        length: SCOOTER_LENGTH,
+        max_speed: Some(map_model::MAX_SCOOTER_SPEED),
+    }
+}
+
+fn truck() -> VehicleSpec {
+    VehicleSpec {
+        vehicle_type: VehicleType::Truck,
+        length: TRUCK_LENGTH,
+        max_speed: Some(map_model::MAX_TRUCK_SPEED),
+    }
+}
+
 pub fn rand_dist(rng: &mut XorShiftRng, low: Distance, high: Distance) -> Distance {
     assert!(high > low);
     Distance::meters(rng.gen_range(low.inner_meters()..high.inner_meters()))
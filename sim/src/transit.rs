@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
+use geom::{Duration, Time};
 use map_model::{Map, Path, TransitRoute, TransitRouteID, TransitStopID};
 
 use crate::sim::Ctx;
@@ -15,6 +15,11 @@ use crate::{
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
 
+/// Every stop takes at least this long, to pull in, open and close the doors, and pull out
+pub const MIN_DWELL_TIME: Duration = Duration::const_seconds(10.0);
+const TIME_PER_BOARDING: Duration = Duration::const_seconds(3.0);
+const TIME_PER_ALIGHTING: Duration = Duration::const_seconds(2.0);
+
 #[derive(Serialize, Deserialize, Clone)]
 struct Route {
     // Entry i is the path to drive to stop i. The very last path is to drive from the last step to
@@ -22,6 +27,7 @@ struct Route {
     paths: Vec<Path>,
     stops: Vec<TransitStopID>,
     active_vehicles: BTreeSet<CarID>,
+    capacity: usize,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,6 +37,10 @@ struct Bus {
     /// Where does each passenger want to deboard?
     passengers: Vec<(PersonID, Option<TransitStopID>)>,
     state: BusState,
+    /// When the vehicle was scheduled to spawn, which may be earlier than it actually did
+    scheduled_spawn: Time,
+    /// People who boarded after the vehicle started dwelling at the current stop hold it longer
+    extra_dwell: Duration,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -63,6 +73,12 @@ pub(crate) struct TransitSimState {
     )]
     peds_waiting:
         BTreeMap<TransitStopID, Vec<(PedestrianID, TransitRouteID, Option<TransitStopID>, Time)>>,
+    /// Vehicles waiting to spawn, and when they were scheduled to
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pending_spawns: BTreeMap<CarID, Time>,
 
     events: Vec<Event>,
 }
@@ -79,12 +95,14 @@ impl TransitSimState {
             buses: BTreeMap::new(),
             routes: BTreeMap::new(),
             peds_waiting,
+            pending_spawns: BTreeMap::new(),
             events: Vec::new(),
         }
     }
 
     /// Returns the path for the first leg.
     pub fn create_empty_route(&mut self, bus_route: &TransitRoute, map: &Map) -> Path {
+        let capacity = bus_route.vehicle_capacity();
         self.routes
             .entry(bus_route.id)
             .or_insert_with(|| match bus_route.all_paths(map) {
@@ -95,6 +113,7 @@ impl TransitSimState {
                         stops,
                         paths,
                         active_vehicles: BTreeSet::new(),
+                        capacity,
                     }
                 }
                 Err(err) => {
@@ -105,7 +124,17 @@ impl TransitSimState {
         self.routes[&bus_route.id].paths[0].clone()
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: TransitRouteID) {
+    /// A vehicle was scheduled to start at this time, but it may not spawn right away.
+    pub fn bus_scheduled(&mut self, bus: CarID, time: Time) {
+        self.pending_spawns.insert(bus, time);
+    }
+
+    /// A vehicle was scheduled, but it'll never spawn.
+    pub fn bus_abandoned(&mut self, bus: CarID) {
+        self.pending_spawns.remove(&bus);
+    }
+
+    pub fn bus_created(&mut self, now: Time, bus: CarID, r: TransitRouteID) {
         let route = self.routes.get_mut(&r).unwrap();
         route.active_vehicles.insert(bus);
         self.buses.insert(
@@ -115,12 +144,14 @@ impl TransitSimState {
                 route: r,
                 passengers: Vec::new(),
                 state: BusState::DrivingToStop(0),
+                scheduled_spawn: self.pending_spawns.remove(&bus).unwrap_or(now),
+                extra_dwell: Duration::ZERO,
             },
         );
     }
 
-    /// If true, the bus is idling. If false, the bus actually arrived at a border and should now
-    /// vanish.
+    /// If this returns how long to idle, the bus is at a stop. If None, the bus actually arrived
+    /// at a border and should now vanish.
     ///
     /// TODO Misnomer -- callback from Router::follow_bus_route
     pub fn bus_arrived_at_stop(
@@ -130,19 +161,36 @@ impl TransitSimState {
         trips: &mut TripManager,
         walking: &mut WalkingSimState,
         ctx: &mut Ctx,
-    ) -> bool {
+    ) -> Option<Duration> {
         let bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
                 bus.state = BusState::AtStop(stop_idx);
                 let stop1 = self.routes[&bus.route].stops[stop_idx];
+                let capacity = self.routes[&bus.route].capacity;
                 self.events
                     .push(Event::BusArrivedAtStop(id, bus.route, stop1));
+                let scheduled = ctx
+                    .map
+                    .get_tr(bus.route)
+                    .scheduled_offsets(bus.scheduled_spawn)
+                    .and_then(|offsets| offsets.get(stop_idx))
+                    .map(|offset| bus.scheduled_spawn + *offset);
+                if let Some(scheduled) = scheduled {
+                    self.events.push(Event::TransitScheduleDeviation(
+                        id,
+                        bus.route,
+                        stop1,
+                        now - scheduled,
+                    ));
+                }
 
                 // Deboard existing passengers.
+                let mut alightings = 0;
                 let mut still_riding = Vec::new();
                 for (person, maybe_stop2) in bus.passengers.drain(..) {
                     if Some(stop1) == maybe_stop2 {
+                        alightings += 1;
                         trips.person_left_bus(now, person, bus.car, ctx);
                         self.events.push(Event::PassengerAlightsTransit(
                             person, bus.car, bus.route, stop1,
@@ -153,43 +201,65 @@ impl TransitSimState {
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                // Board new passengers, in the order they started waiting, until the vehicle is
+                // full.
+                let mut boardings = 0;
                 let mut still_waiting = Vec::new();
-                for (ped, route, maybe_stop2, started_waiting) in
-                    self.peds_waiting.remove(&stop1).unwrap()
+                let waiting = self.peds_waiting.remove(&stop1).unwrap();
+                let decisions = who_boards(
+                    waiting.iter().map(|(_, route, _, _)| *route),
+                    bus.route,
+                    capacity.saturating_sub(bus.passengers.len()),
+                );
+                for ((ped, route, maybe_stop2, started_waiting), decision) in
+                    waiting.into_iter().zip(decisions)
                 {
-                    if bus.route == route {
-                        let (trip, person) = trips.ped_boarded_bus(
-                            now,
-                            ped,
-                            bus.car,
-                            now - started_waiting,
-                            walking,
-                        );
-                        self.events.push(Event::PassengerBoardsTransit(
-                            person,
-                            bus.car,
-                            bus.route,
-                            stop1,
-                            now - started_waiting,
-                        ));
-                        // TODO Recording the PathRequest for the passenger is actually hard. We
-                        // don't want to route directly between their first and last stop, because
-                        // there might be a much shorter path there. Should we record a leg per leg
-                        // of the transit route being followed?
-                        self.events.push(Event::TripPhaseStarting(
-                            trip,
-                            person,
-                            None,
-                            TripPhaseType::RidingBus(route, stop1, bus.car),
-                        ));
-                        bus.passengers.push((person, maybe_stop2));
-                    } else {
-                        still_waiting.push((ped, route, maybe_stop2, started_waiting));
+                    match decision {
+                        Boarding::Boards => {
+                            boardings += 1;
+                            let (trip, person) = trips.ped_boarded_bus(
+                                now,
+                                ped,
+                                bus.car,
+                                now - started_waiting,
+                                walking,
+                            );
+                            self.events.push(Event::PassengerBoardsTransit(
+                                person,
+                                bus.car,
+                                bus.route,
+                                stop1,
+                                now - started_waiting,
+                            ));
+                            // TODO Recording the PathRequest for the passenger is actually hard.
+                            // We don't want to route directly between their first and last stop,
+                            // because there might be a much shorter path there. Should we record
+                            // a leg per leg of the transit route being followed?
+                            self.events.push(Event::TripPhaseStarting(
+                                trip,
+                                person,
+                                None,
+                                TripPhaseType::RidingBus(route, stop1, bus.car),
+                            ));
+                            bus.passengers.push((person, maybe_stop2));
+                        }
+                        Boarding::LeftBehind => {
+                            self.events
+                                .push(Event::PassengerLeftBehind(ped, bus.car, bus.route, stop1));
+                            still_waiting.push((ped, route, maybe_stop2, started_waiting));
+                        }
+                        Boarding::WaitsForAnotherRoute => {
+                            still_waiting.push((ped, route, maybe_stop2, started_waiting));
+                        }
                     }
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
-                true
+                let dwell = dwell_time(boardings, alightings);
+                // Vehicles running ahead of the timetable hold at the stop until they're due
+                match scheduled {
+                    Some(scheduled) if scheduled - now > dwell => Some(scheduled - now),
+                    _ => Some(dwell),
+                }
             }
             BusState::DrivingOffMap => {
                 self.routes
@@ -208,12 +278,22 @@ impl TransitSimState {
                     trips.transit_rider_reached_border(now, person, id, ctx);
                 }
                 bus.state = BusState::Finished;
-                false
+                None
             }
             BusState::AtStop(_) | BusState::Finished => unreachable!(),
         }
     }
 
+    /// A vehicle is done dwelling at a stop. If people boarded in the meantime, returns how much
+    /// longer to wait before departing.
+    pub fn take_extra_dwell(&mut self, id: CarID) -> Option<Duration> {
+        let bus = self.buses.get_mut(&id).unwrap();
+        if bus.extra_dwell == Duration::ZERO {
+            return None;
+        }
+        Some(std::mem::replace(&mut bus.extra_dwell, Duration::ZERO))
+    }
+
     pub fn bus_departed_from_stop(&mut self, id: CarID, _: &Map) -> Router {
         let bus = self.buses.get_mut(&id).unwrap();
         let route = self.routes.get_mut(&bus.route).unwrap();
@@ -223,6 +303,8 @@ impl TransitSimState {
                     id,
                     bus.route,
                     route.stops[stop_idx],
+                    bus.passengers.len(),
+                    route.capacity,
                 ));
 
                 if stop_idx == route.stops.len() - 1 {
@@ -255,11 +337,14 @@ impl TransitSimState {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
                     if route.stops[idx] == stop1 {
-                        self.buses
-                            .get_mut(bus)
-                            .unwrap()
-                            .passengers
-                            .push((person, maybe_stop2));
+                        if self.buses[bus].passengers.len() >= route.capacity {
+                            self.events
+                                .push(Event::PassengerLeftBehind(ped, *bus, route_id, stop1));
+                            continue;
+                        }
+                        let bus_state = self.buses.get_mut(bus).unwrap();
+                        bus_state.passengers.push((person, maybe_stop2));
+                        bus_state.extra_dwell += TIME_PER_BOARDING;
                         // TODO Same problem as elsewhere with recording the PathRequest
                         self.events.push(Event::TripPhaseStarting(
                             trip,
@@ -285,6 +370,13 @@ impl TransitSimState {
         None
     }
 
+    /// Route capacities may have been edited; vehicles already running use the new capacity.
+    pub fn handle_live_edits(&mut self, map: &Map) {
+        for (id, route) in &mut self.routes {
+            route.capacity = map.get_tr(*id).vehicle_capacity();
+        }
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         self.events.drain(..).collect()
     }
@@ -297,6 +389,10 @@ impl TransitSimState {
         self.buses[&bus].route
     }
 
+    pub fn bus_capacity(&self, bus: CarID) -> usize {
+        self.routes[&self.buses[&bus].route].capacity
+    }
+
     /// also stop idx that the bus is coming from
     pub fn buses_for_route(&self, route: TransitRouteID) -> Vec<(CarID, Option<usize>)> {
         if let Some(r) = self.routes.get(&route) {
@@ -379,3 +475,101 @@ impl TransitSimState {
         results
     }
 }
+
+/// How long a vehicle stays at a stop, if it's not holding for the timetable
+fn dwell_time(boardings: usize, alightings: usize) -> Duration {
+    MIN_DWELL_TIME
+        + (boardings as f64) * TIME_PER_BOARDING
+        + (alightings as f64) * TIME_PER_ALIGHTING
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Boarding {
+    Boards,
+    /// Waiting for this route, but the vehicle is full
+    LeftBehind,
+    WaitsForAnotherRoute,
+}
+
+/// Given the route each person at a stop is waiting for, in the order they started waiting,
+/// decides who gets on a vehicle of `route` with `room` free places.
+fn who_boards<I: Iterator<Item = TransitRouteID>>(
+    waiting_for: I,
+    route: TransitRouteID,
+    mut room: usize,
+) -> Vec<Boarding> {
+    waiting_for
+        .map(|r| {
+            if r != route {
+                Boarding::WaitsForAnotherRoute
+            } else if room == 0 {
+                Boarding::LeftBehind
+            } else {
+                room -= 1;
+                Boarding::Boards
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dwell_time() {
+        assert_eq!(dwell_time(0, 0), MIN_DWELL_TIME);
+        assert_eq!(
+            dwell_time(4, 0),
+            MIN_DWELL_TIME + Duration::seconds(4.0 * 3.0)
+        );
+        assert_eq!(
+            dwell_time(0, 5),
+            MIN_DWELL_TIME + Duration::seconds(5.0 * 2.0)
+        );
+        assert_eq!(dwell_time(4, 5), Duration::seconds(10.0 + 12.0 + 10.0));
+    }
+
+    #[test]
+    fn test_who_boards() {
+        let ours = TransitRouteID(0);
+        let theirs = TransitRouteID(1);
+        let waiting = vec![ours, theirs, ours, ours, theirs];
+
+        // Plenty of room
+        assert_eq!(
+            who_boards(waiting.clone().into_iter(), ours, 10),
+            vec![
+                Boarding::Boards,
+                Boarding::WaitsForAnotherRoute,
+                Boarding::Boards,
+                Boarding::Boards,
+                Boarding::WaitsForAnotherRoute,
+            ]
+        );
+
+        // At capacity, whoever started waiting last is left behind
+        assert_eq!(
+            who_boards(waiting.clone().into_iter(), ours, 2),
+            vec![
+                Boarding::Boards,
+                Boarding::WaitsForAnotherRoute,
+                Boarding::Boards,
+                Boarding::LeftBehind,
+                Boarding::WaitsForAnotherRoute,
+            ]
+        );
+
+        // A full vehicle takes nobody
+        assert_eq!(
+            who_boards(waiting.into_iter(), theirs, 0),
+            vec![
+                Boarding::WaitsForAnotherRoute,
+                Boarding::LeftBehind,
+                Boarding::WaitsForAnotherRoute,
+                Boarding::WaitsForAnotherRoute,
+                Boarding::LeftBehind,
+            ]
+        );
+    }
+}
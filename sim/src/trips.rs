@@ -1,23 +1,25 @@
 use std::collections::{BTreeMap, VecDeque};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, IntersectionID, Map, PathConstraints, PathRequest, Position, TransitRouteID,
-    TransitStopID,
+    BuildingID, IntersectionID, Map, Path, PathConstraints, PathRequest, PathV2, PathfinderCaching,
+    Position, TransitRouteID, TransitStopID, TravelTimeProfile, WalkingProfile,
 };
 use synthpop::{
-    IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose,
+    DeliveryStop, DeliveryTour, IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint,
+    TripMode, TripPurpose,
 };
 
 use crate::sim::Ctx;
 use crate::{
     AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, SidewalkPOI, SidewalkSpot,
-    StartTripArgs, TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState,
+    Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, RideHailSimState,
+    RideRequest, Router, SidewalkPOI, SidewalkSpot, StartTripArgs, TransitSimState, TripID,
+    TripPhaseType, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
@@ -39,23 +41,44 @@ pub(crate) struct TripManager {
     unfinished_trips: usize,
 
     car_id_counter: usize,
+    scooters_on_sidewalks: bool,
+    /// The share of people who use a wheelchair, and so can only walk along accessible routes
+    wheelchair_users: f64,
+    /// If present, vehicles route using travel times for the time of day they depart
+    travel_time_profile: Option<TravelTimeProfile>,
+    /// Cars on these trips follow a route chosen ahead of time, as long as the trip asks for the
+    /// same path
+    #[serde(skip_serializing, skip_deserializing)]
+    fixed_routes: BTreeMap<TripID, PathV2>,
 
     events: Vec<Event>,
 }
 
 // Initialization
 impl TripManager {
-    pub fn new() -> TripManager {
+    pub fn new(
+        scooters_on_sidewalks: bool,
+        wheelchair_users: f64,
+        travel_time_profile: Option<TravelTimeProfile>,
+    ) -> TripManager {
         TripManager {
             trips: Vec::new(),
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             unfinished_trips: 0,
             car_id_counter: 0,
+            scooters_on_sidewalks,
+            wheelchair_users,
+            travel_time_profile,
+            fixed_routes: BTreeMap::new(),
             events: Vec::new(),
         }
     }
 
+    pub fn set_fixed_routes(&mut self, routes: BTreeMap<TripID, PathV2>) {
+        self.fixed_routes = routes;
+    }
+
     // TODO assert the specs are correct yo
     pub fn new_person(
         &mut self,
@@ -74,6 +97,16 @@ impl TripManager {
                 v.make(c, Some(id))
             })
             .collect();
+        // Deterministically scatter sequential IDs across [0, 1), so the same people are picked
+        // across runs
+        let walking_profile = if ((id.0 as u64).wrapping_mul(2_654_435_761) % 10_000) as f64
+            / 10_000.0
+            < self.wheelchair_users
+        {
+            Some(WalkingProfile::wheelchair())
+        } else {
+            None
+        };
         self.people.push(Person {
             id,
             orig_id,
@@ -82,6 +115,7 @@ impl TripManager {
             state: PersonState::OffMap,
             ped: PedestrianID(id.0),
             ped_speed,
+            walking_profile,
             vehicles,
             delayed_trips: Vec::new(),
             on_bus: None,
@@ -117,6 +151,7 @@ impl TripManager {
                     PersonState::Inside(b)
                 }
                 TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => PersonState::OffMap,
+                TripEndpoint::Dockless(pos) => PersonState::Dockless(pos),
             };
         }
         if let Some(t) = person.trips.last() {
@@ -160,14 +195,52 @@ impl TripManager {
         self.trips[trip.0].started = true;
 
         let info = &self.trips[trip.0].info;
-        let spec = match TripSpec::maybe_new(
-            info.start,
-            info.end,
-            info.mode,
-            args.use_vehicle,
-            args.retry_if_no_room,
-            ctx.map,
-        ) {
+        let maybe_spec = if info.mode == TripMode::Scooter && self.scooters_on_sidewalks {
+            args.use_vehicle
+                .ok_or_else(|| anyhow!("{} has no scooter to ride", person.id))
+                .and_then(|scooter| {
+                    TripSpec::scooting_on_sidewalks(info.start, info.end, scooter, ctx.map)
+                })
+        } else if info.mode == TripMode::ParkAndRide && !ctx.parking.is_infinite() {
+            // Park-and-ride trips come in pairs; the second one heads back to the car
+            let returning = person
+                .trips
+                .iter()
+                .take_while(|t| **t != trip)
+                .filter(|t| self.trips[t.0].info.mode == TripMode::ParkAndRide)
+                .count()
+                % 2
+                == 1;
+            args.use_vehicle
+                .ok_or_else(|| anyhow!("{} has no car to park and ride", person.id))
+                .and_then(|car| {
+                    let car_parked_at = ctx
+                        .parking
+                        .lookup_parked_car(car)
+                        .map(|p| SidewalkSpot::parking_spot(p.spot, ctx.map, ctx.parking));
+                    TripSpec::park_and_ride(
+                        info.start,
+                        info.end,
+                        car,
+                        returning,
+                        car_parked_at,
+                        person.walking_profile.as_ref(),
+                        ctx.map,
+                    )
+                })
+        } else {
+            TripSpec::maybe_new(
+                info.start,
+                info.end,
+                info.mode,
+                args.use_vehicle,
+                &info.delivery_stops,
+                args.retry_if_no_room,
+                person.walking_profile.as_ref(),
+                ctx.map,
+            )
+        };
+        let spec = match maybe_spec {
             Ok(spec) => spec,
             Err(error) => TripSpec::SpawningFailure {
                 use_vehicle: args.use_vehicle,
@@ -186,21 +259,28 @@ impl TripManager {
                 retry_if_no_room,
                 use_vehicle,
             } => {
-                assert_eq!(person.state, PersonState::OffMap);
-                self.events.push(Event::PersonEntersMap(
-                    person.id,
-                    AgentID::Car(use_vehicle),
-                    ctx.map.get_l(start_pos.lane()).src_i,
-                ));
+                match person.state {
+                    PersonState::OffMap => {
+                        self.events.push(Event::PersonEntersMap(
+                            person.id,
+                            AgentID::Car(use_vehicle),
+                            ctx.map.get_l(start_pos.lane()).src_i,
+                        ));
+                    }
+                    PersonState::Dockless(pos) => {
+                        self.events.push(Event::PersonLeavesDockless(
+                            person.id,
+                            AgentID::Car(use_vehicle),
+                            pos,
+                        ));
+                    }
+                    PersonState::Trip(_) | PersonState::Inside(_) => unreachable!(),
+                }
                 person.state = PersonState::Trip(trip);
 
                 let vehicle = person.get_vehicle(use_vehicle);
                 assert!(ctx.parking.lookup_parked_car(vehicle.id).is_none());
-                let constraints = if use_vehicle.vehicle_type == VehicleType::Bike {
-                    PathConstraints::Bike
-                } else {
-                    PathConstraints::Car
-                };
+                let constraints = use_vehicle.vehicle_type.to_constraints();
                 let req = PathRequest::vehicle(
                     start_pos,
                     goal.goal_pos(constraints, ctx.map).unwrap(),
@@ -208,7 +288,7 @@ impl TripManager {
                 );
                 let person = person.id;
 
-                match ctx.map.pathfind(req) {
+                match self.pathfind_vehicle(trip, req, now, ctx.map) {
                     Ok(path) => {
                         let router = goal.make_router(vehicle.id, path, ctx.map);
                         ctx.scheduler.push(
@@ -230,8 +310,59 @@ impl TripManager {
                 let vehicle = use_vehicle.map(|v| person.get_vehicle(v));
                 self.cancel_trip(now, trip, error, vehicle, ctx);
             }
+            TripSpec::Delivering {
+                start_pos,
+                truck,
+                retry_if_no_room,
+                ..
+            } => {
+                match person.state {
+                    PersonState::Inside(b) => {
+                        self.events.push(Event::PersonLeavesBuilding(person.id, b));
+                    }
+                    PersonState::OffMap => {
+                        self.events.push(Event::PersonEntersMap(
+                            person.id,
+                            AgentID::Car(truck),
+                            ctx.map.get_l(start_pos.lane()).src_i,
+                        ));
+                    }
+                    PersonState::Trip(_) | PersonState::Dockless(_) => unreachable!(),
+                }
+                person.state = PersonState::Trip(trip);
+
+                let vehicle = person.get_vehicle(truck);
+                let person = person.id;
+                let goal = match self.trips[trip.0].legs[0] {
+                    TripLeg::Drive(_, ref goal) => goal.clone(),
+                    _ => unreachable!(),
+                };
+                let req = PathRequest::vehicle(
+                    start_pos,
+                    goal.goal_pos(PathConstraints::Truck, ctx.map).unwrap(),
+                    PathConstraints::Truck,
+                );
+                match self.pathfind_vehicle(trip, req, now, ctx.map) {
+                    Ok(path) => {
+                        let router = goal.make_router(vehicle.id, path, ctx.map);
+                        ctx.scheduler.push(
+                            now,
+                            Command::SpawnCar(
+                                CreateCar::for_appearing(vehicle, router, trip, person),
+                                retry_if_no_room,
+                            ),
+                        );
+                    }
+                    Err(err) => {
+                        self.cancel_trip(now, trip, err.to_string(), Some(vehicle), ctx);
+                    }
+                }
+            }
             TripSpec::UsingParkedCar {
                 car, start_bldg, ..
+            }
+            | TripSpec::DriveThenTransit {
+                car, start_bldg, ..
             } => {
                 assert_eq!(person.state, PersonState::Inside(start_bldg));
                 person.state = PersonState::Trip(trip);
@@ -241,13 +372,14 @@ impl TripManager {
                     let walking_goal =
                         SidewalkSpot::parking_spot(parked_car.spot, ctx.map, ctx.parking);
                     let req = PathRequest::walking(start.sidewalk_pos, walking_goal.sidewalk_pos);
-                    match ctx.map.pathfind(req) {
+                    match person.pathfind_walking(req, ctx.map) {
                         Ok(path) => {
                             ctx.scheduler.push(
                                 now,
                                 Command::SpawnPed(CreatePedestrian {
                                     id: person.ped,
                                     speed: person.ped_speed,
+                                    on_scooter: false,
                                     start,
                                     goal: walking_goal,
                                     path,
@@ -280,7 +412,11 @@ impl TripManager {
                     );
                 }
             }
-            TripSpec::JustWalking { start, goal } => {
+            TripSpec::JustWalking {
+                start,
+                goal,
+                scooter,
+            } => {
                 assert_eq!(
                     person.state,
                     match start.connection {
@@ -303,19 +439,32 @@ impl TripManager {
                             ));
                             PersonState::OffMap
                         }
+                        SidewalkPOI::Dockless => {
+                            self.events.push(Event::PersonLeavesDockless(
+                                person.id,
+                                AgentID::Pedestrian(person.ped),
+                                start.sidewalk_pos,
+                            ));
+                            PersonState::Dockless(start.sidewalk_pos)
+                        }
                         _ => unreachable!(),
                     }
                 );
                 person.state = PersonState::Trip(trip);
 
+                let speed = match scooter {
+                    Some(c) => person.get_vehicle(c).max_speed.unwrap(),
+                    None => person.ped_speed,
+                };
                 let req = PathRequest::walking(start.sidewalk_pos, goal.sidewalk_pos);
-                match ctx.map.pathfind(req) {
+                match person.pathfind_walking(req, ctx.map) {
                     Ok(path) => {
                         ctx.scheduler.push(
                             now,
                             Command::SpawnPed(CreatePedestrian {
                                 id: person.ped,
-                                speed: person.ped_speed,
+                                speed,
+                                on_scooter: scooter.is_some(),
                                 start,
                                 goal,
                                 path,
@@ -338,7 +487,7 @@ impl TripManager {
                         SidewalkSpot::building(start, ctx.map).sidewalk_pos,
                         walk_to.sidewalk_pos,
                     );
-                    match ctx.map.pathfind(req) {
+                    match person.pathfind_walking(req, ctx.map) {
                         Ok(path) => {
                             // Where we start biking may have slightly changed due to live map
                             // edits!
@@ -357,6 +506,7 @@ impl TripManager {
                                 Command::SpawnPed(CreatePedestrian {
                                     id: person.ped,
                                     speed: person.ped_speed,
+                                    on_scooter: false,
                                     start: SidewalkSpot::building(start, ctx.map),
                                     goal: walk_to,
                                     path,
@@ -382,7 +532,8 @@ impl TripManager {
                     );
                 }
             }
-            TripSpec::UsingTransit { start, stop1, .. } => {
+            TripSpec::UsingTransit { start, stop1, .. }
+            | TripSpec::TransitThenDrive { start, stop1, .. } => {
                 assert_eq!(
                     person.state,
                     match start.connection {
@@ -405,6 +556,14 @@ impl TripManager {
                             ));
                             PersonState::OffMap
                         }
+                        SidewalkPOI::Dockless => {
+                            self.events.push(Event::PersonLeavesDockless(
+                                person.id,
+                                AgentID::Pedestrian(person.ped),
+                                start.sidewalk_pos,
+                            ));
+                            PersonState::Dockless(start.sidewalk_pos)
+                        }
                         _ => unreachable!(),
                     }
                 );
@@ -412,13 +571,14 @@ impl TripManager {
 
                 let walk_to = SidewalkSpot::bus_stop(stop1, ctx.map);
                 let req = PathRequest::walking(start.sidewalk_pos, walk_to.sidewalk_pos);
-                match ctx.map.pathfind(req) {
+                match person.pathfind_walking(req, ctx.map) {
                     Ok(path) => {
                         ctx.scheduler.push(
                             now,
                             Command::SpawnPed(CreatePedestrian {
                                 id: person.ped,
                                 speed: person.ped_speed,
+                                on_scooter: false,
                                 start,
                                 goal: walk_to,
                                 path,
@@ -432,6 +592,32 @@ impl TripManager {
                     }
                 }
             }
+            TripSpec::RideHailing {
+                start_bldg,
+                pickup,
+                dropoff,
+            } => {
+                assert_eq!(person.state, PersonState::Inside(start_bldg));
+                person.state = PersonState::Trip(trip);
+
+                // Wait inside until the vehicle arrives
+                self.events.push(Event::TripPhaseStarting(
+                    trip,
+                    person.id,
+                    None,
+                    TripPhaseType::WaitingForRideHail,
+                ));
+                ctx.scheduler.push(
+                    now,
+                    Command::RequestRide(RideRequest {
+                        trip,
+                        person: person.id,
+                        pickup,
+                        dropoff,
+                        requested_at: now,
+                    }),
+                );
+            }
         }
     }
 
@@ -466,7 +652,8 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_)))
+            | Some(TripLeg::Drive(c, DrivingGoal::ParkInLot(_))) => {
                 assert_eq!(car, c);
             }
             _ => unreachable!(),
@@ -547,7 +734,7 @@ impl TripManager {
 
         let person = trip.person;
         let trip = trip.id;
-        match ctx.map.pathfind(req) {
+        match self.pathfind_vehicle(trip, req, now, ctx.map) {
             Ok(path) => {
                 let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
                 ctx.scheduler.push(
@@ -659,13 +846,29 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_)))
+            | Some(TripLeg::Drive(c, DrivingGoal::Dockless(_))) => {
                 assert_eq!(c, bike);
             }
             _ => unreachable!(),
         };
 
         let id = trip.id;
+        if trip.legs.is_empty() {
+            // Dropped off at a dockless point, which is the end of the trip
+            let pos = match trip.info.end {
+                TripEndpoint::Dockless(pos) => pos,
+                _ => unreachable!(),
+            };
+            self.events.push(Event::PersonStopsAtDockless(
+                trip.person,
+                Some(AgentID::Car(bike)),
+                pos,
+            ));
+            self.people[trip.person.0].state = PersonState::Dockless(pos);
+            self.trip_finished(now, id, ctx);
+            return;
+        }
         self.spawn_ped(now, id, bike_rack, ctx);
     }
 
@@ -833,6 +1036,41 @@ impl TripManager {
         self.trip_finished(now, id, ctx);
     }
 
+    pub fn ped_reached_dockless_point(
+        &mut self,
+        now: Time,
+        ped: PedestrianID,
+        blocked_time: Duration,
+        distance_crossed: Distance,
+        ctx: &mut Ctx,
+    ) {
+        let trip = &mut self.trips[self
+            .active_trip_mode
+            .remove(&AgentID::Pedestrian(ped))
+            .unwrap()
+            .0];
+        trip.total_blocked_time += blocked_time;
+        trip.total_distance += distance_crossed;
+
+        match trip.legs.pop_front() {
+            Some(TripLeg::Walk(spot)) => assert_eq!(spot.connection, SidewalkPOI::Dockless),
+            _ => unreachable!(),
+        }
+        let pos = match trip.info.end {
+            TripEndpoint::Dockless(pos) => pos,
+            _ => unreachable!(),
+        };
+        self.events.push(Event::PersonStopsAtDockless(
+            trip.person,
+            Some(AgentID::Pedestrian(ped)),
+            pos,
+        ));
+        self.people[trip.person.0].state = PersonState::Dockless(pos);
+
+        let id = trip.id;
+        self.trip_finished(now, id, ctx);
+    }
+
     pub fn transit_rider_reached_border(
         &mut self,
         now: Time,
@@ -860,6 +1098,112 @@ impl TripManager {
         self.trip_finished(now, id, ctx);
     }
 
+    /// A truck finished waiting at a stop. Returns the route to the next stop, or None if the
+    /// truck is done and should vanish.
+    pub fn truck_finished_delivery(
+        &mut self,
+        now: Time,
+        truck: CarID,
+        pos: Position,
+        blocked_time: Duration,
+        distance_crossed: Distance,
+        ctx: &mut Ctx,
+    ) -> Option<Router> {
+        let trip = &mut self.trips[self.active_trip_mode[&AgentID::Car(truck)].0];
+        trip.total_blocked_time += blocked_time;
+        trip.total_distance += distance_crossed;
+
+        match trip.legs.pop_front() {
+            Some(TripLeg::Drive(c, DrivingGoal::Deliver(_, _))) => {
+                assert_eq!(truck, c);
+            }
+            _ => unreachable!(),
+        };
+
+        let id = trip.id;
+        if trip.legs.is_empty() {
+            // Back at the depot
+            let b = match trip.info.end {
+                TripEndpoint::Building(b) => b,
+                _ => unreachable!(),
+            };
+            self.active_trip_mode.remove(&AgentID::Car(truck));
+            self.people[trip.person.0].state = PersonState::Inside(b);
+            self.events
+                .push(Event::PersonEntersBuilding(trip.person, b));
+            self.trip_finished(now, id, ctx);
+            return None;
+        }
+
+        let goal = match trip.legs[0] {
+            TripLeg::Drive(_, ref goal) => goal.clone(),
+            _ => unreachable!(),
+        };
+        let req = PathRequest::vehicle(
+            pos,
+            goal.goal_pos(PathConstraints::Truck, ctx.map).unwrap(),
+            PathConstraints::Truck,
+        );
+        match self.pathfind_vehicle(id, req, now, ctx.map) {
+            Ok(path) => Some(goal.make_router(truck, path, ctx.map)),
+            Err(err) => {
+                // The truck is still on the road, so don't warp it anywhere
+                self.cancel_trip(now, id, err.to_string(), None, ctx);
+                None
+            }
+        }
+    }
+
+    /// A ride-hailing vehicle arrived to pick somebody up.
+    pub fn ride_hail_pickup(&mut self, trip: TripID, car: CarID, waiting: Duration) {
+        let trip = &mut self.trips[trip.0];
+        trip.total_blocked_time += waiting;
+        if let TripEndpoint::Building(b) = trip.info.start {
+            self.events
+                .push(Event::PersonLeavesBuilding(trip.person, b));
+        }
+        self.active_trip_mode
+            .insert(AgentID::BusPassenger(trip.person, car), trip.id);
+        self.people[trip.person.0].on_bus = Some(car);
+        self.events.push(Event::TripPhaseStarting(
+            trip.id,
+            trip.person,
+            None,
+            TripPhaseType::RidingRideHail(car),
+        ));
+    }
+
+    /// A ride-hailing vehicle dropped somebody off outside their destination.
+    pub fn ride_hail_dropoff(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        car: CarID,
+        pos: Position,
+        ctx: &mut Ctx,
+    ) {
+        let trip = &mut self.trips[trip.0];
+        let person = trip.person;
+        self.active_trip_mode
+            .remove(&AgentID::BusPassenger(person, car))
+            .unwrap();
+        match trip.legs.pop_front() {
+            Some(TripLeg::RideHail(dropoff)) => assert_eq!(pos, dropoff),
+            _ => unreachable!(),
+        }
+        self.people[person.0].on_bus.take().unwrap();
+
+        let b = match trip.info.end {
+            TripEndpoint::Building(b) => b,
+            _ => unreachable!(),
+        };
+        self.people[person.0].state = PersonState::Inside(b);
+        self.events.push(Event::PersonEntersBuilding(person, b));
+
+        let id = trip.id;
+        self.trip_finished(now, id, ctx);
+    }
+
     pub fn car_or_bike_reached_border(
         &mut self,
         now: Time,
@@ -931,13 +1275,32 @@ impl TripManager {
 
     fn spawn_ped(&mut self, now: Time, id: TripID, start: SidewalkSpot, ctx: &mut Ctx) {
         let trip = &self.trips[id.0];
-        let walk_to = match trip.legs[0] {
+        let mut walk_to = match trip.legs[0] {
             TripLeg::Walk(ref to) => to.clone(),
             _ => unreachable!(),
         };
+        // Coming back from a park-and-ride, head to wherever the car was left
+        if walk_to.connection == SidewalkPOI::DeferredParkingSpot {
+            let car = match trip.legs[1] {
+                TripLeg::Drive(c, _) => c,
+                _ => unreachable!(),
+            };
+            if let Some(parked_car) = ctx.parking.lookup_parked_car(car) {
+                walk_to = SidewalkSpot::parking_spot(parked_car.spot, ctx.map, ctx.parking);
+            } else {
+                self.cancel_trip(
+                    now,
+                    id,
+                    format!("should have {} parked somewhere, but it's unavailable", car),
+                    None,
+                    ctx,
+                );
+                return;
+            }
+        }
 
         let req = PathRequest::walking(start.sidewalk_pos, walk_to.sidewalk_pos);
-        match ctx.map.pathfind(req) {
+        match self.people[trip.person.0].pathfind_walking(req, ctx.map) {
             Ok(path) => {
                 let person = &self.people[trip.person.0];
                 ctx.scheduler.push(
@@ -945,6 +1308,7 @@ impl TripManager {
                     Command::SpawnPed(CreatePedestrian {
                         id: person.ped,
                         speed: person.ped_speed,
+                        on_scooter: false,
                         start,
                         goal: walk_to,
                         path,
@@ -958,6 +1322,28 @@ impl TripManager {
             }
         }
     }
+
+    /// Cars and trucks follow a fixed route for their trip if there is one. Otherwise they route
+    /// using the travel time profile, if there is one, for when they depart.
+    fn pathfind_vehicle(
+        &self,
+        trip: TripID,
+        req: PathRequest,
+        now: Time,
+        map: &Map,
+    ) -> Result<Path> {
+        if let Some(path) = self.fixed_routes.get(&trip) {
+            if path.get_req() == &req {
+                if let Ok(path) = path.clone().into_v1(map) {
+                    return Ok(path);
+                }
+            }
+        }
+        if let Some(ref profile) = self.travel_time_profile {
+            return map.pathfind_with_params_at(req, map.routing_params(), now, profile);
+        }
+        map.pathfind(req)
+    }
 }
 
 // Cancelling trips
@@ -1002,6 +1388,11 @@ impl TripManager {
                 self.events.push(Event::PersonLeavesMap(person, None, i));
                 PersonState::OffMap
             }
+            TripEndpoint::Dockless(pos) => {
+                self.events
+                    .push(Event::PersonStopsAtDockless(person, None, pos));
+                PersonState::Dockless(pos)
+            }
             // Can't end trips here yet
             TripEndpoint::SuddenlyAppear(_) => unreachable!(),
         };
@@ -1067,6 +1458,26 @@ impl TripManager {
         self.start_delayed_trip(now, person, ctx);
     }
 
+    /// Cancel a ride-hailing trip, whether the person is still waiting or already riding.
+    pub fn cancel_ride_hail(
+        &mut self,
+        now: Time,
+        id: TripID,
+        on_board: Option<CarID>,
+        reason: String,
+        ctx: &mut Ctx,
+    ) {
+        let person = self.trips[id.0].person;
+        if let Some(car) = on_board {
+            self.trip_abruptly_cancelled(id, AgentID::BusPassenger(person, car));
+            self.people[person.0].on_bus = None;
+        } else if let TripEndpoint::Building(b) = self.trips[id.0].info.start {
+            // They were still waiting inside
+            self.events.push(Event::PersonLeavesBuilding(person, b));
+        }
+        self.cancel_trip(now, id, reason, None, ctx);
+    }
+
     pub fn trip_abruptly_cancelled(&mut self, trip: TripID, agent: AgentID) {
         assert_eq!(self.active_trip_mode.remove(&agent), Some(trip));
     }
@@ -1105,6 +1516,13 @@ impl TripManager {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
             TripLeg::Drive(c, _) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideHail(_) => match person.on_bus {
+                Some(car) => AgentID::BusPassenger(person.id, car),
+                // Still waiting for the vehicle
+                None => {
+                    return TripResult::ModeChange;
+                }
+            },
         };
         if self.active_trip_mode.get(&a) == Some(&id) {
             TripResult::Ok(a)
@@ -1134,7 +1552,11 @@ impl TripManager {
             self.unfinished_trips,
         )
     }
-    pub fn num_agents(&self, transit: &TransitSimState) -> Counter<AgentType> {
+    pub fn num_agents(
+        &self,
+        transit: &TransitSimState,
+        ride_hail: &RideHailSimState,
+    ) -> Counter<AgentType> {
         let mut cnt = Counter::new();
         for a in self.active_trip_mode.keys() {
             cnt.inc(a.to_type());
@@ -1142,11 +1564,13 @@ impl TripManager {
         let (buses, trains) = transit.active_vehicles();
         cnt.add(AgentType::Bus, buses);
         cnt.add(AgentType::Train, trains);
+        cnt.add(AgentType::Car, ride_hail.active_vehicles().0);
         cnt
     }
     pub fn num_commuters_vehicles(
         &self,
         transit: &TransitSimState,
+        ride_hail: &RideHailSimState,
         walking: &WalkingSimState,
     ) -> CommutersVehiclesCounts {
         let (buses, trains) = transit.active_vehicles();
@@ -1164,27 +1588,35 @@ impl TripManager {
             trains,
             bus_riders: 0,
             train_riders: 0,
+
+            ride_hail_vehicles: ride_hail.active_vehicles().0,
+            ride_hail_riders: 0,
         };
 
         for a in self.active_trip_mode.keys() {
             match a {
                 AgentID::Car(c) => match c.vehicle_type {
-                    VehicleType::Car => {
+                    VehicleType::Car | VehicleType::Truck => {
                         cnt.sov_drivers += 1;
                     }
-                    VehicleType::Bike => {
+                    VehicleType::Bike | VehicleType::Scooter => {
                         cnt.cyclists += 1;
                     }
-                    VehicleType::Bus | VehicleType::Train => unreachable!(),
+                    VehicleType::Bus | VehicleType::Train | VehicleType::Tram => unreachable!(),
                 },
                 AgentID::BusPassenger(_, c) => match c.vehicle_type {
                     VehicleType::Bus => {
                         cnt.bus_riders += 1;
                     }
-                    VehicleType::Train => {
+                    VehicleType::Train | VehicleType::Tram => {
                         cnt.train_riders += 1;
                     }
-                    VehicleType::Car | VehicleType::Bike => unreachable!(),
+                    VehicleType::Car => {
+                        cnt.ride_hail_riders += 1;
+                    }
+                    VehicleType::Bike | VehicleType::Truck | VehicleType::Scooter => {
+                        unreachable!()
+                    }
                 },
                 // These're counted separately
                 AgentID::Pedestrian(_) => {}
@@ -1206,6 +1638,7 @@ impl TripManager {
                 PersonState::OffMap => {
                     ppl_off_map += 1;
                 }
+                PersonState::Dockless(_) => {}
             }
         }
         (self.people.len(), ppl_in_bldg, ppl_off_map)
@@ -1265,8 +1698,11 @@ impl TripManager {
                     // We can make some assumptions here.
                     let agent_type = match t.info.mode {
                         TripMode::Walk => AgentType::Pedestrian,
-                        TripMode::Bike => AgentType::Bike,
-                        TripMode::Drive => AgentType::Car,
+                        TripMode::Bike | TripMode::Scooter => AgentType::Bike,
+                        TripMode::Drive | TripMode::RideHail => AgentType::Car,
+                        TripMode::Freight => AgentType::Truck,
+                        // Only the trip back from a park-and-ride can start off-map, on foot
+                        TripMode::ParkAndRide => AgentType::Pedestrian,
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
@@ -1284,23 +1720,32 @@ impl TripManager {
     pub fn generate_scenario(&self, map: &Map, name: String) -> Scenario {
         let mut scenario = Scenario::empty(map, &name);
         for p in &self.people {
-            scenario.people.push(PersonSpec {
-                orig_id: p.orig_id,
-                trips: p
-                    .trips
-                    .iter()
-                    .map(|t| {
-                        let trip = &self.trips[t.0];
-                        IndividTrip::new(
-                            trip.info.departure,
-                            trip.info.purpose,
-                            trip.info.start,
-                            trip.info.end,
-                            trip.info.mode,
-                        )
-                    })
-                    .collect(),
-            });
+            let mut trips = Vec::new();
+            for t in &p.trips {
+                let info = &self.trips[t.0].info;
+                if info.delivery_stops.is_empty() {
+                    trips.push(IndividTrip::new(
+                        info.departure,
+                        info.purpose,
+                        info.start,
+                        info.end,
+                        info.mode,
+                    ));
+                } else {
+                    scenario.deliveries.push(DeliveryTour {
+                        depart: info.departure,
+                        origin: info.start,
+                        stops: info.delivery_stops.clone(),
+                        destination: info.end,
+                    });
+                }
+            }
+            if !trips.is_empty() {
+                scenario.people.push(PersonSpec {
+                    orig_id: p.orig_id,
+                    trips,
+                });
+            }
         }
         scenario
     }
@@ -1330,6 +1775,8 @@ pub struct TripInfo {
     /// Did a ScenarioModifier apply to this?
     pub modified: bool,
     pub cancellation_reason: Option<String>,
+    /// Only trucks making deliveries have stops along the way
+    pub delivery_stops: Vec<DeliveryStop>,
 }
 
 impl Trip {
@@ -1352,6 +1799,8 @@ pub(crate) enum TripLeg {
     Drive(CarID, DrivingGoal),
     /// Maybe get off at a stop, maybe ride off-map
     RideBus(TransitRouteID, Option<TransitStopID>),
+    /// Ride an on-demand vehicle, getting dropped off here
+    RideHail(Position),
 }
 
 pub enum TripResult<T> {
@@ -1392,6 +1841,8 @@ pub struct Person {
 
     pub ped: PedestrianID,
     pub ped_speed: Speed,
+    /// If set, this person only walks along sidewalks and crossings this profile can use.
+    pub walking_profile: Option<WalkingProfile>,
     /// Both cars and bikes
     pub vehicles: Vec<Vehicle>,
 
@@ -1403,6 +1854,15 @@ impl Person {
     fn get_vehicle(&self, id: CarID) -> Vehicle {
         self.vehicles.iter().find(|v| v.id == id).unwrap().clone()
     }
+
+    fn pathfind_walking(&self, req: PathRequest, map: &Map) -> Result<Path> {
+        if let Some(ref profile) = self.walking_profile {
+            let mut params = map.routing_params().clone();
+            params.walking_profile = Some(profile.clone());
+            return map.pathfind_with_params(req, &params, PathfinderCaching::CacheDijkstra);
+        }
+        map.pathfind(req)
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -1410,6 +1870,8 @@ pub enum PersonState {
     Trip(TripID),
     Inside(BuildingID),
     OffMap,
+    /// Outside, at a dockless point along a sidewalk, between trips on a bike or scooter
+    Dockless(Position),
 }
 
 /// The number of active vehicles and commuters, broken into different categories.
@@ -1427,4 +1889,8 @@ pub struct CommutersVehiclesCounts {
     pub trains: usize,
     pub bus_riders: usize,
     pub train_riders: usize,
+
+    /// Ride-hailing vehicles on the road, whether or not they're carrying anybody
+    pub ride_hail_vehicles: usize,
+    pub ride_hail_riders: usize,
 }
@@ -84,8 +84,10 @@ impl MapBorders {
     pub fn for_mode(&self, mode: TripMode) -> (&Vec<MapBorder>, &Vec<MapBorder>) {
         match mode {
             TripMode::Walk | TripMode::Transit => (&self.incoming_walking, &self.outgoing_walking),
-            TripMode::Drive => (&self.incoming_driving, &self.outgoing_driving),
-            TripMode::Bike => (&self.incoming_biking, &self.outgoing_biking),
+            TripMode::Drive | TripMode::ParkAndRide | TripMode::RideHail | TripMode::Freight => {
+                (&self.incoming_driving, &self.outgoing_driving)
+            }
+            TripMode::Bike | TripMode::Scooter => (&self.incoming_biking, &self.outgoing_biking),
         }
     }
 }
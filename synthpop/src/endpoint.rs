@@ -11,6 +11,9 @@ pub enum TripEndpoint {
     Border(IntersectionID),
     /// Used for interactive spawning, tests, etc. For now, only valid as a trip's start.
     SuddenlyAppear(Position),
+    /// A pickup or dropoff point for dockless scooters and bikes, somewhere along a sidewalk.
+    /// People appear or disappear here, like at a border. Driving trips can't use these.
+    Dockless(Position),
 }
 
 impl TripEndpoint {
@@ -19,7 +22,7 @@ impl TripEndpoint {
         match self {
             TripEndpoint::Building(b) => map.get_b(b).polygon.center(),
             TripEndpoint::Border(i) => map.get_i(i).polygon.center(),
-            TripEndpoint::SuddenlyAppear(pos) => pos.pt(map),
+            TripEndpoint::SuddenlyAppear(pos) | TripEndpoint::Dockless(pos) => pos.pt(map),
         }
     }
 
@@ -37,23 +40,40 @@ impl TripEndpoint {
         let end = to.pos(mode, false, map)?;
         Some(match mode {
             TripMode::Walk | TripMode::Transit => PathRequest::walking(start, end),
-            TripMode::Bike => PathRequest::vehicle(start, end, PathConstraints::Bike),
+            TripMode::Bike | TripMode::Scooter => {
+                PathRequest::vehicle(start, end, PathConstraints::Bike)
+            }
             // Only cars leaving from a building might turn out from the driveway in a special way
-            TripMode::Drive => {
+            TripMode::Drive | TripMode::ParkAndRide => {
                 if matches!(from, TripEndpoint::Building(_)) {
                     PathRequest::leave_from_driveway(start, end, PathConstraints::Car, map)
                 } else {
                     PathRequest::vehicle(start, end, PathConstraints::Car)
                 }
             }
+            // The vehicle picks up from the road, so there's no driveway
+            TripMode::RideHail => PathRequest::vehicle(start, end, PathConstraints::Car),
+            // Trucks appear on the road, even when leaving a building
+            TripMode::Freight => PathRequest::vehicle(start, end, PathConstraints::Truck),
         })
     }
 
     fn pos(self, mode: TripMode, from: bool, map: &Map) -> Option<Position> {
         match mode {
             TripMode::Walk | TripMode::Transit => self.sidewalk_pos(map, from),
-            TripMode::Drive | TripMode::Bike => {
+            TripMode::Drive
+            | TripMode::Bike
+            | TripMode::Scooter
+            | TripMode::ParkAndRide
+            | TripMode::RideHail
+            | TripMode::Freight => {
                 let constraints = mode.to_constraints();
+                if let TripEndpoint::Dockless(pos) = self {
+                    if constraints != PathConstraints::Bike {
+                        return None;
+                    }
+                    return map.find_biking_pos_near_sidewalk(pos);
+                }
                 if from {
                     match self {
                         // Fall through
@@ -68,12 +88,13 @@ impl TripEndpoint {
                         TripEndpoint::SuddenlyAppear(pos) => {
                             return Some(pos);
                         }
+                        TripEndpoint::Dockless(_) => unreachable!(),
                     }
                 }
 
                 match self {
                     TripEndpoint::Building(b) => match constraints {
-                        PathConstraints::Car => {
+                        PathConstraints::Car | PathConstraints::Truck => {
                             let driving_lane = map.find_driving_lane_near_building(b);
                             let sidewalk_pos = map.get_b(b).sidewalk_pos;
                             if driving_lane.road == sidewalk_pos.lane().road {
@@ -102,6 +123,7 @@ impl TripEndpoint {
                     }
                     // This can also be an ending, despite the naming
                     TripEndpoint::SuddenlyAppear(pos) => Some(pos),
+                    TripEndpoint::Dockless(_) => unreachable!(),
                 }
             }
         }
@@ -117,7 +139,7 @@ impl TripEndpoint {
                     TripEndpoint::end_walking_at_border(i, map)
                 }
             }
-            TripEndpoint::SuddenlyAppear(pos) => Some(pos),
+            TripEndpoint::SuddenlyAppear(pos) | TripEndpoint::Dockless(pos) => Some(pos),
         }
     }
 
@@ -5,7 +5,7 @@ use anyhow::Result;
 use serde::Deserialize;
 
 use geom::{Distance, FindClosest, LonLat, Time};
-use map_model::Map;
+use map_model::{LaneID, Map, Position};
 
 use crate::{IndividTrip, MapBorders, PersonSpec, TripEndpoint, TripMode, TripPurpose};
 
@@ -27,6 +27,8 @@ pub struct ExternalTrip {
 pub enum ExternalTripEndpoint {
     TripEndpoint(TripEndpoint),
     Position(LonLat),
+    /// A dockless scooter or bike pickup or dropoff point, snapped to the nearest sidewalk
+    Dockless(LonLat),
 }
 
 impl ExternalPerson {
@@ -34,8 +36,9 @@ impl ExternalPerson {
     /// `PersonSpec` is a way to specify endpoints by a `LonLat`. This is snapped to the nearest
     /// building. If the point is outside of the map boundary, it's snapped to the nearest border
     /// (by Euclidean distance -- the network outside the given map isn't known). Failure happens
-    /// if a point is within the map, but not close enough to any buildings. If `skip_problems` is
-    /// true, then those failures are logged; otherwise this panics at the first problem.
+    /// if a point is within the map, but not close enough to any buildings. Dockless points are
+    /// snapped to the nearest sidewalk instead. If `skip_problems` is true, then those failures
+    /// are logged; otherwise this panics at the first problem.
     pub fn import(
         map: &Map,
         input: Vec<ExternalPerson>,
@@ -45,6 +48,12 @@ impl ExternalPerson {
         for b in map.all_buildings() {
             closest.add_polygon(TripEndpoint::Building(b.id), &b.polygon);
         }
+        let mut closest_sidewalk: FindClosest<LaneID> = FindClosest::new();
+        for l in map.all_lanes() {
+            if l.is_walkable() {
+                closest_sidewalk.add(l.id, l.lane_center_pts.points());
+            }
+        }
         let borders = MapBorders::new(map);
 
         let lookup_pt = |endpt, is_origin, mode| match endpt {
@@ -68,6 +77,16 @@ impl ExternalPerson {
                     ))
                 }
             }
+            ExternalTripEndpoint::Dockless(gps) => {
+                let pt = gps.to_pt(map.get_gps_bounds());
+                closest_sidewalk
+                    .closest_pt(pt, Distance::meters(50.0))
+                    .and_then(|(l, pt)| {
+                        let dist = map.get_l(l).dist_along_of_point(pt)?;
+                        Some(TripEndpoint::Dockless(Position::new(l, dist)))
+                    })
+                    .ok_or_else(|| anyhow!("No sidewalk within 50m of dockless point {}", gps))
+            }
         };
 
         let mut results = Vec::new();
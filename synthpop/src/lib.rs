@@ -20,7 +20,9 @@ pub use self::counts::TrafficCounts;
 pub use self::endpoint::TripEndpoint;
 pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint};
 pub use self::modifier::ScenarioModifier;
-pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
+pub use self::scenario::{
+    DeliveryStop, DeliveryTour, IndividTrip, PersonSpec, Scenario, TripPurpose,
+};
 
 mod borders;
 mod counts;
@@ -40,6 +42,16 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    /// Shared e-scooters and e-bikes. Depending on the map, they ride in bike lanes or on sidewalks.
+    Scooter,
+    /// Drive to a parking lot near a transit stop, then ride transit the rest of the way. The trip
+    /// back reverses this, picking up the car from the lot.
+    ParkAndRide,
+    /// Ride in an on-demand vehicle, like a taxi, from door to door. Several riders may share the
+    /// vehicle.
+    RideHail,
+    /// Vans and trucks making deliveries, stopping at several buildings along the way
+    Freight,
 }
 
 impl TripMode {
@@ -49,6 +61,10 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::Scooter,
+            TripMode::ParkAndRide,
+            TripMode::RideHail,
+            TripMode::Freight,
         ]
     }
 
@@ -58,6 +74,10 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::Scooter => "ride a scooter",
+            TripMode::ParkAndRide => "park and ride",
+            TripMode::RideHail => "take a ride-hail",
+            TripMode::Freight => "deliver freight",
         }
     }
 
@@ -68,6 +88,10 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::Scooter => "scooting",
+            TripMode::ParkAndRide => "parking and riding",
+            TripMode::RideHail => "riding in a ride-hail",
+            TripMode::Freight => "delivering freight",
         }
     }
 
@@ -77,6 +101,10 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::Scooter => "Scooter",
+            TripMode::ParkAndRide => "Park-and-ride",
+            TripMode::RideHail => "Ride-hail",
+            TripMode::Freight => "Truck",
         }
     }
 
@@ -87,6 +115,13 @@ impl TripMode {
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
             TripMode::Drive => PathConstraints::Car,
+            // When scooters ride on sidewalks, the simulation routes them as pedestrians
+            TripMode::Scooter => PathConstraints::Bike,
+            // The person's own vehicle is a car
+            TripMode::ParkAndRide => PathConstraints::Car,
+            // The vehicle is driven by somebody else, but it's still a car
+            TripMode::RideHail => PathConstraints::Car,
+            TripMode::Freight => PathConstraints::Truck,
         }
     }
 
@@ -97,6 +132,7 @@ impl TripMode {
             // TODO The bijection breaks down... transit rider vs train vs bus...
             PathConstraints::Bus | PathConstraints::Train => TripMode::Transit,
             PathConstraints::Car => TripMode::Drive,
+            PathConstraints::Truck => TripMode::Freight,
         }
     }
 }
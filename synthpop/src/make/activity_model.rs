@@ -157,8 +157,10 @@ impl ScenarioGenerator {
                     (TripEndpoint::Border(_), TripEndpoint::Border(_)) => {
                         num_trips_passthru += 1;
                     }
-                    (TripEndpoint::SuddenlyAppear(_), _) => unreachable!(),
-                    (_, TripEndpoint::SuddenlyAppear(_)) => unreachable!(),
+                    (TripEndpoint::SuddenlyAppear(_), _)
+                    | (_, TripEndpoint::SuddenlyAppear(_))
+                    | (TripEndpoint::Dockless(_), _)
+                    | (_, TripEndpoint::Dockless(_)) => unreachable!(),
                 };
 
                 Some((home, work, fork_rng(rng)))
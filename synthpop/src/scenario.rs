@@ -6,8 +6,8 @@ use serde::{Deserialize, Serialize};
 
 use abstio::{CityName, MapName};
 use abstutil::prettyprint_usize;
-use geom::Time;
-use map_model::Map;
+use geom::{Duration, Time};
+use map_model::{BuildingID, Map};
 
 use crate::{OrigPersonID, TripEndpoint, TripMode};
 
@@ -20,6 +20,9 @@ pub struct Scenario {
     pub people: Vec<PersonSpec>,
     /// None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
+    /// Vans and trucks making deliveries, in addition to everyone's personal trips
+    #[serde(default)]
+    pub deliveries: Vec<DeliveryTour>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -64,6 +67,24 @@ impl IndividTrip {
     }
 }
 
+/// A truck leaving from `origin`, stopping at each building in order to load or unload, then
+/// finishing at `destination`. Trucks don't park at their stops; they wait in the street, blocking
+/// the lane.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeliveryTour {
+    pub depart: Time,
+    pub origin: TripEndpoint,
+    pub stops: Vec<DeliveryStop>,
+    pub destination: TripEndpoint,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct DeliveryStop {
+    pub building: BuildingID,
+    /// How long the truck waits at the building
+    pub dwell: Duration,
+}
+
 /// Lifted from Seattle's Soundcast model, but seems general enough to use anyhere.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum TripPurpose {
@@ -78,6 +99,7 @@ pub enum TripPurpose {
     Recreation,
     Medical,
     ParkAndRideTransfer,
+    Delivery,
 }
 
 impl fmt::Display for TripPurpose {
@@ -98,6 +120,7 @@ impl fmt::Display for TripPurpose {
                 TripPurpose::Recreation => "recreation",
                 TripPurpose::Medical => "medical",
                 TripPurpose::ParkAndRideTransfer => "park-and-ride transfer",
+                TripPurpose::Delivery => "delivery",
             }
         )
     }
@@ -117,6 +140,7 @@ impl Scenario {
             map_name: map.get_name().clone(),
             people: Vec::new(),
             only_seed_buses: Some(BTreeSet::new()),
+            deliveries: Vec::new(),
         }
     }
 
@@ -207,3 +231,25 @@ impl PersonSpec {
         Ok(())
     }
 }
+
+impl DeliveryTour {
+    /// Verify that a tour makes sense
+    pub fn check(&self) -> Result<()> {
+        if self.stops.is_empty() {
+            bail!("Delivery tour from {:?} has no stops", self.origin);
+        }
+        if let TripEndpoint::SuddenlyAppear(_) | TripEndpoint::Dockless(_) = self.destination {
+            bail!("Delivery tour from {:?} ends at a position", self.origin);
+        }
+        for pair in self.stops.windows(2) {
+            if pair[0].building == pair[1].building {
+                bail!(
+                    "Delivery tour from {:?} stops at {} twice in a row",
+                    self.origin,
+                    pair[0].building
+                );
+            }
+        }
+        Ok(())
+    }
+}
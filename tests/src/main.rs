@@ -11,9 +11,17 @@ use abstio::{CityName, MapName};
 use abstutil::Timer;
 use blockfinding::Perimeter;
 use geom::{Distance, Duration, Time};
-use map_model::{IntersectionID, LaneType, Map, RoadID};
-use sim::{AlertHandler, PrebakeSummary, Sim, SimFlags, SimOptions};
-use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
+use map_model::{
+    osm, AccessibilityBarrier, IntersectionID, LaneID, LaneType, Map, OriginalRoad,
+    PathConstraints, PathRequest, PathfinderCaching, Position, Road, RoadID, TravelTimeProfile,
+    Traversable, WalkingProfile,
+};
+use sim::assignment::{assign_traffic, AssignmentConfig};
+use sim::{AgentID, AlertHandler, PrebakeSummary, Sim, SimFlags, SimOptions, VehicleType};
+use synthpop::{
+    DeliveryStop, DeliveryTour, IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode,
+    TripPurpose,
+};
 
 use ::tests::{compare_with_goldenfile, import_map};
 
@@ -23,9 +31,13 @@ fn main() -> Result<()> {
         geometry_test()?;
     }
     test_blockfinding()?;
-    test_lane_changing(&import_map(abstio::path(
-        "../tests/input/lane_selection.osm",
-    )))?;
+    let lane_selection = import_map(abstio::path("../tests/input/lane_selection.osm"));
+    test_lane_changing(&lane_selection)?;
+    test_time_dependent_routing(&lane_selection)?;
+    test_path_reroute(&lane_selection)?;
+    test_kerbs()?;
+    test_overtaking()?;
+    test_traffic_assignment()?;
     test_map_importer()?;
     check_proposals()?;
     if false {
@@ -35,6 +47,9 @@ fn main() -> Result<()> {
         bus_test()?;
     }
     bus_route_test()?;
+    test_ride_hail_live_edits()?;
+    test_delivery_tour()?;
+    test_park_and_ride()?;
     if false {
         smoke_test()?;
     }
@@ -262,6 +277,633 @@ fn test_lane_changing(map: &Map) -> Result<()> {
     Ok(())
 }
 
+/// Time-dependent routing should agree with the regular pathfinder when the profile doesn't change
+/// costs at the time of the trip, and find slower paths when it does.
+fn test_time_dependent_routing(map: &Map) -> Result<()> {
+    let driving_lanes: Vec<LaneID> = map
+        .all_lanes()
+        .filter(|l| l.lane_type == LaneType::Driving)
+        .map(|l| l.id)
+        .collect();
+    let departure = Time::START_OF_DAY + Duration::hours(8);
+
+    // Every road is slower in the middle of the night, but not at 8am
+    let mut profile = TravelTimeProfile::default();
+    for r in map.all_roads() {
+        for dr in r.id.both_directions() {
+            let mut hours = vec![1.0; 24];
+            hours[3] = 3.0;
+            profile.per_road.insert(dr, hours);
+        }
+    }
+    // And twice as slow everywhere all day
+    let mut slow = TravelTimeProfile::default();
+    for r in map.all_roads() {
+        for dr in r.id.both_directions() {
+            slow.per_road.insert(dr, vec![2.0; 24]);
+        }
+    }
+
+    for l1 in &driving_lanes {
+        for l2 in &driving_lanes {
+            if l1 == l2 {
+                continue;
+            }
+            let req = PathRequest::vehicle(
+                Position::start(*l1),
+                Position::end(*l2, map),
+                PathConstraints::Car,
+            );
+            let expected = match map.pathfind_v2(req.clone()) {
+                Ok(path) => path.get_cost(),
+                Err(_) => continue,
+            };
+            let actual = map
+                .pathfind_v2_with_params_at(req.clone(), map.routing_params(), departure, &profile)?
+                .get_cost();
+            if actual != expected {
+                bail!(
+                    "{} costs {} with a profile that's flat at {}, but {} normally",
+                    req,
+                    actual,
+                    departure,
+                    expected
+                );
+            }
+            let slower = map
+                .pathfind_v2_with_params_at(req.clone(), map.routing_params(), departure, &slow)?
+                .get_cost();
+            if slower < expected {
+                bail!(
+                    "{} costs {} when every road is slower, but {} normally",
+                    req,
+                    slower,
+                    expected
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `barrier=kerb` nodes are usually mapped on footways beside the road, so they're snapped to the
+/// closest crossing or road end. People with a walking profile can't cross over a raised kerb, or
+/// start or end a walk on a sidewalk they can't use.
+fn test_kerbs() -> Result<()> {
+    let map = import_map(abstio::path("../tests/input/kerbs.osm"));
+    let west = map.get_r(map.find_r_by_osm_id(OriginalRoad::new(11, (5, 1)))?);
+    let north = map.get_r(map.find_r_by_osm_id(OriginalRoad::new(10, (1, 2)))?);
+    let gravel = map.get_r(map.find_r_by_osm_id(OriginalRoad::new(12, (4, 13)))?);
+
+    // The far-away kerb is ignored
+    let total: usize = map.all_roads().iter().map(|r| r.kerb_nodes.len()).sum();
+    if total != 2 {
+        bail!("expected 2 kerbs to be snapped to roads, but got {}", total);
+    }
+    if west.kerb_nodes.len() != 1
+        || west.crossing_nodes.len() != 1
+        || west.kerb_nodes[0].1 != Distance::meters(0.03)
+        || (west.kerb_nodes[0].0 - west.crossing_nodes[0].0).abs() > Distance::meters(1.0)
+    {
+        bail!(
+            "the lowered kerb wasn't snapped to the crossing on {}: {:?}",
+            west.id,
+            west.kerb_nodes
+        );
+    }
+    // An untyped kerb is raised, and it's closest to the start of the north road
+    if north.kerb_nodes.len() != 1
+        || north.kerb_nodes[0].1 != Distance::meters(0.12)
+        || north.kerb_nodes[0].0 > Distance::meters(1.0)
+    {
+        bail!(
+            "the raised kerb wasn't snapped to the start of {}: {:?}",
+            north.id,
+            north.kerb_nodes
+        );
+    }
+
+    let profile = WalkingProfile::wheelchair();
+    let crosswalks: Vec<_> = map
+        .get_i(north.src_i)
+        .turns
+        .iter()
+        .filter(|t| t.crosswalk_over_road(&map).map(|dr| dr.road) == Some(north.id))
+        .collect();
+    if crosswalks.is_empty() {
+        bail!("no crosswalks over {}", north.id);
+    }
+    for t in crosswalks {
+        if profile.turn_barrier(t, &map) != Some(AccessibilityBarrier::Kerb(Distance::meters(0.12)))
+        {
+            bail!("{} should be blocked by the raised kerb", t.id);
+        }
+    }
+
+    let sidewalk = |r: &Road| {
+        r.lanes
+            .iter()
+            .find(|l| l.is_sidewalk())
+            .map(|l| l.id)
+            .unwrap()
+    };
+    let mut params = map.routing_params().clone();
+    params.walking_profile = Some(profile);
+    let on_gravel = Position::start(sidewalk(gravel));
+    let on_west = Position::start(sidewalk(west));
+    for req in [
+        PathRequest::walking(on_gravel, on_west),
+        PathRequest::walking(on_west, on_gravel),
+    ] {
+        map.pathfind(req.clone())?;
+        if map
+            .pathfind_with_params(req.clone(), &params, PathfinderCaching::NoCache)
+            .is_ok()
+        {
+            bail!(
+                "{} starts or ends on a gravel sidewalk, but was allowed",
+                req
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// With overtaking through the oncoming lane enabled, a car stuck behind a cyclist on a two-way
+/// street passes them. The pass takes some time; the car shouldn't jump ahead of the cyclist.
+fn test_overtaking() -> Result<()> {
+    let map = import_map(abstio::path("../tests/input/overtaking.osm"));
+    let west = map.find_i_by_osm_id(osm::NodeID(1))?;
+    let east = map.find_i_by_osm_id(osm::NodeID(3))?;
+
+    let mut scenario = Scenario::empty(&map, "overtaking");
+    for (mode, depart) in [(TripMode::Bike, 0.0), (TripMode::Drive, 5.0)] {
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY + Duration::seconds(depart),
+                TripPurpose::Shopping,
+                TripEndpoint::Border(west),
+                TripEndpoint::Border(east),
+                mode,
+            )],
+        });
+    }
+    let mut opts = SimOptions::new("test_overtaking");
+    opts.alerts = AlertHandler::Silence;
+    opts.overtake_using_oncoming_lane = true;
+    let mut sim = Sim::new(&map, opts);
+    let mut rng = SimFlags::for_test("test_overtaking").make_rng();
+    sim.instantiate(&scenario, &map, &mut rng, &mut Timer::throwaway());
+
+    // How far along the street is somebody?
+    let center = &map.all_roads()[0].center_pts;
+    let dist_along = |sim: &Sim, mode: TripMode| -> Option<Distance> {
+        let (trip, _) = sim
+            .all_trip_info()
+            .into_iter()
+            .find(|(_, info)| info.mode == mode)?;
+        let pt = sim.canonical_pt_for_agent(sim.trip_to_agent(trip).ok()?, &map)?;
+        Some(center.dist_along_of_point(center.project_pt(pt))?.0)
+    };
+
+    let mut passed = false;
+    let mut last_car_dist = None;
+    while !sim.is_done() {
+        sim.tiny_step(&map, &mut None);
+        let car_dist = dist_along(&sim, TripMode::Drive);
+        if let (Some(car), Some(bike)) = (car_dist, dist_along(&sim, TripMode::Bike)) {
+            passed |= car > bike;
+        }
+        if let (Some(before), Some(after)) = (last_car_dist, car_dist) {
+            // 25mph over 0.1s is just over 1m
+            if after - before > Distance::meters(3.0) {
+                bail!(
+                    "The car jumped from {} to {} along the street at {}",
+                    before,
+                    after,
+                    sim.time()
+                );
+            }
+        }
+        last_car_dist = car_dist;
+    }
+    if !passed {
+        bail!("The car never passed the cyclist");
+    }
+    Ok(())
+}
+
+/// With everybody crowding onto the direct route at first, some drivers could save time by taking
+/// the detour. As some of them switch, the relative gap should shrink.
+fn test_traffic_assignment() -> Result<()> {
+    let map = import_map(abstio::path("../tests/input/assignment.osm"));
+    let west = map.find_i_by_osm_id(osm::NodeID(1))?;
+    let east = map.find_i_by_osm_id(osm::NodeID(4))?;
+
+    let mut scenario = Scenario::empty(&map, "assignment");
+    for idx in 0..400 {
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY + Duration::seconds(1.5 * idx as f64),
+                TripPurpose::Shopping,
+                TripEndpoint::Border(west),
+                TripEndpoint::Border(east),
+                TripMode::Drive,
+            )],
+        });
+    }
+    let results = assign_traffic(
+        &map,
+        &scenario,
+        &AssignmentConfig {
+            max_iterations: 3,
+            max_relative_gap: 0.0,
+            reroute_share: Some(0.5),
+            rng_seed: SimFlags::RNG_SEED,
+        },
+        &mut Timer::throwaway(),
+    );
+
+    let first = &results.iterations[0];
+    let last = results.iterations.last().unwrap();
+    if first.relative_gap == 0.0 {
+        bail!("With everybody on the direct route, nobody could do better by taking the detour");
+    }
+    if last.relative_gap >= first.relative_gap {
+        bail!(
+            "The relative gap went from {} to {} after {} iterations",
+            first.relative_gap,
+            last.relative_gap,
+            last.iteration
+        );
+    }
+    Ok(())
+}
+
+/// Drivers rerouting mid-trip splice a new path onto the lane they're currently on. The new path
+/// may start from a different lane on the same road, but must end at the same place.
+fn test_path_reroute(map: &Map) -> Result<()> {
+    let driving_lanes: Vec<LaneID> = map
+        .all_lanes()
+        .filter(|l| l.lane_type == LaneType::Driving)
+        .map(|l| l.id)
+        .collect();
+
+    for l1 in &driving_lanes {
+        for l2 in &driving_lanes {
+            if l1 == l2 {
+                continue;
+            }
+            let end = Position::end(*l2, map);
+            let req = PathRequest::vehicle(Position::start(*l1), end, PathConstraints::Car);
+            let orig = match map.pathfind(req.clone()) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+
+            // Rerouting onto the same route changes nothing
+            let mut path = orig.clone();
+            path.reroute(map.pathfind(req.clone())?, map)?;
+            if path.get_steps() != orig.get_steps() || path.total_length() != orig.total_length() {
+                bail!("rerouting {} onto the same route changed it", req);
+            }
+
+            // Starting from a neighboring lane on the same road turns from the current lane
+            for l3 in map
+                .get_l(*l1)
+                .get_directed_parent()
+                .lanes(PathConstraints::Car, map)
+            {
+                if l3 == *l1 {
+                    continue;
+                }
+                let new = match map.pathfind(PathRequest::vehicle(
+                    Position::start(l3),
+                    end,
+                    PathConstraints::Car,
+                )) {
+                    Ok(path) => path,
+                    Err(_) => continue,
+                };
+                let mut path = orig.clone();
+                if path.reroute(new.clone(), map).is_err() {
+                    continue;
+                }
+                let steps = path.get_steps();
+                if steps[0].as_lane() != *l1
+                    || steps[1].as_turn().src != *l1
+                    || steps.len() != new.get_steps().len()
+                    || steps.back() != new.get_steps().back()
+                {
+                    bail!(
+                        "rerouting {} from neighboring {} produced {:?}",
+                        req,
+                        l3,
+                        steps
+                    );
+                }
+            }
+
+            // A path to somewhere else can't be spliced in
+            for l4 in &driving_lanes {
+                if l4 == l1 || l4 == l2 {
+                    continue;
+                }
+                if let Ok(elsewhere) = map.pathfind(PathRequest::vehicle(
+                    Position::start(*l1),
+                    Position::end(*l4, map),
+                    PathConstraints::Car,
+                )) {
+                    if orig.clone().reroute(elsewhere, map).is_ok() {
+                        bail!("rerouted {} to end at {} instead", req, l4);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keep closing lanes that vehicles are heading for or driving on while a ride-hailing fleet is
+/// busy. Fleet vehicles don't belong to any one trip, so they have to survive their path becoming
+/// invalid before they manage to spawn, and leave the road when it changes under them.
+fn test_ride_hail_live_edits() -> Result<()> {
+    let mut timer = Timer::new("ride-hail live edits");
+    let mut map =
+        map_model::Map::load_synchronously(MapName::seattle("montlake").path(), &mut timer);
+    let mut rng = SimFlags::for_test("test_ride_hail_live_edits").make_rng();
+
+    let mut bldgs: Vec<_> = map.all_buildings().iter().map(|b| b.id).collect();
+    bldgs.shuffle(&mut rng);
+    let mut scenario = Scenario::empty(&map, "ride_hail_live_edits");
+    for (idx, pair) in bldgs.chunks_exact(2).take(300).enumerate() {
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY + Duration::seconds(idx as f64),
+                TripPurpose::Shopping,
+                TripEndpoint::Building(pair[0]),
+                TripEndpoint::Building(pair[1]),
+                TripMode::RideHail,
+            )],
+        });
+    }
+
+    let mut opts = SimOptions::new("test_ride_hail_live_edits");
+    opts.alerts = AlertHandler::Silence;
+    opts.ride_hail_fleet_size = 30;
+    let mut sim = Sim::new(&map, opts);
+    sim.instantiate(&scenario, &map, &mut rng, &mut timer);
+
+    for _ in 0..20 {
+        sim.timed_step(&map, Duration::minutes(1), &mut None, &mut timer);
+
+        // Close a few driving lanes that somebody is about to enter
+        let mut destinations = Vec::new();
+        for i in map.all_intersections() {
+            for (_, t) in sim.get_accepted_agents(i.id) {
+                if map.get_l(t.dst).lane_type == LaneType::Driving {
+                    destinations.push(t.dst);
+                }
+            }
+        }
+        destinations.sort();
+        destinations.dedup();
+        destinations.shuffle(&mut rng);
+        let mut closed: Vec<LaneID> = destinations.into_iter().take(3).collect();
+
+        // And one that a fleet vehicle is in the middle of
+        let mut fleet_lanes = Vec::new();
+        for car in sim.get_all_draw_cars(&map) {
+            if let Traversable::Lane(l) = car.on {
+                if car.id.vehicle_type == VehicleType::Car
+                    && sim.agent_to_trip(AgentID::Car(car.id)).is_none()
+                    && map.get_l(l).lane_type == LaneType::Driving
+                {
+                    fleet_lanes.push(l);
+                }
+            }
+        }
+        fleet_lanes.shuffle(&mut rng);
+        closed.extend(fleet_lanes.into_iter().take(1));
+        closed.sort();
+        closed.dedup();
+
+        let mut edits = map.get_edits().clone();
+        for l in &closed {
+            edits.commands.push(map.edit_road_cmd(l.road, |new| {
+                new.lanes_ltr[l.offset].lt = LaneType::Construction;
+            }));
+        }
+        map.must_apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        sim.handle_live_edited_traffic_signals(&map);
+        sim.handle_live_edits(&map, &mut timer);
+
+        for car in sim.get_all_draw_cars(&map) {
+            if let Traversable::Lane(l) = car.on {
+                if closed.contains(&l) {
+                    bail!("{} is still on {} after it was closed", car.id, l);
+                }
+            }
+        }
+    }
+
+    sim.timed_step(&map, Duration::hours(3), &mut None, &mut timer);
+    Ok(())
+}
+
+/// Find people who'd park-and-ride, check the lot and transit route chosen make sense, then make
+/// sure they really drive to the lot and ride transit, and come back the other way later.
+fn test_park_and_ride() -> Result<()> {
+    let mut timer = Timer::new("park-and-ride");
+    let map = map_model::Map::load_synchronously(MapName::seattle("montlake").path(), &mut timer);
+    let mut rng = SimFlags::for_test("test_park_and_ride").make_rng();
+
+    let mut bldgs: Vec<_> = map.all_buildings().iter().map(|b| b.id).collect();
+    bldgs.shuffle(&mut rng);
+    let mut scenario = Scenario::empty(&map, "park_and_ride");
+    for (idx, pair) in bldgs.chunks_exact(2).take(500).enumerate() {
+        let start = match map.get_b(pair[0]).driving_connection(&map) {
+            Some((pos, _)) => pos,
+            None => continue,
+        };
+        let end = map.get_b(pair[1]).sidewalk_pos;
+        let (lot, stop1, maybe_stop2, route) = match map.should_park_and_ride(start, end, None) {
+            Some(x) => x,
+            None => continue,
+        };
+        if !map.get_tr(route).stops.contains(&stop1)
+            || maybe_stop2
+                .map(|stop2| !map.get_tr(route).stops.contains(&stop2))
+                .unwrap_or(false)
+        {
+            bail!("park-and-ride from {} uses stops not on {}", pair[0], route);
+        }
+        // The transit part should match what somebody walking from the lot would do
+        let lot = map.get_pl(lot);
+        if map.should_use_transit(lot.sidewalk_pos, end, None) != Some((stop1, maybe_stop2, route))
+        {
+            bail!(
+                "park-and-ride from {} to {} picked different transit from {}",
+                pair[0],
+                pair[1],
+                lot.id
+            );
+        }
+        map.pathfind(PathRequest::vehicle(
+            start,
+            lot.driving_pos,
+            PathConstraints::Car,
+        ))?;
+
+        let depart = Time::START_OF_DAY + Duration::hours(7) + Duration::seconds(idx as f64);
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![
+                IndividTrip::new(
+                    depart,
+                    TripPurpose::Work,
+                    TripEndpoint::Building(pair[0]),
+                    TripEndpoint::Building(pair[1]),
+                    TripMode::ParkAndRide,
+                ),
+                IndividTrip::new(
+                    depart + Duration::hours(2),
+                    TripPurpose::Home,
+                    TripEndpoint::Building(pair[1]),
+                    TripEndpoint::Building(pair[0]),
+                    TripMode::ParkAndRide,
+                ),
+            ],
+        });
+    }
+    if scenario.people.is_empty() {
+        bail!("nobody in montlake would park-and-ride");
+    }
+
+    let mut opts = SimOptions::new("test_park_and_ride");
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(&map, opts);
+    sim.instantiate(&scenario, &map, &mut rng, &mut timer);
+    sim.timed_step(&map, Duration::hours(12), &mut None, &mut timer);
+
+    // Going there drives first, and coming back rides transit first
+    let (mut drive_then_transit, mut transit_then_drive) = (0, 0);
+    let analytics = sim.get_analytics();
+    for (_, id, mode, maybe_dt) in &analytics.finished_trips {
+        if *mode != TripMode::ParkAndRide || maybe_dt.is_none() {
+            continue;
+        }
+        let legs: Vec<&str> = analytics
+            .time_per_leg(*id)
+            .into_iter()
+            .map(|(leg, _)| leg)
+            .collect();
+        let driving = legs.iter().position(|leg| *leg == "driving");
+        let riding = legs.iter().position(|leg| *leg == "riding transit");
+        if let (Some(driving), Some(riding)) = (driving, riding) {
+            if driving < riding {
+                drive_then_transit += 1;
+            } else {
+                transit_then_drive += 1;
+            }
+        }
+    }
+    if drive_then_transit == 0 || transit_then_drive == 0 {
+        bail!(
+            "{} park-and-ride trips drove then rode transit, and {} rode transit then drove",
+            drive_then_transit,
+            transit_then_drive
+        );
+    }
+    Ok(())
+}
+
+/// A truck leaves a depot, stops at a few buildings, and comes back. It should wait at every stop
+/// and finish the tour as one freight trip.
+fn test_delivery_tour() -> Result<()> {
+    let mut timer = Timer::new("delivery tour");
+    let map = map_model::Map::load_synchronously(MapName::seattle("montlake").path(), &mut timer);
+
+    // Find a few buildings a truck can drive between
+    let mut bldgs = Vec::new();
+    for b in map.all_buildings() {
+        if let Some(prev) = bldgs.last() {
+            let reachable = TripEndpoint::path_req(
+                TripEndpoint::Building(*prev),
+                TripEndpoint::Building(b.id),
+                TripMode::Freight,
+                &map,
+            )
+            .map(|req| map.pathfind(req).is_ok())
+            .unwrap_or(false);
+            if !reachable {
+                continue;
+            }
+        }
+        bldgs.push(b.id);
+        if bldgs.len() == 4 {
+            break;
+        }
+    }
+    let depot = bldgs.remove(0);
+    let dwell = Duration::minutes(10);
+
+    let mut scenario = Scenario::empty(&map, "delivery_tour");
+    scenario.deliveries.push(DeliveryTour {
+        depart: Time::START_OF_DAY,
+        origin: TripEndpoint::Building(depot),
+        stops: bldgs
+            .iter()
+            .map(|b| DeliveryStop {
+                building: *b,
+                dwell,
+            })
+            .collect(),
+        destination: TripEndpoint::Building(depot),
+    });
+
+    let mut opts = SimOptions::new("test_delivery_tour");
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(&map, opts);
+    let mut rng = SimFlags::for_test("test_delivery_tour").make_rng();
+    sim.instantiate(&scenario, &map, &mut rng, &mut timer);
+    sim.timed_step(&map, Duration::hours(3), &mut None, &mut timer);
+
+    let (id, info) = sim.all_trip_info().pop().unwrap();
+    if info.mode != TripMode::Freight {
+        bail!("A delivery tour became a {:?} trip", info.mode);
+    }
+    if let Some(reason) = info.cancellation_reason {
+        bail!("The delivery tour was cancelled: {}", reason);
+    }
+    let (total_time, _, _) = match sim.finished_trip_details(id) {
+        Some(x) => x,
+        None => bail!("The delivery tour didn't finish"),
+    };
+    if total_time < (bldgs.len() as f64) * dwell {
+        bail!(
+            "The delivery tour took {}, but it should've waited {} at each of {} stops",
+            total_time,
+            dwell,
+            bldgs.len()
+        );
+    }
+
+    // The tour should survive a round-trip through the scenario
+    let regenerated = sim.generate_scenario(&map, "regenerated".to_string());
+    if regenerated.deliveries.len() != 1 || regenerated.deliveries[0].stops.len() != bldgs.len() {
+        bail!("Regenerating the scenario lost the delivery tour");
+    }
+
+    Ok(())
+}
+
 /// Generate single blocks and merged LTN-style blocks for some maps, counting the number of
 /// failures. Store in a goldenfile, so somebody can manually do a visual diff if anything changes.
 fn test_blockfinding() -> Result<()> {